@@ -13,8 +13,8 @@ use bms_rs::chart::prelude::*;
 use clap::Parser;
 use gametime::{TimeSpan, TimeStamp};
 use kira::{
-    AudioManager, AudioManagerSettings, Capacities, DefaultBackend,
-    sound::static_sound::StaticSoundData,
+    AudioManager, AudioManagerSettings, Capacities, DefaultBackend, Tween,
+    sound::static_sound::{StaticSoundData, StaticSoundHandle},
 };
 use macroquad::prelude::Color;
 use macroquad::prelude::*;
@@ -89,6 +89,9 @@ async fn main() -> Result<(), String> {
 
     // Track played events to prevent duplicate audio playback
     let mut played_events = HashSet::new();
+    // Voices of the sounds, to cut them off when their sounds are triggered again
+    let mut voice_allocator = VoiceAllocator::new();
+    let mut voice_handles: HashMap<VoiceId, StaticSoundHandle> = HashMap::new();
 
     // 7. Main loop
     println!("Starting playback...");
@@ -118,31 +121,43 @@ async fn main() -> Result<(), String> {
         }
 
         // Process all triggered events (play audio once per event)
+        voice_handles.retain(|_, handle| handle.state() != kira::sound::PlaybackState::Stopped);
         for event in &events {
-            let wav_id = match event.event() {
-                ChartEvent::Note { wav_id, .. } | ChartEvent::Bgm { wav_id } => wav_id,
-                _ => continue,
-            };
-
-            let Some(id) = wav_id else { continue };
-            let Some(audio) = audio_data_map.get(id) else {
-                continue;
-            };
-
             // Skip if this event has already been played
             if played_events.contains(&event.id()) {
                 continue;
             }
 
-            if let Err(e) = audio_manager.play(audio.clone()) {
-                if matches!(e, kira::PlaySoundError::SoundLimitReached) {
-                    missed_sounds += 1;
-                } else {
-                    eprintln!("Failed to play audio: {e}");
+            // Allocate a voice, and give it back if the sound does not start
+            let Some(command) = voice_allocator.trigger(event) else {
+                continue;
+            };
+            let Some(audio) = audio_data_map.get(&command.wav_id) else {
+                voice_allocator.cancel(command);
+                continue;
+            };
+
+            // BMSON continue notes start from the middle of the sound
+            match audio_manager.play(audio.start_position(command.offset.as_secs_f64())) {
+                Ok(handle) => {
+                    // Cut the previous voice of the same sound
+                    if let Some(mut previous) =
+                        command.stop.and_then(|voice| voice_handles.remove(&voice))
+                    {
+                        previous.stop(Tween::default());
+                    }
+                    voice_handles.insert(command.voice, handle);
+                    // Mark as played only on success
+                    played_events.insert(event.id());
+                }
+                Err(e) => {
+                    voice_allocator.cancel(command);
+                    if matches!(e, kira::PlaySoundError::SoundLimitReached) {
+                        missed_sounds += 1;
+                    } else {
+                        eprintln!("Failed to play audio: {e}");
+                    }
                 }
-            } else {
-                // Mark as played only on success
-                played_events.insert(event.id());
             }
         }
 
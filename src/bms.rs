@@ -17,6 +17,7 @@
 
 use std::{cell::RefCell, marker::PhantomData, rc::Rc};
 
+pub mod builder;
 pub mod command;
 
 pub mod lex;
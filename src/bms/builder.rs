@@ -0,0 +1,560 @@
+//! Programmatic construction of [`Bms`] from code.
+//!
+//! [`BmsBuilder`] takes notes in musical time and lanes as ([`PlayerSide`], [`Key`], [`NoteKind`]), and allocates the object ids of resources by itself. Positions given in beats are resolved with the time signatures on [`BmsBuilder::build`], so they can be registered in any order.
+//!
+//! ```rust
+//! use bms_rs::bms::prelude::*;
+//! use strict_num_extended::PositiveF64;
+//!
+//! let mut builder = BmsBuilder::<KeyLayoutBeat>::new();
+//! builder.title("Generated").bpm(PositiveF64::new_const(150.0));
+//! let kick = builder.wav("kick.wav").expect("ids should be left");
+//! for beat in 0..8 {
+//!     let time = MusicalTime::beat(beat, 0, 1).expect("denominator should be non-zero");
+//!     builder.note(time, (PlayerSide::Player1, Key::Key(1), NoteKind::Visible), kick);
+//! }
+//! let bms = builder.build().expect("the chart should be valid");
+//! assert_eq!(bms.notes().all_notes().count(), 8);
+//! ```
+
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    marker::PhantomData,
+    num::NonZeroU64,
+    path::{Path, PathBuf},
+};
+
+use num::Integer;
+use strict_num_extended::{FinF64, NonNegativeF64, PositiveF64};
+use thiserror::Error;
+
+use crate::bms::{
+    command::string_value::StringValue,
+    model::bmp::Bmp,
+    parse::{check_playing::PlayingCheckOutput, validity::ValidityCheckOutput},
+    prelude::*,
+};
+
+/// A position on the score in musical time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MusicalTime {
+    /// A track (measure) plus a fraction of it.
+    Measure(ObjTime),
+    /// `beat + numerator / denominator` beats (quarter notes) from the start of the track `#001`.
+    Beat {
+        /// Whole beats.
+        beat: u64,
+        /// Numerator of the fraction of a beat.
+        numerator: u64,
+        /// Denominator of the fraction of a beat.
+        denominator: NonZeroU64,
+    },
+}
+
+impl MusicalTime {
+    /// Creates a position of `beat + numerator / denominator` beats from the start of the track `#001`.
+    /// Returns `None` if the denominator is zero.
+    #[must_use]
+    pub fn beat(beat: u64, numerator: u64, denominator: u64) -> Option<Self> {
+        Some(Self::Beat {
+            beat,
+            numerator,
+            denominator: NonZeroU64::new(denominator)?,
+        })
+    }
+}
+
+impl From<ObjTime> for MusicalTime {
+    fn from(value: ObjTime) -> Self {
+        Self::Measure(value)
+    }
+}
+
+/// An error occurred when building a [`Bms`].
+#[non_exhaustive]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Error)]
+pub enum BmsBuildError {
+    /// All object ids for the kind of resource were allocated.
+    #[error("object ids for {0} are exhausted")]
+    ObjIdExhausted(&'static str),
+    /// The built score is unplayable.
+    #[error("the built score is unplayable: {0:?}")]
+    Unplayable(Vec<PlayingError>),
+    /// The built score failed the validity check.
+    #[error("the built score is invalid: {0:?}")]
+    Invalid(ValidityCheckOutput),
+}
+
+/// An object waiting for its position to be resolved on build.
+#[derive(Debug, Clone)]
+enum PendingObj {
+    Note {
+        channel_id: NoteChannelId,
+        wav_id: ObjId,
+    },
+    LongNote {
+        end: MusicalTime,
+        side: PlayerSide,
+        key: Key,
+        wav_id: ObjId,
+    },
+    BpmChange(PositiveF64),
+    Stop(NonNegativeF64),
+    Bga {
+        layer: BgaLayer,
+        bmp_id: ObjId,
+    },
+}
+
+/// Issues unused object ids in the order of [`ObjId::all_values`].
+#[derive(Debug, Clone)]
+struct IdIssuer<K> {
+    assigned: HashMap<K, ObjId>,
+    unused: VecDeque<ObjId>,
+}
+
+impl<K: std::hash::Hash + Eq> IdIssuer<K> {
+    fn new() -> Self {
+        Self {
+            assigned: HashMap::new(),
+            unused: ObjId::all_values().collect(),
+        }
+    }
+
+    /// Returns the id assigned to `key` and whether it is newly assigned.
+    fn get_or_issue(&mut self, key: K) -> Option<(ObjId, bool)> {
+        if let Some(&id) = self.assigned.get(&key) {
+            return Some((id, false));
+        }
+        let id = self.unused.pop_front()?;
+        self.assigned.insert(key, id);
+        Some((id, true))
+    }
+}
+
+/// A builder of [`Bms`] from code. Its methods can be chained to place objects.
+///
+/// Notes are placed on the channels of the key layout `T`. Long notes are written in the encoding of the score: a pair ended by the object of `#LNOBJ` if any, or as `#LNTYPE` says otherwise.
+#[must_use]
+pub struct BmsBuilder<T = KeyLayoutBeat> {
+    bms: Bms,
+    wav_ids: IdIssuer<PathBuf>,
+    bmp_ids: IdIssuer<PathBuf>,
+    bpm_ids: IdIssuer<u64>,
+    stop_ids: IdIssuer<u64>,
+    time_signatures: BTreeMap<Track, (NonZeroU64, NonZeroU64)>,
+    pending: Vec<(MusicalTime, PendingObj)>,
+    _phantom: PhantomData<fn() -> T>,
+}
+
+impl<T: KeyLayoutMapper> Default for BmsBuilder<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: KeyLayoutMapper> BmsBuilder<T> {
+    /// Creates a new builder of an empty score.
+    pub fn new() -> Self {
+        Self::from_bms(Bms::default())
+    }
+
+    /// Creates a new builder placing objects on the existing score `bms`. Ids already used by `bms` are not issued again.
+    pub fn from_bms(bms: Bms) -> Self {
+        let mut wav_ids = IdIssuer::new();
+        wav_ids
+            .unused
+            .retain(|id| !bms.wav.wav_files.contains_key(id));
+        let mut bmp_ids = IdIssuer::new();
+        bmp_ids
+            .unused
+            .retain(|id| !bms.bmp.bmp_files.contains_key(id));
+        let mut bpm_ids = IdIssuer::new();
+        bpm_ids
+            .unused
+            .retain(|id| !bms.bpm.bpm_defs.contains_key(id));
+        let mut stop_ids = IdIssuer::new();
+        stop_ids
+            .unused
+            .retain(|id| !bms.stop.stop_defs.contains_key(id));
+        Self {
+            bms,
+            wav_ids,
+            bmp_ids,
+            bpm_ids,
+            stop_ids,
+            time_signatures: BTreeMap::new(),
+            pending: vec![],
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Gets the score being built, to edit the parts this builder does not cover.
+    pub const fn bms_mut(&mut self) -> &mut Bms {
+        &mut self.bms
+    }
+
+    /// Sets the title. `#TITLE`
+    pub fn title(&mut self, title: impl Into<String>) -> &mut Self {
+        self.bms.music_info.title = Some(title.into());
+        self
+    }
+
+    /// Sets the artist. `#ARTIST`
+    pub fn artist(&mut self, artist: impl Into<String>) -> &mut Self {
+        self.bms.music_info.artist = Some(artist.into());
+        self
+    }
+
+    /// Sets the genre. `#GENRE`
+    pub fn genre(&mut self, genre: impl Into<String>) -> &mut Self {
+        self.bms.music_info.genre = Some(genre.into());
+        self
+    }
+
+    /// Sets the judge level. `#RANK`
+    pub const fn rank(&mut self, rank: JudgeLevel) -> &mut Self {
+        self.bms.judge.rank = Some(rank);
+        self
+    }
+
+    /// Sets the gauge total. `#TOTAL`
+    pub fn total(&mut self, total: FinF64) -> &mut Self {
+        self.bms.judge.total = Some(StringValue::from_value(total));
+        self
+    }
+
+    /// Sets the initial BPM. `#BPM`
+    pub fn bpm(&mut self, bpm: PositiveF64) -> &mut Self {
+        self.bms.bpm.bpm = Some(StringValue::from_value(bpm));
+        self
+    }
+
+    /// Sets the time signature `beats / beat_unit` from the track `track` until the next time signature, emitted as the section length `#xxx02`.
+    pub fn time_signature(
+        &mut self,
+        track: Track,
+        beats: NonZeroU64,
+        beat_unit: NonZeroU64,
+    ) -> &mut Self {
+        self.time_signatures.insert(track, (beats, beat_unit));
+        self
+    }
+
+    /// Registers the sound file `path` and returns its id. `#WAVxx`
+    ///
+    /// The same path always gets the same id.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BmsBuildError::ObjIdExhausted`] if all the ids are used.
+    pub fn wav(&mut self, path: impl AsRef<Path>) -> Result<ObjId, BmsBuildError> {
+        let path = path.as_ref().to_path_buf();
+        let (id, is_new) = self
+            .wav_ids
+            .get_or_issue(path.clone())
+            .ok_or(BmsBuildError::ObjIdExhausted("#WAV"))?;
+        if is_new {
+            self.use_id(id);
+            self.bms.wav.wav_files.insert(id, path);
+        }
+        Ok(id)
+    }
+
+    /// Registers the image file `path` and returns its id. `#BMPxx`
+    ///
+    /// The same path always gets the same id.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BmsBuildError::ObjIdExhausted`] if all the ids are used.
+    pub fn bmp(&mut self, path: impl AsRef<Path>) -> Result<ObjId, BmsBuildError> {
+        let path = path.as_ref().to_path_buf();
+        let (id, is_new) = self
+            .bmp_ids
+            .get_or_issue(path.clone())
+            .ok_or(BmsBuildError::ObjIdExhausted("#BMP"))?;
+        if is_new {
+            self.use_id(id);
+            self.bms.bmp.bmp_files.insert(
+                id,
+                Bmp {
+                    file: path,
+                    transparent_color: Argb::default(),
+                },
+            );
+        }
+        Ok(id)
+    }
+
+    /// Places a note of the sound `wav_id` on the lane.
+    ///
+    /// A [`NoteKind::Long`] object placed here is paired with the next one on the lane, prefer [`Self::long_note`] for long notes. A [`NoteKind::Landmine`] object uses `wav_id` as its damage.
+    pub fn note(
+        &mut self,
+        time: impl Into<MusicalTime>,
+        (side, key, kind): (PlayerSide, Key, NoteKind),
+        wav_id: ObjId,
+    ) -> &mut Self {
+        let channel_id = T::new(side, kind, key).to_channel_id();
+        self.pending
+            .push((time.into(), PendingObj::Note { channel_id, wav_id }));
+        self
+    }
+
+    /// Places a long note of the sound `wav_id` from `start` until `end` on the lane.
+    pub fn long_note(
+        &mut self,
+        start: impl Into<MusicalTime>,
+        end: impl Into<MusicalTime>,
+        (side, key): (PlayerSide, Key),
+        wav_id: ObjId,
+    ) -> &mut Self {
+        self.pending.push((
+            start.into(),
+            PendingObj::LongNote {
+                end: end.into(),
+                side,
+                key,
+                wav_id,
+            },
+        ));
+        self
+    }
+
+    /// Places a BGM (auto-played) note of the sound `wav_id`.
+    pub fn bgm(&mut self, time: impl Into<MusicalTime>, wav_id: ObjId) -> &mut Self {
+        self.pending.push((
+            time.into(),
+            PendingObj::Note {
+                channel_id: NoteChannelId::bgm(),
+                wav_id,
+            },
+        ));
+        self
+    }
+
+    /// Changes the BPM into `bpm` at `time`.
+    pub fn bpm_change(&mut self, time: impl Into<MusicalTime>, bpm: PositiveF64) -> &mut Self {
+        self.pending.push((time.into(), PendingObj::BpmChange(bpm)));
+        self
+    }
+
+    /// Stops the scroll at `time` for `duration` in 192nd notes of 4/4 measure.
+    pub fn stop(&mut self, time: impl Into<MusicalTime>, duration: NonNegativeF64) -> &mut Self {
+        self.pending.push((time.into(), PendingObj::Stop(duration)));
+        self
+    }
+
+    /// Shows the image `bmp_id` on the BGA `layer` at `time`.
+    pub fn bga(
+        &mut self,
+        time: impl Into<MusicalTime>,
+        layer: BgaLayer,
+        bmp_id: ObjId,
+    ) -> &mut Self {
+        self.pending
+            .push((time.into(), PendingObj::Bga { layer, bmp_id }));
+        self
+    }
+
+    /// Builds the score, and checks it with [`Bms::check_playing`] and [`Bms::check_validity`].
+    ///
+    /// The initial BPM defaults to 120 if neither it nor any BPM change is specified, and `#PLAYER` is set to match the sides used if not specified.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BmsBuildError`] if ids for BPM or STOP definitions are exhausted, or the checks failed.
+    pub fn build(mut self) -> Result<Bms, BmsBuildError> {
+        let pending = std::mem::take(&mut self.pending);
+        let mut last_track = Track(1);
+        let mut long_notes = vec![];
+        for (time, obj) in pending {
+            let time = self.resolve(time);
+            last_track = last_track.max(time.track());
+            match obj {
+                PendingObj::Note { channel_id, wav_id } => {
+                    self.bms.wav.notes.push_note(WavObj {
+                        offset: time,
+                        channel_id,
+                        wav_id,
+                    });
+                }
+                PendingObj::LongNote {
+                    end,
+                    side,
+                    key,
+                    wav_id,
+                } => {
+                    let end = self.resolve(end);
+                    last_track = last_track.max(end.track());
+                    long_notes.push(LongNote {
+                        side,
+                        key,
+                        start: time,
+                        end,
+                        start_wav_id: wav_id,
+                        end_wav_id: wav_id,
+                        ln_mode: self.bms.repr.ln_mode,
+                        encoding: LongNoteEncoding::Rdm,
+                        objects: vec![],
+                    });
+                }
+                PendingObj::BpmChange(bpm) => {
+                    let (id, is_new) = self
+                        .bpm_ids
+                        .get_or_issue(bpm.as_f64().to_bits())
+                        .ok_or(BmsBuildError::ObjIdExhausted("#BPM"))?;
+                    if is_new {
+                        self.use_id(id);
+                        self.bms
+                            .bpm
+                            .bpm_defs
+                            .insert(id, StringValue::from_value(bpm));
+                    }
+                    self.bms
+                        .bpm
+                        .bpm_changes
+                        .insert(time, BpmChangeObj { time, bpm });
+                }
+                PendingObj::Stop(duration) => {
+                    let (id, is_new) = self
+                        .stop_ids
+                        .get_or_issue(duration.as_f64().to_bits())
+                        .ok_or(BmsBuildError::ObjIdExhausted("#STOP"))?;
+                    if is_new {
+                        self.use_id(id);
+                        self.bms
+                            .stop
+                            .stop_defs
+                            .insert(id, StringValue::from_value(duration));
+                    }
+                    self.bms
+                        .stop
+                        .push_stop_ignore_duplicate(StopObj { time, duration });
+                }
+                PendingObj::Bga { layer, bmp_id } => {
+                    self.bms.bmp.bga_changes.insert(
+                        time,
+                        BgaObj {
+                            time,
+                            id: bmp_id,
+                            layer,
+                        },
+                    );
+                }
+            }
+        }
+
+        // `#LNOBJ` long notes are held on the long note channels as parsing does.
+        let encoding = match self.bms.wav.ln_objects.first() {
+            Some(&end_id) => {
+                for ln in &mut long_notes {
+                    ln.end_wav_id = end_id;
+                }
+                LongNoteEncoding::Rdm
+            }
+            None if self.bms.repr.ln_type == LnType::Mgq => LongNoteEncoding::Mgq,
+            None => LongNoteEncoding::Rdm,
+        };
+        self.bms.push_long_notes::<T>(&long_notes, encoding);
+
+        for track in 0..=last_track.0 {
+            let (beats, beat_unit) = self.time_signature_of(Track(track));
+            if beats == beat_unit {
+                continue;
+            }
+            let length = beats.get() as f64 / beat_unit.get() as f64;
+            let Ok(length) = FinF64::new(length) else {
+                continue;
+            };
+            self.bms.section_len.section_len_changes.insert(
+                Track(track),
+                SectionLenChangeObj {
+                    track: Track(track),
+                    length,
+                },
+            );
+        }
+
+        if self.bms.bpm.bpm.is_none() && self.bms.bpm.bpm_changes.is_empty() {
+            self.bms.bpm.bpm = Some(StringValue::from_value(PositiveF64::new_const(120.0)));
+        }
+        if self.bms.metadata.player.is_none() {
+            let has_player2 = self.bms.wav.notes.all_notes().any(|obj| {
+                T::from_channel_id(obj.channel_id)
+                    .is_some_and(|map| map.side() == PlayerSide::Player2)
+            });
+            self.bms.metadata.player = Some(if has_player2 {
+                PlayerMode::Double
+            } else {
+                PlayerMode::Single
+            });
+        }
+
+        let PlayingCheckOutput { playing_errors, .. } = self.bms.check_playing::<T>();
+        if !playing_errors.is_empty() {
+            return Err(BmsBuildError::Unplayable(playing_errors));
+        }
+        let validity = self.bms.check_validity();
+        if !validity.missing.is_empty() || !validity.invalid.is_empty() {
+            return Err(BmsBuildError::Invalid(validity));
+        }
+        Ok(self.bms)
+    }
+
+    /// Switches the score to `#BASE 62` if `id` is out of base 36.
+    fn use_id(&mut self, id: ObjId) {
+        if !id.is_base36() {
+            self.bms.repr.case_sensitive_obj_id = true;
+        }
+    }
+
+    /// Gets the time signature in effect at `track`, defaults to 4/4.
+    fn time_signature_of(&self, track: Track) -> (NonZeroU64, NonZeroU64) {
+        let four = NonZeroU64::MIN.saturating_add(3);
+        self.time_signatures
+            .range(..=track)
+            .next_back()
+            .map_or((four, four), |(_, &signature)| signature)
+    }
+
+    /// Resolves the position into [`ObjTime`] with the time signatures.
+    fn resolve(&self, time: MusicalTime) -> ObjTime {
+        let (beat, numerator, denominator) = match time {
+            MusicalTime::Measure(time) => return time,
+            MusicalTime::Beat {
+                beat,
+                numerator,
+                denominator,
+            } => (beat, numerator, denominator.get()),
+        };
+        // Remaining beats as the fraction `rest_num / rest_den`.
+        let mut rest_num = u128::from(beat) * u128::from(denominator) + u128::from(numerator);
+        let mut rest_den = u128::from(denominator);
+        let mut track = Track(1);
+        loop {
+            let (beats, beat_unit) = self.time_signature_of(track);
+            // A measure of `beats / beat_unit` lasts `4 * beats / beat_unit` beats.
+            let measure_num = 4 * u128::from(beats.get());
+            let measure_den = u128::from(beat_unit.get());
+            if rest_num * measure_den < measure_num * rest_den {
+                let fraction_num = rest_num * measure_den;
+                let fraction_den = rest_den * measure_num;
+                let gcd = fraction_num.gcd(&fraction_den);
+                return ObjTime::new_checked(
+                    track.0,
+                    u64::try_from(fraction_num / gcd).unwrap_or(u64::MAX),
+                    NonZeroU64::new(u64::try_from(fraction_den / gcd).unwrap_or(u64::MAX))
+                        .unwrap_or(NonZeroU64::MIN),
+                );
+            }
+            rest_num = rest_num * measure_den - measure_num * rest_den;
+            rest_den *= measure_den;
+            let gcd = rest_num.gcd(&rest_den);
+            rest_num /= gcd;
+            rest_den /= gcd;
+            track = Track(track.0 + 1);
+        }
+    }
+}
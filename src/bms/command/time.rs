@@ -127,8 +127,10 @@ impl PartialOrd for ObjTime {
 
 impl Ord for ObjTime {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        let self_time_in_track = self.numerator() * other.denominator().get();
-        let other_time_in_track = other.numerator() * self.denominator().get();
+        let self_time_in_track =
+            u128::from(self.numerator()) * u128::from(other.denominator().get());
+        let other_time_in_track =
+            u128::from(other.numerator()) * u128::from(self.denominator().get());
         self.track()
             .cmp(&other.track())
             .then(self_time_in_track.cmp(&other_time_in_track))
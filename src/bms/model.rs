@@ -1,10 +1,15 @@
 //! Header information from parsed BMS file.
 //! Note objects manager.
 
+pub mod assist;
 pub mod bmp;
 pub mod bpm;
 pub mod control_flow;
+pub mod double_play;
 pub mod judge;
+pub mod key_convert;
+pub mod key_mode;
+pub mod long_note;
 pub mod metadata;
 pub mod music_info;
 pub mod notes;
@@ -18,6 +23,7 @@ pub mod speed;
 pub mod sprite;
 pub mod stop;
 pub mod text;
+pub mod union;
 pub mod video;
 pub mod volume;
 pub mod wav;
@@ -131,16 +137,13 @@ impl Bms {
     /// Fields from `other` overwrite `self` if they are present.
     /// Collections are extended.
     ///
-    /// # Panics
-    ///
-    /// Panics if the stop definition merge fails (which should never happen with `AlwaysUseNewer`).
+    /// Use [`Bms::union_with`] to be prompted about conflicting definitions and events instead.
     pub fn union_inplace(&mut self, other: &Bms) {
+        self.union_headers(other);
+
         // bmp
         self.bmp.bmp_files.extend(other.bmp.bmp_files.clone());
         self.bmp.bga_changes.extend(other.bmp.bga_changes.clone());
-        if other.bmp.poor_bmp.is_some() {
-            self.bmp.poor_bmp.clone_from(&other.bmp.poor_bmp);
-        }
         self.bmp.atbga_defs.extend(other.bmp.atbga_defs.clone());
         self.bmp.bga_defs.extend(other.bmp.bga_defs.clone());
         self.bmp.swbga_events.extend(other.bmp.swbga_events.clone());
@@ -165,16 +168,96 @@ impl Bms {
 
         // bpm
         self.bpm.bpm_changes.extend(other.bpm.bpm_changes.clone());
+        self.bpm.bpm_defs.extend(other.bpm.bpm_defs.clone());
+        self.bpm
+            .bpm_changes_u8
+            .extend(other.bpm.bpm_changes_u8.clone());
+
+        // judge
+        self.judge
+            .exrank_defs
+            .extend(other.judge.exrank_defs.clone());
+        self.judge
+            .judge_events
+            .extend(other.judge.judge_events.clone());
+
+        // option
+        self.option
+            .change_options
+            .extend(other.option.change_options.clone());
+        self.option
+            .option_events
+            .extend(other.option.option_events.clone());
+
+        // scroll
+        self.scroll
+            .scrolling_factor_changes
+            .extend(other.scroll.scrolling_factor_changes.clone());
+        self.scroll
+            .scroll_defs
+            .extend(other.scroll.scroll_defs.clone());
+
+        // section_len
+        self.section_len
+            .section_len_changes
+            .extend(other.section_len.section_len_changes.clone());
+
+        // speed
+        self.speed
+            .speed_factor_changes
+            .extend(other.speed.speed_factor_changes.clone());
+        self.speed.speed_defs.extend(other.speed.speed_defs.clone());
+
+        // stop
+        self.stop.stop_defs.extend(other.stop.stop_defs.clone());
+        self.stop.stp_events.extend(other.stop.stp_events.clone());
+        for stop in other.stop.stops.values() {
+            // `AlwaysUseNewer` never fails.
+            let _ = self.stop.push_stop(stop.clone(), &AlwaysUseNewer);
+        }
+
+        // text
+        self.text.text_events.extend(other.text.text_events.clone());
+
+        // video
+        self.video.seek_defs.extend(other.video.seek_defs.clone());
+        self.video
+            .seek_events
+            .extend(other.video.seek_events.clone());
+
+        // volume
+        self.volume
+            .bgm_volume_changes
+            .extend(other.volume.bgm_volume_changes.clone());
+        self.volume
+            .key_volume_changes
+            .extend(other.volume.key_volume_changes.clone());
+
+        // wav
+        self.wav.wav_files.extend(other.wav.wav_files.clone());
+        for (idx, note) in other.wav.notes.all_entries() {
+            self.wav
+                .notes
+                .push_note_on_grid(note.clone(), other.wav.notes.grid_of(idx));
+        }
+    }
+
+    /// Merges the header fields and the collections which are not indexed by id or time.
+    ///
+    /// Fields from `other` overwrite `self` if they are present.
+    fn union_headers(&mut self, other: &Bms) {
+        // bmp
+        if other.bmp.poor_bmp.is_some() {
+            self.bmp.poor_bmp.clone_from(&other.bmp.poor_bmp);
+        }
+
+        // bpm
         if other.bpm.bpm.is_some() {
             self.bpm.bpm.clone_from(&other.bpm.bpm);
         }
         if other.bpm.base_bpm.is_some() {
             self.bpm.base_bpm.clone_from(&other.bpm.base_bpm);
         }
-        self.bpm.bpm_defs.extend(other.bpm.bpm_defs.clone());
-        self.bpm
-            .bpm_changes_u8
-            .extend(other.bpm.bpm_changes_u8.clone());
 
         // judge
         if other.judge.rank.is_some() {
@@ -183,12 +266,6 @@ impl Bms {
         if other.judge.total.is_some() {
             self.judge.total.clone_from(&other.judge.total);
         }
-        self.judge
-            .exrank_defs
-            .extend(other.judge.exrank_defs.clone());
-        self.judge
-            .judge_events
-            .extend(other.judge.judge_events.clone());
 
         // metadata
         if other.metadata.player.is_some() {
@@ -260,12 +337,6 @@ impl Bms {
         if other.option.options.is_some() {
             self.option.options.clone_from(&other.option.options);
         }
-        self.option
-            .change_options
-            .extend(other.option.change_options.clone());
-        self.option
-            .option_events
-            .extend(other.option.option_events.clone());
 
         // repr
         if other.repr.ln_type != LnType::default() {
@@ -306,25 +377,6 @@ impl Bms {
                 .clone_from(&other.resources.materials_path);
         }
 
-        // scroll
-        self.scroll
-            .scrolling_factor_changes
-            .extend(other.scroll.scrolling_factor_changes.clone());
-        self.scroll
-            .scroll_defs
-            .extend(other.scroll.scroll_defs.clone());
-
-        // section_len
-        self.section_len
-            .section_len_changes
-            .extend(other.section_len.section_len_changes.clone());
-
-        // speed
-        self.speed
-            .speed_factor_changes
-            .extend(other.speed.speed_factor_changes.clone());
-        self.speed.speed_defs.extend(other.speed.speed_defs.clone());
-
         // sprite
         if other.sprite.back_bmp.is_some() {
             self.sprite.back_bmp.clone_from(&other.sprite.back_bmp);
@@ -342,18 +394,6 @@ impl Bms {
             self.sprite.char_file.clone_from(&other.sprite.char_file);
         }
 
-        // stop
-        self.stop.stop_defs.extend(other.stop.stop_defs.clone());
-        self.stop.stp_events.extend(other.stop.stp_events.clone());
-        for stop in other.stop.stops.values() {
-            self.stop
-                .push_stop(stop.clone(), &AlwaysUseNewer)
-                .expect("AlwaysUseNewer should never error");
-        }
-
-        // text
-        self.text.text_events.extend(other.text.text_events.clone());
-
         // video
         if other.video.video_file.is_some() {
             self.video.video_file.clone_from(&other.video.video_file);
@@ -367,24 +407,11 @@ impl Bms {
         if other.video.video_fs.is_some() {
             self.video.video_fs.clone_from(&other.video.video_fs);
         }
-        self.video.seek_defs.extend(other.video.seek_defs.clone());
-        self.video
-            .seek_events
-            .extend(other.video.seek_events.clone());
-
-        // volume
-        self.volume
-            .bgm_volume_changes
-            .extend(other.volume.bgm_volume_changes.clone());
-        self.volume
-            .key_volume_changes
-            .extend(other.volume.key_volume_changes.clone());
 
         // wav
-        self.wav.wav_files.extend(other.wav.wav_files.clone());
-        for note in other.wav.notes.all_notes() {
-            self.wav.notes.push_note(note.clone());
-        }
+        self.wav
+            .ln_objects
+            .extend(other.wav.ln_objects.iter().copied());
 
         // randomized
         self.randomized.extend(other.randomized.clone());
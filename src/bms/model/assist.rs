@@ -0,0 +1,138 @@
+//! Assist options which make a score easier to play.
+//!
+//! Each option is applied by [`Bms::apply_assist`] or [`crate::chart::Chart::apply_assist`], and
+//! reports how many notes it changed in an [`AssistReport`], so a play using it can be told apart.
+
+use std::collections::HashSet;
+
+use crate::bms::prelude::*;
+
+/// An assist option.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum Assist {
+    /// Notes on the scratch lanes become BGM, so they still sound.
+    AutoScratch,
+    /// Long notes become single notes at their starts.
+    LegacyNote,
+    /// Landmines are removed.
+    NoLandmine,
+    /// The keys 6 and 7 of 7K are folded into 5K with the strategy.
+    FiveKeys(FoldStrategy),
+}
+
+/// How many notes an [`Assist`] changed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[must_use]
+pub struct AssistReport {
+    /// The applied assist option.
+    pub assist: Assist,
+    /// Number of notes, counting a long note as one, moved, removed or turned into BGM or single notes.
+    pub changed: usize,
+}
+
+impl AssistReport {
+    /// Returns whether the assist option changed the score, so the play should be marked as assisted.
+    #[must_use]
+    pub const fn is_assisted(&self) -> bool {
+        self.changed > 0
+    }
+}
+
+impl Bms {
+    /// Applies `assist` to the notes on the channels of `T`.
+    pub fn apply_assist<T: KeyLayoutMapper>(&mut self, assist: Assist) -> AssistReport {
+        let changed = match assist {
+            Assist::AutoScratch => self.scratch_to_bgm::<T>(),
+            Assist::LegacyNote => self.remove_long_notes::<T>(),
+            Assist::NoLandmine => self.remove_landmines::<T>(),
+            Assist::FiveKeys(strategy) => {
+                let output = self
+                    .relane::<T, T>(|side, key| strategy.fold(key).map(|folded| (side, folded)));
+                output.moved + output.unplaced.len()
+            }
+        };
+        AssistReport { assist, changed }
+    }
+
+    /// Turns the notes on the scratch lanes into BGM, and returns how many were turned.
+    ///
+    /// Only the keysounds are kept, that are visible notes and starts of long notes. Invisible notes,
+    /// landmines and the rest of long notes are removed.
+    fn scratch_to_bgm<T: KeyLayoutMapper>(&mut self) -> usize {
+        let mut ln_rests = HashSet::new();
+        for ln in self.long_notes::<T>().long_notes {
+            if matches!(ln.key, Key::Scratch(_)) {
+                ln_rests.extend(ln.objects.into_iter().skip(1));
+            }
+        }
+
+        let mut keysounds = vec![];
+        let mut removing = vec![];
+        let mut changed = 0;
+        for (idx, obj) in self.wav.notes.all_entries() {
+            if obj.wav_id.is_null() {
+                continue;
+            }
+            let Some(layout) = T::from_channel_id(obj.channel_id) else {
+                continue;
+            };
+            if !matches!(layout.key(), Key::Scratch(_)) {
+                continue;
+            }
+            if ln_rests.contains(&idx) {
+                removing.push(idx);
+            } else if matches!(layout.kind(), NoteKind::Invisible | NoteKind::Landmine) {
+                removing.push(idx);
+                changed += 1;
+            } else {
+                keysounds.push(idx);
+                changed += 1;
+            }
+        }
+        for idx in removing {
+            self.wav.notes.pop_by_idx(idx);
+        }
+        self.wav
+            .notes
+            .change_note_channel(keysounds, NoteChannelId::bgm());
+        changed
+    }
+
+    /// Turns long notes into visible notes at their starts, and returns how many were turned.
+    fn remove_long_notes<T: KeyLayoutMapper>(&mut self) -> usize {
+        let long_notes = self.long_notes::<T>().long_notes;
+        for ln in &long_notes {
+            let mut objects = ln.objects.iter().copied();
+            if let Some(start) = objects.next() {
+                let visible = T::new(ln.side, NoteKind::Visible, ln.key).to_channel_id();
+                self.wav.notes.change_note_channel([start], visible);
+            }
+            for rest in objects {
+                self.wav.notes.pop_by_idx(rest);
+            }
+        }
+        long_notes.len()
+    }
+
+    /// Removes landmines, and returns how many were removed.
+    fn remove_landmines<T: KeyLayoutMapper>(&mut self) -> usize {
+        let landmines: Vec<_> = self
+            .wav
+            .notes
+            .all_entries()
+            .filter(|(_, obj)| {
+                !obj.wav_id.is_null()
+                    && T::from_channel_id(obj.channel_id)
+                        .is_some_and(|layout| layout.kind() == NoteKind::Landmine)
+            })
+            .map(|(idx, _)| idx)
+            .collect();
+        for &idx in &landmines {
+            self.wav.notes.pop_by_idx(idx);
+        }
+        landmines.len()
+    }
+}
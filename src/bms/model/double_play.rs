@@ -0,0 +1,298 @@
+//! Conversion between single play and double play scores on [`KeyLayoutBeat`].
+//!
+//! [`Bms::combine_double_play`] places two single play scores on both sides of a `#PLAYER 3` score, and
+//! [`Bms::split_double_play`] splits a double play score into the scores of each side.
+
+use std::collections::{HashMap, HashSet};
+
+use thiserror::Error;
+
+use crate::bms::prelude::*;
+
+/// A problem found while combining single play scores.
+#[non_exhaustive]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Error)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DoublePlayWarning {
+    /// The BPM, stops or section lengths of the 2P score differ from the 1P score, so they were ignored.
+    #[error("Timing of the 2P score differs from the 1P score")]
+    TimingMismatch,
+    /// A note was already on the 2P side of a single play score, so its keysound was kept as BGM.
+    #[error("Note at {time:?} is on the 2P side of a single play score (key={key:?})")]
+    OffSideNote {
+        /// Key of the lane.
+        key: Key,
+        /// Time of the note.
+        time: ObjTime,
+    },
+}
+
+/// An error on combining single play scores.
+#[non_exhaustive]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Error)]
+pub enum DoublePlayError {
+    /// No id is left to remap a definition of the 2P score.
+    #[error("No object id is left for #{0} definitions")]
+    ObjIdExhausted(&'static str),
+}
+
+/// Output of combining single play scores.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[must_use]
+pub struct DoublePlayOutput {
+    /// The combined double play score.
+    pub bms: Bms,
+    /// List of [`DoublePlayWarning`]s.
+    pub double_play_warnings: Vec<DoublePlayWarning>,
+}
+
+impl Bms {
+    /// Combines two single play scores into a `#PLAYER 3` score, with `player2` on the 2P side.
+    ///
+    /// Headers and timing are taken from `player1`. Sounds and images of `player2` are merged into
+    /// the ones of `player1`, reusing the id of the same file or issuing an unused id. BGM objects of
+    /// `player2` which are the same as the ones of `player1` are not duplicated, and BGA changes of
+    /// `player2` are only placed where `player1` has none, so passing a score twice makes a double
+    /// play score of it.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DoublePlayError::ObjIdExhausted`] if no id is left to remap a definition of `player2`.
+    pub fn combine_double_play(
+        player1: &Bms,
+        player2: &Bms,
+    ) -> Result<DoublePlayOutput, DoublePlayError> {
+        let mut double_play_warnings = vec![];
+        if !same_timing(player1, player2) {
+            double_play_warnings.push(DoublePlayWarning::TimingMismatch);
+        }
+
+        let mut bms = player1.clone();
+        let mut incoming = player2.clone();
+        if incoming.repr.ln_type != bms.repr.ln_type {
+            let encoding = match bms.repr.ln_type {
+                LnType::Rdm => LongNoteEncoding::Rdm,
+                LnType::Mgq => LongNoteEncoding::Mgq,
+            };
+            let _ = incoming.encode_long_notes::<KeyLayoutBeat>(encoding);
+        }
+        for score in [&mut bms, &mut incoming] {
+            double_play_warnings.extend(
+                score
+                    .side_to_bgm(PlayerSide::Player2)
+                    .into_iter()
+                    .map(|(key, time)| DoublePlayWarning::OffSideNote { key, time }),
+            );
+        }
+        incoming.move_side(PlayerSide::Player1, PlayerSide::Player2);
+
+        let wav_ids = remap_defs(
+            &mut bms.wav.wav_files,
+            &incoming.wav.wav_files,
+            &mut bms.repr.case_sensitive_obj_id,
+        )
+        .ok_or(DoublePlayError::ObjIdExhausted("WAV"))?;
+        let bmp_ids = remap_defs(
+            &mut bms.bmp.bmp_files,
+            &incoming.bmp.bmp_files,
+            &mut bms.repr.case_sensitive_obj_id,
+        )
+        .ok_or(DoublePlayError::ObjIdExhausted("BMP"))?;
+        let remap = |ids: &HashMap<ObjId, ObjId>, id: ObjId| ids.get(&id).copied().unwrap_or(id);
+
+        let bgms: HashSet<_> = bms
+            .notes()
+            .bgms::<KeyLayoutBeat>()
+            .map(|obj| (obj.offset, obj.wav_id))
+            .collect();
+        // `#LNOBJ` ends of 2P are already on the long note channels, so its ids are not merged.
+        for (idx, obj) in incoming.wav.notes.all_entries() {
+            if obj.wav_id.is_null() {
+                continue;
+            }
+            let wav_id = remap(&wav_ids, obj.wav_id);
+            if obj.channel_id == NoteChannelId::bgm() && bgms.contains(&(obj.offset, wav_id)) {
+                continue;
+            }
+            let note = WavObj {
+                wav_id,
+                ..obj.clone()
+            };
+            bms.wav
+                .notes
+                .push_note_on_grid(note, incoming.wav.notes.grid_of(idx));
+        }
+        for bga in incoming.bmp.bga_changes.into_values() {
+            bms.bmp
+                .bga_changes
+                .entry(bga.time)
+                .or_insert_with(|| BgaObj {
+                    id: remap(&bmp_ids, bga.id),
+                    ..bga
+                });
+        }
+
+        bms.metadata.player = Some(PlayerMode::Double);
+        Ok(DoublePlayOutput {
+            bms,
+            double_play_warnings,
+        })
+    }
+
+    /// Splits a double play score into the single play scores of 1P and 2P.
+    ///
+    /// The notes of the other side are removed from each score, but their keysounds are kept as BGM.
+    /// The notes of 2P are moved onto the 1P side. Definitions are shared with the original score.
+    #[must_use]
+    pub fn split_double_play(&self) -> (Bms, Bms) {
+        let mut player1 = self.clone();
+        let _ = player1.side_to_bgm(PlayerSide::Player2);
+        player1.metadata.player = Some(PlayerMode::Single);
+
+        let mut player2 = self.clone();
+        let _ = player2.side_to_bgm(PlayerSide::Player1);
+        player2.move_side(PlayerSide::Player2, PlayerSide::Player1);
+        player2.metadata.player = Some(PlayerMode::Single);
+
+        (player1, player2)
+    }
+
+    /// Turns the notes on `side` into BGM, and returns their lanes and times.
+    ///
+    /// Only the keysounds are kept, that are visible notes and starts of long notes. Invisible notes,
+    /// landmines and the rest of long notes are removed.
+    fn side_to_bgm(&mut self, side: PlayerSide) -> Vec<(Key, ObjTime)> {
+        let mut ln_rests = HashSet::new();
+        for ln in self.long_notes::<KeyLayoutBeat>().long_notes {
+            if ln.side == side {
+                ln_rests.extend(ln.objects.into_iter().skip(1));
+            }
+        }
+
+        let mut keysounds = vec![];
+        let mut removing = vec![];
+        let mut moved = vec![];
+        for (idx, obj) in self.wav.notes.all_entries() {
+            let Some(layout) = KeyLayoutBeat::from_channel_id(obj.channel_id) else {
+                continue;
+            };
+            if layout.side() != side {
+                continue;
+            }
+            if ln_rests.contains(&idx)
+                || matches!(layout.kind(), NoteKind::Invisible | NoteKind::Landmine)
+            {
+                removing.push(idx);
+            } else {
+                keysounds.push(idx);
+                moved.push((layout.key(), obj.offset));
+            }
+        }
+        for idx in removing {
+            self.wav.notes.pop_by_idx(idx);
+        }
+        self.wav
+            .notes
+            .change_note_channel(keysounds, NoteChannelId::bgm());
+        moved
+    }
+
+    /// Moves the notes on `from` side onto `to` side, keeping their keys and kinds.
+    fn move_side(&mut self, from: PlayerSide, to: PlayerSide) {
+        let moving: Vec<_> = self
+            .wav
+            .notes
+            .all_entries()
+            .filter_map(|(idx, obj)| {
+                let layout = KeyLayoutBeat::from_channel_id(obj.channel_id)?;
+                (layout.side() == from).then(|| {
+                    let dst = KeyLayoutBeat::new(to, layout.kind(), layout.key()).to_channel_id();
+                    (idx, dst)
+                })
+            })
+            .collect();
+        for (idx, dst) in moving {
+            self.wav.notes.change_note_channel([idx], dst);
+        }
+    }
+}
+
+/// Returns whether the BPM, stops and section lengths are the same.
+fn same_timing(a: &Bms, b: &Bms) -> bool {
+    let bpm = |bms: &Bms| {
+        bms.bpm
+            .bpm
+            .as_ref()
+            .and_then(|bpm| bpm.value().as_ref().ok().copied())
+    };
+    bpm(a) == bpm(b)
+        && a.bpm
+            .bpm_changes
+            .values()
+            .map(|change| (change.time, change.bpm))
+            .eq(b
+                .bpm
+                .bpm_changes
+                .values()
+                .map(|change| (change.time, change.bpm)))
+        && a.stop
+            .stops
+            .values()
+            .map(|stop| (stop.time, stop.duration))
+            .eq(b.stop.stops.values().map(|stop| (stop.time, stop.duration)))
+        && a.section_len
+            .section_len_changes
+            .values()
+            .map(|change| (change.track, change.length))
+            .eq(b
+                .section_len
+                .section_len_changes
+                .values()
+                .map(|change| (change.track, change.length)))
+}
+
+/// Merges the definitions of `source` into `target`, and returns how the ids of `source` are remapped.
+///
+/// An id keeps the id of the same definition in `target`, or itself if it is not used in `target`, or
+/// an unused id otherwise. Returns `None` if no id is left.
+fn remap_defs<T: Clone + PartialEq>(
+    target: &mut HashMap<ObjId, T>,
+    source: &HashMap<ObjId, T>,
+    case_sensitive_obj_id: &mut bool,
+) -> Option<HashMap<ObjId, ObjId>> {
+    let mut ids: Vec<_> = source.keys().copied().collect();
+    ids.sort_unstable();
+    let mut unused = ObjId::all_values()
+        .filter(|id| !target.contains_key(id) && !source.contains_key(id))
+        .collect::<Vec<_>>()
+        .into_iter();
+    let mut remapped = HashMap::new();
+    for id in ids {
+        let Some(def) = source.get(&id) else {
+            continue;
+        };
+        let same = if target.get(&id) == Some(def) {
+            Some(id)
+        } else {
+            let mut same_ids: Vec<_> = target
+                .iter()
+                .filter(|&(_, existing)| existing == def)
+                .map(|(&existing_id, _)| existing_id)
+                .collect();
+            same_ids.sort_unstable();
+            same_ids.first().copied()
+        };
+        let new_id = match same {
+            Some(same) => same,
+            None if !target.contains_key(&id) => id,
+            None => {
+                let new_id = unused.next()?;
+                *case_sensitive_obj_id |= !new_id.is_base36();
+                new_id
+            }
+        };
+        target.entry(new_id).or_insert_with(|| def.clone());
+        remapped.insert(id, new_id);
+    }
+    Some(remapped)
+}
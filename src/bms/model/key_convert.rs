@@ -0,0 +1,40 @@
+//! Applying [`KeyConverter`]s and [`PlayerSideKeyConverter`]s to all the notes of a score.
+//!
+//! The converters are asked once for each note, counting a long note as one, so both ends of a
+//! long note stay on the same lane even if the converter is not deterministic. Landmines and
+//! invisible notes are asked for separately.
+
+use crate::bms::prelude::*;
+
+impl Bms {
+    /// Moves the notes on `side` of the channels of `T` onto the keys which `converter` returns.
+    ///
+    /// The notes on the other side are kept, so a different option can be applied to each side of a
+    /// double play score. A note which overlaps another note on its new lane, or which `T` has no
+    /// channel for, becomes BGM.
+    pub fn convert_keys<T: KeyLayoutMapper>(
+        &mut self,
+        side: PlayerSide,
+        converter: &mut impl KeyConverter,
+    ) -> RelaneOutput {
+        self.relane::<T, T>(|note_side, key| {
+            let key = if note_side == side {
+                converter.convert(key)
+            } else {
+                key
+            };
+            Some((note_side, key))
+        })
+    }
+
+    /// Moves the notes on the channels of `T` onto the lanes which `converter` returns.
+    ///
+    /// A note which overlaps another note on its new lane, or which `T` has no channel for, becomes
+    /// BGM.
+    pub fn convert_player_side_keys<T: KeyLayoutMapper>(
+        &mut self,
+        converter: &mut impl PlayerSideKeyConverter,
+    ) -> RelaneOutput {
+        self.relane::<T, T>(|side, key| Some(converter.convert((side, key))))
+    }
+}
@@ -0,0 +1,279 @@
+//! Conversion of notes between key modes and channel layouts.
+//!
+//! - [`Bms::rechannel`] moves notes from the channels of a [`KeyLayoutMapper`] onto the channels of another one, such as between [`KeyLayoutPms`] and [`KeyLayoutPmsBmeType`].
+//! - [`Bms::fold_7k_to_5k`] folds the keys 6 and 7 of 7K/14K into 5K/10K by a [`FoldStrategy`].
+//! - [`Bms::spread_5k_to_7k`] places the keys of 5K/10K into 7K/14K by a [`SpreadStrategy`].
+//!
+//! Long notes are moved as a whole. A note which cannot be placed, because the destination has no channel for it or the destination lane is already occupied, is turned into BGM. Only its keysound is kept, so invisible notes, landmines and the rest of long notes are removed.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::bms::prelude::*;
+
+/// How to fold the keys 6 and 7 of 7K into 5K.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum FoldStrategy {
+    /// Notes on the keys 6 and 7 become BGM.
+    #[default]
+    Bgm,
+    /// The keys 6 and 7 are both folded onto the key 5.
+    Adjacent,
+    /// The keys 6 and 7 are folded onto the keys 4 and 5.
+    Pairwise,
+    /// The keys 6 and 7 are folded onto the keys 2 and 1, as the lanes are mirrored.
+    Mirrored,
+}
+
+impl FoldStrategy {
+    /// Returns the key of 5K which the `key` of 7K is folded onto.
+    #[must_use]
+    pub const fn fold(self, key: Key) -> Option<Key> {
+        match (self, key) {
+            (Self::Bgm, Key::Key(6 | 7)) => None,
+            (Self::Adjacent, Key::Key(6 | 7)) | (Self::Pairwise, Key::Key(7)) => Some(Key::Key(5)),
+            (Self::Pairwise, Key::Key(6)) => Some(Key::Key(4)),
+            (Self::Mirrored, Key::Key(6)) => Some(Key::Key(2)),
+            (Self::Mirrored, Key::Key(7)) => Some(Key::Key(1)),
+            _ => Some(key),
+        }
+    }
+}
+
+/// Where to place the keys 1 to 5 of 5K in 7K.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum SpreadStrategy {
+    /// The keys stay on the keys 1 to 5.
+    #[default]
+    Left,
+    /// The keys move onto the keys 2 to 6.
+    Center,
+    /// The keys move onto the keys 3 to 7.
+    Right,
+}
+
+impl SpreadStrategy {
+    /// Returns the key of 7K which the `key` of 5K is placed onto.
+    #[must_use]
+    pub const fn spread(self, key: Key) -> Key {
+        match (self, key) {
+            (Self::Center, Key::Key(n @ 1..=5)) => Key::Key(n + 1),
+            (Self::Right, Key::Key(n @ 1..=5)) => Key::Key(n + 2),
+            _ => key,
+        }
+    }
+}
+
+/// A note which could not be placed and was turned into BGM.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UnplacedNote {
+    /// Player side of the original lane.
+    pub side: PlayerSide,
+    /// Key of the original lane.
+    pub key: Key,
+    /// Kind of the note.
+    pub kind: NoteKind,
+    /// Time of the note, or the start of the long note.
+    pub time: ObjTime,
+}
+
+/// Output of moving notes between lanes.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[must_use]
+pub struct RelaneOutput {
+    /// Number of notes, counting a long note as one, moved to another channel.
+    pub moved: usize,
+    /// Notes turned into BGM, sorted by time.
+    pub unplaced: Vec<UnplacedNote>,
+}
+
+/// A note, or all the objects of a long note, moved together.
+struct Unit {
+    /// The objects with their kinds and channels, sorted by time.
+    objects: Vec<(WavObjArenaIndex, NoteKind, NoteChannelId)>,
+    side: PlayerSide,
+    key: Key,
+    kind: NoteKind,
+    start: ObjTime,
+    end: ObjTime,
+}
+
+impl Bms {
+    /// Moves the notes on the channels of `From` onto the channels of `To`, keeping their lanes.
+    ///
+    /// Notes on the lanes which `To` has no channels for become BGM.
+    pub fn rechannel<From, To>(&mut self) -> RelaneOutput
+    where
+        From: KeyLayoutMapper,
+        To: KeyLayoutMapper,
+    {
+        self.relane::<From, To>(|side, key| Some((side, key)))
+    }
+
+    /// Folds 7K (or 14K) notes on [`KeyLayoutBeat`] into 5K (or 10K) with `strategy`.
+    pub fn fold_7k_to_5k(&mut self, strategy: FoldStrategy) -> RelaneOutput {
+        self.relane::<KeyLayoutBeat, KeyLayoutBeat>(|side, key| {
+            strategy.fold(key).map(|folded| (side, folded))
+        })
+    }
+
+    /// Places 5K (or 10K) notes on [`KeyLayoutBeat`] into 7K (or 14K) with `strategy`.
+    pub fn spread_5k_to_7k(&mut self, strategy: SpreadStrategy) -> RelaneOutput {
+        self.relane::<KeyLayoutBeat, KeyLayoutBeat>(|side, key| Some((side, strategy.spread(key))))
+    }
+
+    /// Moves the notes on the channels of `From` onto the lanes which `map` returns, on the channels of `To`.
+    ///
+    /// A note which `map` returns `None` for, which `To` has no channel for, or which overlaps another
+    /// note on the destination lane, becomes BGM. Notes staying on their lanes are placed first, then
+    /// moving notes are placed in order of time.
+    pub(crate) fn relane<From, To>(
+        &mut self,
+        mut map: impl FnMut(PlayerSide, Key) -> Option<(PlayerSide, Key)>,
+    ) -> RelaneOutput
+    where
+        From: KeyLayoutMapper,
+        To: KeyLayoutMapper,
+    {
+        let units = self.lane_units::<From>();
+
+        // Destination of each unit, `None` if it cannot be placed.
+        let destinations: Vec<_> = units
+            .iter()
+            .map(|unit| {
+                let (side, key) = map(unit.side, unit.key)?;
+                let channel_id = To::new(side, unit.kind, key).to_channel_id();
+                let round_trip = To::from_channel_id(channel_id)?;
+                (round_trip.as_tuple() == (side, unit.kind, key)).then_some((side, key))
+            })
+            .collect();
+        let stays = |unit: &Unit, destination: Option<(PlayerSide, Key)>| {
+            destination == Some((unit.side, unit.key))
+        };
+
+        // Intervals occupied in each lane, excluding their ends so that a note may be placed where a long
+        // note ends. Invisible notes do not collide with the others.
+        let mut occupied: HashMap<(PlayerSide, Key, bool), Vec<(ObjTime, ObjTime)>> =
+            HashMap::new();
+        let mut order: Vec<_> = (0..units.len()).collect();
+        order.sort_by_key(|&i| {
+            let unit = units.get(i)?;
+            let destination = destinations.get(i).copied().flatten();
+            Some((!stays(unit, destination), unit.start))
+        });
+        let mut output = RelaneOutput::default();
+        let mut unplaced = HashSet::new();
+        for i in order {
+            let (Some(unit), Some(&destination)) = (units.get(i), destinations.get(i)) else {
+                continue;
+            };
+            let placed = destination.filter(|&(side, key)| {
+                let lane = occupied
+                    .entry((side, key, unit.kind == NoteKind::Invisible))
+                    .or_default();
+                let overlaps = lane.iter().any(|&(start, end)| {
+                    start == unit.start || (start < unit.end && unit.start < end)
+                });
+                if !overlaps {
+                    lane.push((unit.start, unit.end));
+                }
+                !overlaps
+            });
+            let Some((side, key)) = placed else {
+                unplaced.insert(i);
+                continue;
+            };
+            let mut moved = false;
+            for &(idx, kind, channel_id) in &unit.objects {
+                let dst = To::new(side, kind, key).to_channel_id();
+                if dst != channel_id {
+                    self.wav.notes.change_note_channel([idx], dst);
+                    moved = true;
+                }
+            }
+            output.moved += usize::from(moved);
+        }
+
+        for (i, unit) in units.iter().enumerate() {
+            if !unplaced.contains(&i) {
+                continue;
+            }
+            output.unplaced.push(UnplacedNote {
+                side: unit.side,
+                key: unit.key,
+                kind: unit.kind,
+                time: unit.start,
+            });
+            let mut objects = unit.objects.iter().map(|&(idx, _, _)| idx);
+            if let Some(first) = objects.next() {
+                if matches!(unit.kind, NoteKind::Visible | NoteKind::Long) {
+                    self.wav
+                        .notes
+                        .change_note_channel([first], NoteChannelId::bgm());
+                } else {
+                    self.wav.notes.pop_by_idx(first);
+                }
+            }
+            for rest in objects {
+                self.wav.notes.pop_by_idx(rest);
+            }
+        }
+        output
+    }
+
+    /// Collects the notes on the lanes of `T` into units, sorted by time.
+    fn lane_units<T: KeyLayoutMapper>(&self) -> Vec<Unit> {
+        let channels: HashMap<_, _> = self
+            .wav
+            .notes
+            .all_entries()
+            .filter_map(|(idx, obj)| {
+                let kind = T::from_channel_id(obj.channel_id)?.kind();
+                Some((idx, (kind, obj.channel_id)))
+            })
+            .collect();
+        let mut units = vec![];
+        let mut in_long_notes = HashSet::new();
+        for ln in self.long_notes::<T>().long_notes {
+            in_long_notes.extend(ln.objects.iter().copied());
+            units.push(Unit {
+                objects: ln
+                    .objects
+                    .into_iter()
+                    .filter_map(|idx| {
+                        let &(kind, channel_id) = channels.get(&idx)?;
+                        Some((idx, kind, channel_id))
+                    })
+                    .collect(),
+                side: ln.side,
+                key: ln.key,
+                kind: NoteKind::Long,
+                start: ln.start,
+                end: ln.end,
+            });
+        }
+        for (idx, obj) in self.wav.notes.all_entries() {
+            if in_long_notes.contains(&idx) || obj.wav_id.is_null() {
+                continue;
+            }
+            let Some(layout) = T::from_channel_id(obj.channel_id) else {
+                continue;
+            };
+            units.push(Unit {
+                objects: vec![(idx, layout.kind(), obj.channel_id)],
+                side: layout.side(),
+                key: layout.key(),
+                kind: layout.kind(),
+                start: obj.offset,
+                end: obj.offset,
+            });
+        }
+        units.sort_by_key(|unit| unit.start);
+        units
+    }
+}
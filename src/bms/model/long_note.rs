@@ -0,0 +1,523 @@
+//! Resolution of long notes from their encodings in the score.
+//!
+//! BMS has three ways to write a long note:
+//!
+//! - `#LNTYPE 1` (RDM): a pair of objects on a long note channel (`5x`/`6x`), the first one is the start and the second one is the end.
+//! - `#LNTYPE 2` (MGQ): a run of consecutive objects on a long note channel, the long note lasts while the objects continue.
+//! - `#LNOBJ xx`: an object of `xx` on a visible channel ends the long note started by the preceding object in the same lane.
+//!
+//! [`Notes::long_notes`] resolves all of them into [`LongNote`]s, and [`Bms::encode_long_notes`] rewrites them into one of the encodings.
+
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    num::NonZeroU64,
+};
+
+use num::Integer;
+use thiserror::Error;
+
+use crate::bms::prelude::*;
+
+/// An encoding of long notes on the score.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LongNoteEncoding {
+    /// A pair of start and end objects on a long note channel. `#LNTYPE 1`
+    Rdm,
+    /// A run of consecutive objects on a long note channel. `#LNTYPE 2`
+    Mgq,
+    /// A visible object followed by the end object of the id in the same lane. `#LNOBJ`
+    LnObj(ObjId),
+}
+
+/// A long note resolved from the score.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LongNote {
+    /// The player side of the lane.
+    pub side: PlayerSide,
+    /// The key of the lane.
+    pub key: Key,
+    /// The time the long note starts.
+    pub start: ObjTime,
+    /// The time the long note ends.
+    pub end: ObjTime,
+    /// The `#WAVxx` id rung on the start.
+    pub start_wav_id: ObjId,
+    /// The `#WAVxx` id of the end object.
+    pub end_wav_id: ObjId,
+    /// The long note mode from `#LNMODE`.
+    pub ln_mode: LnMode,
+    /// The encoding the long note was written in.
+    pub encoding: LongNoteEncoding,
+    /// The objects forming the long note, sorted by time.
+    pub objects: Vec<WavObjArenaIndex>,
+}
+
+/// A problem found while resolving long notes.
+#[non_exhaustive]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Error)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LongNoteWarning {
+    /// A long note start has no end object in the lane.
+    #[error("Unterminated long note at {start:?} (side={side:?}, key={key:?})")]
+    Unterminated {
+        /// Player side of the lane.
+        side: PlayerSide,
+        /// Key of the lane.
+        key: Key,
+        /// Time of the start object.
+        start: ObjTime,
+    },
+    /// An `#LNOBJ` end object has no preceding object to start from in the lane.
+    #[error("LNOBJ end object {end_wav_id:?} at {end:?} has no start (side={side:?}, key={key:?})")]
+    LnObjWithoutStart {
+        /// Player side of the lane.
+        side: PlayerSide,
+        /// Key of the lane.
+        key: Key,
+        /// Time of the end object.
+        end: ObjTime,
+        /// The `#LNOBJ` id of the end object.
+        end_wav_id: ObjId,
+    },
+    /// A long note overlaps another long note or a single note in the same lane.
+    #[error(
+        "Long note [{ln_start:?}..{ln_end:?}] overlaps another note at {time:?} (side={side:?}, key={key:?})"
+    )]
+    Overlapping {
+        /// Player side of the lane.
+        side: PlayerSide,
+        /// Key of the lane.
+        key: Key,
+        /// Start time of the long note.
+        ln_start: ObjTime,
+        /// End time of the long note.
+        ln_end: ObjTime,
+        /// Time of the overlapping note.
+        time: ObjTime,
+    },
+}
+
+/// Output of resolving long notes.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[must_use]
+pub struct LongNotesOutput {
+    /// Resolved long notes, sorted by the start time.
+    pub long_notes: Vec<LongNote>,
+    /// List of [`LongNoteWarning`]s.
+    pub long_note_warnings: Vec<LongNoteWarning>,
+}
+
+/// Objects of a lane, split into the long note channel and the visible channel.
+#[derive(Default)]
+struct LaneObjects<'a> {
+    long: Vec<(WavObjArenaIndex, &'a WavObj)>,
+    visible: Vec<(WavObjArenaIndex, &'a WavObj)>,
+}
+
+impl Notes {
+    /// Resolves the long notes on the score.
+    ///
+    /// Objects on long note channels are read as `ln_type` describes. Objects of `ln_objects` end the long note started by the preceding object in the same lane, on either the long note channel, where parsing moves them, or the visible channel.
+    ///
+    /// # MGQ end time
+    ///
+    /// An object of MGQ fills the cell of the message it was written on, from [`Notes::grid_of`], and a run of objects in consecutive cells is one long note ending at the end of the cell of the last object. An object without the grid, such as pushed by [`Notes::push_note`], fills the cell of its reduced time, so `1/2` lasts until the end of the track.
+    pub fn long_notes<T>(
+        &self,
+        ln_type: LnType,
+        ln_mode: LnMode,
+        ln_objects: &BTreeSet<ObjId>,
+    ) -> LongNotesOutput
+    where
+        T: KeyLayoutMapper,
+    {
+        let mut lanes: HashMap<(PlayerSide, Key), LaneObjects> = HashMap::new();
+        for (idx, obj) in self.all_entries() {
+            if obj.wav_id.is_null() {
+                continue;
+            }
+            let Some(map) = T::from_channel_id(obj.channel_id) else {
+                continue;
+            };
+            let lane = lanes.entry((map.side(), map.key())).or_default();
+            match map.kind() {
+                NoteKind::Long => lane.long.push((idx, obj)),
+                NoteKind::Visible => lane.visible.push((idx, obj)),
+                NoteKind::Invisible | NoteKind::Landmine => {}
+            }
+        }
+        let mut lanes: Vec<_> = lanes.into_iter().collect();
+        lanes.sort_unstable_by_key(|&((side, key), _)| {
+            T::new(side, NoteKind::Visible, key).to_channel_id()
+        });
+        let grid_of = |idx: WavObjArenaIndex, obj: &WavObj| {
+            self.grid_of(idx)
+                .unwrap_or_else(|| obj.offset.denominator())
+        };
+
+        let mut long_notes = vec![];
+        let mut long_note_warnings = vec![];
+        for ((side, key), lane) in lanes {
+            let (mut lane_notes, long_rest) = pair_ln_obj(side, key, &lane.long, ln_objects);
+            lane_notes.extend(match ln_type {
+                LnType::Rdm => pair_rdm(side, key, &long_rest, &mut long_note_warnings),
+                LnType::Mgq => join_mgq(side, key, &long_rest, grid_of),
+            });
+            let (visible_notes, visible_rest) = pair_ln_obj(side, key, &lane.visible, ln_objects);
+            lane_notes.extend(visible_notes);
+            long_note_warnings.extend(
+                visible_rest
+                    .iter()
+                    .filter(|(_, obj)| ln_objects.contains(&obj.wav_id))
+                    .map(|(_, obj)| LongNoteWarning::LnObjWithoutStart {
+                        side,
+                        key,
+                        end: obj.offset,
+                        end_wav_id: obj.wav_id,
+                    }),
+            );
+            lane_notes.sort_unstable_by_key(|ln| (ln.start, ln.end));
+            for ln in &mut lane_notes {
+                ln.ln_mode = ln_mode;
+            }
+            check_overlaps(&lane_notes, &lane, &mut long_note_warnings);
+            long_notes.extend(lane_notes);
+        }
+        long_notes.sort_by_key(|ln| {
+            (
+                ln.start,
+                T::new(ln.side, NoteKind::Long, ln.key).to_channel_id(),
+            )
+        });
+
+        LongNotesOutput {
+            long_notes,
+            long_note_warnings,
+        }
+    }
+}
+
+impl Bms {
+    /// Resolves the long notes on the score with `#LNTYPE`, `#LNMODE` and `#LNOBJ` of this score.
+    pub fn long_notes<T: KeyLayoutMapper>(&self) -> LongNotesOutput {
+        self.wav
+            .notes
+            .long_notes::<T>(self.repr.ln_type, self.repr.ln_mode, &self.wav.ln_objects)
+    }
+
+    /// Rewrites all the long notes on the score into the `encoding`, and updates `#LNTYPE` and `#LNOBJ` to match it.
+    ///
+    /// Objects which could not be resolved into long notes, such as unterminated starts, are left as they are. Returns the output of resolving the long notes before rewriting.
+    ///
+    /// MGQ runs are written on the grid fine enough for the long notes in the track, which is kept by [`Notes::grid_of`]. Long notes touching each other in the same lane are joined into one by MGQ. `#LNOBJ` long notes are written on the visible channels as in the source.
+    pub fn encode_long_notes<T: KeyLayoutMapper>(
+        &mut self,
+        encoding: LongNoteEncoding,
+    ) -> LongNotesOutput {
+        let output = self.long_notes::<T>();
+        for ln in &output.long_notes {
+            for &idx in &ln.objects {
+                self.wav.notes.pop_by_idx(idx);
+            }
+        }
+
+        self.push_long_notes::<T>(&output.long_notes, encoding);
+
+        self.wav.ln_objects.clear();
+        match encoding {
+            LongNoteEncoding::Rdm => self.repr.ln_type = LnType::Rdm,
+            LongNoteEncoding::Mgq => self.repr.ln_type = LnType::Mgq,
+            LongNoteEncoding::LnObj(end_id) => {
+                self.wav.ln_objects.insert(end_id);
+            }
+        }
+        output
+    }
+
+    /// Places the objects of `long_notes` in the `encoding`, without touching `#LNTYPE` and `#LNOBJ`.
+    pub(crate) fn push_long_notes<T: KeyLayoutMapper>(
+        &mut self,
+        long_notes: &[LongNote],
+        encoding: LongNoteEncoding,
+    ) {
+        // Resolution of the MGQ grid for each lane and track, fine enough for all the long notes on it.
+        let mut mgq_resolutions: HashMap<(NoteChannelId, Track), u64> = HashMap::new();
+        for ln in long_notes {
+            let long_channel = T::new(ln.side, NoteKind::Long, ln.key).to_channel_id();
+            for time in [ln.start, ln.end] {
+                let resolution = mgq_resolutions
+                    .entry((long_channel, time.track()))
+                    .or_insert(1);
+                *resolution = resolution.lcm(&time.denominator_u64());
+            }
+        }
+
+        let resolution_of = |channel: NoteChannelId| {
+            let mgq_resolutions = &mgq_resolutions;
+            move |track: Track| mgq_resolutions.get(&(channel, track)).copied().unwrap_or(1)
+        };
+
+        for ln in long_notes {
+            let long_channel = T::new(ln.side, NoteKind::Long, ln.key).to_channel_id();
+            let visible_channel = T::new(ln.side, NoteKind::Visible, ln.key).to_channel_id();
+            let objs = match encoding {
+                LongNoteEncoding::Rdm => vec![
+                    (ln.start, long_channel, ln.start_wav_id),
+                    (ln.end, long_channel, ln.end_wav_id),
+                ],
+                LongNoteEncoding::Mgq => {
+                    let cells = mgq_cells(ln.start, ln.end, resolution_of(long_channel));
+                    let last = cells.len().saturating_sub(1);
+                    cells
+                        .into_iter()
+                        .enumerate()
+                        .map(|(i, time)| {
+                            let wav_id = if i == last && i != 0 {
+                                ln.end_wav_id
+                            } else {
+                                ln.start_wav_id
+                            };
+                            (time, long_channel, wav_id)
+                        })
+                        .collect()
+                }
+                LongNoteEncoding::LnObj(end_id) => vec![
+                    (ln.start, visible_channel, ln.start_wav_id),
+                    (ln.end, visible_channel, end_id),
+                ],
+            };
+            for (offset, channel_id, wav_id) in objs {
+                let note = WavObj {
+                    offset,
+                    channel_id,
+                    wav_id,
+                };
+                let grid = (encoding == LongNoteEncoding::Mgq)
+                    .then(|| NonZeroU64::new(resolution_of(channel_id)(offset.track())))
+                    .flatten();
+                self.wav.notes.push_note_on_grid(note, grid);
+            }
+        }
+    }
+}
+
+/// Pairs RDM long note objects in a lane one by one.
+fn pair_rdm(
+    side: PlayerSide,
+    key: Key,
+    objs: &[(WavObjArenaIndex, &WavObj)],
+    warnings: &mut Vec<LongNoteWarning>,
+) -> Vec<LongNote> {
+    let mut chunks = objs.chunks_exact(2);
+    let long_notes = chunks
+        .by_ref()
+        .filter_map(|pair| {
+            let [(start_idx, start), (end_idx, end)] = pair else {
+                return None;
+            };
+            Some(LongNote {
+                side,
+                key,
+                start: start.offset,
+                end: end.offset,
+                start_wav_id: start.wav_id,
+                end_wav_id: end.wav_id,
+                ln_mode: LnMode::default(),
+                encoding: LongNoteEncoding::Rdm,
+                objects: vec![*start_idx, *end_idx],
+            })
+        })
+        .collect();
+    if let [(_, start)] = chunks.remainder() {
+        warnings.push(LongNoteWarning::Unterminated {
+            side,
+            key,
+            start: start.offset,
+        });
+    }
+    long_notes
+}
+
+/// Joins runs of MGQ long note objects in consecutive cells in a lane, where `grid_of` gives the resolution of the message of each object.
+fn join_mgq(
+    side: PlayerSide,
+    key: Key,
+    objs: &[(WavObjArenaIndex, &WavObj)],
+    grid_of: impl Fn(WavObjArenaIndex, &WavObj) -> NonZeroU64,
+) -> Vec<LongNote> {
+    let mut long_notes = vec![];
+    let mut run: Vec<(WavObjArenaIndex, &WavObj)> = vec![];
+    let mut run_end = None;
+    for &(idx, obj) in objs {
+        if let Some(end) = run_end
+            && end != obj.offset
+        {
+            long_notes.extend(close_mgq_run(side, key, &run, end));
+            run.clear();
+        }
+        run.push((idx, obj));
+        let grid = grid_of(idx, obj).get();
+        run_end = Some(time_of_cell(cell_of(obj.offset, grid) + 1, grid));
+    }
+    if let Some(end) = run_end {
+        long_notes.extend(close_mgq_run(side, key, &run, end));
+    }
+    long_notes
+}
+
+/// Makes a long note from the run of MGQ objects, ending at `end`.
+fn close_mgq_run(
+    side: PlayerSide,
+    key: Key,
+    run: &[(WavObjArenaIndex, &WavObj)],
+    end: ObjTime,
+) -> Option<LongNote> {
+    let (_, first) = run.first()?;
+    let (_, last) = run.last()?;
+    Some(LongNote {
+        side,
+        key,
+        start: first.offset,
+        end,
+        start_wav_id: first.wav_id,
+        end_wav_id: last.wav_id,
+        ln_mode: LnMode::default(),
+        encoding: LongNoteEncoding::Mgq,
+        objects: run.iter().map(|&(idx, _)| idx).collect(),
+    })
+}
+
+/// Pairs `#LNOBJ` end objects with the preceding objects in a lane. Returns the long notes and the objects not paired.
+fn pair_ln_obj<'a>(
+    side: PlayerSide,
+    key: Key,
+    objs: &[(WavObjArenaIndex, &'a WavObj)],
+    ln_objects: &BTreeSet<ObjId>,
+) -> (Vec<LongNote>, Vec<(WavObjArenaIndex, &'a WavObj)>) {
+    if ln_objects.is_empty() {
+        return (vec![], objs.to_vec());
+    }
+    let mut long_notes = vec![];
+    let mut rest = vec![];
+    let mut prev: Option<(WavObjArenaIndex, &WavObj)> = None;
+    for &(idx, obj) in objs {
+        if !ln_objects.contains(&obj.wav_id) {
+            rest.extend(prev.replace((idx, obj)));
+            continue;
+        }
+        let Some((start_idx, start)) = prev.take() else {
+            rest.push((idx, obj));
+            continue;
+        };
+        long_notes.push(LongNote {
+            side,
+            key,
+            start: start.offset,
+            end: obj.offset,
+            start_wav_id: start.wav_id,
+            end_wav_id: obj.wav_id,
+            ln_mode: LnMode::default(),
+            encoding: LongNoteEncoding::LnObj(obj.wav_id),
+            objects: vec![start_idx, idx],
+        });
+    }
+    rest.extend(prev);
+    rest.sort_unstable_by_key(|&(_, obj)| obj);
+    (long_notes, rest)
+}
+
+/// Reports long notes overlapping other long notes or single notes in the lane.
+fn check_overlaps(
+    lane_notes: &[LongNote],
+    lane: &LaneObjects,
+    warnings: &mut Vec<LongNoteWarning>,
+) {
+    for pair in lane_notes.windows(2) {
+        let [prev, next] = pair else {
+            continue;
+        };
+        if next.start < prev.end {
+            warnings.push(LongNoteWarning::Overlapping {
+                side: prev.side,
+                key: prev.key,
+                ln_start: prev.start,
+                ln_end: prev.end,
+                time: next.start,
+            });
+        }
+    }
+
+    let members: HashSet<WavObjArenaIndex> = lane_notes
+        .iter()
+        .flat_map(|ln| ln.objects.iter().copied())
+        .collect();
+    for &(_, single) in lane
+        .visible
+        .iter()
+        .filter(|(idx, _)| !members.contains(idx))
+    {
+        let pos = lane_notes.partition_point(|ln| ln.start <= single.offset);
+        let Some(ln) = pos.checked_sub(1).and_then(|pos| lane_notes.get(pos)) else {
+            continue;
+        };
+        if single.offset < ln.end {
+            warnings.push(LongNoteWarning::Overlapping {
+                side: ln.side,
+                key: ln.key,
+                ln_start: ln.start,
+                ln_end: ln.end,
+                time: single.offset,
+            });
+        }
+    }
+}
+
+/// Times of the MGQ objects filling from `start` until `end`, on the grid of `resolution_of` each track.
+fn mgq_cells(start: ObjTime, end: ObjTime, resolution_of: impl Fn(Track) -> u64) -> Vec<ObjTime> {
+    let mut cells = vec![];
+    for track in start.track().0..=end.track().0 {
+        let track = Track(track);
+        let resolution = resolution_of(track);
+        let in_track = |time: ObjTime| {
+            let cell = u128::from(time.numerator()) * u128::from(resolution)
+                / u128::from(time.denominator_u64());
+            u64::try_from(cell).unwrap_or(resolution)
+        };
+        let from = if track == start.track() {
+            in_track(start)
+        } else {
+            0
+        };
+        let to = if track == end.track() {
+            in_track(end)
+        } else {
+            resolution
+        };
+        cells.extend((from..to).filter_map(|cell| ObjTime::new(track.0, cell, resolution)));
+    }
+    if cells.is_empty() {
+        cells.push(start);
+    }
+    cells
+}
+
+/// Index of the grid cell of `time`, on the grid dividing each track into `resolution` cells.
+fn cell_of(time: ObjTime, resolution: u64) -> u128 {
+    u128::from(time.track().0) * u128::from(resolution)
+        + u128::from(time.numerator()) * u128::from(resolution) / u128::from(time.denominator_u64())
+}
+
+/// Time of the grid cell `cell`, on the grid dividing each track into `resolution` cells.
+fn time_of_cell(cell: u128, resolution: u64) -> ObjTime {
+    let resolution = NonZeroU64::new(resolution).unwrap_or(NonZeroU64::MIN);
+    let track = cell / u128::from(resolution.get());
+    let numerator = cell % u128::from(resolution.get());
+    ObjTime::new_checked(
+        u64::try_from(track).unwrap_or(u64::MAX),
+        u64::try_from(numerator).expect("remainder must be less than the resolution"),
+        resolution,
+    )
+}
@@ -6,6 +6,7 @@
 
 use std::{
     collections::{BTreeMap, HashMap},
+    num::NonZeroU64,
     ops::Bound,
 };
 
@@ -45,6 +46,8 @@ pub struct Notes {
     idx_by_channel: HashMap<NoteChannelId, Vec<WavObjArenaIndex>>,
     /// Note objects index sorted by its time.
     idx_by_time: BTreeMap<ObjTime, Vec<WavObjArenaIndex>>,
+    /// Resolution of the message the object was written on, which `#LNTYPE 2` needs to know the cell of the object.
+    grids: HashMap<WavObjArenaIndex, NonZeroU64>,
 }
 
 // query methods
@@ -286,6 +289,23 @@ impl Notes {
             .find(|obj| obj.channel_id == channel_id)
     }
 
+    /// Gets the resolution of the message the object of `idx` was written on, which is the number of the objects in the message.
+    ///
+    /// It is `None` if the object was not parsed from a message, such as pushed by [`Self::push_note`].
+    #[must_use]
+    pub fn grid_of(&self, idx: WavObjArenaIndex) -> Option<NonZeroU64> {
+        self.grids.get(&idx).copied()
+    }
+
+    /// Gets the resolution of the message the object equal to `obj` was written on.
+    pub(crate) fn grid_of_obj(&self, obj: &WavObj) -> Option<NonZeroU64> {
+        self.idx_by_time
+            .get(&obj.offset)?
+            .iter()
+            .filter(|idx| self.arena.0.get(idx.0) == Some(obj))
+            .find_map(|idx| self.grid_of(*idx))
+    }
+
     /// Gets the latest starting time of all notes.
     #[must_use]
     pub fn last_obj_time(&self) -> Option<ObjTime> {
@@ -350,7 +370,17 @@ impl Notes {
         self.arena.0.push(note);
     }
 
+    /// Adds the new note object written on the message of the resolution `grid`, if known. See also [`Self::grid_of`].
+    pub fn push_note_on_grid(&mut self, note: WavObj, grid: Option<NonZeroU64>) {
+        if let Some(grid) = grid {
+            self.grids
+                .insert(WavObjArenaIndex(self.arena.0.len()), grid);
+        }
+        self.push_note(note);
+    }
+
     fn remove_index(&mut self, idx: usize, removing: &WavObj) {
+        self.grids.remove(&WavObjArenaIndex(idx));
         let channel_id = removing.channel_id;
         if let Some(indexes) = self.idx_by_channel.get_mut(&channel_id)
             && let Some(pos) = indexes.iter().position(|id| id.0 == idx)
@@ -368,6 +398,7 @@ impl Notes {
     pub fn pop_note(&mut self) -> Option<WavObj> {
         let last_idx = self.arena.0.len().checked_sub(1)?;
         let last = self.arena.0.pop()?;
+        self.grids.remove(&WavObjArenaIndex(last_idx));
         if let Some(indexes) = self.idx_by_wav_id.get_mut(&last.wav_id)
             && let Some(pos) = indexes.iter().position(|id| id.0 == last_idx)
         {
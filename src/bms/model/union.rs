@@ -0,0 +1,651 @@
+//! This module introduces [`Bms::union_with`], which merges two scores and reports conflicts between them.
+//!
+//! Every definition sharing an id, and every event sharing a time or a lane, is passed to a [`Prompter`]
+//! as [`DefDuplication`], [`TrackDuplication`] or [`ChannelDuplication`] as on parsing. The chosen
+//! [`DuplicationWorkaround`] is applied and recorded as an [`UnionConflict`].
+
+use std::{
+    cell::Cell,
+    collections::{BTreeMap, HashMap, HashSet},
+};
+
+use crate::bms::{
+    command::channel::Channel,
+    parse::prompt::{
+        ChannelDuplication, DefDuplication, DuplicationWorkaround, Prompter, TrackDuplication,
+    },
+    prelude::*,
+};
+
+/// A conflict found on [`Bms::union_with`], and how it was resolved.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct UnionConflict {
+    /// What was conflicted.
+    pub kind: UnionConflictKind,
+    /// The workaround chosen by the [`Prompter`].
+    pub workaround: DuplicationWorkaround,
+}
+
+impl UnionConflict {
+    /// Returns whether the incoming definition or event from the other score was taken.
+    #[must_use]
+    pub const fn uses_newer(&self) -> bool {
+        matches!(
+            self.workaround,
+            DuplicationWorkaround::UseNewer | DuplicationWorkaround::WarnAndUseNewer
+        )
+    }
+}
+
+/// A place where both scores have different contents.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum UnionConflictKind {
+    /// Both scores define the same id differently.
+    Def {
+        /// The command name of the definition, such as `WAV` for `#WAVxx`.
+        command: &'static str,
+        /// The conflicted id.
+        id: ObjId,
+    },
+    /// Both scores have different objects on the same track.
+    Track {
+        /// The conflicted track.
+        track: Track,
+        /// The channel of the objects.
+        channel: Channel,
+    },
+    /// Both scores have different objects at the same time on the same channel.
+    Channel {
+        /// The conflicted time.
+        time: ObjTime,
+        /// The channel of the objects.
+        channel: Channel,
+    },
+}
+
+/// A [`Prompter`] which remembers the last answer of the inner one.
+struct ConflictLog<'a, P> {
+    prompter: &'a P,
+    answer: Cell<Option<DuplicationWorkaround>>,
+    conflicts: Vec<UnionConflict>,
+}
+
+impl<'a, P: Prompter> ConflictLog<'a, P> {
+    const fn new(prompter: &'a P) -> Self {
+        Self {
+            prompter,
+            answer: Cell::new(None),
+            conflicts: Vec::new(),
+        }
+    }
+
+    /// Records a conflict at `kind` if the prompter was asked since the last call.
+    fn record(&mut self, kind: UnionConflictKind) {
+        if let Some(workaround) = self.answer.take() {
+            self.conflicts.push(UnionConflict { kind, workaround });
+        }
+    }
+
+    fn merge_defs<T: Clone + PartialEq>(
+        &mut self,
+        target: &mut HashMap<ObjId, T>,
+        source: &HashMap<ObjId, T>,
+        command: &'static str,
+        duplication: for<'b> fn(ObjId, &'b T, &'b T) -> DefDuplication<'b>,
+    ) {
+        let mut ids: Vec<_> = source.keys().copied().collect();
+        ids.sort_unstable();
+        for id in ids {
+            let Some(newer) = source.get(&id) else {
+                continue;
+            };
+            let Some(older) = target.get_mut(&id) else {
+                target.insert(id, newer.clone());
+                continue;
+            };
+            if older == newer {
+                continue;
+            }
+            let workaround = self.handle_def_duplication(duplication(id, older, newer));
+            let _ = workaround.apply_def(older, newer.clone(), id);
+            self.record(UnionConflictKind::Def { command, id });
+        }
+    }
+}
+
+impl<P: Prompter> Prompter for ConflictLog<'_, P> {
+    fn handle_def_duplication(&self, duplication: DefDuplication) -> DuplicationWorkaround {
+        let workaround = self.prompter.handle_def_duplication(duplication);
+        self.answer.set(Some(workaround.clone()));
+        workaround
+    }
+
+    fn handle_track_duplication(&self, duplication: TrackDuplication) -> DuplicationWorkaround {
+        let workaround = self.prompter.handle_track_duplication(duplication);
+        self.answer.set(Some(workaround.clone()));
+        workaround
+    }
+
+    fn handle_channel_duplication(&self, duplication: ChannelDuplication) -> DuplicationWorkaround {
+        let workaround = self.prompter.handle_channel_duplication(duplication);
+        self.answer.set(Some(workaround.clone()));
+        workaround
+    }
+}
+
+/// Collects the events in `source` which are not in `target` with the same contents.
+fn incoming<K: Ord, V: Clone>(
+    target: Option<&BTreeMap<K, V>>,
+    source: &BTreeMap<K, V>,
+    same: impl Fn(&V, &V) -> bool,
+) -> Vec<V> {
+    source
+        .iter()
+        .filter(|(key, newer)| {
+            target
+                .and_then(|target| target.get(key))
+                .is_none_or(|older| !same(older, newer))
+        })
+        .map(|(_, newer)| newer.clone())
+        .collect()
+}
+
+impl Bms {
+    /// Merges another Bms object into this one in-place, asking `prompter` how to resolve conflicts.
+    ///
+    /// Header fields are merged as [`Bms::union_inplace`] does. Definitions with the same id, and
+    /// objects at the same time on the same channel or lane, are passed to `prompter` when their
+    /// contents differ. BGM objects never conflict because they can be layered.
+    ///
+    /// Notes on the same lane of the key layout `T` conflict regardless of their kinds, so a visible note and a long note at the same time on the lane are passed to `prompter`.
+    /// A long note is kept or dropped as a whole, so if an object of it loses a conflict, the other
+    /// objects of it are dropped too.
+    ///
+    /// Returns the conflicts in the order they were found, with the chosen workarounds.
+    pub fn union_with<T: KeyLayoutMapper>(
+        &mut self,
+        other: &Bms,
+        prompter: &impl Prompter,
+    ) -> Vec<UnionConflict> {
+        self.union_headers(other);
+        let mut log = ConflictLog::new(prompter);
+
+        self.merge_defs_with(other, &mut log);
+        self.merge_events_with(other, &mut log);
+        self.merge_notes_with::<T>(other, &mut log);
+
+        log.conflicts
+    }
+
+    fn merge_defs_with(&mut self, other: &Bms, log: &mut ConflictLog<'_, impl Prompter>) {
+        log.merge_defs(
+            &mut self.wav.wav_files,
+            &other.wav.wav_files,
+            "WAV",
+            |id, older, newer| DefDuplication::Wav {
+                id,
+                older: older.as_path(),
+                newer: newer.as_path(),
+            },
+        );
+        log.merge_defs(
+            &mut self.wav.exwav_defs,
+            &other.wav.exwav_defs,
+            "EXWAV",
+            |id, older, newer| DefDuplication::ExWav { id, older, newer },
+        );
+        log.merge_defs(
+            &mut self.wav.wavcmd_events,
+            &other.wav.wavcmd_events,
+            "WAVCMD",
+            |wav_index, older, newer| DefDuplication::WavCmdEvent {
+                wav_index,
+                older,
+                newer,
+            },
+        );
+        log.merge_defs(
+            &mut self.bmp.bmp_files,
+            &other.bmp.bmp_files,
+            "BMP",
+            |id, older, newer| DefDuplication::Bmp { id, older, newer },
+        );
+        log.merge_defs(
+            &mut self.bmp.atbga_defs,
+            &other.bmp.atbga_defs,
+            "@BGA",
+            |id, older, newer| DefDuplication::AtBga { id, older, newer },
+        );
+        log.merge_defs(
+            &mut self.bmp.bga_defs,
+            &other.bmp.bga_defs,
+            "BGA",
+            |id, older, newer| DefDuplication::Bga { id, older, newer },
+        );
+        log.merge_defs(
+            &mut self.bmp.swbga_events,
+            &other.bmp.swbga_events,
+            "SWBGA",
+            |id, older, newer| DefDuplication::SwBgaEvent { id, older, newer },
+        );
+        log.merge_defs(
+            &mut self.bmp.argb_defs,
+            &other.bmp.argb_defs,
+            "ARGB",
+            |id, older, newer| DefDuplication::BgaArgb { id, older, newer },
+        );
+        log.merge_defs(
+            &mut self.bpm.bpm_defs,
+            &other.bpm.bpm_defs,
+            "BPM",
+            |id, older, newer| DefDuplication::BpmChange { id, older, newer },
+        );
+        log.merge_defs(
+            &mut self.stop.stop_defs,
+            &other.stop.stop_defs,
+            "STOP",
+            |id, older, newer| DefDuplication::Stop { id, older, newer },
+        );
+        log.merge_defs(
+            &mut self.scroll.scroll_defs,
+            &other.scroll.scroll_defs,
+            "SCROLL",
+            |id, older, newer| DefDuplication::ScrollingFactorChange { id, older, newer },
+        );
+        log.merge_defs(
+            &mut self.speed.speed_defs,
+            &other.speed.speed_defs,
+            "SPEED",
+            |id, older, newer| DefDuplication::SpeedFactorChange { id, older, newer },
+        );
+        log.merge_defs(
+            &mut self.judge.exrank_defs,
+            &other.judge.exrank_defs,
+            "EXRANK",
+            |id, older, newer| DefDuplication::ExRank { id, older, newer },
+        );
+        log.merge_defs(
+            &mut self.text.texts,
+            &other.text.texts,
+            "TEXT",
+            |id, older, newer| DefDuplication::Text {
+                id,
+                older: older.as_str(),
+                newer: newer.as_str(),
+            },
+        );
+        log.merge_defs(
+            &mut self.option.change_options,
+            &other.option.change_options,
+            "CHANGEOPTION",
+            |id, older, newer| DefDuplication::ChangeOption {
+                id,
+                older: older.as_str(),
+                newer: newer.as_str(),
+            },
+        );
+        log.merge_defs(
+            &mut self.video.seek_defs,
+            &other.video.seek_defs,
+            "SEEK",
+            |id, older, newer| DefDuplication::SeekEvent { id, older, newer },
+        );
+    }
+
+    fn merge_events_with(&mut self, other: &Bms, log: &mut ConflictLog<'_, impl Prompter>) {
+        // The results of `push_*` are ignored, because the log already has the chosen workarounds.
+        for section_len_change in incoming(
+            Some(&self.section_len.section_len_changes),
+            &other.section_len.section_len_changes,
+            |older, newer| older.length == newer.length,
+        ) {
+            let track = section_len_change.track;
+            let _ = self
+                .section_len
+                .push_section_len_change(section_len_change, log);
+            log.record(UnionConflictKind::Track {
+                track,
+                channel: Channel::SectionLen,
+            });
+        }
+
+        for bpm_change in incoming(
+            Some(&self.bpm.bpm_changes),
+            &other.bpm.bpm_changes,
+            |older, newer| older.bpm == newer.bpm,
+        ) {
+            let time = bpm_change.time;
+            let _ = self.bpm.push_bpm_change(bpm_change, log);
+            log.record(UnionConflictKind::Channel {
+                time,
+                channel: Channel::BpmChange,
+            });
+        }
+        for (&time, &bpm_change) in &other.bpm.bpm_changes_u8 {
+            if self.bpm.bpm_changes_u8.get(&time) == Some(&bpm_change) {
+                continue;
+            }
+            let _ = self.bpm.push_bpm_change_u8(time, bpm_change, log);
+            log.record(UnionConflictKind::Channel {
+                time,
+                channel: Channel::BpmChangeU8,
+            });
+        }
+
+        for stop in incoming(Some(&self.stop.stops), &other.stop.stops, |older, newer| {
+            older.duration == newer.duration
+        }) {
+            let time = stop.time;
+            let _ = self.stop.push_stop(stop, log);
+            log.record(UnionConflictKind::Channel {
+                time,
+                channel: Channel::Stop,
+            });
+        }
+        for stp in incoming(
+            Some(&self.stop.stp_events),
+            &other.stop.stp_events,
+            |older, newer| older == newer,
+        ) {
+            let time = stp.time;
+            if let Some(older) = self.stop.stp_events.get_mut(&time) {
+                let _ = log
+                    .handle_channel_duplication(ChannelDuplication::StpEvent {
+                        time,
+                        older,
+                        newer: &stp,
+                    })
+                    .apply_channel(older, stp, time, Channel::Stop);
+            } else {
+                self.stop.stp_events.insert(time, stp);
+            }
+            log.record(UnionConflictKind::Channel {
+                time,
+                channel: Channel::Stop,
+            });
+        }
+
+        for scrolling_factor_change in incoming(
+            Some(&self.scroll.scrolling_factor_changes),
+            &other.scroll.scrolling_factor_changes,
+            |older, newer| older.factor == newer.factor,
+        ) {
+            let time = scrolling_factor_change.time;
+            let _ = self
+                .scroll
+                .push_scrolling_factor_change(scrolling_factor_change, log);
+            log.record(UnionConflictKind::Channel {
+                time,
+                channel: Channel::Scroll,
+            });
+        }
+        for speed_factor_change in incoming(
+            Some(&self.speed.speed_factor_changes),
+            &other.speed.speed_factor_changes,
+            |older, newer| older.factor == newer.factor,
+        ) {
+            let time = speed_factor_change.time;
+            let _ = self
+                .speed
+                .push_speed_factor_change(speed_factor_change, log);
+            log.record(UnionConflictKind::Channel {
+                time,
+                channel: Channel::Speed,
+            });
+        }
+
+        for bga in incoming(
+            Some(&self.bmp.bga_changes),
+            &other.bmp.bga_changes,
+            |older, newer| older.id == newer.id && older.layer == newer.layer,
+        ) {
+            let (time, channel) = (bga.time, bga.layer.to_channel());
+            let _ = self.bmp.push_bga_change(bga, channel, log);
+            log.record(UnionConflictKind::Channel { time, channel });
+        }
+        for (layer, opacity_changes) in &other.bmp.bga_opacity_changes {
+            let channel = match layer {
+                BgaLayer::Base => Channel::BgaBaseOpacity,
+                BgaLayer::Poor => Channel::BgaPoorOpacity,
+                BgaLayer::Overlay => Channel::BgaLayerOpacity,
+                BgaLayer::Overlay2 => Channel::BgaLayer2Opacity,
+            };
+            for opacity_change in incoming(
+                self.bmp.bga_opacity_changes.get(layer),
+                opacity_changes,
+                PartialEq::eq,
+            ) {
+                let time = opacity_change.time;
+                let _ = self
+                    .bmp
+                    .push_bga_opacity_change(opacity_change, channel, log);
+                log.record(UnionConflictKind::Channel { time, channel });
+            }
+        }
+        for (layer, argb_changes) in &other.bmp.bga_argb_changes {
+            let channel = match layer {
+                BgaLayer::Base => Channel::BgaBaseArgb,
+                BgaLayer::Poor => Channel::BgaPoorArgb,
+                BgaLayer::Overlay => Channel::BgaLayerArgb,
+                BgaLayer::Overlay2 => Channel::BgaLayer2Argb,
+            };
+            for argb_change in incoming(
+                self.bmp.bga_argb_changes.get(layer),
+                argb_changes,
+                PartialEq::eq,
+            ) {
+                let time = argb_change.time;
+                let _ = self.bmp.push_bga_argb_change(argb_change, channel, log);
+                log.record(UnionConflictKind::Channel { time, channel });
+            }
+        }
+        for keybound_event in incoming(
+            Some(&self.bmp.bga_keybound_events),
+            &other.bmp.bga_keybound_events,
+            PartialEq::eq,
+        ) {
+            let time = keybound_event.time;
+            let _ = self.bmp.push_bga_keybound_event(keybound_event, log);
+            log.record(UnionConflictKind::Channel {
+                time,
+                channel: Channel::BgaKeybound,
+            });
+        }
+
+        for volume_change in incoming(
+            Some(&self.volume.bgm_volume_changes),
+            &other.volume.bgm_volume_changes,
+            PartialEq::eq,
+        ) {
+            let time = volume_change.time;
+            let _ = self.volume.push_bgm_volume_change(volume_change, log);
+            log.record(UnionConflictKind::Channel {
+                time,
+                channel: Channel::BgmVolume,
+            });
+        }
+        for volume_change in incoming(
+            Some(&self.volume.key_volume_changes),
+            &other.volume.key_volume_changes,
+            PartialEq::eq,
+        ) {
+            let time = volume_change.time;
+            let _ = self.volume.push_key_volume_change(volume_change, log);
+            log.record(UnionConflictKind::Channel {
+                time,
+                channel: Channel::KeyVolume,
+            });
+        }
+
+        for seek_event in incoming(
+            Some(&self.video.seek_events),
+            &other.video.seek_events,
+            PartialEq::eq,
+        ) {
+            let time = seek_event.time;
+            let _ = self.video.push_seek_event(seek_event, log);
+            log.record(UnionConflictKind::Channel {
+                time,
+                channel: Channel::Seek,
+            });
+        }
+        for text_event in incoming(
+            Some(&self.text.text_events),
+            &other.text.text_events,
+            PartialEq::eq,
+        ) {
+            let time = text_event.time;
+            let _ = self.text.push_text_event(text_event, log);
+            log.record(UnionConflictKind::Channel {
+                time,
+                channel: Channel::Text,
+            });
+        }
+        for judge_event in incoming(
+            Some(&self.judge.judge_events),
+            &other.judge.judge_events,
+            PartialEq::eq,
+        ) {
+            let time = judge_event.time;
+            let _ = self.judge.push_judge_event(judge_event, log);
+            log.record(UnionConflictKind::Channel {
+                time,
+                channel: Channel::Judge,
+            });
+        }
+        for option_event in incoming(
+            Some(&self.option.option_events),
+            &other.option.option_events,
+            PartialEq::eq,
+        ) {
+            let time = option_event.time;
+            let _ = self.option.push_option_event(option_event, log);
+            log.record(UnionConflictKind::Channel {
+                time,
+                channel: Channel::OptionChange,
+            });
+        }
+    }
+
+    fn merge_notes_with<T: KeyLayoutMapper>(
+        &mut self,
+        other: &Bms,
+        log: &mut ConflictLog<'_, impl Prompter>,
+    ) {
+        // Visible, long and landmine notes occupy the lane, which is named by its visible channel.
+        let place_of = |obj: &WavObj| {
+            T::from_channel_id(obj.channel_id)
+                .filter(|map| map.kind().is_displayable())
+                .map_or(obj.channel_id, |map| {
+                    T::new(map.side(), NoteKind::Visible, map.key()).to_channel_id()
+                })
+        };
+        // Objects of each long note, which are kept or dropped together.
+        let long_notes = self
+            .long_notes::<T>()
+            .long_notes
+            .into_iter()
+            .map(|ln| {
+                ln.objects
+                    .into_iter()
+                    .map(NoteOrigin::Older)
+                    .collect::<Vec<_>>()
+            })
+            .chain(
+                other
+                    .long_notes::<T>()
+                    .long_notes
+                    .into_iter()
+                    .map(|ln| ln.objects.into_iter().map(NoteOrigin::Newer).collect()),
+            );
+        // The winner on each place and time, with where it comes from.
+        let mut places: BTreeMap<(ObjTime, NoteChannelId), (NoteOrigin, PlacedNote)> = self
+            .wav
+            .notes
+            .all_entries()
+            .filter(|(_, obj)| obj.channel_id != NoteChannelId::bgm())
+            .map(|(idx, obj)| {
+                (
+                    (obj.offset, place_of(obj)),
+                    (
+                        NoteOrigin::Older(idx),
+                        (obj.clone(), self.wav.notes.grid_of(idx)),
+                    ),
+                )
+            })
+            .collect();
+        // Objects of `other` which are the same as the winners.
+        let mut same = HashSet::new();
+        for (idx, newer) in other.wav.notes.all_entries() {
+            let grid = other.wav.notes.grid_of(idx);
+            if newer.channel_id == NoteChannelId::bgm() {
+                self.wav.notes.push_note_on_grid(newer.clone(), grid);
+                continue;
+            }
+            let (time, place) = (newer.offset, place_of(newer));
+            let incoming = (NoteOrigin::Newer(idx), (newer.clone(), grid));
+            let Some(winner) = places.get_mut(&(time, place)) else {
+                places.insert((time, place), incoming);
+                continue;
+            };
+            let older = &(winner.1).0;
+            if older.wav_id == newer.wav_id && older.channel_id == newer.channel_id {
+                same.insert(NoteOrigin::Newer(idx));
+                continue;
+            }
+            let workaround = log.handle_channel_duplication(ChannelDuplication::NoteEvent {
+                time,
+                older,
+                newer,
+            });
+            let channel = Channel::Note {
+                channel_id: newer.channel_id,
+            };
+            let _ = workaround.apply_channel(winner, incoming, time, channel);
+            log.record(UnionConflictKind::Channel { time, channel });
+        }
+
+        let kept: HashSet<NoteOrigin> = places
+            .values()
+            .map(|&(origin, _)| origin)
+            .chain(same)
+            .collect();
+        let broken: HashSet<NoteOrigin> = long_notes
+            .filter(|objects| !objects.iter().all(|origin| kept.contains(origin)))
+            .flatten()
+            .collect();
+        places.retain(|_, (origin, _)| !broken.contains(origin));
+
+        let winners: HashSet<NoteOrigin> = places.values().map(|&(origin, _)| origin).collect();
+        let removing: Vec<_> = self
+            .wav
+            .notes
+            .all_entries()
+            .filter(|&(idx, obj)| {
+                obj.channel_id != NoteChannelId::bgm() && !winners.contains(&NoteOrigin::Older(idx))
+            })
+            .map(|(idx, _)| idx)
+            .collect();
+        for idx in removing {
+            self.wav.notes.pop_by_idx(idx);
+        }
+        for (origin, (note, grid)) in places.into_values() {
+            if let NoteOrigin::Newer(_) = origin {
+                self.wav.notes.push_note_on_grid(note, grid);
+            }
+        }
+    }
+}
+
+/// Where a note object comes from on merging notes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum NoteOrigin {
+    /// An object already in the score, by its index.
+    Older(WavObjArenaIndex),
+    /// An object of the other score, by its index in it.
+    Newer(WavObjArenaIndex),
+}
+
+/// A note object with the resolution of the message it was written on.
+type PlacedNote = (WavObj, Option<std::num::NonZeroU64>);
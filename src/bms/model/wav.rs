@@ -1,6 +1,9 @@
 //! This module introduces struct [`WavObjects`], which manages definitions and events of sound.
 
-use std::{collections::HashMap, path::PathBuf};
+use std::{
+    collections::{BTreeSet, HashMap},
+    path::PathBuf,
+};
 
 use crate::bms::{
     command::minor_command::{ExWavFrequency, ExWavPan, ExWavVolume},
@@ -19,6 +22,10 @@ pub struct WavObjects {
     pub exwav_defs: HashMap<ObjId, ExWavDef>,
     /// WAVCMD events, indexed by `wav_index`. `#WAVCMD`
     pub wavcmd_events: HashMap<ObjId, WavCmdEvent>,
+    /// The ids of objects ending a long note. `#LNOBJ`
+    ///
+    /// On parsing, an object of these ids and the preceding object in the same lane are moved together from the visible channel onto the long note channel, so [`Self::notes`] holds them as a long note.
+    pub ln_objects: BTreeSet<ObjId>,
 }
 
 /// A definition for `#EXWAV` command.
@@ -23,7 +23,7 @@ use crate::bms::{
 
 use crate::bms::model::obj::{
     BgaObj, BgmVolumeObj, BpmChangeObj, JudgeObj, KeyVolumeObj, ScrollingFactorObj,
-    SectionLenChangeObj, SpeedObj, StopObj, TextObj,
+    SectionLenChangeObj, SpeedObj, StopObj, TextObj, WavObj,
 };
 
 use crate::bms::{
@@ -342,6 +342,15 @@ pub enum ChannelDuplication<'a> {
         /// Incoming definition.
         newer: &'a OptionObj,
     },
+    /// Note object is duplicated on the same lane.
+    NoteEvent {
+        /// Duplicated note time.
+        time: ObjTime,
+        /// Existing definition.
+        older: &'a WavObj,
+        /// Incoming definition.
+        newer: &'a WavObj,
+    },
 }
 
 /// A choice to handle the duplicated definition.
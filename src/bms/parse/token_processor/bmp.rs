@@ -420,7 +420,11 @@ impl BmpProcessor {
                 let (pairs, w) = parse_obj_ids(track, message, &self.case_sensitive_obj_id);
                 warnings.extend(w);
                 for (time, obj) in pairs {
-                    if !objects.bmp_files.contains_key(&obj) {
+                    // `#BGA` and `#@BGA` define images by cropping BMPs.
+                    if !objects.bmp_files.contains_key(&obj)
+                        && !objects.atbga_defs.contains_key(&obj)
+                        && !objects.bga_defs.contains_key(&obj)
+                    {
                         return Err(ParseWarning::UndefinedObject(obj));
                     }
                     let layer = Self::bga_layer(channel)?;
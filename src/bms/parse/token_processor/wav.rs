@@ -17,7 +17,9 @@
 //! - `#xxx[D1-DZ]:` - Player 1 landmine channel with damage amount.
 //! - `#xxx[E1-EZ]:` - Player 2 landmine channel with damage amount.
 
-use std::{cell::RefCell, marker::PhantomData, path::Path, rc::Rc};
+use std::{
+    cell::RefCell, collections::HashMap, marker::PhantomData, num::NonZeroU64, path::Path, rc::Rc,
+};
 
 use super::{
     super::prompt::{DefDuplication, Prompter},
@@ -77,6 +79,7 @@ impl<T: KeyLayoutMapper> TokenProcessor for WavProcessor<T> {
                 .next()),
             Token::NotACommand(_) => Ok(None),
         })?;
+        Self::move_ln_objects(&mut objects);
         Ok(objects)
     }
 }
@@ -89,7 +92,9 @@ impl<T: KeyLayoutMapper> WavProcessor<T> {
         prompter: &impl Prompter,
         objects: &mut WavObjects,
     ) -> Result<()> {
-        if let Some(id) = name.strip_prefix_ignore_case("WAV") {
+        if !name.eq_ignore_ascii_case("WAVCMD")
+            && let Some(id) = name.strip_prefix_ignore_case("WAV")
+        {
             if args.is_empty() {
                 return Err(ParseWarning::SyntaxError(
                     "expected key audio filename".into(),
@@ -188,53 +193,7 @@ impl<T: KeyLayoutMapper> WavProcessor<T> {
         }
         if name.eq_ignore_ascii_case("LNOBJ") {
             let end_id = ObjId::try_from(args, *self.case_sensitive_obj_id.borrow())?;
-            let mut end_note = objects
-                .notes
-                .pop_latest_of::<T>(end_id)
-                .ok_or(ParseWarning::UndefinedObject(end_id))?;
-            let WavObj {
-                offset, channel_id, ..
-            } = &end_note;
-            let begin_idx = objects
-                .notes
-                .notes_in(..offset)
-                .rev()
-                .find(|(_, obj)| obj.channel_id == *channel_id)
-                .ok_or_else(|| {
-                    ParseWarning::SyntaxError(format!(
-                        "expected preceding object for #LNOBJ {end_id:?}",
-                    ))
-                })
-                .map(|(index, _)| index)?;
-            let mut begin_note = objects.notes.pop_by_idx(begin_idx).ok_or_else(|| {
-                ParseWarning::SyntaxError(format!("Cannot find begin note for LNOBJ {end_id:?}"))
-            })?;
-
-            let mut begin_note_tuple = begin_note
-                .channel_id
-                .try_into_map::<T>()
-                .ok_or_else(|| {
-                    ParseWarning::SyntaxError(format!(
-                        "channel of specified note for LNOBJ cannot become LN {end_id:?}"
-                    ))
-                })?
-                .as_tuple();
-            begin_note_tuple.1 = NoteKind::Long;
-            begin_note.channel_id = T::from_tuple(begin_note_tuple).to_channel_id();
-            objects.notes.push_note(begin_note);
-
-            let mut end_note_tuple = end_note
-                .channel_id
-                .try_into_map::<T>()
-                .ok_or_else(|| {
-                    ParseWarning::SyntaxError(format!(
-                        "channel of specified note for LNOBJ cannot become LN {end_id:?}"
-                    ))
-                })?
-                .as_tuple();
-            end_note_tuple.1 = NoteKind::Long;
-            end_note.channel_id = T::from_tuple(end_note_tuple).to_channel_id();
-            objects.notes.push_note(end_note);
+            objects.ln_objects.insert(end_id);
         }
         if name.eq_ignore_ascii_case("WAVCMD") {
             let args: Vec<_> = args.split_whitespace().collect();
@@ -286,6 +245,36 @@ impl<T: KeyLayoutMapper> WavProcessor<T> {
         Ok(())
     }
 
+    /// Moves the objects of `#LNOBJ` and the preceding objects in the same lanes onto the long note channels, as RDM long notes.
+    ///
+    /// It runs after all the tokens, so `#LNOBJ` may be placed before or after the objects. An object of `#LNOBJ` without a preceding object is left on the visible channel.
+    fn move_ln_objects(objects: &mut WavObjects) {
+        if objects.ln_objects.is_empty() {
+            return;
+        }
+        let mut prev_in_lane: HashMap<NoteChannelId, WavObjArenaIndex> = HashMap::new();
+        let mut pairs = vec![];
+        for (idx, obj) in objects.notes.all_entries() {
+            let Some(map) = T::from_channel_id(obj.channel_id) else {
+                continue;
+            };
+            if obj.wav_id.is_null() || map.kind() != NoteKind::Visible {
+                continue;
+            }
+            if !objects.ln_objects.contains(&obj.wav_id) {
+                prev_in_lane.insert(obj.channel_id, idx);
+                continue;
+            }
+            if let Some(start) = prev_in_lane.remove(&obj.channel_id) {
+                let long = T::new(map.side(), NoteKind::Long, map.key()).to_channel_id();
+                pairs.push(([start, idx], long));
+            }
+        }
+        for (pair, long) in pairs {
+            objects.notes.change_note_channel(pair, long);
+        }
+    }
+
     fn on_message(
         &self,
         track: Track,
@@ -304,12 +293,17 @@ impl<T: KeyLayoutMapper> WavProcessor<T> {
         if let Channel::Note { channel_id } = channel {
             let (pairs, mut w) = parse_obj_ids(track, message, &self.case_sensitive_obj_id);
             warnings.append(&mut w);
+            let grid =
+                NonZeroU64::new(message.content().len() as u64 / 2).unwrap_or(NonZeroU64::MIN);
             for (offset, obj) in pairs {
-                objects.notes.push_note(WavObj {
-                    offset,
-                    channel_id,
-                    wav_id: obj,
-                });
+                objects.notes.push_note_on_grid(
+                    WavObj {
+                        offset,
+                        channel_id,
+                        wav_id: obj,
+                    },
+                    Some(grid),
+                );
             }
         }
         warnings
@@ -10,6 +10,7 @@ pub use crate::diagnostics::{SimpleSource, ToAriadne, emit_bms_warnings};
 // Re-export types from bms module
 pub use super::{
     BmsOutput, BmsWarning, ParseConfig,
+    builder::{BmsBuildError, BmsBuilder, MusicalTime},
     command::{
         JudgeLevel, LnMode, LnType, ObjId, ObjIdManager, PlayerMode, PoorMode, Volume,
         channel::{
@@ -41,15 +42,20 @@ pub use super::{
     },
     model::{
         Bms,
+        assist::{Assist, AssistReport},
         bmp::{AtBgaDef, BgaDef, Bmp},
         control_flow::{ControlFlowValue, RandomizedBranch, RandomizedObjects},
+        double_play::{DoublePlayError, DoublePlayOutput, DoublePlayWarning},
         judge::ExRankDef,
-        notes::Notes,
+        key_mode::{FoldStrategy, RelaneOutput, SpreadStrategy, UnplacedNote},
+        long_note::{LongNote, LongNoteEncoding, LongNoteWarning, LongNotesOutput},
+        notes::{Notes, WavObjArenaIndex},
         obj::{
             BgaArgbObj, BgaKeyboundObj, BgaObj, BgaOpacityObj, BgmVolumeObj, BpmChangeObj,
             JudgeObj, KeyVolumeObj, OptionObj, ScrollingFactorObj, SectionLenChangeObj, SeekObj,
             SpeedObj, StopObj, TextObj, WavObj,
         },
+        union::{UnionConflict, UnionConflictKind},
         wav::ExWavDef,
     },
     parse::{
@@ -57,7 +63,7 @@ pub use super::{
         check_playing::{PlayingCheckOutput, PlayingError, PlayingWarning},
         prompt::{
             AlwaysUseNewer, AlwaysUseOlder, AlwaysWarnAndUseNewer, AlwaysWarnAndUseOlder,
-            DefDuplication, DuplicationWorkaround, Prompter,
+            ChannelDuplication, DefDuplication, DuplicationWorkaround, Prompter, TrackDuplication,
         },
         token_processor::{
             DefaultTokenRelaxer, NoopTokenModifier, SequentialTokenModifier, TokenModifier,
@@ -1,7 +1,7 @@
 //! Bms Processor Module.
 
 use std::{
-    collections::{BTreeMap, HashMap},
+    collections::{BTreeMap, HashMap, HashSet},
     convert::TryFrom,
     path::PathBuf,
 };
@@ -9,15 +9,18 @@ use std::{
 use itertools::Itertools;
 use strict_num_extended::{FinF64, PositiveF64};
 
+use crate::bms::command::graphics::PixelSize;
 use crate::bms::command::string_value::StringValue;
 use crate::bms::parse::check_playing::PlayingError;
 use crate::bms::prelude::*;
+use crate::chart::bga::{BgaCrop, BgaImage, BgaResources};
 use crate::chart::event::{BmsEvent, ChartEvent, FlowEvent, PlayheadEvent};
 use crate::chart::prelude::{TimeSpan, YCoordinate};
 use crate::chart::process::{
-    AllEventsIndex, BmpId, ChartEventIdGenerator, ChartResources, Process, WavId,
+    AllEventsIndex, BmpId, ChartEventIdGenerator, ChartResources, Process, WavId, WavParams,
     calculate_cumulative_times,
 };
+use crate::chart::stats::ChartStats;
 use crate::chart::{Chart, DEFAULT_BPM, DEFAULT_SPEED, MAX_FIN_F64, MAX_NON_NEGATIVE_F64};
 use strict_num_extended::NonNegativeF64;
 
@@ -119,12 +122,18 @@ impl BmsProcessor {
             .unwrap_or_else(|| StringValue::from_value(DEFAULT_BPM));
 
         // Precompute resource maps
-        let wav_files: HashMap<WavId, PathBuf> = bms
+        let mut wav_files: HashMap<WavId, PathBuf> = bms
             .wav
             .wav_files
             .iter()
             .map(|(obj_id, path)| (WavId::from(obj_id.as_u16() as usize), path.clone()))
             .collect();
+        // `#EXWAV` also defines the file, unless `#WAV` does.
+        for (obj_id, def) in &bms.wav.exwav_defs {
+            wav_files
+                .entry(WavId::from(obj_id.as_u16() as usize))
+                .or_insert_with(|| def.path.clone());
+        }
         let bmp_files: HashMap<BmpId, PathBuf> = bms
             .bmp
             .bmp_files
@@ -147,7 +156,8 @@ impl BmsProcessor {
             })?;
 
         Ok(Chart::from_parts(
-            ChartResources::new(wav_files, bmp_files),
+            ChartResources::new(wav_files, bmp_files, wav_params(bms), bms.volume.volume)
+                .with_bga(bga_resources(bms)),
             all_events,
             y_memo.flow_events().clone(),
             init_bpm_value,
@@ -291,6 +301,39 @@ impl YMemo {
                 .push(FlowEvent::Speed(change.factor));
         }
 
+        // Stops, which last for the duration at the BPM at their positions
+        let init_bpm = bms
+            .bpm
+            .bpm
+            .as_ref()
+            .and_then(|bpm| bpm.value().as_ref().ok().copied())
+            .unwrap_or(DEFAULT_BPM);
+        let bpm_at = |time: ObjTime| {
+            bms.bpm
+                .bpm_changes
+                .range(..=time)
+                .next_back()
+                .map_or(init_bpm, |(_, change)| change.bpm)
+        };
+        let stops = bms.stop.stops.values().map(|stop| {
+            let secs = stop.duration.as_f64() / 192.0 * 240.0 / bpm_at(stop.time).as_f64();
+            (stop.time, secs)
+        });
+        let stps = bms
+            .stop
+            .stp_events
+            .values()
+            .map(|stp| (stp.time, stp.duration.as_secs_f64()));
+        for (time, secs) in stops.chain(stps) {
+            let event_y = get_event_y(time);
+            flow_events
+                .entry(event_y)
+                .or_default()
+                .push(FlowEvent::Stop(TimeSpan::from_duration(
+                    std::time::Duration::from_secs_f64(secs.clamp(0.0, u32::MAX as f64)),
+                )));
+        }
+
         Self {
             y_by_track,
             speed_changes: bms.speed.speed_factor_changes.clone(),
@@ -369,18 +412,31 @@ impl AllEventsIndex {
 
         let get_event_y = |time: ObjTime| -> YCoordinate { y_memo.get_y(time) };
 
-        let note_events: Vec<(YCoordinate, WavObj)> = bms
+        // Long notes are resolved once, so each of them yields exactly one event at its start
+        // and the rest of its objects (RDM end, MGQ run, `#LNOBJ` end) are skipped.
+        let long_notes = bms.long_notes::<T>().long_notes;
+        let mut ln_by_start: HashMap<WavObjArenaIndex, &LongNote> = HashMap::new();
+        let mut ln_rest: HashSet<WavObjArenaIndex> = HashSet::new();
+        for ln in &long_notes {
+            let Some((&start, rest)) = ln.objects.split_first() else {
+                continue;
+            };
+            ln_by_start.insert(start, ln);
+            ln_rest.extend(rest.iter().copied());
+        }
+
+        let note_events: Vec<(YCoordinate, WavObjArenaIndex, WavObj)> = bms
             .notes()
-            .all_notes()
-            .map(|obj| (get_event_y(obj.offset), obj.clone()))
-            .sorted_by(|(y1, _), (y2, _)| y1.cmp(y2))
+            .all_entries()
+            .map(|(idx, obj)| (get_event_y(obj.offset), idx, obj.clone()))
+            .sorted_by(|(y1, _, _), (y2, _, _)| y1.cmp(y2))
             .collect();
 
         // Use ordered Vec instead of HashMap since f64 doesn't implement Hash
         // and NonNegativeF64 doesn't implement Hash either
         let mut zero_length_key_tracker: Vec<(YCoordinate, PlayerSide, Key, usize)> = Vec::new();
 
-        for (i, (y, obj)) in note_events.iter().enumerate() {
+        for (i, (y, _, obj)) in note_events.iter().enumerate() {
             let is_zero_length_section = y_memo.zero_length_tracks.contains(&obj.offset.track());
             let lane = BmsProcessor::lane_of_channel_id::<T>(obj.channel_id);
 
@@ -391,28 +447,10 @@ impl AllEventsIndex {
             }
         }
 
-        // Track LN start markers to prevent double-triggering (BMS format concern only).
-        //
-        // BMS format represents long notes using two consecutive Long note markers:
-        //   - First marker: start of long note (with length calculated to next marker)
-        //   - Second marker: end of long note (with no length)
-        //
-        // Example in BMS format:
-        //   #00151:11  <- Long note start (Player1, Key1, time=1)
-        //   #00351:22  <- Long note end   (Player1, Key1, time=3)
-        //
-        // Without this fix, both markers would trigger events, causing:
-        //   1. Double-triggering: same long note fires twice (at start and end)
-        //   2. Incorrect playback: end marker creates a zero-length note event
-        //
-        // IMPORTANT: This is purely a BMS FORMAT PARSING concern.
-        // The term "started" here refers to PARSING STATE, not GAMEPLAY STATE.
-        // The actual LN visibility (including LNs whose start has passed)
-        // is handled by AllEventsIndex using precomputed indices.
-        let mut ln_start_markers: std::collections::HashSet<(PlayerSide, Key)> =
-            std::collections::HashSet::new();
-
-        for (i, (y, obj)) in note_events.iter().enumerate() {
+        for (i, (y, arena_idx, obj)) in note_events.iter().enumerate() {
+            if ln_rest.contains(arena_idx) {
+                continue;
+            }
             let is_zero_length_section = y_memo.zero_length_tracks.contains(&obj.offset.track());
             let lane = BmsProcessor::lane_of_channel_id::<T>(obj.channel_id);
             let should_include = match lane {
@@ -423,50 +461,37 @@ impl AllEventsIndex {
             };
 
             if should_include {
-                let event = event_for_note_static::<T>(bms, y_memo, obj);
-
-                // Fix double-triggering by skipping LN end markers.
-                //
-                // Logic:
-                //   - When we encounter a Long note:
-                //     * If its lane already has a start marker → this is the END marker, skip it
-                //     * If its lane has no start marker AND it has length → this is the START marker, track it
-                //     * If its lane has no start marker AND no length → edge case, ignore (no next marker)
-                //
-                // Result: Each long note generates exactly one event with the correct length.
-                //
-                // IMPORTANT: This is purely a BMS FORMAT PARSING concern.
-                // The term "started" here refers to PARSING STATE, not GAMEPLAY STATE.
-                // The actual LN visibility (including LNs whose start has passed)
-                // is handled by AllEventsIndex using precomputed indices.
-                if let ChartEvent::Note {
-                    side,
-                    key,
-                    kind: NoteKind::Long,
-                    length,
-                    ..
-                } = &event
-                {
-                    let lane_key = (*side, *key);
-                    if ln_start_markers.contains(&lane_key) {
-                        // This lane already has a start marker.
-                        // This marker is the end of that long note.
-                        // Skip it to prevent double-triggering.
-                        ln_start_markers.remove(&lane_key);
-                        continue;
-                    }
-                    if length.is_some() {
-                        // This lane has no start marker.
-                        // This marker is the start of a new long note.
-                        // Track it so we can skip the end marker when we encounter it.
-                        ln_start_markers.insert(lane_key);
-                    }
-                    // If length is None, this is an orphan end marker or zero-length note.
-                    // Skip it silently as it doesn't represent a valid playable note.
-                }
-
-                let evp = PlayheadEvent::new(id_gen.next_id(), *y, event, TimeSpan::ZERO);
+                let event = ln_by_start.get(arena_idx).map_or_else(
+                    || event_for_note_static::<T>(bms, y_memo, obj),
+                    |ln| {
+                        let end_y = y_memo.get_y(ln.end);
+                        ChartEvent::Note {
+                            side: ln.side,
+                            key: ln.key,
+                            kind: NoteKind::Long,
+                            wav_id: Some(WavId::from(ln.start_wav_id.as_u16() as usize)),
+                            length: Some(
+                                NonNegativeF64::new((end_y - *y).as_f64())
+                                    .unwrap_or(NonNegativeF64::ZERO),
+                            ),
+                            continue_play: None,
+                        }
+                    },
+                );
+                let start_id = id_gen.next_id();
+                let evp = PlayheadEvent::new(start_id, *y, event, TimeSpan::ZERO);
                 events_map.entry(*y).or_default().push(evp);
+                if let Some(ln) = ln_by_start.get(arena_idx) {
+                    let end_y = y_memo.get_y(ln.end);
+                    let end = ChartEvent::NoteEnd {
+                        side: ln.side,
+                        key: ln.key,
+                        start_id,
+                        ln_mode: ln.ln_mode,
+                    };
+                    let end_evp = PlayheadEvent::new(id_gen.next_id(), end_y, end, TimeSpan::ZERO);
+                    events_map.entry(end_y).or_default().push(end_evp);
+                }
             }
         }
 
@@ -656,6 +681,32 @@ impl AllEventsIndex {
             ));
         }
 
+        // `#STP` stops, whose timing is applied by the flow events
+        for stp in bms.stop.stp_events.values() {
+            let y = get_event_y(stp.time);
+            let event = ChartEvent::Bms(BmsEvent::MillisecondStop {
+                duration: TimeSpan::from_duration(stp.duration),
+            });
+            events_map.entry(y).or_default().push(PlayheadEvent::new(
+                id_gen.next_id(),
+                y,
+                event,
+                TimeSpan::ZERO,
+            ));
+        }
+
+        // `#EXTCHR` has no position, so it applies from the start
+        for extchr in &bms.sprite.extchr_events {
+            let y = YCoordinate::ZERO;
+            let event = ChartEvent::Bms(BmsEvent::CharacterSpriteChange { event: *extchr });
+            events_map.entry(y).or_default().push(PlayheadEvent::new(
+                id_gen.next_id(),
+                y,
+                event,
+                TimeSpan::ZERO,
+            ));
+        }
+
         for option_obj in bms.option.option_events.values() {
             let y = get_event_y(option_obj.time);
             let event = ChartEvent::Bms(BmsEvent::OptionChange {
@@ -674,6 +725,88 @@ impl AllEventsIndex {
     }
 }
 
+/// Collects the playback parameters of the WAVs from `#EXWAV` and `#WAVCMD`.
+///
+/// `#EXWAV` pan and volume are in hundredths of decibels, and `#WAVCMD` volume is in percent,
+/// which is added to the `#EXWAV` volume.
+fn wav_params(bms: &Bms) -> HashMap<WavId, WavParams> {
+    let mut params: HashMap<WavId, WavParams> = HashMap::new();
+    for (obj_id, def) in &bms.wav.exwav_defs {
+        let param = params
+            .entry(WavId::from(obj_id.as_u16() as usize))
+            .or_default();
+        param.pan = FinF64::new(def.pan.value() as f64 / 10000.0).unwrap_or(FinF64::ZERO);
+        param.volume_db = FinF64::new(def.volume.value() as f64 / 100.0).unwrap_or(FinF64::ZERO);
+        param.frequency = def.frequency.map(ExWavFrequency::value);
+    }
+    for (obj_id, cmd) in &bms.wav.wavcmd_events {
+        let param = params
+            .entry(WavId::from(obj_id.as_u16() as usize))
+            .or_default();
+        match cmd.param {
+            WavCmdParam::Pitch => param.pitch = Some(cmd.value),
+            WavCmdParam::Volume => {
+                let db = if cmd.value == 0 {
+                    WavParams::MIN_VOLUME_DB
+                } else {
+                    (20.0 * (f64::from(cmd.value) / 100.0).log10()).max(WavParams::MIN_VOLUME_DB)
+                };
+                param.volume_db =
+                    FinF64::new(param.volume_db.as_f64() + db).unwrap_or(param.volume_db);
+            }
+            WavCmdParam::Time => {
+                // In units of 0.5 ms, where 0 plays the whole sound.
+                param.length =
+                    (cmd.value > 0).then(|| TimeSpan::MICROSECOND * (i64::from(cmd.value) * 500));
+            }
+        }
+    }
+    params
+}
+
+/// Collects the BGA definitions from `#BGA`, `#@BGA`, `#EXBMP`, `#BMP00` and `#POORBGA`.
+fn bga_resources(bms: &Bms) -> BgaResources {
+    let bmp_id = |obj_id: &ObjId| BmpId::from(obj_id.as_u16() as usize);
+    let at_bga_images = bms.bmp.atbga_defs.values().map(|def| {
+        let image = BgaImage {
+            source: bmp_id(&def.source_bmp),
+            crop: BgaCrop {
+                top_left: def.trim_top_left,
+                size: def.trim_size,
+            },
+            draw_point: def.draw_point,
+        };
+        (bmp_id(&def.id), image)
+    });
+    let bga_images = bms.bmp.bga_defs.values().map(|def| {
+        let top_left = def.trim_top_left;
+        let bottom_right = def.trim_bottom_right;
+        let image = BgaImage {
+            source: bmp_id(&def.source_bmp),
+            crop: BgaCrop {
+                top_left,
+                size: PixelSize::new(
+                    bottom_right.x.saturating_sub(top_left.x).max(0) as u16,
+                    bottom_right.y.saturating_sub(top_left.y).max(0) as u16,
+                ),
+            },
+            draw_point: def.draw_point,
+        };
+        (bmp_id(&def.id), image)
+    });
+    BgaResources {
+        images: at_bga_images.chain(bga_images).collect(),
+        color_keys: bms
+            .bmp
+            .bmp_files
+            .iter()
+            .map(|(obj_id, bmp)| (bmp_id(obj_id), bmp.transparent_color))
+            .collect(),
+        poor_bmp: bms.bmp.poor_bmp.clone(),
+        poor_mode: bms.bmp.poor_bga_mode,
+    }
+}
+
 /// Precompute absolute `activate_time` for all events based on BPM segmentation and Stops.
 ///
 /// # Errors
@@ -716,13 +849,28 @@ pub fn precompute_activate_times(
         .collect();
     points.extend(bpm_changes.iter().map(|(y, _)| *y));
 
+    // `#STOP` is in 192nds of a measure, and `#STP` is converted to measures at the BPM there.
+    let bpm_at = |y: YCoordinate| {
+        bpm_changes
+            .iter()
+            .filter(|(change_y, _)| *change_y <= y)
+            .max_by_key(|(change_y, _)| *change_y)
+            .map_or(init_bpm_value, |(_, bpm)| *bpm)
+    };
     let stop_list: Vec<(YCoordinate, NonNegativeF64)> = bms
         .stop
         .stops
         .values()
-        .map(|st| {
-            let sy = y_memo.get_y(st.time);
-            (sy, st.duration)
+        .map(|st| (y_memo.get_y(st.time), st.duration.as_f64() / 192.0))
+        .chain(bms.stop.stp_events.values().map(|stp| {
+            let sy = y_memo.get_y(stp.time);
+            (sy, stp.duration.as_secs_f64() * bpm_at(sy).as_f64() / 240.0)
+        }))
+        .map(|(sy, measures)| {
+            (
+                sy,
+                NonNegativeF64::new(measures).unwrap_or(MAX_NON_NEGATIVE_F64),
+            )
         })
         .sorted_by_key(|(y, _)| *y)
         .collect();
@@ -819,7 +967,8 @@ impl Process for Bms {
 // ---- BaseBpmGenerator implementations for BMS ----
 
 use crate::chart::player::base_bpm::{
-    BaseBpm, BaseBpmGenerator, MaxBpmGenerator, MinBpmGenerator, StartBpmGenerator,
+    AverageBpmGenerator, BaseBpm, BaseBpmGenerator, MainBpmByDurationGenerator,
+    MainBpmByNotesGenerator, MaxBpmGenerator, MinBpmGenerator, StartBpmGenerator,
 };
 
 impl BaseBpmGenerator<Bms> for StartBpmGenerator {
@@ -856,12 +1005,41 @@ impl BaseBpmGenerator<Bms> for MaxBpmGenerator {
     }
 }
 
+impl BaseBpmGenerator<Bms> for MainBpmByDurationGenerator {
+    fn generate(&self, bms: &Bms) -> Option<BaseBpm> {
+        self.generate(&bms.process().ok()?)
+    }
+}
+
+impl BaseBpmGenerator<Bms> for MainBpmByNotesGenerator {
+    fn generate(&self, bms: &Bms) -> Option<BaseBpm> {
+        self.generate(&bms.process().ok()?)
+    }
+}
+
+impl BaseBpmGenerator<Bms> for AverageBpmGenerator {
+    fn generate(&self, bms: &Bms) -> Option<BaseBpm> {
+        self.generate(&bms.process().ok()?)
+    }
+}
+
 impl BaseBpmGenerator<Bms> for crate::chart::player::base_bpm::ManualBpmGenerator {
     fn generate(&self, _bms: &Bms) -> Option<BaseBpm> {
         Some(self.0)
     }
 }
 
+impl Bms {
+    /// Computes the statistics of the chart processed with the beat key layout.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`PlayingError`] if the score cannot be processed into a chart.
+    pub fn chart_stats(&self) -> Result<ChartStats, PlayingError> {
+        Ok(self.process()?.stats())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -310,6 +310,11 @@ impl Bms {
                 args: (self.repr.ln_mode as u8).to_string().into(),
             });
         }
+        // LnObj
+        tokens.extend(self.wav.ln_objects.iter().map(|id| Token::Header {
+            name: "LNOBJ".into(),
+            args: id.to_string().into(),
+        }));
 
         tokens.extend(
             self.wav
@@ -1017,35 +1022,8 @@ impl Bms {
         }
 
         // Messages: BGM (#xxx01) and Notes (various #xx)
-        // Use build_event_messages to process note and BGM objects
         // We need to preserve the original insertion order, so we process each object individually
-        let EventProcessingResult {
-            message_tokens: notes_message_tokens,
-            ..
-        } = build_event_messages(
-            self.wav
-                .notes
-                .all_notes_insertion_order()
-                .map(|obj| (&obj.offset, obj)),
-            None::<(
-                fn(ObjId, &()) -> Token,
-                fn(&_) -> &(),
-                &mut ObjIdManager<()>,
-            )>,
-            |obj| {
-                // Channel mapping: determine channel based on channel_id
-                obj.channel_id
-                    .try_into_map::<T>()
-                    .map_or(Channel::Bgm, |_map| Channel::Note {
-                        channel_id: obj.channel_id,
-                    })
-            },
-            |obj, _id| {
-                let s = obj.wav_id.to_string();
-                let mut chars = s.chars();
-                [chars.next().unwrap_or('0'), chars.next().unwrap_or('0')]
-            }, // Message formatting: use wav_id
-        );
+        let notes_message_tokens = build_note_messages::<T>(&self.wav.notes);
 
         message_tokens.extend(notes_message_tokens);
 
@@ -1291,6 +1269,8 @@ struct EventUnit<'a, Event> {
     event: &'a Event,
     channel: Channel,
     id: Option<ObjId>,
+    /// The resolution of the message to write the event on, a multiple of the denominator of `time`.
+    resolution: u64,
 }
 
 /// Complete result from `build_messages_event` containing all processing outputs
@@ -1366,6 +1346,7 @@ where
                 event,
                 channel: channel_mapper(event),
                 id,
+                resolution: time.denominator_u64(),
             }
         })
         .collect();
@@ -1465,6 +1446,7 @@ where
                 event,
                 channel: channel_mapper(event),
                 id,
+                resolution: time.denominator_u64(),
             }
         })
         .collect();
@@ -1487,6 +1469,46 @@ where
     }
 }
 
+/// A version of `build_event_messages` for BGM and note objects.
+///
+/// An object on a long note channel is written on a message at least as fine as the message it was parsed from, because the cell of the object is the length of a long note in `#LNTYPE 2`.
+fn build_note_messages<T: KeyLayoutMapper>(notes: &Notes) -> Vec<Token<'_>> {
+    let processed_events: Vec<EventUnit<'_, WavObj>> = notes
+        .all_notes_insertion_order()
+        .map(|obj| {
+            // Channel mapping: determine channel based on channel_id
+            let map = obj.channel_id.try_into_map::<T>();
+            let denominator = obj.offset.denominator_u64();
+            let resolution = notes
+                .grid_of_obj(obj)
+                .filter(|_| map.as_ref().is_some_and(|map| map.kind() == NoteKind::Long))
+                .map_or(denominator, |grid| grid.get().lcm(&denominator));
+            EventUnit {
+                time: obj.offset,
+                event: obj,
+                channel: map.map_or(Channel::Bgm, |_map| Channel::Note {
+                    channel_id: obj.channel_id,
+                }),
+                id: None,
+                resolution,
+            }
+        })
+        .collect();
+
+    group_events_by_track_channel_time(processed_events)
+        .into_iter()
+        .flat_map(split_group_into_message_segments)
+        .map(|message_segment| {
+            convert_message_segment_to_token(message_segment, &|obj: &WavObj, _id| {
+                // Message formatting: use wav_id
+                let s = obj.wav_id.to_string();
+                let mut chars = s.chars();
+                [chars.next().unwrap_or('0'), chars.next().unwrap_or('0')]
+            })
+        })
+        .collect()
+}
+
 /// Group events by track, channel, and non-strictly increasing time
 fn group_events_by_track_channel_time<'a, Event>(
     processed_events: Vec<EventUnit<'a, Event>>,
@@ -1567,12 +1589,12 @@ fn is_denominator_compatible<'a, Event>(
     // Find the maximum denominator from the current message segment as reference
     let reference_denominator = message_segment
         .iter()
-        .map(|unit| unit.time.denominator_u64())
+        .map(|unit| unit.resolution)
         .max()
         .unwrap_or(1);
 
     // Check if the event unit's denominator shares a common factor relationship
-    let event_denominator = event_unit.time.denominator_u64();
+    let event_denominator = event_unit.resolution;
     reference_denominator.is_multiple_of(event_denominator)
         || event_denominator.is_multiple_of(reference_denominator)
 }
@@ -1610,7 +1632,7 @@ where
     // Example: if we have events at 1/3 and 1/5, LCM(3,5)=15, so we need length 15 to represent them both accurately.
     let denominators: Vec<u64> = message_segment
         .iter()
-        .map(|event_unit| event_unit.time.denominator_u64())
+        .map(|event_unit| event_unit.resolution)
         .collect();
     let lcm_denom = lcm_slice(&denominators);
 
@@ -10,13 +10,15 @@ use std::{
 use itertools::Itertools;
 use strict_num_extended::{FinF64, NonNegativeF64, PositiveF64};
 
+use crate::bms::command::Volume;
 use crate::bmson::prelude::*;
 use crate::chart::event::{ChartEvent, FlowEvent, PlayheadEvent};
 use crate::chart::prelude::{TimeSpan, YCoordinate};
 use crate::chart::process::{
-    AllEventsIndex, BmpId, ChartEventIdGenerator, ChartResources, Process, WavId,
+    AllEventsIndex, BmpId, ChartEventId, ChartEventIdGenerator, ChartResources, Process, WavId,
     calculate_cumulative_times,
 };
+use crate::chart::stats::ChartStats;
 use crate::chart::types::{BgaLayer, Key, NoteKind, PlayerSide};
 use crate::chart::{Chart, DEFAULT_SPEED, MAX_FIN_F64, MAX_NON_NEGATIVE_F64};
 use crate::util::StrExtension;
@@ -113,6 +115,21 @@ impl BmsonProcessor {
                     FinF64::new(rate.as_f64()).expect("rate should be finite"),
                 ));
         }
+        for StopEvent { y, duration } in &bmson.stop_events {
+            let bpm = bmson
+                .bpm_events
+                .iter()
+                .filter(|ev| ev.y.0 <= y.0)
+                .max_by_key(|ev| ev.y.0)
+                .map_or(init_bpm.as_f64(), |ev| ev.bpm.as_f64());
+            let secs = pulses_to_y(*duration as i64).as_f64() * 240.0 / bpm;
+            flow_events_by_y
+                .entry(pulses_to_y(y.0 as i64))
+                .or_default()
+                .push(FlowEvent::Stop(TimeSpan::from_duration(
+                    std::time::Duration::from_secs_f64(secs.clamp(0.0, u32::MAX as f64)),
+                )));
+        }
 
         let all_events =
             AllEventsIndex::precompute_events(bmson, &audio_name_to_id, &bmp_name_to_id);
@@ -127,13 +144,16 @@ impl BmsonProcessor {
             .map(|(name, id)| (id, PathBuf::from(name)))
             .collect();
 
-        Chart::from_parts(
-            ChartResources::new(wav_files, bmp_files),
+        let landmine_damages = landmine_damages(bmson, &all_events);
+        let mut chart = Chart::from_parts(
+            ChartResources::new(wav_files, bmp_files, HashMap::new(), Volume::default()),
             all_events,
             flow_events_by_y,
             init_bpm,
             DEFAULT_SPEED, // BMSON doesn't have Speed concept, default to 1.0
-        )
+        );
+        chart.landmine_damages = landmine_damages;
+        chart
     }
 }
 
@@ -157,6 +177,42 @@ fn lane_from_x(mode_hint: &str, x: Option<std::num::NonZeroU8>) -> Option<(Playe
     Some((side, key))
 }
 
+/// Finds the damage of each landmine event, by the position and the lane of the mine which makes it.
+fn landmine_damages(
+    bmson: &Bmson<'_>,
+    all_events: &AllEventsIndex,
+) -> BTreeMap<ChartEventId, FinF64> {
+    // Same as the positions in `AllEventsIndex::precompute_events`.
+    let denom_inv = 1.0 / (4 * bmson.info.resolution.get()) as f64;
+    let mut damages: HashMap<(u64, PlayerSide, Key), FinF64> = HashMap::new();
+    for MineChannel { notes, .. } in &bmson.mine_channels {
+        for MineEvent { x, y, damage } in notes {
+            let Some((side, key)) = lane_from_x(bmson.info.mode_hint.as_ref(), *x) else {
+                continue;
+            };
+            let y = (y.0 as f64 * denom_inv).to_bits();
+            damages.insert((y, side, key), *damage);
+        }
+    }
+    all_events
+        .as_events()
+        .iter()
+        .filter_map(|event| {
+            let ChartEvent::Note {
+                side,
+                key,
+                kind: NoteKind::Landmine,
+                ..
+            } = event.event
+            else {
+                return None;
+            };
+            let y = event.position.as_f64().to_bits();
+            Some((event.id, *damages.get(&(y, side, key))?))
+        })
+        .collect()
+}
+
 impl AllEventsIndex {
     fn precompute_events(
         bmson: &Bmson<'_>,
@@ -227,6 +283,12 @@ impl AllEventsIndex {
                 ));
             }
         }
+        // Long note ends are timed, but do not extend the generated bar lines.
+        for SoundChannel { notes, .. } in &bmson.sound_channels {
+            for Note { y, l, .. } in notes.iter().filter(|note| note.l > 0) {
+                points.insert(pulses_to_y(y.0 + l));
+            }
+        }
         let init_bpm: PositiveF64 =
             PositiveF64::new(bmson.info.init_bpm.as_f64()).expect("init_bpm should be positive");
         let bpm_changes: Vec<(YCoordinate, PositiveF64)> = bmson
@@ -257,7 +319,7 @@ impl AllEventsIndex {
         let mut id_gen: ChartEventIdGenerator = ChartEventIdGenerator::default();
         for SoundChannel { name, notes } in &bmson.sound_channels {
             let mut last_restart_y = YCoordinate::ZERO;
-            for Note { y, x, l, c, .. } in notes {
+            for Note { y, x, l, c, t, .. } in notes {
                 let y_coord = pulses_to_y(y.0);
                 let wav_id = audio_name_to_id.get(name.as_ref()).copied();
                 if let Some((side, key)) = lane_from_x(bmson.info.mode_hint.as_ref(), *x) {
@@ -285,11 +347,24 @@ impl AllEventsIndex {
                         continue_play,
                     };
                     let at = to_time_span(cum_map.get(&y_coord).copied().unwrap_or(0.0));
-                    let evp = PlayheadEvent::new(id_gen.next_id(), y_coord, event, at);
+                    let start_id = id_gen.next_id();
+                    let evp = PlayheadEvent::new(start_id, y_coord, event, at);
                     if !*c {
                         last_restart_y = y_coord;
                     }
                     events_map.entry(y_coord).or_default().push(evp);
+                    if *l > 0 {
+                        let end_y = pulses_to_y(y.0 + l);
+                        let end = ChartEvent::NoteEnd {
+                            side,
+                            key,
+                            start_id,
+                            ln_mode: t.unwrap_or(bmson.info.ln_type),
+                        };
+                        let end_at = to_time_span(cum_map.get(&end_y).copied().unwrap_or(0.0));
+                        let end_evp = PlayheadEvent::new(id_gen.next_id(), end_y, end, end_at);
+                        events_map.entry(end_y).or_default().push(end_evp);
+                    }
                 } else {
                     let event = ChartEvent::Bgm { wav_id };
                     let at = to_time_span(cum_map.get(&y_coord).copied().unwrap_or(0.0));
@@ -454,7 +529,8 @@ impl Process for Bmson<'_> {
 // ---- BaseBpmGenerator implementations for BMSON ----
 
 use crate::chart::player::base_bpm::{
-    BaseBpm, BaseBpmGenerator, MaxBpmGenerator, MinBpmGenerator, StartBpmGenerator,
+    AverageBpmGenerator, BaseBpm, BaseBpmGenerator, MainBpmByDurationGenerator,
+    MainBpmByNotesGenerator, MaxBpmGenerator, MinBpmGenerator, StartBpmGenerator,
 };
 
 impl<'a> BaseBpmGenerator<Bmson<'a>> for StartBpmGenerator {
@@ -481,8 +557,34 @@ impl<'a> BaseBpmGenerator<Bmson<'a>> for MaxBpmGenerator {
     }
 }
 
+impl<'a> BaseBpmGenerator<Bmson<'a>> for MainBpmByDurationGenerator {
+    fn generate(&self, bmson: &Bmson<'a>) -> Option<BaseBpm> {
+        self.generate(&BmsonProcessor::parse(bmson))
+    }
+}
+
+impl<'a> BaseBpmGenerator<Bmson<'a>> for MainBpmByNotesGenerator {
+    fn generate(&self, bmson: &Bmson<'a>) -> Option<BaseBpm> {
+        self.generate(&BmsonProcessor::parse(bmson))
+    }
+}
+
+impl<'a> BaseBpmGenerator<Bmson<'a>> for AverageBpmGenerator {
+    fn generate(&self, bmson: &Bmson<'a>) -> Option<BaseBpm> {
+        self.generate(&BmsonProcessor::parse(bmson))
+    }
+}
+
 impl<'a> BaseBpmGenerator<Bmson<'a>> for crate::chart::player::base_bpm::ManualBpmGenerator {
     fn generate(&self, _bmson: &Bmson<'a>) -> Option<BaseBpm> {
         Some(self.0)
     }
 }
+
+impl Bmson<'_> {
+    /// Computes the statistics of the chart processed from the score.
+    #[must_use]
+    pub fn chart_stats(&self) -> ChartStats {
+        BmsonProcessor::parse(self).stats()
+    }
+}
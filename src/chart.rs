@@ -47,12 +47,24 @@
 //!
 //! ### Display Coordinates
 //!
+//! **Display Y (integrates scroll over Y, starting from scroll 1.0 at y = 0):**
+//! ```text
+//! display_y(y) = display_y(scroll_change_y) + (y - scroll_change_y) * scroll
+//! ```
+//!
+//! where `scroll_change_y` is the last scroll change at or before `y`, and `scroll` is its factor.
+//!
 //! **Display ratio (0 = judgment line, 1 = appearance position):**
 //! ```text
+//! display_ratio = (display_y(event_y) - display_y(current_y)) / visible_window_y
+//! ```
+//!
+//! With `DisplayScroll::Current`, the whole distance is scaled by the current scroll instead:
+//! ```text
 //! display_ratio = (event_y - current_y) / visible_window_y * current_scroll
 //! ```
 //!
-//! The value of this type is only affected by: current Y, Y visible range, and current Speed, Scroll values.
+//! The value of this type is only affected by: current Y, Y visible range, current Speed, and the Scroll values.
 //!
 //! ### Reaction Time
 //!
@@ -62,26 +74,54 @@
 //! where playhead_speed = 1/240
 //! ```
 
+pub mod assist;
+
+pub mod autoplay;
+
+pub mod bga;
+
 pub mod event;
 
+pub mod gauge;
+
+pub mod judge;
+
+pub mod mixdown;
+
 pub mod player;
 
 pub mod prelude;
 
 pub mod process;
 
+pub mod random;
+
+pub mod replay;
+
+pub mod score;
+
+pub mod stats;
+
+pub mod timing;
+
 pub mod types;
 
+pub mod voice;
+
 use std::collections::{BTreeMap, HashMap};
 use std::path::PathBuf;
 
-use crate::chart::process::{AllEventsIndex, ChartResources, WavId};
+use crate::chart::process::{AllEventsIndex, ChartEventId, ChartResources, WavId};
 use gametime::TimeSpan;
 use strict_num_extended::FinF64;
 use strict_num_extended::NonNegativeF64;
 use strict_num_extended::PositiveF64;
 
-use self::event::{FlowEvent, YCoordinate};
+use self::event::{ChartEvent, FlowEvent, PlayheadEvent, YCoordinate};
+use self::timing::EventTimes;
+use self::types::{Key, PlayerSide};
+use crate::bms::command::LnMode;
+use crate::bms::command::channel::converter::{KeyConverter, PlayerSideKeyConverter};
 
 /// Maximum value for `NonNegativeF64` when overflow occurs
 pub(crate) const MAX_NON_NEGATIVE_F64: NonNegativeF64 = NonNegativeF64::new_const(f64::MAX);
@@ -112,6 +152,10 @@ pub struct Chart {
     pub(crate) init_bpm: PositiveF64,
     /// Initial Speed (BMS-specific, BMSON defaults to 1.0).
     pub(crate) init_speed: PositiveF64,
+    /// Damage of the landmines which define it (BMSON-specific).
+    pub(crate) landmine_damages: BTreeMap<ChartEventId, FinF64>,
+    /// Display Y and scroll factor at each scroll change, integrated from the start.
+    pub(crate) scroll_display_ys: BTreeMap<YCoordinate, (FinF64, FinF64)>,
 }
 
 impl Chart {
@@ -145,6 +189,31 @@ impl Chart {
         &self.init_speed
     }
 
+    /// Get the damage of a landmine event, if the chart defines it.
+    ///
+    /// Only BMSON `mine_channels` define the damage.
+    #[must_use]
+    pub fn landmine_damage(&self, id: ChartEventId) -> Option<FinF64> {
+        self.landmine_damages.get(&id).copied()
+    }
+
+    /// Get the display Y of a Y coordinate, which integrates the scroll factor over Y.
+    ///
+    /// The display Y is equal to the Y until the first scroll change, advances by the scroll factor
+    /// per Y after each change, and goes back while the scroll factor is negative. The difference
+    /// of the display Ys between the judgment line and a note is the distance on the screen.
+    #[must_use]
+    pub fn display_y(&self, y: &YCoordinate) -> FinF64 {
+        let (base_y, base_display_y, scroll) = self
+            .scroll_display_ys
+            .range(..=y)
+            .next_back()
+            .map_or((0.0, 0.0, 1.0), |(point_y, (display_y, scroll))| {
+                (point_y.as_f64(), display_y.as_f64(), scroll.as_f64())
+            });
+        FinF64::new(base_display_y + (y.as_f64() - base_y) * scroll).unwrap_or(MAX_FIN_F64)
+    }
+
     /// Get audio file resources (WAV ID to path mapping).
     ///
     /// This is a convenience method that directly accesses the audio files.
@@ -163,24 +232,172 @@ impl Chart {
         self.resources.bmp_files()
     }
 
+    /// Moves the notes on `side` onto the keys which `converter` returns.
+    ///
+    /// The notes on the other side are kept, so a different option can be applied to each side of a
+    /// double play chart. Notes are not checked for overlaps, so `converter` is expected to map the
+    /// lanes one to one.
+    pub fn convert_keys(&mut self, side: PlayerSide, converter: &mut impl KeyConverter) {
+        self.relane_notes(|note_side, key| {
+            let key = if note_side == side {
+                converter.convert(key)
+            } else {
+                key
+            };
+            (note_side, key)
+        });
+    }
+
+    /// Moves the notes onto the lanes which `converter` returns.
+    ///
+    /// Notes are not checked for overlaps, so `converter` is expected to map the lanes one to one.
+    pub fn convert_player_side_keys(&mut self, converter: &mut impl PlayerSideKeyConverter) {
+        self.relane_notes(|side, key| converter.convert((side, key)));
+    }
+
+    /// Adds a [`ChartEvent::HcnTick`] every `interval` from the start of each long note of
+    /// [`LnMode::Hcn`], up to its [`ChartEvent::NoteEnd`] excluding both.
+    ///
+    /// The ticks are timed in the same way as the other events, and keep still during stops.
+    /// Nothing is added if `interval` is not positive.
+    pub fn add_hcn_ticks(&mut self, interval: TimeSpan) {
+        if interval <= TimeSpan::ZERO {
+            return;
+        }
+        let times = EventTimes::new(self);
+        let starts: HashMap<ChartEventId, TimeSpan> = self
+            .events
+            .as_events()
+            .iter()
+            .map(|event| (event.id, event.activate_time))
+            .collect();
+        let mut next_id = self
+            .events
+            .as_events()
+            .iter()
+            .map(|event| event.id.value() + 1)
+            .max()
+            .unwrap_or(0);
+        let mut ticks = vec![];
+        for event in self.events.as_events() {
+            let ChartEvent::NoteEnd {
+                side,
+                key,
+                start_id,
+                ln_mode: LnMode::Hcn,
+            } = event.event
+            else {
+                continue;
+            };
+            let Some(&start) = starts.get(&start_id) else {
+                continue;
+            };
+            let mut time = start + interval;
+            while time < event.activate_time {
+                let y = times.y_at(time.as_secs_f64());
+                ticks.push(PlayheadEvent::new(
+                    ChartEventId::new(next_id),
+                    YCoordinate::new(NonNegativeF64::new(y).unwrap_or(MAX_NON_NEGATIVE_F64)),
+                    ChartEvent::HcnTick {
+                        side,
+                        key,
+                        start_id,
+                    },
+                    time,
+                ));
+                next_id += 1;
+                time += interval;
+            }
+        }
+        self.events.extend_events(ticks);
+    }
+
+    /// Moves each [`ChartEvent::Note`] onto the lane which `map` returns.
+    ///
+    /// A long note is a single event, so it moves as a whole.
+    fn relane_notes(&mut self, mut map: impl FnMut(PlayerSide, Key) -> (PlayerSide, Key)) {
+        for event in self.events.as_events_mut() {
+            if let ChartEvent::Note { side, key, .. } = &mut event.event {
+                (*side, *key) = map(*side, *key);
+            }
+        }
+        self.sync_long_note_lanes();
+    }
+
+    /// Moves the [`ChartEvent::NoteEnd`] and the [`ChartEvent::HcnTick`]s of each long note onto
+    /// the lane of its start.
+    pub(crate) fn sync_long_note_lanes(&mut self) {
+        let lanes: HashMap<ChartEventId, (PlayerSide, Key)> = self
+            .events
+            .as_events()
+            .iter()
+            .filter_map(|event| match event.event {
+                ChartEvent::Note { side, key, .. } => Some((event.id, (side, key))),
+                _ => None,
+            })
+            .collect();
+        for event in self.events.as_events_mut() {
+            if let ChartEvent::NoteEnd {
+                side,
+                key,
+                start_id,
+                ..
+            }
+            | ChartEvent::HcnTick {
+                side,
+                key,
+                start_id,
+            } = &mut event.event
+                && let Some(&lane) = lanes.get(start_id)
+            {
+                (*side, *key) = lane;
+            }
+        }
+    }
+
     /// Create a new `Chart` from its constituent parts.
     ///
     /// This is an internal constructor used by chart processors to assemble
     /// a parsed chart from its components.
     #[must_use]
-    pub(crate) const fn from_parts(
+    pub(crate) fn from_parts(
         resources: ChartResources,
         events: AllEventsIndex,
         flow_events: BTreeMap<YCoordinate, Vec<FlowEvent>>,
         init_bpm: PositiveF64,
         init_speed: PositiveF64,
     ) -> Self {
+        let scroll_display_ys = scroll_display_ys(&flow_events);
         Self {
             resources,
             events,
             flow_events,
             init_bpm,
             init_speed,
+            landmine_damages: BTreeMap::new(),
+            scroll_display_ys,
         }
     }
 }
+
+/// Integrates the scroll factor over Y up to each scroll change.
+fn scroll_display_ys(
+    flow_events: &BTreeMap<YCoordinate, Vec<FlowEvent>>,
+) -> BTreeMap<YCoordinate, (FinF64, FinF64)> {
+    let mut display_ys = BTreeMap::new();
+    let (mut last_y, mut last_display_y, mut last_scroll) = (0.0, 0.0, 1.0);
+    for (y, events) in flow_events {
+        // The last scroll change at the same Y wins, as the player applies them in order.
+        let Some(scroll) = events.iter().rev().find_map(|event| match event {
+            FlowEvent::Scroll(scroll) => Some(*scroll),
+            _ => None,
+        }) else {
+            continue;
+        };
+        let display_y = last_display_y + (y.as_f64() - last_y) * last_scroll;
+        let display_y = FinF64::new(display_y).unwrap_or(MAX_FIN_F64);
+        display_ys.insert(*y, (display_y, scroll));
+        (last_y, last_display_y, last_scroll) = (y.as_f64(), display_y.as_f64(), scroll.as_f64());
+    }
+    display_ys
+}
@@ -0,0 +1,144 @@
+//! Assist options applied to a [`Chart`].
+//!
+//! See [`crate::bms::model::assist`] for the options.
+
+use std::collections::HashMap;
+
+use strict_num_extended::NonNegativeF64;
+
+use super::Chart;
+use super::event::{ChartEvent, PlayheadEvent};
+use super::process::ChartEventId;
+use super::types::{Key, NoteKind, PlayerSide};
+use crate::bms::model::assist::{Assist, AssistReport};
+use crate::bms::model::key_mode::FoldStrategy;
+
+impl Chart {
+    /// Applies `assist` to the note events.
+    ///
+    /// A note turned into BGM keeps its keysound as a [`ChartEvent::Bgm`] if it is a visible note or a
+    /// long note, and is removed otherwise.
+    pub fn apply_assist(&mut self, assist: Assist) -> AssistReport {
+        let mut changed = 0;
+        match assist {
+            Assist::AutoScratch => self.events.retain_events(|event| match event.event {
+                ChartEvent::Note {
+                    key: Key::Scratch(_),
+                    ..
+                } => {
+                    changed += 1;
+                    into_bgm(event)
+                }
+                ChartEvent::NoteEnd {
+                    key: Key::Scratch(_),
+                    ..
+                }
+                | ChartEvent::HcnTick {
+                    key: Key::Scratch(_),
+                    ..
+                } => false,
+                _ => true,
+            }),
+            Assist::LegacyNote => self.events.retain_events(|event| {
+                if let ChartEvent::Note {
+                    kind: kind @ NoteKind::Long,
+                    length,
+                    ..
+                } = &mut event.event
+                {
+                    *kind = NoteKind::Visible;
+                    *length = None;
+                    changed += 1;
+                }
+                event.event.long_note_start().is_none()
+            }),
+            Assist::NoLandmine => self.events.retain_events(|event| {
+                let is_landmine = matches!(
+                    event.event,
+                    ChartEvent::Note {
+                        kind: NoteKind::Landmine,
+                        ..
+                    }
+                );
+                changed += usize::from(is_landmine);
+                !is_landmine
+            }),
+            Assist::FiveKeys(strategy) => {
+                let folded = self.fold_destinations(strategy);
+                changed = folded.len();
+                self.events.retain_events(|event| {
+                    let note_id = event.event.long_note_start().unwrap_or(event.id);
+                    match folded.get(&note_id) {
+                        None => true,
+                        Some(Some(folded)) => {
+                            if let ChartEvent::Note { key, .. } = &mut event.event {
+                                *key = *folded;
+                            }
+                            true
+                        }
+                        Some(None) => into_bgm(event),
+                    }
+                });
+                self.sync_long_note_lanes();
+            }
+        }
+        AssistReport { assist, changed }
+    }
+
+    /// Returns the keys which the notes on the keys 6 and 7 are folded onto, `None` if they cannot be placed.
+    ///
+    /// Notes staying on their lanes are placed first, then folded notes are placed in order of position,
+    /// unless they overlap a note placed before.
+    fn fold_destinations(&self, strategy: FoldStrategy) -> HashMap<ChartEventId, Option<Key>> {
+        // Intervals occupied in each lane. Invisible notes do not collide with the others.
+        let mut occupied: HashMap<(PlayerSide, Key, bool), Vec<(f64, f64)>> = HashMap::new();
+        let mut folding = vec![];
+        for event in self.events.as_events() {
+            let ChartEvent::Note {
+                side,
+                key,
+                kind,
+                length,
+                ..
+            } = event.event
+            else {
+                continue;
+            };
+            let start = event.position.as_f64();
+            let span = (start, start + length.map_or(0.0, NonNegativeF64::as_f64));
+            let lane = (side, key, kind == NoteKind::Invisible);
+            match strategy.fold(key) {
+                Some(folded) if folded == key => occupied.entry(lane).or_default().push(span),
+                folded => folding.push((event.id, lane, span, folded)),
+            }
+        }
+
+        let mut destinations = HashMap::new();
+        for (id, (side, _, invisible), (start, end), folded) in folding {
+            let placed = folded.filter(|&key| {
+                let lane = occupied.entry((side, key, invisible)).or_default();
+                let overlaps = lane.iter().any(|&(s, e)| s <= end && start <= e);
+                if !overlaps {
+                    lane.push((start, end));
+                }
+                !overlaps
+            });
+            destinations.insert(id, placed);
+        }
+        destinations
+    }
+}
+
+/// Turns a visible note or a long note into BGM, and returns whether the event is kept.
+///
+/// The end and the ticks of a long note are removed.
+fn into_bgm(event: &mut PlayheadEvent) -> bool {
+    let ChartEvent::Note { kind, wav_id, .. } = event.event else {
+        return event.event.long_note_start().is_none();
+    };
+    if !kind.is_playable() {
+        return false;
+    }
+    event.event = ChartEvent::Bgm { wav_id };
+    true
+}
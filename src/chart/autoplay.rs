@@ -0,0 +1,153 @@
+//! Autoplay, which generates the ideal key inputs of a chart.
+//!
+//! [`Chart::autoplay`] presses each visible note and long note at its
+//! [`activate_time`](super::event::PlayheadEvent::activate_time), holds long notes until their
+//! ends, and releases the other notes after [`AutoplayConfig::hold`]. The inputs can be fed to
+//! [`Judge::input`](super::judge::Judge::input) or recorded in a
+//! [`Replay`](super::replay::Replay).
+//!
+//! A key is released before the next landmine on its lane passes, except while a long note is held
+//! over the landmine.
+
+use gametime::TimeSpan;
+
+use super::Chart;
+use super::event::ChartEvent;
+use super::judge::{KeyAction, KeyInput};
+use super::timing::EventTimes;
+use super::types::{Key, NoteKind, PlayerSide};
+use crate::bms::rng::JavaRandom;
+
+/// Options of [`Chart::autoplay`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AutoplayConfig {
+    /// Duration to hold a key for a note which is not a long note.
+    pub hold: TimeSpan,
+    /// Maximum offset of the inputs from the exact times, for humanised play. The offsets are
+    /// uniformly distributed in microseconds.
+    pub jitter: TimeSpan,
+    /// Seed of the [`JavaRandom`] for the offsets.
+    pub seed: i64,
+}
+
+impl Default for AutoplayConfig {
+    fn default() -> Self {
+        Self {
+            hold: TimeSpan::MILLISECOND * 80,
+            jitter: TimeSpan::ZERO,
+            seed: 0,
+        }
+    }
+}
+
+/// Notes and landmines on a lane.
+#[derive(Debug, Default)]
+struct Lane {
+    /// Times of the notes and the ends of the long notes, sorted by the time.
+    notes: Vec<(TimeSpan, Option<TimeSpan>)>,
+    /// Times of the landmines, sorted.
+    mines: Vec<TimeSpan>,
+}
+
+impl Chart {
+    /// Generates the inputs to play the chart, sorted by the time.
+    #[must_use]
+    pub fn autoplay(&self, config: &AutoplayConfig) -> Vec<KeyInput> {
+        let times = EventTimes::new(self);
+        let mut lanes: Vec<((PlayerSide, Key), Lane)> = vec![];
+        for event in self.events().as_events() {
+            let ChartEvent::Note {
+                side, key, kind, ..
+            } = event.event
+            else {
+                continue;
+            };
+            let index = lanes
+                .iter()
+                .position(|&(lane, _)| lane == (side, key))
+                .unwrap_or_else(|| {
+                    lanes.push(((side, key), Lane::default()));
+                    lanes.len() - 1
+                });
+            let Some((_, lane)) = lanes.get_mut(index) else {
+                continue;
+            };
+            match kind {
+                NoteKind::Visible => lane.notes.push((event.activate_time, None)),
+                NoteKind::Long => lane
+                    .notes
+                    .push((event.activate_time, times.long_note_end(event))),
+                NoteKind::Landmine => lane.mines.push(event.activate_time),
+                NoteKind::Invisible => {}
+            }
+        }
+
+        let mut jitter = Jitter::new(config);
+        let mut inputs = vec![];
+        for ((side, key), mut lane) in lanes {
+            lane.notes.sort_by_key(|&(time, _)| time);
+            lane.mines.sort();
+            let presses: Vec<_> = lane
+                .notes
+                .iter()
+                .map(|&(time, _)| time + jitter.next())
+                .collect();
+            let mut released = TimeSpan::MIN;
+            for (index, (&(_, end), &press)) in lane.notes.iter().zip(&presses).enumerate() {
+                let press = press.max(released);
+                let next_press = presses.get(index + 1).copied().unwrap_or(TimeSpan::MAX);
+                let release = end
+                    .map_or_else(
+                        || {
+                            // A landmine at the press is passed before it, so only later ones
+                            // matter.
+                            let next_mine = lane
+                                .mines
+                                .iter()
+                                .find(|&&mine| mine > press)
+                                .map_or(TimeSpan::MAX, |&mine| mine - TimeSpan::new(1));
+                            (press + config.hold).min(next_press).min(next_mine)
+                        },
+                        |end| (end + jitter.next()).min(next_press),
+                    )
+                    .max(press);
+                let input = |time, action| KeyInput {
+                    time,
+                    side,
+                    key,
+                    action,
+                };
+                inputs.push(input(press, KeyAction::Press));
+                inputs.push(input(release, KeyAction::Release));
+                released = release;
+            }
+        }
+        inputs.sort_by_key(|input| input.time);
+        inputs
+    }
+}
+
+/// Random offsets of the inputs.
+struct Jitter {
+    rng: JavaRandom,
+    /// Maximum offset in microseconds.
+    max_micros: i32,
+}
+
+impl Jitter {
+    fn new(config: &AutoplayConfig) -> Self {
+        let max_micros = (config.jitter.abs().as_nanos() / 1000).min(i64::from(i32::MAX / 2));
+        Self {
+            rng: JavaRandom::new(config.seed),
+            max_micros: max_micros as i32,
+        }
+    }
+
+    fn next(&mut self) -> TimeSpan {
+        if self.max_micros == 0 {
+            return TimeSpan::ZERO;
+        }
+        let micros = self.rng.next_int_bound(self.max_micros * 2 + 1) - self.max_micros;
+        TimeSpan::MICROSECOND * i64::from(micros)
+    }
+}
@@ -0,0 +1,426 @@
+//! BGA compositor timeline, which resolves the image shown on each layer at a time.
+//!
+//! A [`BgaTimeline`] applies the BMS semantics of the BGA to the events of a chart:
+//!
+//! - A BGA change refers to a `#BGA` or `#@BGA` definition if any, which crops a BMP and places it
+//!   at a draw point, or to the BMP itself otherwise.
+//! - The opacity and ARGB changes (`#xxx0B:` to `#xxx0E:`, `#xxxA1:` to `#xxxA4:`) apply to the
+//!   layers from their times.
+//! - The POOR layer shows for [`BgaTimeline::poor_duration`] after a miss, by the latest POOR BGA
+//!   change or `#BMP00`, in the `#POORBGA` mode.
+//! - A key-bound animation change (`#xxxA5:`) binds a `#SWBGA` animation to its key, which plays the
+//!   frames of its pattern from each press of the key given by [`BgaTimeline::press_key`].
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use gametime::TimeSpan;
+
+use super::Chart;
+use super::event::{BmsEvent, ChartEvent};
+use super::process::BmpId;
+use super::types::{Argb, BgaLayer, Key, NoteKind, PlayerSide};
+use crate::bms::command::channel::NoteChannelId;
+use crate::bms::command::channel::mapper::{KeyLayout, KeyLayoutBeat};
+use crate::bms::command::graphics::{PixelPoint, PixelSize};
+use crate::bms::command::minor_command::SwBgaEvent;
+use crate::bms::command::{ObjId, PoorMode};
+
+/// ARGB of a layer without tint.
+pub const NO_TINT: Argb = Argb {
+    alpha: 255,
+    red: 255,
+    green: 255,
+    blue: 255,
+};
+
+/// Rectangle cropped from a BMP.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BgaCrop {
+    /// The top-left position in pixels.
+    pub top_left: PixelPoint,
+    /// The size in pixels.
+    pub size: PixelSize,
+}
+
+/// A cropped image from `#BGA` or `#@BGA`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BgaImage {
+    /// The BMP to crop.
+    pub source: BmpId,
+    /// The rectangle to crop.
+    pub crop: BgaCrop,
+    /// The position to draw the cropped image at, in pixels.
+    pub draw_point: PixelPoint,
+}
+
+/// BGA definitions of a chart, other than the BMP files.
+#[derive(Debug, Clone, Default)]
+pub struct BgaResources {
+    pub(crate) images: HashMap<BmpId, BgaImage>,
+    pub(crate) color_keys: HashMap<BmpId, Argb>,
+    pub(crate) poor_bmp: Option<PathBuf>,
+    pub(crate) poor_mode: PoorMode,
+}
+
+impl BgaResources {
+    /// Get the cropped images, by the ids which BGA changes refer to.
+    #[must_use]
+    pub const fn images(&self) -> &HashMap<BmpId, BgaImage> {
+        &self.images
+    }
+
+    /// Get the colors treated as transparent in the BMPs.
+    #[must_use]
+    pub const fn color_keys(&self) -> &HashMap<BmpId, Argb> {
+        &self.color_keys
+    }
+
+    /// Get the image shown on a miss without a POOR BGA change, from `#BMP00`.
+    #[must_use]
+    pub fn poor_bmp(&self) -> Option<&Path> {
+        self.poor_bmp.as_deref()
+    }
+
+    /// Get the display mode of the POOR layer, from `#POORBGA`.
+    #[must_use]
+    pub const fn poor_mode(&self) -> PoorMode {
+        self.poor_mode
+    }
+}
+
+/// What to draw on a layer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BgaLayerState<'a> {
+    /// The id in the BGA change, or `None` for `#BMP00`.
+    pub bmp_id: Option<BmpId>,
+    /// The BMP to draw.
+    pub source: Option<BmpId>,
+    /// The path of the BMP to draw.
+    pub path: &'a Path,
+    /// The rectangle to crop from the BMP, or `None` to draw the whole BMP.
+    pub crop: Option<BgaCrop>,
+    /// The position to draw at, in pixels.
+    pub draw_point: PixelPoint,
+    /// The opacity, 255 for opaque.
+    pub opacity: u8,
+    /// The tint, [`NO_TINT`] for none.
+    pub argb: Argb,
+    /// The color treated as transparent in the BMP.
+    pub color_key: Option<Argb>,
+}
+
+/// The layers of the BGA at a time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BgaFrame<'a> {
+    /// The base layer.
+    pub base: Option<BgaLayerState<'a>>,
+    /// The overlay layer.
+    pub overlay: Option<BgaLayerState<'a>>,
+    /// The second overlay layer.
+    pub overlay2: Option<BgaLayerState<'a>>,
+    /// The frame of the key-bound animation playing, which is drawn over the overlay layers and
+    /// under the POOR layer. It is not in [`Self::visible_layers`].
+    pub keybound: Option<BgaLayerState<'a>>,
+    /// The POOR layer, which is drawn only if [`Self::poor_showing`].
+    pub poor: Option<BgaLayerState<'a>>,
+    /// Whether the POOR layer is showing after a miss.
+    pub poor_showing: bool,
+    /// The display mode of the POOR layer.
+    pub poor_mode: PoorMode,
+}
+
+impl<'a> BgaFrame<'a> {
+    /// Returns the state of the layer.
+    #[must_use]
+    pub const fn layer(&self, layer: BgaLayer) -> Option<&BgaLayerState<'a>> {
+        match layer {
+            BgaLayer::Base => self.base.as_ref(),
+            BgaLayer::Overlay => self.overlay.as_ref(),
+            BgaLayer::Overlay2 => self.overlay2.as_ref(),
+            BgaLayer::Poor => self.poor.as_ref(),
+        }
+    }
+
+    /// Returns the layers to draw, from the bottom.
+    #[must_use]
+    pub fn visible_layers(&self) -> Vec<(BgaLayer, &BgaLayerState<'a>)> {
+        let poor = self
+            .poor
+            .as_ref()
+            .filter(|_| self.poor_showing)
+            .map(|state| (BgaLayer::Poor, state));
+        if self.poor_mode == PoorMode::Interrupt && poor.is_some() {
+            return poor.into_iter().collect();
+        }
+        [
+            (BgaLayer::Base, self.base.as_ref()),
+            (BgaLayer::Overlay, self.overlay.as_ref()),
+            (BgaLayer::Overlay2, self.overlay2.as_ref()),
+        ]
+        .into_iter()
+        .filter_map(|(layer, state)| Some((layer, state?)))
+        .chain(poor)
+        .collect()
+    }
+}
+
+/// Changes of a layer, sorted by time.
+#[derive(Debug, Default)]
+struct LayerChanges {
+    images: Vec<(TimeSpan, Option<BmpId>)>,
+    opacities: Vec<(TimeSpan, u8)>,
+    argbs: Vec<(TimeSpan, Argb)>,
+}
+
+/// A press of a key, and the release after it if any.
+#[derive(Debug, Clone, Copy)]
+struct KeyPress {
+    pressed_at: TimeSpan,
+    released_at: Option<TimeSpan>,
+}
+
+/// Resolves the layers of the BGA of a chart at any time.
+#[derive(Debug)]
+pub struct BgaTimeline<'a> {
+    chart: &'a Chart,
+    layers: HashMap<BgaLayer, LayerChanges>,
+    /// Key-bound animations of each key, sorted by time.
+    keybounds: HashMap<(PlayerSide, Key), Vec<(TimeSpan, &'a SwBgaEvent)>>,
+    key_presses: HashMap<(PlayerSide, Key), KeyPress>,
+    poor_duration: TimeSpan,
+}
+
+impl<'a> BgaTimeline<'a> {
+    /// Default duration for which the POOR layer shows after a miss.
+    pub const DEFAULT_POOR_DURATION: TimeSpan = TimeSpan::SECOND;
+
+    /// Collects the BGA changes of the chart.
+    #[must_use]
+    pub fn new(chart: &'a Chart) -> Self {
+        let mut events: Vec<_> = chart.events().as_events().iter().collect();
+        events.sort_by_key(|event| (event.activate_time, event.id));
+        let mut layers: HashMap<BgaLayer, LayerChanges> = HashMap::new();
+        let mut keybounds: HashMap<_, Vec<_>> = HashMap::new();
+        for event in events {
+            let time = event.activate_time;
+            match event.event() {
+                ChartEvent::BgaChange { layer, bmp_id } => {
+                    layers
+                        .entry(*layer)
+                        .or_default()
+                        .images
+                        .push((time, *bmp_id));
+                }
+                ChartEvent::Bms(BmsEvent::BgaOpacityChange { layer, opacity }) => {
+                    layers
+                        .entry(*layer)
+                        .or_default()
+                        .opacities
+                        .push((time, *opacity));
+                }
+                ChartEvent::Bms(BmsEvent::BgaArgbChange { layer, argb }) => {
+                    layers.entry(*layer).or_default().argbs.push((time, *argb));
+                }
+                ChartEvent::Bms(BmsEvent::BgaKeybound { event }) => {
+                    if let Some(lane) = keybound_lane(event.line) {
+                        keybounds.entry(lane).or_default().push((time, event));
+                    }
+                }
+                _ => {}
+            }
+        }
+        Self {
+            chart,
+            layers,
+            keybounds,
+            key_presses: HashMap::new(),
+            poor_duration: Self::DEFAULT_POOR_DURATION,
+        }
+    }
+
+    /// Records a press of the key at `time`, which starts the key-bound animation of it.
+    pub fn press_key(&mut self, side: PlayerSide, key: Key, time: TimeSpan) {
+        self.key_presses.insert(
+            (side, key),
+            KeyPress {
+                pressed_at: time,
+                released_at: None,
+            },
+        );
+    }
+
+    /// Records a release of the key at `time`, which stops the key-bound animation of it if the
+    /// animation plays while the key is held.
+    pub fn release_key(&mut self, side: PlayerSide, key: Key, time: TimeSpan) {
+        if let Some(press) = self.key_presses.get_mut(&(side, key)) {
+            press.released_at = Some(time);
+        }
+    }
+
+    /// Returns the duration for which the POOR layer shows after a miss.
+    #[must_use]
+    pub const fn poor_duration(&self) -> TimeSpan {
+        self.poor_duration
+    }
+
+    /// Sets the duration for which the POOR layer shows after a miss.
+    pub const fn set_poor_duration(&mut self, duration: TimeSpan) {
+        self.poor_duration = duration;
+    }
+
+    /// Returns the layers at `time`, where `last_miss` is the time of the latest miss, if any.
+    #[must_use]
+    pub fn frame_at(&self, time: TimeSpan, last_miss: Option<TimeSpan>) -> BgaFrame<'a> {
+        let bga = self.chart.resources().bga();
+        let layer = |layer| self.layer_at(layer, time);
+
+        let poor = self
+            .layers
+            .get(&BgaLayer::Poor)
+            .and_then(|changes| latest(&changes.images, time))
+            .map_or_else(
+                || {
+                    // `#BMP00` without a POOR BGA change
+                    let path = bga.poor_bmp()?;
+                    Some(self.layer_state(BgaLayer::Poor, None, None, path, time))
+                },
+                |_| layer(BgaLayer::Poor),
+            );
+        let poor_showing = bga.poor_mode() != PoorMode::Hidden
+            && poor.is_some()
+            && last_miss.is_some_and(|miss| miss <= time && time < miss + self.poor_duration);
+
+        BgaFrame {
+            base: layer(BgaLayer::Base),
+            overlay: layer(BgaLayer::Overlay),
+            overlay2: layer(BgaLayer::Overlay2),
+            keybound: self.keybound_at(time),
+            poor,
+            poor_showing,
+            poor_mode: bga.poor_mode(),
+        }
+    }
+
+    /// Resolves the image of the layer at `time`.
+    fn layer_at(&self, layer: BgaLayer, time: TimeSpan) -> Option<BgaLayerState<'a>> {
+        let bmp_id = latest(&self.layers.get(&layer)?.images, time)??;
+        let (source, path, image) = self.resolve(bmp_id)?;
+        let mut state = self.layer_state(layer, Some(bmp_id), Some(source), path, time);
+        if let Some(image) = image {
+            state.crop = Some(image.crop);
+            state.draw_point = image.draw_point;
+        }
+        Some(state)
+    }
+
+    /// Resolves the frame of the latest pressed key whose key-bound animation plays at `time`.
+    ///
+    /// The animation bound at the press plays for its total time, or while the key is held if it
+    /// is zero. Each frame lasts for the frame rate in milliseconds, and the last frame stays
+    /// unless the animation loops.
+    fn keybound_at(&self, time: TimeSpan) -> Option<BgaLayerState<'a>> {
+        self.keybounds
+            .iter()
+            .filter_map(|(lane, bindings)| {
+                let press = self
+                    .key_presses
+                    .get(lane)
+                    .filter(|press| press.pressed_at <= time)?;
+                let event = latest(bindings, press.pressed_at)?;
+                let elapsed = (time - press.pressed_at).as_millis();
+                let playing = if event.total_time == 0 {
+                    press.released_at.is_none_or(|released| time < released)
+                } else {
+                    elapsed < i64::from(event.total_time)
+                };
+                if !playing {
+                    return None;
+                }
+                let frames: Vec<_> = event
+                    .pattern
+                    .as_bytes()
+                    .chunks(2)
+                    .filter_map(|chunk| {
+                        ObjId::try_from(std::str::from_utf8(chunk).ok()?, false).ok()
+                    })
+                    .collect();
+                let index = usize::try_from(elapsed / i64::from(event.frame_rate.max(1)))
+                    .unwrap_or(usize::MAX);
+                let index = if event.loop_mode {
+                    index.checked_rem(frames.len())?
+                } else {
+                    index.min(frames.len().checked_sub(1)?)
+                };
+                let bmp_id = BmpId::from(frames.get(index)?.as_u16() as usize);
+                let (source, path, image) = self.resolve(bmp_id)?;
+                let state = BgaLayerState {
+                    bmp_id: Some(bmp_id),
+                    source: Some(source),
+                    path,
+                    crop: image.map(|image| image.crop),
+                    draw_point: image.map_or(PixelPoint::new(0, 0), |image| image.draw_point),
+                    opacity: u8::MAX,
+                    argb: NO_TINT,
+                    color_key: Some(event.argb),
+                };
+                Some((press.pressed_at, state))
+            })
+            .max_by_key(|&(pressed_at, _)| pressed_at)
+            .map(|(_, state)| state)
+    }
+
+    /// Resolves the BMP, its path and the crop which `bmp_id` refers to.
+    fn resolve(&self, bmp_id: BmpId) -> Option<(BmpId, &'a Path, Option<&'a BgaImage>)> {
+        let resources = self.chart.resources();
+        let image = resources.bga().images().get(&bmp_id);
+        let source = image.map_or(bmp_id, |image| image.source);
+        let path = resources.bmp_files().get(&source)?;
+        Some((source, path, image))
+    }
+
+    /// Creates the state of the layer with the opacity and the ARGB at `time`.
+    fn layer_state(
+        &self,
+        layer: BgaLayer,
+        bmp_id: Option<BmpId>,
+        source: Option<BmpId>,
+        path: &'a Path,
+        time: TimeSpan,
+    ) -> BgaLayerState<'a> {
+        let changes = self.layers.get(&layer);
+        BgaLayerState {
+            bmp_id,
+            source,
+            path,
+            crop: None,
+            draw_point: PixelPoint::new(0, 0),
+            opacity: changes
+                .and_then(|changes| latest(&changes.opacities, time))
+                .unwrap_or(u8::MAX),
+            argb: changes
+                .and_then(|changes| latest(&changes.argbs, time))
+                .unwrap_or(NO_TINT),
+            color_key: source
+                .and_then(|source| self.chart.resources().bga().color_keys().get(&source))
+                .copied(),
+        }
+    }
+}
+
+/// Returns the key of a `#SWBGA` line, which is a visible note channel such as `11`.
+fn keybound_lane(line: u8) -> Option<(PlayerSide, Key)> {
+    let [tens, ones] = format!("{line:02}").into_bytes()[..] else {
+        return None;
+    };
+    let layout = NoteChannelId::try_from([tens, ones])
+        .ok()?
+        .try_into_map::<KeyLayoutBeat>()?;
+    (layout.kind() == NoteKind::Visible).then(|| (layout.side(), layout.key()))
+}
+
+/// Returns the latest value at or before `time` of changes sorted by time.
+fn latest<T: Copy>(changes: &[(TimeSpan, T)], time: TimeSpan) -> Option<T> {
+    let index = changes.partition_point(|&(at, _)| at <= time);
+    changes.get(index.checked_sub(1)?).map(|&(_, value)| value)
+}
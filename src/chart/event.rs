@@ -1,5 +1,6 @@
 //! Chart event types
 
+use crate::bms::command::LnMode;
 use crate::chart::process::{BmpId, ChartEventId, WavId};
 use crate::chart::types::{Argb, BgaLayer, Key, NoteKind, PlayerSide};
 use gametime::TimeSpan;
@@ -161,6 +162,18 @@ pub enum BmsEvent {
         /// Option name.
         option: String,
     },
+    /// Character sprite change event, from `#EXTCHR`. It has no position in the source, so it is
+    /// placed at the start of the chart.
+    CharacterSpriteChange {
+        /// The sprite replacement.
+        event: crate::bms::command::minor_command::ExtChrEvent,
+    },
+    /// Stop event in milliseconds, from `#STP`. The stop itself is applied by the
+    /// [`FlowEvent::Stop`] at the same position.
+    MillisecondStop {
+        /// Duration of the stop.
+        duration: TimeSpan,
+    },
 }
 
 /// Events generated during playback (Elm style).
@@ -187,6 +200,26 @@ pub enum ChartEvent {
         /// Note continue play span. None for BMS; in BMSON, Some(span) when Note.c is true.
         continue_play: Option<TimeSpan>,
     },
+    /// Long note tail reaches judgment line
+    NoteEnd {
+        /// Player side
+        side: PlayerSide,
+        /// Key position
+        key: Key,
+        /// Id of the [`ChartEvent::Note`] event which starts the long note
+        start_id: ChartEventId,
+        /// Long note mode, from BMS `#LNMODE`, or BMSON `ln_type` overridden by the note's `t`
+        ln_mode: LnMode,
+    },
+    /// Gauge tick while a hell charge note lasts, added by [`Chart::add_hcn_ticks`](crate::chart::Chart::add_hcn_ticks)
+    HcnTick {
+        /// Player side
+        side: PlayerSide,
+        /// Key position
+        key: Key,
+        /// Id of the [`ChartEvent::Note`] event which starts the long note
+        start_id: ChartEventId,
+    },
     /// BGM and other non-key triggers (no valid side/key)
     Bgm {
         /// Corresponding sound resource ID (if any)
@@ -230,6 +263,17 @@ pub enum ChartEvent {
     BarLine,
 }
 
+impl ChartEvent {
+    /// Get the id of the long note start, if this is a [`ChartEvent::NoteEnd`] or a [`ChartEvent::HcnTick`].
+    #[must_use]
+    pub const fn long_note_start(&self) -> Option<ChartEventId> {
+        match self {
+            Self::NoteEnd { start_id, .. } | Self::HcnTick { start_id, .. } => Some(*start_id),
+            _ => None,
+        }
+    }
+}
+
 /// Timeline event and position wrapper type.
 /// Represents an event in chart playback and its position on the timeline.
 #[derive(Debug, Clone)]
@@ -309,4 +353,7 @@ pub enum FlowEvent {
     Speed(PositiveF64),
     /// Scroll factor change event.
     Scroll(FinF64),
+    /// Stop of the scroll for the duration at the playback ratio 1 (`#STOP`, `#STP` or BMSON stop
+    /// event).
+    Stop(TimeSpan),
 }
@@ -0,0 +1,329 @@
+//! Gauge simulation from judgments.
+//!
+//! A [`Gauge`] changes its value in percentage points by each judgment, following a [`GaugeTable`].
+//! The recovery of [`GaugeType::Easy`] and [`GaugeType::Groove`] is scaled by `TOTAL / notes`, so a
+//! play without misses recovers about TOTAL points in all. The other gauges are survival gauges,
+//! which start full and fail at 0.
+
+use super::Chart;
+use super::judge::{JudgeEvent, JudgeEventKind, Judgment};
+use crate::bms::command::LnMode;
+use crate::bms::model::Bms;
+use crate::bms::parse::check_playing::PlayingError;
+#[cfg(feature = "bmson")]
+use crate::bmson::Bmson;
+use crate::chart::process::Process;
+use strict_num_extended::FinF64;
+
+/// The maximum value of a gauge.
+pub const MAX_GAUGE: f64 = 100.0;
+
+/// A type of gauge.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum GaugeType {
+    /// A gauge which is easier to clear than [`Self::Groove`].
+    Easy,
+    /// The normal gauge, cleared at 80% or more at the end.
+    Groove,
+    /// A survival gauge, cleared unless it runs out.
+    Hard,
+    /// A survival gauge with more damage than [`Self::Hard`].
+    ExHard,
+    /// A survival gauge which runs out by a BAD or a POOR.
+    Hazard,
+}
+
+impl GaugeType {
+    /// Returns whether the gauge starts full and fails at 0.
+    #[must_use]
+    pub const fn is_survival(self) -> bool {
+        matches!(self, Self::Hard | Self::ExHard | Self::Hazard)
+    }
+}
+
+/// Rules of the changes of gauges.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum GaugeRules {
+    /// The rules of beatoraja.
+    #[default]
+    Beatoraja,
+    /// The rules of LR2. LR2 has no EX-HARD gauge, so it is HARD with doubled damage and no relief.
+    Lr2,
+}
+
+/// Changes of a gauge type.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GaugeTable {
+    /// The value at the start.
+    pub initial: f64,
+    /// The lowest value of a non-survival gauge.
+    pub min: f64,
+    /// The lowest value to clear a non-survival gauge.
+    pub border: f64,
+    /// Change by PGREAT, GREAT, GOOD, BAD, POOR and empty POOR in order.
+    pub changes: [f64; 6],
+    /// Whether the positive changes are multiplied by `TOTAL / notes`.
+    pub scales_recovery: bool,
+    /// Whether the gauge fails at 0.
+    pub survival: bool,
+    /// Multiplier of the damage while the gauge is 30 or less.
+    pub low_damage_ratio: f64,
+}
+
+impl GaugeTable {
+    /// Returns the table of a gauge type by the rules.
+    #[must_use]
+    pub const fn new(rules: GaugeRules, gauge_type: GaugeType) -> Self {
+        let changes = match (rules, gauge_type) {
+            (GaugeRules::Beatoraja, GaugeType::Easy) => [1.0, 1.0, 0.5, -1.5, -4.5, -1.0],
+            (GaugeRules::Beatoraja, GaugeType::Groove) => [1.0, 1.0, 0.5, -3.0, -6.0, -2.0],
+            (GaugeRules::Beatoraja, GaugeType::Hard) => [0.15, 0.12, 0.03, -5.0, -10.0, -5.0],
+            (GaugeRules::Beatoraja, GaugeType::ExHard) => [0.15, 0.06, 0.0, -8.0, -16.0, -8.0],
+            (GaugeRules::Beatoraja, GaugeType::Hazard) => [0.15, 0.06, 0.0, -100.0, -100.0, -10.0],
+            (GaugeRules::Lr2, GaugeType::Easy) => [1.0, 1.0, 0.5, -3.2, -4.8, -1.6],
+            (GaugeRules::Lr2, GaugeType::Groove) => [1.0, 1.0, 0.5, -4.0, -6.0, -2.0],
+            (GaugeRules::Lr2, GaugeType::Hard) => [0.1, 0.1, 0.05, -6.0, -10.0, -2.0],
+            (GaugeRules::Lr2, GaugeType::ExHard) => [0.1, 0.1, 0.05, -12.0, -20.0, -4.0],
+            (GaugeRules::Lr2, GaugeType::Hazard) => [0.1, 0.1, 0.05, -100.0, -100.0, -2.0],
+        };
+        let survival = gauge_type.is_survival();
+        Self {
+            initial: if survival { MAX_GAUGE } else { 20.0 },
+            min: if survival { 0.0 } else { 2.0 },
+            border: if survival { 0.0 } else { 80.0 },
+            changes,
+            scales_recovery: !survival,
+            survival,
+            low_damage_ratio: match (rules, gauge_type) {
+                (GaugeRules::Lr2, GaugeType::Hard) => 0.6,
+                _ => 1.0,
+            },
+        }
+    }
+
+    /// Returns the change by a judgment before the scaling.
+    #[must_use]
+    pub const fn change(&self, judgment: Judgment) -> f64 {
+        let [pgreat, great, good, bad, poor, empty_poor] = self.changes;
+        match judgment {
+            Judgment::PGreat => pgreat,
+            Judgment::Great => great,
+            Judgment::Good => good,
+            Judgment::Bad => bad,
+            Judgment::Poor => poor,
+            Judgment::EmptyPoor => empty_poor,
+        }
+    }
+}
+
+/// TOTAL and the number of notes of a chart, which determine the recovery of gauges.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GaugeConfig {
+    /// Recovery in percentage points by all the notes.
+    pub total: f64,
+    /// Number of judgments of notes in a full play.
+    pub notes: usize,
+}
+
+impl GaugeConfig {
+    /// Creates a configuration, with [`Self::default_total`] if `total` is `None` or not positive.
+    #[must_use]
+    pub fn new(total: Option<f64>, notes: usize) -> Self {
+        Self {
+            total: total
+                .filter(|&total| total > 0.0)
+                .unwrap_or_else(|| Self::default_total(notes)),
+            notes,
+        }
+    }
+
+    /// Returns the TOTAL for a chart without it, as beatoraja does.
+    #[must_use]
+    pub fn default_total(notes: usize) -> f64 {
+        let notes = notes as f64;
+        160.0 + (notes + (notes - 400.0).clamp(0.0, 200.0)) * 0.16
+    }
+
+    /// Creates a configuration from `#TOTAL` and the notes of a BMS score by its `#LNMODE`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`PlayingError`] if the score cannot be processed into a chart.
+    pub fn from_bms(bms: &Bms) -> Result<Self, PlayingError> {
+        let notes = bms.process()?.judgment_count(bms.repr.ln_mode);
+        let total = bms
+            .judge
+            .total
+            .as_ref()
+            .and_then(|total| total.value().as_ref().ok())
+            .map(|total| total.as_f64());
+        Ok(Self::new(total, notes))
+    }
+
+    /// Creates a configuration from the notes of a BMSON score by its `ln_type`.
+    ///
+    /// BMSON `total` is a percentage of [`Self::default_total`].
+    #[cfg(feature = "bmson")]
+    #[must_use]
+    pub fn from_bmson(bmson: &Bmson<'_>) -> Self {
+        let notes = bmson
+            .process()
+            .map_or(0, |chart| chart.judgment_count(bmson.info.ln_type));
+        let total = Self::default_total(notes) * bmson.info.total.as_f64() / 100.0;
+        Self::new(Some(total), notes)
+    }
+
+    /// Creates a configuration from a chart played in `ln_mode`, with [`Self::default_total`] if
+    /// `total` is `None`.
+    #[must_use]
+    pub fn from_chart(chart: &Chart, ln_mode: LnMode, total: Option<f64>) -> Self {
+        Self::new(total, chart.judgment_count(ln_mode))
+    }
+}
+
+/// A gauge changed by judgments.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Gauge {
+    kind: GaugeType,
+    table: GaugeTable,
+    /// Multiplier of the recovery.
+    recovery_scale: f64,
+    value: f64,
+    failed: bool,
+}
+
+impl Gauge {
+    /// Creates a gauge of the type by the rules.
+    #[must_use]
+    pub const fn new(gauge_type: GaugeType, rules: GaugeRules, config: GaugeConfig) -> Self {
+        Self::with_table(gauge_type, GaugeTable::new(rules, gauge_type), config)
+    }
+
+    /// Creates a gauge with a custom table.
+    #[must_use]
+    pub const fn with_table(gauge_type: GaugeType, table: GaugeTable, config: GaugeConfig) -> Self {
+        let recovery_scale = if !table.scales_recovery {
+            1.0
+        } else if config.notes == 0 {
+            0.0
+        } else {
+            config.total / config.notes as f64
+        };
+        Self {
+            kind: gauge_type,
+            table,
+            recovery_scale,
+            value: table.initial,
+            failed: false,
+        }
+    }
+
+    /// Returns the type of the gauge.
+    #[must_use]
+    pub const fn gauge_type(&self) -> GaugeType {
+        self.kind
+    }
+
+    /// Returns the table of the gauge.
+    #[must_use]
+    pub const fn table(&self) -> &GaugeTable {
+        &self.table
+    }
+
+    /// Returns the value of the gauge in percentage.
+    #[must_use]
+    pub const fn value(&self) -> f64 {
+        self.value
+    }
+
+    /// Returns whether the survival gauge has run out.
+    #[must_use]
+    pub const fn is_failed(&self) -> bool {
+        self.failed
+    }
+
+    /// Returns whether the gauge clears the chart if the play ends now.
+    #[must_use]
+    pub fn is_cleared(&self) -> bool {
+        !self.failed && self.value >= self.table.border
+    }
+
+    /// Changes the gauge by a judgment.
+    pub fn apply(&mut self, judgment: Judgment) {
+        let change = self.table.change(judgment);
+        if change > 0.0 {
+            self.add(change * self.recovery_scale);
+        } else {
+            self.damage(-change);
+        }
+    }
+
+    /// Changes the gauge by a landmine, whose damage is the one of a POOR if it is `None`.
+    pub fn apply_landmine(&mut self, damage: Option<f64>) {
+        let damage = damage.unwrap_or_else(|| -self.table.change(Judgment::Poor));
+        self.damage(damage);
+    }
+
+    /// Changes the gauge by an event of a [`Judge`](super::judge::Judge) on `chart`, with the damage of
+    /// landmines defined in the chart.
+    pub fn apply_event(&mut self, event: &JudgeEvent, chart: &Chart) {
+        match event.kind {
+            JudgeEventKind::Note { judgment, .. } => self.apply(judgment),
+            JudgeEventKind::EmptyPoor => self.apply(Judgment::EmptyPoor),
+            JudgeEventKind::Landmine { id } => {
+                self.apply_landmine(chart.landmine_damage(id).map(FinF64::as_f64));
+            }
+            JudgeEventKind::HcnHold { .. } => {}
+        }
+    }
+
+    /// Decreases the gauge by `damage`, reduced while the survival gauge is low.
+    fn damage(&mut self, damage: f64) {
+        let damage = if self.table.survival && self.value <= 30.0 {
+            damage * self.table.low_damage_ratio
+        } else {
+            damage
+        };
+        self.add(-damage);
+    }
+
+    fn add(&mut self, change: f64) {
+        if self.failed {
+            return;
+        }
+        self.value = (self.value + change).clamp(self.table.min, MAX_GAUGE);
+        if self.table.survival && self.value <= 0.0 {
+            self.value = 0.0;
+            self.failed = true;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Gauge, GaugeConfig, GaugeRules, GaugeType};
+    use crate::chart::judge::Judgment;
+
+    #[test]
+    fn default_total_follows_note_count() {
+        assert!((GaugeConfig::default_total(0) - 160.0).abs() < 1e-9);
+        assert!((GaugeConfig::default_total(500) - 256.0).abs() < 1e-9);
+        assert!((GaugeConfig::default_total(1000) - 352.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn lr2_hard_damage_is_reduced_when_low() {
+        let config = GaugeConfig::new(Some(300.0), 1000);
+        let mut gauge = Gauge::new(GaugeType::Hard, GaugeRules::Lr2, config);
+        for _ in 0..7 {
+            gauge.apply(Judgment::Poor);
+        }
+        // 100 - 70 = 30, then 30 - 6 = 24.
+        assert!((gauge.value() - 30.0).abs() < 1e-9);
+        gauge.apply(Judgment::Poor);
+        assert!((gauge.value() - 24.0).abs() < 1e-9);
+        assert!(!gauge.is_failed());
+    }
+}
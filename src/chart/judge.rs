@@ -0,0 +1,805 @@
+//! Judgment of the key inputs against the notes of a chart.
+//!
+//! [`Judge`] matches the presses and releases on each lane with the upcoming [`ChartEvent::Note`]s on
+//! it, and reports [`JudgeEvent`]s. The times of the inputs are on the same clock as
+//! [`PlayheadEvent::activate_time`](super::event::PlayheadEvent::activate_time), that is the time
+//! elapsed from [`ChartPlayer::started_at`](super::player::ChartPlayer::started_at) at the playback
+//! ratio 1.
+//!
+//! - A press in the windows of the next note on the lane judges it by the offset. Any other press is
+//!   an empty POOR.
+//! - A note which is not pressed until its BAD window passes is a POOR.
+//! - A long note is judged according to the [`LnMode`] of its [`ChartEvent::NoteEnd`], or
+//!   [`JudgeConfig::ln_mode`] without it:
+//!   - [`LnMode::Ln`]: the judgment of the head is reported once the note is held until its end, or
+//!     released in the BAD window before it. Releasing it earlier is a POOR.
+//!   - [`LnMode::Cn`]: the head and the tail are judged, the tail by the offset of the release from
+//!     the end.
+//!   - [`LnMode::Hcn`]: as [`LnMode::Cn`], but the body can be held again after missing the head or
+//!     releasing it early, and each start and stop of holding is reported for the gauge.
+//! - A landmine explodes when it passes while its lane is held.
+
+use std::collections::{HashMap, VecDeque};
+
+use gametime::TimeSpan;
+
+use super::Chart;
+use super::event::{BmsEvent, ChartEvent};
+use super::process::ChartEventId;
+use super::timing::EventTimes;
+use super::types::{Key, NoteKind, PlayerSide};
+use crate::bms::command::{JudgeLevel, LnMode, ObjId};
+use crate::bms::model::Bms;
+#[cfg(feature = "bmson")]
+use crate::bmson::Bmson;
+
+/// A judgment of a note or an input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Judgment {
+    /// The most precise judgment.
+    PGreat,
+    /// The second precise judgment.
+    Great,
+    /// The least precise judgment which keeps the combo.
+    Good,
+    /// A judgment which breaks the combo.
+    Bad,
+    /// A missed note.
+    Poor,
+    /// A press which hits no note.
+    EmptyPoor,
+}
+
+impl Judgment {
+    /// Returns whether the judgment keeps the combo.
+    #[must_use]
+    pub const fn is_combo(self) -> bool {
+        matches!(self, Self::PGreat | Self::Great | Self::Good)
+    }
+}
+
+/// Half widths of the judge windows around the time of a note.
+///
+/// An offset on the boundary of a window is in it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JudgeWindows {
+    /// Window for [`Judgment::PGreat`].
+    pub pgreat: TimeSpan,
+    /// Window for [`Judgment::Great`].
+    pub great: TimeSpan,
+    /// Window for [`Judgment::Good`].
+    pub good: TimeSpan,
+    /// Window for [`Judgment::Bad`].
+    pub bad: TimeSpan,
+}
+
+impl JudgeWindows {
+    /// Windows of `#RANK 0`.
+    pub const VERY_HARD: Self = Self::from_millis(8, 24, 40, 200);
+    /// Windows of `#RANK 1`.
+    pub const HARD: Self = Self::from_millis(15, 30, 60, 200);
+    /// Windows of `#RANK 2`.
+    pub const NORMAL: Self = Self::from_millis(18, 40, 100, 200);
+    /// Windows of `#RANK 3`.
+    pub const EASY: Self = Self::from_millis(21, 60, 120, 200);
+
+    const fn from_millis(pgreat: i64, great: i64, good: i64, bad: i64) -> Self {
+        const NANOS_PER_MILLI: i64 = 1_000_000;
+        Self {
+            pgreat: TimeSpan::new(pgreat * NANOS_PER_MILLI),
+            great: TimeSpan::new(great * NANOS_PER_MILLI),
+            good: TimeSpan::new(good * NANOS_PER_MILLI),
+            bad: TimeSpan::new(bad * NANOS_PER_MILLI),
+        }
+    }
+
+    /// Returns the windows of a judge level.
+    ///
+    /// [`JudgeLevel::OtherInt`] is a percentage as [`Self::from_percentage`], which is how `#EXRANK`
+    /// and `#DEFEXRANK` are stored.
+    #[must_use]
+    pub fn from_level(level: JudgeLevel) -> Self {
+        match level {
+            JudgeLevel::VeryHard => Self::VERY_HARD,
+            JudgeLevel::Hard => Self::HARD,
+            JudgeLevel::Normal => Self::NORMAL,
+            JudgeLevel::Easy => Self::EASY,
+            JudgeLevel::OtherInt(percent) => Self::from_percentage(percent as f64),
+        }
+    }
+
+    /// Returns the windows scaled from [`Self::NORMAL`] by `percent`, as `#DEFEXRANK` and BMSON
+    /// `judge_rank`.
+    ///
+    /// The BAD window is not narrowed, following LR2.
+    #[must_use]
+    pub fn from_percentage(percent: f64) -> Self {
+        let scale = |window: TimeSpan| {
+            TimeSpan::new((window.as_nanos() as f64 * percent.max(0.0) / 100.0) as i64)
+        };
+        let good = scale(Self::NORMAL.good);
+        Self {
+            pgreat: scale(Self::NORMAL.pgreat),
+            great: scale(Self::NORMAL.great),
+            good,
+            bad: Self::NORMAL.bad.max(good),
+        }
+    }
+
+    /// Judges an offset of an input from a note, or returns `None` if it is out of the windows.
+    #[must_use]
+    pub fn judge(&self, offset: TimeSpan) -> Option<Judgment> {
+        let offset = offset.abs();
+        [
+            (self.pgreat, Judgment::PGreat),
+            (self.great, Judgment::Great),
+            (self.good, Judgment::Good),
+            (self.bad, Judgment::Bad),
+        ]
+        .into_iter()
+        .find(|&(window, _)| offset <= window)
+        .map(|(_, judgment)| judgment)
+    }
+}
+
+impl Default for JudgeWindows {
+    fn default() -> Self {
+        Self::NORMAL
+    }
+}
+
+/// Configuration of a [`Judge`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct JudgeConfig {
+    /// Windows from the start of the chart, until a judge level change event.
+    pub windows: JudgeWindows,
+    /// How long notes are judged, unless their [`ChartEvent::NoteEnd`] gives the mode.
+    pub ln_mode: LnMode,
+}
+
+impl JudgeConfig {
+    /// Creates a configuration from `#DEFEXRANK`, `#RANK` and `#LNMODE` of a BMS score.
+    ///
+    /// `#DEFEXRANK` takes precedence over `#RANK`, and the NORMAL windows are used without both. A
+    /// `#RANK` out of `0..=3` is clamped into it.
+    #[must_use]
+    pub fn from_bms(bms: &Bms) -> Self {
+        let rank_windows = || match bms.judge.rank {
+            Some(JudgeLevel::OtherInt(rank)) if rank < 0 => JudgeWindows::VERY_HARD,
+            Some(JudgeLevel::OtherInt(_)) => JudgeWindows::EASY,
+            Some(level) => JudgeWindows::from_level(level),
+            None => JudgeWindows::NORMAL,
+        };
+        let windows = bms
+            .judge
+            .exrank_defs
+            .get(&ObjId::null())
+            .map_or_else(rank_windows, |def| {
+                JudgeWindows::from_level(def.judge_level)
+            });
+        Self {
+            windows,
+            ln_mode: bms.repr.ln_mode,
+        }
+    }
+
+    /// Creates a configuration from `judge_rank` and `ln_type` of a BMSON score.
+    #[cfg(feature = "bmson")]
+    #[must_use]
+    pub fn from_bmson(bmson: &Bmson<'_>) -> Self {
+        Self {
+            windows: JudgeWindows::from_percentage(bmson.info.judge_rank.as_f64()),
+            ln_mode: bmson.info.ln_type,
+        }
+    }
+}
+
+/// An action on a key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum KeyAction {
+    /// The key is pressed.
+    Press,
+    /// The key is released.
+    Release,
+}
+
+/// A timestamped input on a lane.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct KeyInput {
+    /// Time of the input from the start of the chart.
+    pub time: TimeSpan,
+    /// The side of the lane.
+    pub side: PlayerSide,
+    /// The key of the lane.
+    pub key: Key,
+    /// The action on the key.
+    pub action: KeyAction,
+}
+
+/// Which part of a note is judged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum NotePart {
+    /// A normal note, or a long note judged once in [`LnMode::Ln`].
+    Whole,
+    /// The head of a long note in [`LnMode::Cn`] or [`LnMode::Hcn`].
+    Head,
+    /// The tail of a long note in [`LnMode::Cn`] or [`LnMode::Hcn`].
+    Tail,
+}
+
+/// What a [`JudgeEvent`] reports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JudgeEventKind {
+    /// A note or a part of it is judged.
+    Note {
+        /// The id of the note event.
+        id: ChartEventId,
+        /// The judged part of the note.
+        part: NotePart,
+        /// The judgment.
+        judgment: Judgment,
+        /// The offset of the input from the note, negative if early, or `None` if it is missed.
+        offset: Option<TimeSpan>,
+    },
+    /// A press hits no note.
+    EmptyPoor,
+    /// A landmine explodes.
+    Landmine {
+        /// The id of the landmine event.
+        id: ChartEventId,
+    },
+    /// Holding the body of a HCN starts or stops.
+    HcnHold {
+        /// The id of the note event.
+        id: ChartEventId,
+        /// Whether the body is held from the time.
+        holding: bool,
+    },
+}
+
+/// A result of judging.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JudgeEvent {
+    /// Time when it is decided.
+    pub time: TimeSpan,
+    /// The side of the lane.
+    pub side: PlayerSide,
+    /// The key of the lane.
+    pub key: Key,
+    /// What is decided.
+    pub kind: JudgeEventKind,
+}
+
+impl JudgeEvent {
+    /// Returns the judgment of a note or an empty POOR, if it is one.
+    #[must_use]
+    pub const fn judgment(&self) -> Option<Judgment> {
+        match self.kind {
+            JudgeEventKind::Note { judgment, .. } => Some(judgment),
+            JudgeEventKind::EmptyPoor => Some(Judgment::EmptyPoor),
+            JudgeEventKind::Landmine { .. } | JudgeEventKind::HcnHold { .. } => None,
+        }
+    }
+}
+
+/// Judge of the inputs on a chart.
+///
+/// Inputs and updates must be given in the order of time. An earlier time than the last one is
+/// treated as the last one.
+#[derive(Debug, Clone)]
+pub struct Judge {
+    ln_mode: LnMode,
+    /// Windows from each time, sorted by the time.
+    windows: Vec<(TimeSpan, JudgeWindows)>,
+    lanes: Vec<Lane>,
+    now: TimeSpan,
+}
+
+impl Judge {
+    /// Creates a judge of the notes in `chart`.
+    ///
+    /// The windows change at each [`BmsEvent::JudgeLevelChange`] in the chart, and each long note
+    /// is judged in the [`LnMode`] of its [`ChartEvent::NoteEnd`].
+    #[must_use]
+    pub fn new(chart: &Chart, config: JudgeConfig) -> Self {
+        let times = EventTimes::new(chart);
+        let ln_modes = chart.long_note_modes();
+        let mut windows = vec![(TimeSpan::ZERO, config.windows)];
+        let mut lanes: Vec<Lane> = vec![];
+        for event in chart.events.as_events() {
+            match &event.event {
+                ChartEvent::Bms(BmsEvent::JudgeLevelChange { level }) => {
+                    windows.push((event.activate_time, JudgeWindows::from_level(*level)));
+                }
+                ChartEvent::Note {
+                    side, key, kind, ..
+                } => {
+                    let lane = lane_mut(&mut lanes, *side, *key);
+                    match kind {
+                        NoteKind::Visible | NoteKind::Long => lane.notes.push_back(PendingNote {
+                            id: event.id,
+                            time: event.activate_time,
+                            end: times.long_note_end(event),
+                            ln_mode: ln_modes.get(&event.id).copied().unwrap_or(config.ln_mode),
+                        }),
+                        NoteKind::Landmine => {
+                            lane.mines.push_back((event.id, event.activate_time));
+                        }
+                        NoteKind::Invisible => {}
+                    }
+                }
+                _ => {}
+            }
+        }
+        windows.sort_by_key(|&(time, _)| time);
+        for lane in &mut lanes {
+            lane.notes.make_contiguous().sort_by_key(|note| note.time);
+            lane.mines.make_contiguous().sort_by_key(|&(_, time)| time);
+        }
+        Self {
+            ln_mode: config.ln_mode,
+            windows,
+            lanes,
+            now: TimeSpan::ZERO,
+        }
+    }
+
+    /// Returns how long notes without the mode in their [`ChartEvent::NoteEnd`] are judged.
+    #[must_use]
+    pub const fn ln_mode(&self) -> LnMode {
+        self.ln_mode
+    }
+
+    /// Returns the time of the last input or update.
+    #[must_use]
+    pub const fn now(&self) -> TimeSpan {
+        self.now
+    }
+
+    /// Returns whether the lane is held.
+    #[must_use]
+    pub fn is_pressed(&self, side: PlayerSide, key: Key) -> bool {
+        self.lanes
+            .iter()
+            .any(|lane| lane.side == side && lane.key == key && lane.pressed)
+    }
+
+    /// Returns whether all the notes and landmines are judged.
+    #[must_use]
+    pub fn is_finished(&self) -> bool {
+        self.lanes
+            .iter()
+            .all(|lane| lane.notes.is_empty() && lane.mines.is_empty() && lane.hold.is_none())
+    }
+
+    /// Judges an input, after the notes passed until its time.
+    pub fn input(&mut self, input: KeyInput) -> Vec<JudgeEvent> {
+        match input.action {
+            KeyAction::Press => self.press(input.side, input.key, input.time),
+            KeyAction::Release => self.release(input.side, input.key, input.time),
+        }
+    }
+
+    /// Judges a press on the lane at `time`, after the notes passed until it.
+    pub fn press(&mut self, side: PlayerSide, key: Key, time: TimeSpan) -> Vec<JudgeEvent> {
+        let mut out = self.advance(time);
+        let now = self.now;
+        let windows = &self.windows;
+        lane_mut(&mut self.lanes, side, key).press(now, windows, &mut out);
+        out
+    }
+
+    /// Judges a release on the lane at `time`, after the notes passed until it.
+    pub fn release(&mut self, side: PlayerSide, key: Key, time: TimeSpan) -> Vec<JudgeEvent> {
+        let mut out = self.advance(time);
+        let now = self.now;
+        lane_mut(&mut self.lanes, side, key).release(now, &mut out);
+        out
+    }
+
+    /// Judges the notes, long notes and landmines passed until `time`.
+    pub fn update(&mut self, time: TimeSpan) -> Vec<JudgeEvent> {
+        self.advance(time)
+    }
+
+    /// Judges all the rest notes as if the chart is played to the end without inputs.
+    pub fn finish(&mut self) -> Vec<JudgeEvent> {
+        self.advance(TimeSpan::MAX)
+    }
+
+    fn advance(&mut self, time: TimeSpan) -> Vec<JudgeEvent> {
+        self.now = self.now.max(time);
+        let mut out = vec![];
+        for lane in &mut self.lanes {
+            lane.advance(self.now, &self.windows, &mut out);
+        }
+        out.sort_by_key(|event| event.time);
+        out
+    }
+}
+
+impl Chart {
+    /// Returns the number of note judgments in a full play, which counts the head and the tail of a
+    /// long note separately unless it is judged in [`LnMode::Ln`].
+    ///
+    /// A long note is judged in the mode of its [`ChartEvent::NoteEnd`], or `ln_mode` without it.
+    #[must_use]
+    pub fn judgment_count(&self, ln_mode: LnMode) -> usize {
+        let ln_modes = self.long_note_modes();
+        self.events
+            .as_events()
+            .iter()
+            .map(|event| match event.event {
+                ChartEvent::Note {
+                    kind: NoteKind::Visible,
+                    ..
+                } => 1,
+                ChartEvent::Note {
+                    kind: NoteKind::Long,
+                    ..
+                } if ln_modes.get(&event.id).copied().unwrap_or(ln_mode) == LnMode::Ln => 1,
+                ChartEvent::Note {
+                    kind: NoteKind::Long,
+                    ..
+                } => 2,
+                _ => 0,
+            })
+            .sum()
+    }
+
+    /// Returns the modes of the long notes from their [`ChartEvent::NoteEnd`]s, by the ids of the
+    /// starts.
+    fn long_note_modes(&self) -> HashMap<ChartEventId, LnMode> {
+        self.events
+            .as_events()
+            .iter()
+            .filter_map(|event| match event.event {
+                ChartEvent::NoteEnd {
+                    start_id, ln_mode, ..
+                } => Some((start_id, ln_mode)),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// A note which is not judged yet.
+#[derive(Debug, Clone, Copy)]
+struct PendingNote {
+    id: ChartEventId,
+    time: TimeSpan,
+    /// Time of the end if it is a long note.
+    end: Option<TimeSpan>,
+    /// How the note is judged if it is a long note.
+    ln_mode: LnMode,
+}
+
+/// A long note whose head is judged and the rest is not.
+#[derive(Debug, Clone, Copy)]
+struct Hold {
+    id: ChartEventId,
+    end: TimeSpan,
+    windows: JudgeWindows,
+    head: Judgment,
+    head_offset: TimeSpan,
+    held: bool,
+    ln_mode: LnMode,
+}
+
+#[derive(Debug, Clone)]
+struct Lane {
+    side: PlayerSide,
+    key: Key,
+    notes: VecDeque<PendingNote>,
+    mines: VecDeque<(ChartEventId, TimeSpan)>,
+    pressed: bool,
+    hold: Option<Hold>,
+}
+
+impl Lane {
+    const fn event(&self, time: TimeSpan, kind: JudgeEventKind) -> JudgeEvent {
+        JudgeEvent {
+            time,
+            side: self.side,
+            key: self.key,
+            kind,
+        }
+    }
+
+    const fn note_event(
+        &self,
+        time: TimeSpan,
+        id: ChartEventId,
+        part: NotePart,
+        judgment: Judgment,
+        offset: Option<TimeSpan>,
+    ) -> JudgeEvent {
+        self.event(
+            time,
+            JudgeEventKind::Note {
+                id,
+                part,
+                judgment,
+                offset,
+            },
+        )
+    }
+
+    fn advance(
+        &mut self,
+        time: TimeSpan,
+        windows: &[(TimeSpan, JudgeWindows)],
+        out: &mut Vec<JudgeEvent>,
+    ) {
+        while let Some(&(id, at)) = self.mines.front() {
+            if at > time {
+                break;
+            }
+            self.mines.pop_front();
+            if self.pressed {
+                out.push(self.event(at, JudgeEventKind::Landmine { id }));
+            }
+        }
+        self.advance_hold(time, out);
+        while let Some(&note) = self.notes.front() {
+            let note_windows = windows_at(windows, note.time);
+            let missed_at = note.time + note_windows.bad;
+            if time <= missed_at {
+                break;
+            }
+            self.notes.pop_front();
+            self.miss(note, note_windows, missed_at, out);
+            self.advance_hold(time, out);
+        }
+    }
+
+    /// Ends the hold which is completed or missed until `time`.
+    fn advance_hold(&mut self, time: TimeSpan, out: &mut Vec<JudgeEvent>) {
+        let Some(hold) = self.hold else {
+            return;
+        };
+        if !hold.held {
+            if time >= hold.end {
+                self.hold = None;
+                out.push(self.note_event(hold.end, hold.id, NotePart::Tail, Judgment::Poor, None));
+            }
+            return;
+        }
+        if hold.ln_mode == LnMode::Ln {
+            if time >= hold.end {
+                self.hold = None;
+                out.push(self.note_event(
+                    hold.end,
+                    hold.id,
+                    NotePart::Whole,
+                    hold.head,
+                    Some(hold.head_offset),
+                ));
+            }
+            return;
+        }
+        let missed_at = hold.end + hold.windows.bad;
+        if time > missed_at {
+            self.hold = None;
+            out.push(self.note_event(missed_at, hold.id, NotePart::Tail, Judgment::Poor, None));
+            if hold.ln_mode == LnMode::Hcn {
+                out.push(self.event(
+                    missed_at,
+                    JudgeEventKind::HcnHold {
+                        id: hold.id,
+                        holding: false,
+                    },
+                ));
+            }
+        }
+    }
+
+    fn miss(
+        &mut self,
+        note: PendingNote,
+        windows: JudgeWindows,
+        time: TimeSpan,
+        out: &mut Vec<JudgeEvent>,
+    ) {
+        let Some(end) = note.end.filter(|_| note.ln_mode != LnMode::Ln) else {
+            out.push(self.note_event(time, note.id, NotePart::Whole, Judgment::Poor, None));
+            return;
+        };
+        out.push(self.note_event(time, note.id, NotePart::Head, Judgment::Poor, None));
+        if note.ln_mode == LnMode::Hcn {
+            self.hold = Some(Hold {
+                id: note.id,
+                end,
+                windows,
+                head: Judgment::Poor,
+                head_offset: TimeSpan::ZERO,
+                held: false,
+                ln_mode: note.ln_mode,
+            });
+        } else {
+            out.push(self.note_event(time, note.id, NotePart::Tail, Judgment::Poor, None));
+        }
+    }
+
+    fn press(
+        &mut self,
+        time: TimeSpan,
+        windows: &[(TimeSpan, JudgeWindows)],
+        out: &mut Vec<JudgeEvent>,
+    ) {
+        self.pressed = true;
+        if let Some(hold) = self.hold.as_mut().filter(|hold| !hold.held) {
+            hold.held = true;
+            let id = hold.id;
+            out.push(self.event(time, JudgeEventKind::HcnHold { id, holding: true }));
+            return;
+        }
+        let judged = self.notes.front().and_then(|note| {
+            let note_windows = windows_at(windows, note.time);
+            let offset = time - note.time;
+            Some((*note, note_windows, offset, note_windows.judge(offset)?))
+        });
+        let Some((note, note_windows, offset, judgment)) = judged else {
+            out.push(self.event(time, JudgeEventKind::EmptyPoor));
+            return;
+        };
+        self.notes.pop_front();
+        let Some(end) = note.end else {
+            out.push(self.note_event(time, note.id, NotePart::Whole, judgment, Some(offset)));
+            return;
+        };
+        let hold = Hold {
+            id: note.id,
+            end,
+            windows: note_windows,
+            head: judgment,
+            head_offset: offset,
+            held: true,
+            ln_mode: note.ln_mode,
+        };
+        match note.ln_mode {
+            LnMode::Ln if judgment == Judgment::Bad => {
+                out.push(self.note_event(time, note.id, NotePart::Whole, judgment, Some(offset)));
+            }
+            LnMode::Ln => self.hold = Some(hold),
+            LnMode::Cn => {
+                out.push(self.note_event(time, note.id, NotePart::Head, judgment, Some(offset)));
+                if judgment == Judgment::Bad {
+                    out.push(self.note_event(time, note.id, NotePart::Tail, Judgment::Poor, None));
+                } else {
+                    self.hold = Some(hold);
+                }
+            }
+            LnMode::Hcn => {
+                out.push(self.note_event(time, note.id, NotePart::Head, judgment, Some(offset)));
+                out.push(self.event(
+                    time,
+                    JudgeEventKind::HcnHold {
+                        id: note.id,
+                        holding: true,
+                    },
+                ));
+                self.hold = Some(hold);
+            }
+        }
+    }
+
+    fn release(&mut self, time: TimeSpan, out: &mut Vec<JudgeEvent>) {
+        self.pressed = false;
+        let Some(hold) = self.hold.filter(|hold| hold.held) else {
+            return;
+        };
+        let offset = time - hold.end;
+        match hold.ln_mode {
+            LnMode::Ln => {
+                self.hold = None;
+                let event = if time >= hold.end - hold.windows.bad {
+                    self.note_event(
+                        time,
+                        hold.id,
+                        NotePart::Whole,
+                        hold.head,
+                        Some(hold.head_offset),
+                    )
+                } else {
+                    self.note_event(time, hold.id, NotePart::Whole, Judgment::Poor, None)
+                };
+                out.push(event);
+            }
+            LnMode::Cn => {
+                self.hold = None;
+                let judged = hold.windows.judge(offset);
+                out.push(self.note_event(
+                    time,
+                    hold.id,
+                    NotePart::Tail,
+                    judged.unwrap_or(Judgment::Poor),
+                    judged.map(|_| offset),
+                ));
+            }
+            LnMode::Hcn => {
+                out.push(self.event(
+                    time,
+                    JudgeEventKind::HcnHold {
+                        id: hold.id,
+                        holding: false,
+                    },
+                ));
+                if let Some(judgment) = hold.windows.judge(offset) {
+                    self.hold = None;
+                    out.push(self.note_event(
+                        time,
+                        hold.id,
+                        NotePart::Tail,
+                        judgment,
+                        Some(offset),
+                    ));
+                } else {
+                    self.hold = Some(Hold {
+                        held: false,
+                        ..hold
+                    });
+                }
+            }
+        }
+    }
+}
+
+fn lane_mut(lanes: &mut Vec<Lane>, side: PlayerSide, key: Key) -> &mut Lane {
+    let index = lanes
+        .iter()
+        .position(|lane| lane.side == side && lane.key == key)
+        .unwrap_or_else(|| {
+            lanes.push(Lane {
+                side,
+                key,
+                notes: VecDeque::new(),
+                mines: VecDeque::new(),
+                pressed: false,
+                hold: None,
+            });
+            lanes.len() - 1
+        });
+    lanes
+        .get_mut(index)
+        .expect("the lane should be found or pushed")
+}
+
+fn windows_at(windows: &[(TimeSpan, JudgeWindows)], time: TimeSpan) -> JudgeWindows {
+    let index = windows.partition_point(|&(from, _)| from <= time);
+    windows
+        .get(index.saturating_sub(1))
+        .map_or_else(JudgeWindows::default, |&(_, windows)| windows)
+}
+
+#[cfg(test)]
+mod tests {
+    use gametime::TimeSpan;
+
+    use super::{JudgeWindows, Judgment};
+    use crate::bms::command::JudgeLevel;
+
+    #[test]
+    fn windows_judge_inclusive_boundaries() {
+        let windows = JudgeWindows::NORMAL;
+        let ms = |millis: i64| TimeSpan::MILLISECOND * millis;
+
+        assert_eq!(windows.judge(ms(-18)), Some(Judgment::PGreat));
+        assert_eq!(windows.judge(ms(19)), Some(Judgment::Great));
+        assert_eq!(windows.judge(ms(-100)), Some(Judgment::Good));
+        assert_eq!(windows.judge(ms(200)), Some(Judgment::Bad));
+        assert_eq!(windows.judge(ms(-201)), None);
+    }
+
+    #[test]
+    fn percentage_scales_from_normal() {
+        assert_eq!(JudgeWindows::from_percentage(100.0), JudgeWindows::NORMAL);
+        let wide = JudgeWindows::from_level(JudgeLevel::OtherInt(250));
+        assert_eq!(wide.pgreat, TimeSpan::MILLISECOND * 45);
+        assert_eq!(wide.good, TimeSpan::MILLISECOND * 250);
+        assert_eq!(wide.bad, TimeSpan::MILLISECOND * 250);
+    }
+}
@@ -0,0 +1,433 @@
+//! Offline audio mixdown, which renders the sounds of a chart into PCM.
+//!
+//! [`Chart::mixdown`] plays the keysounds of the visible notes and long notes and the BGM at
+//! their [`activate_time`](super::event::PlayheadEvent::activate_time), as in autoplay. The
+//! decoded sounds come from a [`SampleProvider`], so no codec or audio device is needed.
+//!
+//! - The voices are allocated by a [`VoiceAllocator`], so triggering a WAV which is still playing
+//!   cuts the previous voice of it, and a note with
+//!   [`continue_play`](super::event::ChartEvent::Note::continue_play) (BMSON `c`) resumes the
+//!   sound from the offset instead of the start.
+//! - The volume is the product of `#VOLWAV`, the latest BGM or key volume change (`#xxx97:` and
+//!   `#xxx98:`), and the volume of the WAV from [`WavParams`]. The volume changes apply to the
+//!   voices already playing.
+//! - The pan, frequency, pitch and length of the WAV from [`WavParams`] are applied.
+
+#![cfg(feature = "mixdown")]
+#![cfg_attr(docsrs, doc(cfg(feature = "mixdown")))]
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::path::Path;
+
+use gametime::TimeSpan;
+use itertools::Itertools;
+
+use super::Chart;
+use super::event::{BmsEvent, ChartEvent};
+use super::process::{WavId, WavParams};
+use super::types::NoteKind;
+use super::voice::{VoiceAllocator, VoiceId};
+
+/// Source of the decoded sounds of a chart.
+pub trait SampleProvider {
+    /// Decodes the sound of the WAV at `path`, which is as written in the chart. Returns `None`
+    /// if the sound is not available, in which case it is silent.
+    ///
+    /// This is called at most once for each WAV.
+    fn load(&mut self, id: WavId, path: &Path) -> Option<Samples>;
+}
+
+/// Decoded sound.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Samples {
+    /// Sample rate in Hz.
+    pub sample_rate: u32,
+    /// Number of channels. Mono sounds play on both output channels, and the channels after the
+    /// first two are ignored.
+    pub channels: u16,
+    /// Interleaved samples, from -1.0 to 1.0.
+    pub data: Vec<f32>,
+}
+
+impl Samples {
+    /// Returns the number of frames, the samples for all channels at one time.
+    #[must_use]
+    pub fn frames(&self) -> usize {
+        self.data
+            .len()
+            .checked_div(usize::from(self.channels))
+            .unwrap_or(0)
+    }
+
+    /// Returns the left and right samples of the frame.
+    fn frame(&self, index: usize) -> (f32, f32) {
+        let channels = usize::from(self.channels);
+        let sample = |channel: usize| {
+            self.data
+                .get(index * channels + channel)
+                .copied()
+                .unwrap_or(0.0)
+        };
+        match channels {
+            0 => (0.0, 0.0),
+            1 => (sample(0), sample(0)),
+            _ => (sample(0), sample(1)),
+        }
+    }
+}
+
+/// Options of [`Chart::mixdown`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MixdownConfig {
+    /// Sample rate of the output in Hz.
+    pub sample_rate: u32,
+    /// Upper bound of the duration of the output. The sounds after it are cut.
+    pub max_length: TimeSpan,
+}
+
+impl Default for MixdownConfig {
+    fn default() -> Self {
+        Self {
+            sample_rate: 44100,
+            max_length: TimeSpan::SECOND * 60 * 30,
+        }
+    }
+}
+
+/// Rendered stereo audio.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mixdown {
+    sample_rate: u32,
+    /// Interleaved left and right samples.
+    data: Vec<f32>,
+}
+
+impl Mixdown {
+    /// Number of channels of the output.
+    pub const CHANNELS: u16 = 2;
+
+    /// Returns the sample rate in Hz.
+    #[must_use]
+    pub const fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Returns the interleaved left and right samples. They are not clipped, so loud parts may
+    /// exceed the range from -1.0 to 1.0.
+    #[must_use]
+    pub fn samples(&self) -> &[f32] {
+        &self.data
+    }
+
+    /// Returns the duration of the output.
+    #[must_use]
+    pub fn duration(&self) -> TimeSpan {
+        let frames = self.data.len() / usize::from(Self::CHANNELS);
+        TimeSpan::MICROSECOND
+            * (frames as f64 * 1_000_000.0 / f64::from(self.sample_rate.max(1))) as i64
+    }
+
+    /// Returns the samples as interleaved 16-bit PCM, clipping them.
+    #[must_use]
+    pub fn to_pcm_i16(&self) -> Vec<i16> {
+        self.data
+            .iter()
+            .map(|sample| (sample.clamp(-1.0, 1.0) * f32::from(i16::MAX)) as i16)
+            .collect()
+    }
+
+    /// Writes the output as a 16-bit PCM WAV file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing fails, or the output is too long for a WAV file.
+    pub fn write_wav<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let pcm = self.to_pcm_i16();
+        let too_long = || io::Error::new(io::ErrorKind::InvalidInput, "too long for a WAV file");
+        let data_len = u32::try_from(pcm.len() * 2).map_err(|_| too_long())?;
+        let riff_len = data_len.checked_add(36).ok_or_else(too_long)?;
+        let block_align = Self::CHANNELS * 2;
+
+        writer.write_all(b"RIFF")?;
+        writer.write_all(&riff_len.to_le_bytes())?;
+        writer.write_all(b"WAVEfmt ")?;
+        writer.write_all(&16u32.to_le_bytes())?;
+        // Linear PCM
+        writer.write_all(&1u16.to_le_bytes())?;
+        writer.write_all(&Self::CHANNELS.to_le_bytes())?;
+        writer.write_all(&self.sample_rate.to_le_bytes())?;
+        let byte_rate = self.sample_rate.saturating_mul(u32::from(block_align));
+        writer.write_all(&byte_rate.to_le_bytes())?;
+        writer.write_all(&block_align.to_le_bytes())?;
+        writer.write_all(&16u16.to_le_bytes())?;
+        writer.write_all(b"data")?;
+        writer.write_all(&data_len.to_le_bytes())?;
+        for sample in pcm {
+            writer.write_all(&sample.to_le_bytes())?;
+        }
+        writer.flush()
+    }
+}
+
+/// Volume groups of the voices.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Group {
+    Bgm,
+    Key,
+}
+
+/// Volumes of the groups, from the volume change channels.
+#[derive(Debug, Clone, Copy)]
+struct Volumes {
+    bgm: f32,
+    key: f32,
+}
+
+impl Volumes {
+    const fn of(self, group: Group) -> f32 {
+        match group {
+            Group::Bgm => self.bgm,
+            Group::Key => self.key,
+        }
+    }
+}
+
+/// A sound being played.
+#[derive(Debug)]
+struct Voice {
+    id: VoiceId,
+    wav_id: WavId,
+    group: Group,
+    /// Position in the frames of the sound.
+    position: f64,
+    /// Position at which the sound ends.
+    end: f64,
+    /// Frames of the sound advanced for each output frame.
+    step: f64,
+    left: f32,
+    right: f32,
+}
+
+impl Chart {
+    /// Renders the keysounds and the BGM of the chart into stereo audio.
+    #[must_use]
+    pub fn mixdown<P: SampleProvider + ?Sized>(
+        &self,
+        provider: &mut P,
+        config: &MixdownConfig,
+    ) -> Mixdown {
+        let out_rate = f64::from(config.sample_rate.max(1));
+        let to_frame = |time: TimeSpan| (time.as_secs_f64().max(0.0) * out_rate).round() as usize;
+        let max_frames = to_frame(config.max_length);
+        let master = f32::from(self.resources.volume().relative_percent) / 100.0;
+
+        let mut sounds: HashMap<WavId, Option<Samples>> = HashMap::new();
+        let mut voices: Vec<Voice> = Vec::new();
+        let mut data: Vec<f32> = Vec::new();
+        let mut volumes = Volumes { bgm: 1.0, key: 1.0 };
+        let mut allocator = VoiceAllocator::new();
+        let mut rendered = 0;
+
+        let events = self
+            .events
+            .as_events()
+            .iter()
+            .sorted_by_key(|event| (event.activate_time, event.id));
+        for event in events {
+            let group = match event.event() {
+                ChartEvent::Note {
+                    kind: NoteKind::Visible | NoteKind::Long,
+                    ..
+                } => Group::Key,
+                ChartEvent::Bgm { .. } => Group::Bgm,
+                ChartEvent::Bms(
+                    BmsEvent::BgmVolumeChange { volume } | BmsEvent::KeyVolumeChange { volume },
+                ) => {
+                    let frame = to_frame(event.activate_time).min(max_frames);
+                    render(&mut data, &mut voices, &sounds, volumes, rendered, frame);
+                    rendered = rendered.max(frame);
+                    let volume = f32::from(*volume) / 255.0;
+                    if matches!(
+                        event.event(),
+                        ChartEvent::Bms(BmsEvent::BgmVolumeChange { .. })
+                    ) {
+                        volumes.bgm = volume;
+                    } else {
+                        volumes.key = volume;
+                    }
+                    continue;
+                }
+                _ => continue,
+            };
+            let Some(command) = allocator.trigger(event) else {
+                continue;
+            };
+            let frame = to_frame(event.activate_time);
+            if frame >= max_frames {
+                break;
+            }
+            render(&mut data, &mut voices, &sounds, volumes, rendered, frame);
+            rendered = rendered.max(frame);
+
+            voices.retain(|voice| Some(voice.id) != command.stop);
+            let wav_id = command.wav_id;
+            let samples = sounds.entry(wav_id).or_insert_with(|| {
+                let path = self.resources.wav_files().get(&wav_id)?;
+                provider.load(wav_id, path)
+            });
+            let Some(samples) = samples else {
+                continue;
+            };
+            let params = self.resources.wav_param(wav_id);
+            let offset = command.offset;
+            if let Some(voice) = Voice::new(
+                command.voice,
+                wav_id,
+                group,
+                samples,
+                &params,
+                offset,
+                out_rate,
+            ) {
+                voices.push(Voice {
+                    left: voice.left * master,
+                    right: voice.right * master,
+                    ..voice
+                });
+            }
+        }
+
+        // Play the rest of the sounds.
+        let remaining = voices
+            .iter()
+            .map(|voice| ((voice.end - voice.position) / voice.step).ceil() as usize)
+            .max()
+            .unwrap_or(0);
+        let end = rendered.saturating_add(remaining).min(max_frames);
+        render(&mut data, &mut voices, &sounds, volumes, rendered, end);
+        data.truncate(max_frames * usize::from(Mixdown::CHANNELS));
+
+        Mixdown {
+            sample_rate: config.sample_rate,
+            data,
+        }
+    }
+}
+
+impl Voice {
+    /// Creates a voice starting at `offset` in the sound, or `None` if it is past the end.
+    fn new(
+        id: VoiceId,
+        wav_id: WavId,
+        group: Group,
+        samples: &Samples,
+        params: &WavParams,
+        offset: TimeSpan,
+        out_rate: f64,
+    ) -> Option<Self> {
+        let source_rate = params.frequency.map_or_else(
+            || f64::from(samples.sample_rate),
+            |frequency| frequency as f64,
+        ) * params.pitch_ratio();
+        let position = offset.as_secs_f64().max(0.0) * source_rate;
+        let frames = samples.frames() as f64;
+        let end = params.length.map_or(frames, |length| {
+            (position + length.as_secs_f64() * source_rate).min(frames)
+        });
+        if position >= end {
+            return None;
+        }
+        let volume = params.volume_factor() as f32;
+        let pan = params.pan.as_f64() as f32;
+        Some(Self {
+            id,
+            wav_id,
+            group,
+            position,
+            end,
+            step: source_rate / out_rate,
+            left: volume * (1.0 - pan).min(1.0),
+            right: volume * (1.0 + pan).min(1.0),
+        })
+    }
+}
+
+/// Mixes the voices into the output frames from `from` to `to`, and removes the voices which end.
+fn render(
+    data: &mut Vec<f32>,
+    voices: &mut Vec<Voice>,
+    sounds: &HashMap<WavId, Option<Samples>>,
+    volumes: Volumes,
+    from: usize,
+    to: usize,
+) {
+    if to <= from {
+        return;
+    }
+    let len = to * usize::from(Mixdown::CHANNELS);
+    if data.len() < len {
+        data.resize(len, 0.0);
+    }
+    let Some(frames) = data.get_mut(from * usize::from(Mixdown::CHANNELS)..len) else {
+        return;
+    };
+    for voice in voices.iter_mut() {
+        let Some(Some(samples)) = sounds.get(&voice.wav_id) else {
+            voice.position = voice.end;
+            continue;
+        };
+        let volume = volumes.of(voice.group);
+        for frame in frames.chunks_exact_mut(usize::from(Mixdown::CHANNELS)) {
+            if voice.position >= voice.end {
+                break;
+            }
+            let index = voice.position as usize;
+            let fraction = (voice.position - index as f64) as f32;
+            let (left0, right0) = samples.frame(index);
+            let (left1, right1) = if index + 1 < samples.frames() {
+                samples.frame(index + 1)
+            } else {
+                (left0, right0)
+            };
+            if let [left, right] = frame {
+                *left += (left0 + (left1 - left0) * fraction) * voice.left * volume;
+                *right += (right0 + (right1 - right0) * fraction) * voice.right * volume;
+            }
+            voice.position += voice.step;
+        }
+    }
+    voices.retain(|voice| voice.position < voice.end);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Mixdown, Samples};
+
+    #[test]
+    fn wav_header() {
+        let mixdown = Mixdown {
+            sample_rate: 8000,
+            data: vec![0.5, -0.5, 1.5, -1.5],
+        };
+        assert_eq!(
+            mixdown.to_pcm_i16(),
+            vec![16383, -16383, i16::MAX, -i16::MAX]
+        );
+        let mut bytes = Vec::new();
+        mixdown
+            .write_wav(&mut bytes)
+            .expect("writing to a Vec should succeed");
+        assert_eq!(bytes.len(), 44 + 8);
+        assert_eq!(bytes.get(..4), Some(&b"RIFF"[..]));
+        assert_eq!(bytes.get(36..40), Some(&b"data"[..]));
+
+        let mono = Samples {
+            sample_rate: 8000,
+            channels: 1,
+            data: vec![0.25, 0.5],
+        };
+        assert_eq!(mono.frames(), 2);
+        assert_eq!(mono.frame(1), (0.5, 0.5));
+    }
+}
@@ -2,17 +2,23 @@
 //!
 //! Unified player for parsed charts, managing playback state and event processing.
 
-use std::collections::BTreeSet;
+use std::collections::{BTreeSet, HashSet};
 use std::ops::{Bound, RangeBounds};
 use std::time::Duration;
 
 use gametime::{TimeSpan, TimeStamp};
 use strict_num_extended::{FinF64, NonNegativeF64, PositiveF64};
 
-use crate::chart::event::{ChartEvent, FlowEvent, PlayheadEvent, YCoordinate};
+use crate::chart::event::{BmsEvent, ChartEvent, FlowEvent, PlayheadEvent, YCoordinate};
+use crate::chart::types::{BgaLayer, NoteKind};
 use crate::chart::{Chart, MAX_FIN_F64, MAX_NON_NEGATIVE_F64};
 
+/// Tolerance of display ratios on checking the visibility range.
+const DISPLAY_RATIO_EPSILON: f64 = 1e-9;
+
 pub mod base_bpm;
+pub mod display;
+pub mod practice;
 
 /// Unified chart player.
 ///
@@ -22,10 +28,14 @@ pub struct ChartPlayer<'a> {
     // Playback state
     started_at: TimeStamp,
     last_poll_at: TimeStamp,
+    paused_at: Option<TimeStamp>,
+    /// Time of the playhead on the clock of [`PlayheadEvent::activate_time`].
+    playhead_time: TimeSpan,
 
     // Configuration
     pub(crate) visible_range_per_bpm: VisibleRangePerBpm,
     pub(crate) visibility_range: (Bound<FinF64>, Bound<FinF64>),
+    display_scroll: DisplayScroll,
 
     // Performance: velocity caching
     cached_velocity: Option<FinF64>,
@@ -84,8 +94,11 @@ impl<'a> ChartPlayer<'a> {
             chart,
             started_at: start_time,
             last_poll_at: start_time,
+            paused_at: None,
+            playhead_time: TimeSpan::ZERO,
             visible_range_per_bpm,
             visibility_range: (Bound::Included(FinF64::ZERO), Bound::Included(FinF64::ONE)),
+            display_scroll: DisplayScroll::Integrated,
             cached_velocity: None,
             velocity_dirty: true,
             preloaded_events: Vec::new(),
@@ -114,16 +127,20 @@ impl<'a> ChartPlayer<'a> {
     /// # Returns
     ///
     /// A vector of events triggered during this time slice. May be empty if
-    /// no events were triggered.
+    /// no events were triggered, or while paused.
     ///
     /// # Flow Events Processing
     ///
-    /// This method automatically processes BPM changes, scroll changes, and
-    /// speed changes that occur during the time slice, updating the internal
-    /// `playback_state` accordingly.
+    /// This method automatically processes BPM changes, scroll changes,
+    /// speed changes and stops that occur during the time slice, updating the
+    /// internal `playback_state` accordingly. The Y position does not advance
+    /// while stopped.
     pub fn update(&mut self, now: TimeStamp) -> Vec<PlayheadEvent> {
         use std::ops::Bound::{Excluded, Included};
 
+        if self.is_paused() {
+            return Vec::new();
+        }
         let prev_y = self.playback_state.progressed_y;
         let speed = self.playback_state.current_speed;
         self.step_to(now, speed);
@@ -197,6 +214,17 @@ impl<'a> ChartPlayer<'a> {
         self.visibility_range
     }
 
+    /// Sets how [`Self::visible_events`] applies the scroll factor.
+    pub const fn set_display_scroll(&mut self, display_scroll: DisplayScroll) {
+        self.display_scroll = display_scroll;
+    }
+
+    /// Gets how [`Self::visible_events`] applies the scroll factor.
+    #[must_use]
+    pub const fn display_scroll(&self) -> DisplayScroll {
+        self.display_scroll
+    }
+
     /// Set playback ratio.
     ///
     /// Controls how fast the playback advances relative to real time.
@@ -210,6 +238,133 @@ impl<'a> ChartPlayer<'a> {
         self.playback_state.playback_ratio = ratio;
     }
 
+    /// Pause playback at the given time.
+    ///
+    /// [`Self::update`] does nothing until [`Self::resume`], and the paused
+    /// duration is not counted as playback time. Pausing twice keeps the
+    /// first pause time.
+    pub const fn pause(&mut self, now: TimeStamp) {
+        if self.paused_at.is_none() {
+            self.paused_at = Some(now);
+        }
+    }
+
+    /// Resume playback paused by [`Self::pause`] at the given time.
+    ///
+    /// The time from `last_poll_at` to the pause is still played by the next
+    /// [`Self::update`].
+    pub fn resume(&mut self, now: TimeStamp) {
+        let Some(paused_at) = self.paused_at.take() else {
+            return;
+        };
+        let paused = now
+            .checked_elapsed_since(paused_at)
+            .unwrap_or(TimeSpan::ZERO);
+        self.started_at += paused;
+        self.last_poll_at += paused;
+    }
+
+    /// Seek to the given Y position, as if the chart were played from the start.
+    ///
+    /// Rebuilds [`PlaybackState`] from the flow events up to `y`, keeping the
+    /// playback ratio, and continues playback from `now`. A stop at `y` starts
+    /// at `y`. Events at `y` are treated as already triggered.
+    ///
+    /// # Returns
+    ///
+    /// The snapshot events to resume from `y`, see [`Self::snapshot_events`].
+    pub fn seek_to_y(&mut self, y: YCoordinate, now: TimeStamp) -> Vec<PlayheadEvent> {
+        let cursor = self.flow_cursor(SeekTarget::Y(y));
+        self.seek(cursor, now)
+    }
+
+    /// Seek to the given time on the clock of [`PlayheadEvent::activate_time`].
+    ///
+    /// See [`Self::seek_to_y`]. A time in a stop keeps the rest of the stop.
+    pub fn seek_to_time(&mut self, time: TimeSpan, now: TimeStamp) -> Vec<PlayheadEvent> {
+        let cursor = self.flow_cursor(SeekTarget::Time(time.max(TimeSpan::ZERO)));
+        self.seek(cursor, now)
+    }
+
+    /// Seek to the start of the given track, which is the position of its bar line.
+    ///
+    /// See [`Self::seek_to_y`]. Returns `None` and does nothing if the chart
+    /// has no bar line for the track.
+    pub fn seek_to_track(&mut self, track: u64, now: TimeStamp) -> Option<Vec<PlayheadEvent>> {
+        let y = bar_line_y(self.chart, track)?;
+        Some(self.seek_to_y(y, now))
+    }
+
+    /// Get the events which make up the state at the current position.
+    ///
+    /// These are the latest events of each BGA layer, BGA opacity, BGA ARGB,
+    /// BGM volume, key volume and character sprite up to the position, and the long notes being
+    /// held over it, sorted by position. A player starting from the middle of
+    /// the chart applies them as if they were triggered.
+    #[must_use]
+    pub fn snapshot_events(&self) -> Vec<PlayheadEvent> {
+        use std::collections::HashMap;
+
+        /// Kinds of state, of which only the latest event is kept.
+        #[derive(PartialEq, Eq, Hash)]
+        enum StateKind {
+            Bga(BgaLayer),
+            BgaOpacity(BgaLayer),
+            BgaArgb(BgaLayer),
+            BgmVolume,
+            KeyVolume,
+            CharacterSprite(i32),
+        }
+
+        let y = self.playback_state.progressed_y;
+        let mut latest: HashMap<StateKind, &PlayheadEvent> = HashMap::new();
+        let mut snapshot = Vec::new();
+        for event in self
+            .chart
+            .events()
+            .as_events()
+            .iter()
+            .filter(|event| event.position <= y)
+        {
+            let kind = match event.event() {
+                ChartEvent::BgaChange { layer, .. } => StateKind::Bga(*layer),
+                ChartEvent::Bms(BmsEvent::BgaOpacityChange { layer, .. }) => {
+                    StateKind::BgaOpacity(*layer)
+                }
+                ChartEvent::Bms(BmsEvent::BgaArgbChange { layer, .. }) => {
+                    StateKind::BgaArgb(*layer)
+                }
+                ChartEvent::Bms(BmsEvent::BgmVolumeChange { .. }) => StateKind::BgmVolume,
+                ChartEvent::Bms(BmsEvent::KeyVolumeChange { .. }) => StateKind::KeyVolume,
+                ChartEvent::Bms(BmsEvent::CharacterSpriteChange { event }) => {
+                    StateKind::CharacterSprite(event.sprite_num)
+                }
+                ChartEvent::Note {
+                    kind: NoteKind::Long,
+                    length: Some(length),
+                    ..
+                } => {
+                    if y.as_f64() < event.position.as_f64() + length.as_f64() {
+                        snapshot.push(event.clone());
+                    }
+                    continue;
+                }
+                _ => continue,
+            };
+            latest
+                .entry(kind)
+                .and_modify(|prev| {
+                    if (prev.position, prev.id) < (event.position, event.id) {
+                        *prev = event;
+                    }
+                })
+                .or_insert(event);
+        }
+        snapshot.extend(latest.into_values().cloned());
+        snapshot.sort_by_key(|event| (event.position, event.id));
+        snapshot
+    }
+
     // ===== State Query =====
 
     /// Get current playback state.
@@ -233,6 +388,18 @@ impl<'a> ChartPlayer<'a> {
         self.started_at
     }
 
+    /// Get the time of the playhead on the clock of [`PlayheadEvent::activate_time`].
+    #[must_use]
+    pub const fn playhead_time(&self) -> TimeSpan {
+        self.playhead_time
+    }
+
+    /// Whether playback is paused.
+    #[must_use]
+    pub const fn is_paused(&self) -> bool {
+        self.paused_at.is_some()
+    }
+
     // ===== Visible Events =====
 
     /// Get all events in current visible area (with display positions).
@@ -249,7 +416,10 @@ impl<'a> ChartPlayer<'a> {
     /// # Display Ratio
     ///
     /// The display ratio ranges from 0.0 (judgment line) to 1.0+ (visible
-    /// area top), with scroll factor applied.
+    /// area top), with scroll factor applied as [`Self::display_scroll`].
+    /// The events ahead of the playhead are looked up by their display
+    /// positions, so slow and negative scroll sections show the events
+    /// which scroll into the visibility range.
     pub fn visible_events(
         &mut self,
     ) -> Vec<(PlayheadEvent, std::ops::RangeInclusive<DisplayRatio>)> {
@@ -257,24 +427,36 @@ impl<'a> ChartPlayer<'a> {
         let visible_window_y = self.visible_window_y(self.playback_state.current_speed);
         let scroll_factor = &self.playback_state.current_scroll;
 
-        let view_start = *current_y;
-        let view_end = *current_y + visible_window_y;
+        let mut seen = HashSet::new();
+        let mut visible_events: Vec<_> = self
+            .visible_y_ranges(visible_window_y)
+            .into_iter()
+            .flat_map(|range| self.chart.events().events_in_y_range(range))
+            .filter(|event| seen.insert(event.id()))
+            .collect();
+        visible_events.sort_by(|a, b| {
+            a.position()
+                .partial_cmp(b.position())
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
 
-        let visible_events = self
-            .chart
-            .events()
-            .events_in_y_range((Bound::Excluded(view_start), Bound::Included(view_end)));
+        let display_ratio = |event_y: &YCoordinate| match self.display_scroll {
+            DisplayScroll::Integrated => Self::compute_integrated_display_ratio(
+                self.chart,
+                event_y,
+                current_y,
+                &visible_window_y,
+            ),
+            DisplayScroll::Current => {
+                Self::compute_display_ratio(event_y, current_y, &visible_window_y, scroll_factor)
+            }
+        };
 
         visible_events
             .iter()
             .filter_map(|event_with_pos| {
                 let event_y = event_with_pos.position();
-                let start_display_ratio = Self::compute_display_ratio(
-                    event_y,
-                    current_y,
-                    &visible_window_y,
-                    scroll_factor,
-                );
+                let start_display_ratio = display_ratio(event_y);
 
                 let end_display_ratio = if let ChartEvent::Note {
                     length: Some(length),
@@ -287,7 +469,7 @@ impl<'a> ChartPlayer<'a> {
                     let end_y = YCoordinate::new(
                         NonNegativeF64::new(end_y_value).unwrap_or(MAX_NON_NEGATIVE_F64),
                     );
-                    Self::compute_display_ratio(&end_y, current_y, &visible_window_y, scroll_factor)
+                    display_ratio(&end_y)
                 } else {
                     start_display_ratio.clone()
                 };
@@ -310,18 +492,7 @@ impl<'a> ChartPlayer<'a> {
         &self,
         range: impl std::ops::RangeBounds<TimeSpan>,
     ) -> Vec<PlayheadEvent> {
-        let started = self.started_at;
-        let last = self.last_poll_at;
-        // Calculate center time: elapsed time scaled by playback ratio
-        let elapsed = last
-            .checked_elapsed_since(started)
-            .unwrap_or(TimeSpan::ZERO);
-        let elapsed_secs = elapsed.as_secs_f64().max(0.0);
-        let playback_ratio = self.playback_state.playback_ratio;
-        // Calculate center time with overflow protection
-        let center_secs = FinF64::new(elapsed_secs * playback_ratio.as_f64())
-            .map_or(f64::MAX, strict_num_extended::FinF64::as_f64);
-        let center = TimeSpan::from_duration(Duration::from_secs_f64(center_secs));
+        let center = self.playhead_time;
         self.chart
             .events()
             .events_in_time_range_offset_from(center, range)
@@ -421,6 +592,14 @@ impl<'a> ChartPlayer<'a> {
                 self.playback_state.current_scroll = *s;
                 // Scroll doesn't affect velocity
             }
+            FlowEvent::Stop(duration) => {
+                self.playback_state.stop_remaining = TimeSpan::new(
+                    self.playback_state
+                        .stop_remaining
+                        .as_nanos()
+                        .saturating_add(duration.as_nanos()),
+                );
+            }
         }
     }
 
@@ -434,11 +613,38 @@ impl<'a> ChartPlayer<'a> {
         }
 
         let mut remaining_time = now - last;
+        let ratio_secs = remaining_time.as_secs_f64() * self.playback_state.playback_ratio.as_f64();
+        self.playhead_time += secs_to_time_span(ratio_secs);
         let mut cur_vel = self.calculate_velocity(speed);
         let mut cur_y = self.playback_state.progressed_y;
+        if cur_y == YCoordinate::ZERO {
+            // Flow events are applied on passing them, but nothing passes the start.
+            self.apply_flow_events_at(YCoordinate::ZERO);
+            cur_vel = self.calculate_velocity(speed);
+        }
 
         // Advance in segments until time slice is used up
         loop {
+            // The playhead does not move until the stop ends.
+            if self.playback_state.stop_remaining > TimeSpan::ZERO {
+                let ratio = self.playback_state.playback_ratio.as_f64();
+                if ratio <= 0.0 {
+                    break;
+                }
+                let stop_secs = self.playback_state.stop_remaining.as_secs_f64() / ratio;
+                let stop_time = TimeSpan::from_duration(Duration::from_secs_f64(
+                    stop_secs.clamp(0.0, u32::MAX as f64),
+                ));
+                if remaining_time < stop_time {
+                    let passed_secs = remaining_time.as_secs_f64().max(0.0) * ratio;
+                    self.playback_state.stop_remaining -=
+                        TimeSpan::from_duration(Duration::from_secs_f64(passed_secs));
+                    break;
+                }
+                remaining_time -= stop_time;
+                self.playback_state.stop_remaining = TimeSpan::ZERO;
+            }
+
             let cur_y_now = cur_y;
             let next_event_y = self.next_flow_event_y_after(cur_y_now);
 
@@ -526,6 +732,88 @@ impl<'a> ChartPlayer<'a> {
         self.last_poll_at = now;
     }
 
+    /// Play the flow events from the start to the target without the playback ratio.
+    fn flow_cursor(&self, target: SeekTarget) -> FlowCursor {
+        let mut cursor = FlowCursor {
+            y: 0.0,
+            secs: 0.0,
+            bpm: self.chart.init_bpm,
+            speed: self.chart.init_speed,
+            scroll: FinF64::ONE,
+            stop_secs: 0.0,
+        };
+        for (&event_y, events) in self.chart.flow_events() {
+            let reach_secs = cursor.secs + (event_y.as_f64() - cursor.y) / cursor.velocity();
+            match target {
+                SeekTarget::Y(y) if y < event_y => break,
+                SeekTarget::Time(time) if time.as_secs_f64() < reach_secs => break,
+                _ => {}
+            }
+            cursor.y = event_y.as_f64();
+            cursor.secs = reach_secs;
+            let mut stop_secs = 0.0;
+            for event in events {
+                match event {
+                    FlowEvent::Bpm(bpm) => cursor.bpm = *bpm,
+                    FlowEvent::Speed(speed) => cursor.speed = *speed,
+                    FlowEvent::Scroll(scroll) => cursor.scroll = *scroll,
+                    FlowEvent::Stop(duration) => stop_secs += duration.as_secs_f64(),
+                }
+            }
+            match target {
+                SeekTarget::Y(y) if y == event_y => {
+                    cursor.stop_secs = stop_secs;
+                    return cursor;
+                }
+                SeekTarget::Time(time) if time.as_secs_f64() < cursor.secs + stop_secs => {
+                    cursor.stop_secs = cursor.secs + stop_secs - time.as_secs_f64();
+                    cursor.secs = time.as_secs_f64();
+                    return cursor;
+                }
+                _ => cursor.secs += stop_secs,
+            }
+        }
+        match target {
+            SeekTarget::Y(y) => {
+                cursor.secs += (y.as_f64() - cursor.y) / cursor.velocity();
+                cursor.y = y.as_f64();
+            }
+            SeekTarget::Time(time) => {
+                cursor.y += (time.as_secs_f64() - cursor.secs) * cursor.velocity();
+                cursor.secs = time.as_secs_f64();
+            }
+        }
+        cursor
+    }
+
+    /// Move the playhead to the cursor and continue playback from `now`.
+    fn seek(&mut self, cursor: FlowCursor, now: TimeStamp) -> Vec<PlayheadEvent> {
+        let y = YCoordinate::new(NonNegativeF64::new(cursor.y).unwrap_or(MAX_NON_NEGATIVE_F64));
+        self.playback_state.current_bpm = cursor.bpm;
+        self.playback_state.current_speed = cursor.speed;
+        self.playback_state.current_scroll = cursor.scroll;
+        self.playback_state.progressed_y = y;
+        self.playback_state.stop_remaining = secs_to_time_span(cursor.stop_secs);
+        self.playhead_time = secs_to_time_span(cursor.secs);
+        self.processed_flow_y = self
+            .chart
+            .flow_events()
+            .range(..=y)
+            .map(|(&flow_y, _)| flow_y)
+            .collect();
+        self.mark_velocity_dirty();
+        self.last_poll_at = now;
+        if self.paused_at.is_some() {
+            self.paused_at = Some(now);
+        }
+
+        let visible_y_length = self.visible_window_y(self.playback_state.current_speed);
+        self.update_preloaded_events(
+            FinF64::new((y + visible_y_length).as_f64()).unwrap_or(MAX_FIN_F64),
+        );
+        self.snapshot_events()
+    }
+
     /// Get visible window length in Y units.
     #[must_use]
     pub fn visible_window_y(&self, speed: PositiveF64) -> YCoordinate {
@@ -566,13 +854,110 @@ impl<'a> ChartPlayer<'a> {
         &self.preloaded_events
     }
 
+    /// Ranges of Y ahead of the playhead whose display ratios are in the visibility range.
+    ///
+    /// The display Y is linear between scroll changes, so each section between them is solved for
+    /// the Y at the bounds of the visibility range.
+    fn visible_y_ranges(
+        &self,
+        visible_window_y: YCoordinate,
+    ) -> Vec<(Bound<YCoordinate>, Bound<YCoordinate>)> {
+        let current_y = self.playback_state.progressed_y;
+        let window = visible_window_y.as_f64();
+        if window <= 0.0 {
+            return Vec::new();
+        }
+        let display_bound = |bound: Bound<FinF64>, unbounded: f64, epsilon: f64| match bound {
+            Bound::Included(ratio) | Bound::Excluded(ratio) => (ratio.as_f64() + epsilon) * window,
+            Bound::Unbounded => unbounded,
+        };
+        let (min_ratio, max_ratio) = self.visibility_range;
+        let min_display = display_bound(min_ratio, f64::NEG_INFINITY, -DISPLAY_RATIO_EPSILON);
+        let max_display = display_bound(max_ratio, f64::INFINITY, DISPLAY_RATIO_EPSILON);
+
+        // Sections as their start Y, the display Y relative to the playhead at it, and the scroll.
+        let sections: Vec<(f64, f64, f64)> = match self.display_scroll {
+            DisplayScroll::Integrated => {
+                let current_display_y = self.chart.display_y(&current_y).as_f64();
+                let current_scroll = self
+                    .chart
+                    .scroll_display_ys
+                    .range(..=current_y)
+                    .next_back()
+                    .map_or(1.0, |(_, (_, scroll))| scroll.as_f64());
+                std::iter::once((current_y.as_f64(), 0.0, current_scroll))
+                    .chain(
+                        self.chart
+                            .scroll_display_ys
+                            .range((Bound::Excluded(current_y), Bound::Unbounded))
+                            .map(|(y, (display_y, scroll))| {
+                                (
+                                    y.as_f64(),
+                                    display_y.as_f64() - current_display_y,
+                                    scroll.as_f64(),
+                                )
+                            }),
+                    )
+                    .collect()
+            }
+            DisplayScroll::Current => vec![(
+                current_y.as_f64(),
+                0.0,
+                self.playback_state.current_scroll.as_f64(),
+            )],
+        };
+
+        let to_y = |value: f64| {
+            YCoordinate::new(NonNegativeF64::new(value).unwrap_or(MAX_NON_NEGATIVE_F64))
+        };
+        let mut ranges = Vec::new();
+        for (i, &(start_y, start_display, scroll)) in sections.iter().enumerate() {
+            let end_y = sections
+                .get(i + 1)
+                .map_or(f64::INFINITY, |&(next_y, _, _)| next_y);
+            let (from, to) = if scroll == 0.0 {
+                if !(min_display..=max_display).contains(&start_display) {
+                    continue;
+                }
+                (start_y, end_y)
+            } else {
+                let at_min = start_y + (min_display - start_display) / scroll;
+                let at_max = start_y + (max_display - start_display) / scroll;
+                (
+                    at_min.min(at_max).max(start_y),
+                    at_min.max(at_max).min(end_y),
+                )
+            };
+            if from > to {
+                continue;
+            }
+            let lower = if from <= current_y.as_f64() {
+                Bound::Excluded(current_y)
+            } else {
+                Bound::Included(to_y(from))
+            };
+            let upper = if to.is_finite() {
+                Bound::Included(to_y(to))
+            } else {
+                Bound::Unbounded
+            };
+            ranges.push((lower, upper));
+        }
+        ranges
+    }
+
     /// Checks if a note's position overlaps with the visibility range.
+    ///
+    /// The position is widened by [`DISPLAY_RATIO_EPSILON`], so a note at the end of the visible
+    /// window is kept despite rounding errors of the integrated Y.
     fn overlaps_visibility_range(&self, ratio_start: FinF64, ratio_end: FinF64) -> bool {
         let (note_min, note_max) = if ratio_start < ratio_end {
             (ratio_start, ratio_end)
         } else {
             (ratio_end, ratio_start)
         };
+        let note_min = FinF64::new(note_min.as_f64() - DISPLAY_RATIO_EPSILON).unwrap_or(note_min);
+        let note_max = FinF64::new(note_max.as_f64() + DISPLAY_RATIO_EPSILON).unwrap_or(note_max);
 
         let (vis_min, vis_max) = self.visibility_range;
         let is_already_end = match vis_min {
@@ -588,6 +973,25 @@ impl<'a> ChartPlayer<'a> {
         !(is_already_end || is_not_started_yet)
     }
 
+    /// Compute display ratio for an event, with the scroll factors integrated
+    /// over Y by [`Chart::display_y`].
+    #[must_use]
+    pub fn compute_integrated_display_ratio(
+        chart: &Chart,
+        event_y: &YCoordinate,
+        current_y: &YCoordinate,
+        visible_window_y: &YCoordinate,
+    ) -> DisplayRatio {
+        let window_value = *visible_window_y.value();
+        if window_value.as_f64() > 0.0 {
+            let distance = chart.display_y(event_y).as_f64() - chart.display_y(current_y).as_f64();
+            let ratio_value = FinF64::new(distance / window_value.as_f64()).unwrap_or(FinF64::ZERO);
+            DisplayRatio::from(ratio_value)
+        } else {
+            DisplayRatio::at_judgment_line()
+        }
+    }
+
     /// Compute display ratio for an event.
     #[must_use]
     pub fn compute_display_ratio(
@@ -611,6 +1015,74 @@ impl<'a> ChartPlayer<'a> {
     }
 }
 
+/// How [`ChartPlayer::visible_events`] applies the scroll factor.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DisplayScroll {
+    /// Integrate the scroll factors between the judgment line and the event over Y, as beatoraja
+    /// and LR2 do. See [`Chart::display_y`].
+    #[default]
+    Integrated,
+    /// Multiply the whole distance by the current scroll factor, which ignores the scroll changes
+    /// ahead of the judgment line.
+    Current,
+}
+
+/// Convert seconds to a [`TimeSpan`], clamping negative and too large values, and treating NaN as
+/// zero.
+pub(crate) fn secs_to_time_span(secs: f64) -> TimeSpan {
+    if secs.is_nan() {
+        return TimeSpan::ZERO;
+    }
+    TimeSpan::from_duration(Duration::from_secs_f64(
+        secs.clamp(0.0, f64::from(u32::MAX)),
+    ))
+}
+
+/// Get the position of the bar line at the start of the given track.
+fn bar_line_y(chart: &Chart, track: u64) -> Option<YCoordinate> {
+    // Bar lines are generated in the order of tracks.
+    let mut bar_lines: Vec<_> = chart
+        .events()
+        .as_events()
+        .iter()
+        .filter(|event| matches!(event.event(), ChartEvent::BarLine))
+        .map(|event| (event.id(), event.position))
+        .collect();
+    bar_lines.sort_by_key(|&(id, _)| id);
+    bar_lines.get(usize::try_from(track).ok()?).map(|&(_, y)| y)
+}
+
+/// Target of seeking.
+#[derive(Debug, Clone, Copy)]
+enum SeekTarget {
+    Y(YCoordinate),
+    Time(TimeSpan),
+}
+
+/// Flow state reached by playing from the start.
+#[derive(Debug, Clone, Copy)]
+struct FlowCursor {
+    y: f64,
+    /// Seconds elapsed at the playback ratio 1.
+    secs: f64,
+    bpm: PositiveF64,
+    speed: PositiveF64,
+    scroll: FinF64,
+    /// Remaining seconds of the stop at the position.
+    stop_secs: f64,
+}
+
+impl FlowCursor {
+    /// Y per second at the playback ratio 1 on the clock of [`PlayheadEvent::activate_time`].
+    ///
+    /// Speed factors only change how fast the notes scroll, so the time only depends on the BPM, as
+    /// in [`crate::chart::process`].
+    fn velocity(&self) -> f64 {
+        (self.bpm.as_f64() / 240.0).max(f64::EPSILON)
+    }
+}
+
 /// Playback state snapshot.
 ///
 /// Represents the current playback state of the player, including all
@@ -628,6 +1100,8 @@ pub struct PlaybackState {
     pub playback_ratio: FinF64,
     /// Current Y position in chart
     pub progressed_y: YCoordinate,
+    /// Remaining duration of the current stop at the playback ratio 1, or zero if not stopped
+    pub stop_remaining: TimeSpan,
 }
 
 impl PlaybackState {
@@ -646,6 +1120,7 @@ impl PlaybackState {
             current_scroll,
             playback_ratio,
             progressed_y,
+            stop_remaining: TimeSpan::ZERO,
         }
     }
 
@@ -678,6 +1153,18 @@ impl PlaybackState {
     pub const fn progressed_y(&self) -> &YCoordinate {
         &self.progressed_y
     }
+
+    /// Get remaining duration of the current stop at the playback ratio 1.
+    #[must_use]
+    pub const fn stop_remaining(&self) -> TimeSpan {
+        self.stop_remaining
+    }
+
+    /// Whether the scroll is stopped.
+    #[must_use]
+    pub fn is_stopped(&self) -> bool {
+        self.stop_remaining > TimeSpan::ZERO
+    }
 }
 
 /// Visible range per BPM, representing the relationship between BPM and visible Y range.
@@ -872,6 +1359,7 @@ mod tests {
     use std::collections::{BTreeMap, HashMap};
 
     use super::*;
+    use crate::bms::command::Volume;
     use crate::chart::Chart;
     use crate::chart::YCoordinate;
     use crate::chart::process::{AllEventsIndex, ChartResources};
@@ -895,7 +1383,12 @@ mod tests {
     #[test]
     fn test_velocity_caching() {
         let chart = Chart::from_parts(
-            ChartResources::new(HashMap::new(), HashMap::new()),
+            ChartResources::new(
+                HashMap::new(),
+                HashMap::new(),
+                HashMap::new(),
+                Volume::default(),
+            ),
             AllEventsIndex::new(BTreeMap::new()),
             BTreeMap::new(),
             TEST_BPM_120,
@@ -935,7 +1428,12 @@ mod tests {
         );
 
         let chart = Chart::from_parts(
-            ChartResources::new(HashMap::new(), HashMap::new()),
+            ChartResources::new(
+                HashMap::new(),
+                HashMap::new(),
+                HashMap::new(),
+                Volume::default(),
+            ),
             AllEventsIndex::new(BTreeMap::new()),
             flow_events_by_y,
             TEST_BPM_120,
@@ -994,7 +1492,12 @@ mod tests {
         );
 
         let chart = Chart::from_parts(
-            ChartResources::new(HashMap::new(), HashMap::new()),
+            ChartResources::new(
+                HashMap::new(),
+                HashMap::new(),
+                HashMap::new(),
+                Volume::default(),
+            ),
             AllEventsIndex::new(BTreeMap::new()),
             flow_events_by_y,
             TEST_BPM_120,
@@ -1,7 +1,12 @@
 //! Module for base BPM generation strategies and types.
 
+use std::collections::BTreeMap;
+
 use strict_num_extended::PositiveF64;
 
+use crate::chart::Chart;
+use crate::chart::event::ChartEvent;
+
 /// Base BPM wrapper type.
 ///
 /// Represents a positive BPM value used to derive default visible window length.
@@ -72,6 +77,24 @@ pub struct MinBpmGenerator;
 #[derive(Debug, Clone, Copy, Default)]
 pub struct MaxBpmGenerator;
 
+/// Generator that uses the main BPM, which is held for the longest total time.
+///
+/// Time spent in stops is not held at any BPM. Ties are broken by the higher BPM.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MainBpmByDurationGenerator;
+
+/// Generator that uses the BPM at which the most playable notes are placed.
+///
+/// Ties are broken by the higher BPM.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MainBpmByNotesGenerator;
+
+/// Generator that uses the average BPM weighted by the time each BPM is held.
+///
+/// Time spent in stops is not held at any BPM.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AverageBpmGenerator;
+
 /// Generator that uses a manually specified BPM value.
 #[derive(Debug, Clone, Copy)]
 pub struct ManualBpmGenerator(pub BaseBpm);
@@ -125,3 +148,83 @@ impl ManualBpmGenerator {
         self.0
     }
 }
+
+impl BaseBpmGenerator<Chart> for MainBpmByDurationGenerator {
+    fn generate(&self, chart: &Chart) -> Option<BaseBpm> {
+        let mut durations: BTreeMap<PositiveF64, f64> = BTreeMap::new();
+        for (bpm, secs) in bpm_segments(chart) {
+            *durations.entry(bpm).or_default() += secs;
+        }
+        durations
+            .into_iter()
+            .filter(|&(_, secs)| secs > 0.0)
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(bpm, _)| BaseBpm::new(bpm))
+    }
+}
+
+impl BaseBpmGenerator<Chart> for MainBpmByNotesGenerator {
+    fn generate(&self, chart: &Chart) -> Option<BaseBpm> {
+        let mut counts: BTreeMap<PositiveF64, usize> = BTreeMap::new();
+        let mut bpm = chart.init_bpm;
+        for range in chart.events.as_by_y().values() {
+            let Some(events) = chart.events.as_events().get(range.clone()) else {
+                continue;
+            };
+            for event in events {
+                if let ChartEvent::BpmChange { bpm: changed } = event.event {
+                    bpm = changed;
+                }
+            }
+            let notes = events
+                .iter()
+                .filter(|event| {
+                    matches!(&event.event, ChartEvent::Note { kind, .. } if kind.is_playable())
+                })
+                .count();
+            if notes > 0 {
+                *counts.entry(bpm).or_default() += notes;
+            }
+        }
+        counts
+            .into_iter()
+            .max_by_key(|&(_, count)| count)
+            .map(|(main, _)| BaseBpm::new(main))
+    }
+}
+
+impl BaseBpmGenerator<Chart> for AverageBpmGenerator {
+    fn generate(&self, chart: &Chart) -> Option<BaseBpm> {
+        let (weighted, total) = bpm_segments(chart)
+            .into_iter()
+            .fold((0.0, 0.0), |(weighted, total), (bpm, secs)| {
+                (weighted + bpm.as_f64() * secs, total + secs)
+            });
+        if total <= 0.0 {
+            return None;
+        }
+        PositiveF64::new(weighted / total).ok().map(BaseBpm::new)
+    }
+}
+
+/// Splits the chart at each event position, as the activate times are computed, and returns the BPM
+/// of each segment and the seconds it takes to scroll through it.
+fn bpm_segments(chart: &Chart) -> Vec<(PositiveF64, f64)> {
+    let mut segments = vec![];
+    let mut bpm = chart.init_bpm;
+    let mut prev_y = 0.0;
+    for (y, range) in chart.events.as_by_y() {
+        let y = y.as_f64();
+        if y > prev_y {
+            segments.push((bpm, (y - prev_y) * 240.0 / bpm.as_f64()));
+            prev_y = y;
+        }
+        let events = chart.events.as_events().get(range.clone()).unwrap_or(&[]);
+        for event in events {
+            if let ChartEvent::BpmChange { bpm: changed } = event.event {
+                bpm = changed;
+            }
+        }
+    }
+    segments
+}
@@ -0,0 +1,182 @@
+//! Display settings in the terms of players: lane covers, hi-speed and the green number.
+//!
+//! The lane is 1000 thousandths high. LIFT raises the judgment line from the bottom of the lane,
+//! SUDDEN+ covers the top of the lane, and HIDDEN+ covers the lane just above the judgment line.
+//! At hi-speed 1.0, the lane from the top to the bottom holds one measure, so a note crosses it in
+//! `240000 / bpm` milliseconds. The green number is the duration for which a note is visible
+//! between the covers.
+//!
+//! [`DisplaySettings::apply`] converts the settings into the [`VisibleRangePerBpm`] and the
+//! visibility range of a [`ChartPlayer`], where the display ratio 0 is the judgment line and 1 is
+//! the top of the lane.
+
+use std::ops::RangeInclusive;
+
+use gametime::TimeSpan;
+use strict_num_extended::{FinF64, PositiveF64};
+
+use super::{ChartPlayer, VisibleRangePerBpm, secs_to_time_span};
+use crate::chart::DEFAULT_BPM;
+
+/// Height of the lane in the unit of the lane covers.
+pub const LANE_HEIGHT: u16 = 1000;
+
+/// Lane cover amounts in thousandths of the lane height.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LaneCover {
+    /// SUDDEN+, which covers the top of the lane.
+    pub sudden: u16,
+    /// HIDDEN+, which covers the lane above the judgment line.
+    pub hidden: u16,
+    /// LIFT, which raises the judgment line.
+    pub lift: u16,
+}
+
+impl LaneCover {
+    /// Returns the height between the judgment line and the top of the lane.
+    #[must_use]
+    pub fn lane_height(&self) -> u16 {
+        LANE_HEIGHT.saturating_sub(self.lift.min(LANE_HEIGHT - 1))
+    }
+
+    /// Returns the height of the lane visible between the covers.
+    #[must_use]
+    pub fn visible_height(&self) -> u16 {
+        self.lane_height()
+            .saturating_sub(self.sudden)
+            .saturating_sub(self.hidden)
+    }
+
+    /// Returns the range of the display ratio visible between the covers.
+    #[must_use]
+    pub fn visibility_range(&self) -> RangeInclusive<FinF64> {
+        let lane_height = f64::from(self.lane_height());
+        let bottom = f64::from(self.hidden.min(self.lane_height())) / lane_height;
+        let top = f64::from(self.lane_height().saturating_sub(self.sudden)) / lane_height;
+        let ratio = |value: f64| FinF64::new(value).unwrap_or(FinF64::ZERO);
+        ratio(bottom.min(top))..=ratio(top)
+    }
+}
+
+/// How the hi-speed follows BPM changes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum HiSpeedMode {
+    /// The hi-speed is fixed, so the green number changes with the BPM.
+    #[default]
+    Fixed,
+    /// The hi-speed follows the BPM to keep the green number.
+    Floating {
+        /// The green number to keep.
+        green_number: TimeSpan,
+    },
+}
+
+/// Lane covers and hi-speed of a player.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DisplaySettings {
+    /// The lane covers.
+    pub cover: LaneCover,
+    /// The hi-speed, which multiplies the scroll speed. It is updated by [`Self::apply`] in
+    /// [`HiSpeedMode::Floating`].
+    pub hi_speed: PositiveF64,
+    /// How the hi-speed follows BPM changes.
+    pub mode: HiSpeedMode,
+}
+
+impl Default for DisplaySettings {
+    fn default() -> Self {
+        Self {
+            cover: LaneCover::default(),
+            hi_speed: PositiveF64::ONE,
+            mode: HiSpeedMode::Fixed,
+        }
+    }
+}
+
+impl DisplaySettings {
+    /// Returns the duration for a note to cross the lane from the top to the judgment line at the
+    /// BPM.
+    #[must_use]
+    pub fn reaction_time(&self, bpm: PositiveF64) -> TimeSpan {
+        let lane = f64::from(self.cover.lane_height()) / f64::from(LANE_HEIGHT);
+        secs_to_time_span(240.0 / bpm.as_f64() / self.hi_speed.as_f64() * lane)
+    }
+
+    /// Returns the green number, the duration for which a note is visible between the covers at
+    /// the BPM.
+    #[must_use]
+    pub fn green_number(&self, bpm: PositiveF64) -> TimeSpan {
+        let visible = f64::from(self.cover.visible_height()) / f64::from(LANE_HEIGHT);
+        secs_to_time_span(240.0 / bpm.as_f64() / self.hi_speed.as_f64() * visible)
+    }
+
+    /// Returns the hi-speed which makes the green number at the BPM equal to `green_number`, or
+    /// `None` if no hi-speed does, such as when the covers hide the whole lane.
+    #[must_use]
+    pub fn hi_speed_for_green_number(
+        &self,
+        bpm: PositiveF64,
+        green_number: TimeSpan,
+    ) -> Option<PositiveF64> {
+        let visible = f64::from(self.cover.visible_height()) / f64::from(LANE_HEIGHT);
+        PositiveF64::new(240.0 / bpm.as_f64() * visible / green_number.as_secs_f64()).ok()
+    }
+
+    /// Switches to [`HiSpeedMode::Floating`], keeping the current green number at the BPM.
+    pub fn enable_floating(&mut self, bpm: PositiveF64) {
+        self.mode = HiSpeedMode::Floating {
+            green_number: self.green_number(bpm),
+        };
+    }
+
+    /// Returns the visible range for the current hi-speed, which does not depend on the BPM.
+    #[must_use]
+    pub fn visible_range_per_bpm(&self) -> VisibleRangePerBpm {
+        VisibleRangePerBpm::new(&DEFAULT_BPM, self.reaction_time(DEFAULT_BPM))
+    }
+
+    /// Applies the settings to the player at its current BPM.
+    ///
+    /// In [`HiSpeedMode::Floating`], the hi-speed is adjusted first to keep the green number, so
+    /// this should be called after each [`ChartPlayer::update`].
+    pub fn apply(&mut self, player: &mut ChartPlayer<'_>) {
+        if let HiSpeedMode::Floating { green_number } = self.mode
+            && let Some(hi_speed) =
+                self.hi_speed_for_green_number(player.playback_state().current_bpm(), green_number)
+        {
+            self.hi_speed = hi_speed;
+        }
+        player.set_visible_range_per_bpm(self.visible_range_per_bpm());
+        player.set_visibility_range(self.cover.visibility_range());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DisplaySettings, LaneCover};
+    use gametime::TimeSpan;
+    use strict_num_extended::PositiveF64;
+
+    #[test]
+    fn green_number_with_covers() {
+        let settings = DisplaySettings {
+            cover: LaneCover {
+                sudden: 300,
+                hidden: 100,
+                lift: 100,
+            },
+            hi_speed: PositiveF64::TWO,
+            ..DisplaySettings::default()
+        };
+        let bpm = PositiveF64::new_const(150.0);
+        // 1600 ms for the whole lane at hi-speed 1, 500 of 1000 visible.
+        assert_eq!(settings.green_number(bpm), TimeSpan::MILLISECOND * 400);
+        assert_eq!(settings.reaction_time(bpm), TimeSpan::MILLISECOND * 720);
+
+        let range = settings.cover.visibility_range();
+        assert!((range.start().as_f64() - 1.0 / 9.0).abs() < 1e-9);
+        assert!((range.end().as_f64() - 6.0 / 9.0).abs() < 1e-9);
+    }
+}
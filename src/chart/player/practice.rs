@@ -0,0 +1,260 @@
+//! Practice mode, which loops a range of a chart.
+//!
+//! A [`PracticePlayer`] plays a [`LoopRange`] of a chart over and over on a [`ChartPlayer`]. Each
+//! iteration starts [`PracticeConfig::lead_in`] before the range, and ends when the playhead
+//! reaches the end of the range. The events in the range are triggered once per iteration, and
+//! the events at the end of the range are not triggered.
+
+use gametime::{TimeSpan, TimeStamp};
+use strict_num_extended::FinF64;
+
+use super::{ChartPlayer, SeekTarget, VisibleRangePerBpm, bar_line_y, secs_to_time_span};
+use crate::bms::command::time::Track;
+use crate::chart::Chart;
+use crate::chart::event::{ChartEvent, PlayheadEvent, YCoordinate};
+use crate::chart::types::NoteKind;
+
+/// A range of Y positions to loop, excluding the end.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LoopRange {
+    start: YCoordinate,
+    end: YCoordinate,
+}
+
+impl LoopRange {
+    /// Creates a range from `start` to `end`. Returns `None` if the range is empty.
+    #[must_use]
+    pub fn new(start: YCoordinate, end: YCoordinate) -> Option<Self> {
+        (start < end).then_some(Self { start, end })
+    }
+
+    /// Creates a range from the start of the track `start` to the start of the track `end`, by the
+    /// bar lines of the chart. Returns `None` if the chart has no bar line for either track, or
+    /// the range is empty.
+    #[must_use]
+    pub fn from_tracks(chart: &Chart, start: Track, end: Track) -> Option<Self> {
+        Self::new(bar_line_y(chart, start.0)?, bar_line_y(chart, end.0)?)
+    }
+
+    /// Returns the start of the range.
+    #[must_use]
+    pub const fn start(&self) -> YCoordinate {
+        self.start
+    }
+
+    /// Returns the end of the range.
+    #[must_use]
+    pub const fn end(&self) -> YCoordinate {
+        self.end
+    }
+}
+
+/// How to trigger the long notes over the boundaries of a loop.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum StraddlingNotes {
+    /// Long notes held over the start of an iteration are triggered at the start, and long notes
+    /// held over the end of the range are triggered as usual.
+    #[default]
+    Play,
+    /// Long notes over the start of an iteration or the end of the range are not triggered.
+    Skip,
+}
+
+/// Options of a [`PracticePlayer`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PracticeConfig {
+    /// Chart time played before the start of the range in each iteration.
+    pub lead_in: TimeSpan,
+    /// Playback ratio of the first iteration.
+    pub initial_ratio: FinF64,
+    /// Playback ratio added on each iteration after the first.
+    pub ratio_step: FinF64,
+    /// Upper bound of the playback ratio raised by [`Self::ratio_step`].
+    pub max_ratio: FinF64,
+    /// How to trigger the long notes over the boundaries of a loop.
+    pub straddling_notes: StraddlingNotes,
+}
+
+impl Default for PracticeConfig {
+    fn default() -> Self {
+        Self {
+            lead_in: TimeSpan::ZERO,
+            initial_ratio: FinF64::ONE,
+            ratio_step: FinF64::ZERO,
+            max_ratio: FinF64::TWO,
+            straddling_notes: StraddlingNotes::Play,
+        }
+    }
+}
+
+/// Result of [`PracticePlayer::update`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct PracticeUpdate {
+    /// Events triggered in the update, sorted by position in each iteration.
+    pub events: Vec<PlayheadEvent>,
+    /// Whether a new iteration started in the update. The per-loop state of the caller, such as
+    /// a [`Judge`](crate::chart::judge::Judge) or a [`Score`](crate::chart::score::Score), should
+    /// be reset before applying [`Self::events`].
+    pub restarted: bool,
+}
+
+/// A player looping a range of a chart.
+pub struct PracticePlayer<'a> {
+    player: ChartPlayer<'a>,
+    range: LoopRange,
+    config: PracticeConfig,
+    /// Time of the start of each iteration on the clock of [`PlayheadEvent::activate_time`].
+    lead_in_time: TimeSpan,
+    /// Time of the end of the range on the clock of [`PlayheadEvent::activate_time`].
+    end_time: TimeSpan,
+    iteration: u32,
+    /// Events of the start of the first iteration, triggered by the first update.
+    pending: Vec<PlayheadEvent>,
+}
+
+impl<'a> PracticePlayer<'a> {
+    /// Starts the first iteration at `start_time`.
+    #[must_use]
+    pub fn start(
+        chart: &'a Chart,
+        visible_range_per_bpm: VisibleRangePerBpm,
+        range: LoopRange,
+        config: PracticeConfig,
+        start_time: TimeStamp,
+    ) -> Self {
+        let mut player = ChartPlayer::start(chart, visible_range_per_bpm, start_time);
+        player.set_playback_ratio(config.initial_ratio);
+        let start = secs_to_time_span(player.flow_cursor(SeekTarget::Y(range.start)).secs);
+        let end_time = secs_to_time_span(player.flow_cursor(SeekTarget::Y(range.end)).secs);
+        let lead_in_time = (start - config.lead_in.max(TimeSpan::ZERO)).max(TimeSpan::ZERO);
+        let mut practice = Self {
+            player,
+            range,
+            config,
+            lead_in_time,
+            end_time,
+            iteration: 0,
+            pending: Vec::new(),
+        };
+        practice.pending = practice.restart(start_time);
+        practice
+    }
+
+    /// Advances playback to `now`, and starts a new iteration if the playhead reaches the end of
+    /// the range.
+    pub fn update(&mut self, now: TimeStamp) -> PracticeUpdate {
+        let mut events = std::mem::take(&mut self.pending);
+        events.extend(self.player.update(now));
+        if *self.player.playback_state().progressed_y() < self.range.end {
+            self.skip_straddling_ends(&mut events);
+            return PracticeUpdate {
+                events,
+                restarted: false,
+            };
+        }
+        events.retain(|event| event.position < self.range.end);
+        self.skip_straddling_ends(&mut events);
+
+        // Carry the time played over the end into the next iteration.
+        let overshoot = (self.player.playhead_time() - self.end_time).max(TimeSpan::ZERO);
+        let ratio = self.player.playback_state().playback_ratio().as_f64();
+        let overshoot_secs = if ratio > 0.0 {
+            overshoot.as_secs_f64() / ratio
+        } else {
+            0.0
+        };
+        let resumed_at = now
+            .sub_span(secs_to_time_span(overshoot_secs))
+            .unwrap_or(now);
+
+        self.iteration = self.iteration.saturating_add(1);
+        let ramped = self.player.playback_state().playback_ratio().as_f64()
+            + self.config.ratio_step.as_f64();
+        if let Ok(ramped) = FinF64::new(ramped.min(self.config.max_ratio.as_f64())) {
+            self.player.set_playback_ratio(ramped);
+        }
+        let mut restart_events = self.restart(resumed_at);
+        restart_events.extend(self.player.update(now));
+        // The overshoot is shorter than an iteration unless the update is very late.
+        restart_events.retain(|event| event.position < self.range.end);
+        self.skip_straddling_ends(&mut restart_events);
+        events.extend(restart_events);
+        PracticeUpdate {
+            events,
+            restarted: true,
+        }
+    }
+
+    /// Returns the looped range.
+    #[must_use]
+    pub const fn range(&self) -> LoopRange {
+        self.range
+    }
+
+    /// Returns the options.
+    #[must_use]
+    pub const fn config(&self) -> &PracticeConfig {
+        &self.config
+    }
+
+    /// Returns the number of the current iteration, starting from 0.
+    #[must_use]
+    pub const fn iteration(&self) -> u32 {
+        self.iteration
+    }
+
+    /// Returns the underlying player.
+    #[must_use]
+    pub const fn player(&self) -> &ChartPlayer<'a> {
+        &self.player
+    }
+
+    /// Returns the underlying player, such as to pause it. Seeking it breaks the loop until the
+    /// playhead reaches the end of the range.
+    pub const fn player_mut(&mut self) -> &mut ChartPlayer<'a> {
+        &mut self.player
+    }
+
+    /// Seeks to the start of an iteration and returns the events to trigger there.
+    fn restart(&mut self, now: TimeStamp) -> Vec<PlayheadEvent> {
+        let snapshot = self.player.seek_to_time(self.lead_in_time, now);
+        let start_y = *self.player.playback_state().progressed_y();
+        let skip_straddling = self.config.straddling_notes == StraddlingNotes::Skip;
+        let mut events: Vec<_> = snapshot
+            .into_iter()
+            .filter(|event| event.position < start_y && !(skip_straddling && is_long_note(event)))
+            .collect();
+        // Seeking treats the events at the position as passed, but they belong to the iteration.
+        events.extend(self.player.events_in_y_range(start_y..=start_y));
+        events
+    }
+
+    /// Removes the long notes over the end of the range if they are skipped.
+    fn skip_straddling_ends(&self, events: &mut Vec<PlayheadEvent>) {
+        if self.config.straddling_notes != StraddlingNotes::Skip {
+            return;
+        }
+        let end = self.range.end.as_f64();
+        events.retain(|event| match event.event() {
+            ChartEvent::Note {
+                kind: NoteKind::Long,
+                length: Some(length),
+                ..
+            } => event.position.as_f64() + length.as_f64() <= end,
+            _ => true,
+        });
+    }
+}
+
+/// Whether the event is a long note.
+const fn is_long_note(event: &PlayheadEvent) -> bool {
+    matches!(
+        event.event,
+        ChartEvent::Note {
+            kind: NoteKind::Long,
+            ..
+        }
+    )
+}
@@ -5,16 +5,38 @@
 
 // Re-export types
 pub use super::Chart;
+pub use super::autoplay::AutoplayConfig;
+pub use super::bga::{
+    BgaCrop, BgaFrame, BgaImage, BgaLayerState, BgaResources, BgaTimeline, NO_TINT,
+};
 pub use super::event::FlowEvent;
 pub use super::event::YCoordinate;
+pub use super::gauge::{Gauge, GaugeConfig, GaugeRules, GaugeTable, GaugeType};
+pub use super::judge::{
+    Judge, JudgeConfig, JudgeEvent, JudgeEventKind, JudgeWindows, Judgment, KeyAction, KeyInput,
+    NotePart,
+};
+#[cfg(feature = "mixdown")]
+pub use super::mixdown::{Mixdown, MixdownConfig, SampleProvider, Samples};
 pub use super::player::base_bpm::BaseBpm;
 pub use super::player::base_bpm::{
-    BaseBpmGenerator, ManualBpmGenerator, MaxBpmGenerator, MinBpmGenerator, StartBpmGenerator,
+    AverageBpmGenerator, BaseBpmGenerator, MainBpmByDurationGenerator, MainBpmByNotesGenerator,
+    ManualBpmGenerator, MaxBpmGenerator, MinBpmGenerator, StartBpmGenerator,
+};
+pub use super::player::display::{DisplaySettings, HiSpeedMode, LANE_HEIGHT, LaneCover};
+pub use super::player::practice::{
+    LoopRange, PracticeConfig, PracticePlayer, PracticeUpdate, StraddlingNotes,
 };
-pub use super::player::{DisplayRatio, VisibleRangePerBpm};
+pub use super::player::{DisplayRatio, DisplayScroll, VisibleRangePerBpm};
 pub use super::process::{
     AllEventsIndex, BmpId, ChartEventId, ChartEventIdGenerator, ChartResources, Process, WavId,
+    WavParams,
 };
+pub use super::replay::{LaneOption, Replay, ReplayError};
+pub use super::score::{ClearLamp, JudgmentCounts, PlayResult, Rank, Score};
+pub use super::stats::{ChartStats, DEFAULT_DENSITY_WINDOW, LaneNoteCount, NoteKindCounts};
+pub use super::timing::TimingMap;
+pub use super::voice::{VoiceAllocator, VoiceCommand, VoiceId};
 pub use gametime::TimeSpan;
 
 // Re-export NonNegativeF64 for backward compatibility
@@ -5,9 +5,12 @@ use std::collections::{BTreeMap, HashMap};
 use std::ops::{Bound, Range, RangeBounds};
 use std::path::PathBuf;
 
+use crate::bms::command::Volume;
+use crate::chart::bga::BgaResources;
 use crate::chart::event::{ChartEvent, PlayheadEvent, YCoordinate};
 use crate::chart::types::NoteKind;
 use crate::chart::{Chart, TimeSpan};
+use strict_num_extended::FinF64;
 use strict_num_extended::NonNegativeF64;
 use strict_num_extended::PositiveF64;
 
@@ -271,6 +274,31 @@ impl AllEventsIndex {
         &self.events
     }
 
+    /// Get a mutable reference to all events, for rewriting them without moving them.
+    pub(crate) fn as_events_mut(&mut self) -> &mut [PlayheadEvent] {
+        &mut self.events
+    }
+
+    /// Rewrites the events with `f`, removing the ones it returns `false` for, and rebuilds the index.
+    pub(crate) fn retain_events(&mut self, mut f: impl FnMut(&mut PlayheadEvent) -> bool) {
+        let mut map: BTreeMap<YCoordinate, Vec<PlayheadEvent>> = BTreeMap::new();
+        for mut event in std::mem::take(&mut self.events) {
+            if f(&mut event) {
+                map.entry(event.position).or_default().push(event);
+            }
+        }
+        *self = Self::new(map);
+    }
+
+    /// Adds the events and rebuilds the index.
+    pub(crate) fn extend_events(&mut self, events: impl IntoIterator<Item = PlayheadEvent>) {
+        let mut map: BTreeMap<YCoordinate, Vec<PlayheadEvent>> = BTreeMap::new();
+        for event in std::mem::take(&mut self.events).into_iter().chain(events) {
+            map.entry(event.position).or_default().push(event);
+        }
+        *self = Self::new(map);
+    }
+
     /// Get a reference to the Y-coordinate-based index.
     ///
     /// # Returns
@@ -508,6 +536,12 @@ pub struct ChartResources {
     pub(crate) wav_files: HashMap<WavId, PathBuf>,
     /// BMP ID -> file path mapping.
     pub(crate) bmp_files: HashMap<BmpId, PathBuf>,
+    /// WAV ID -> playback parameters mapping, for the WAVs with any.
+    pub(crate) wav_params: HashMap<WavId, WavParams>,
+    /// Play volume of the sounds.
+    pub(crate) volume: Volume,
+    /// BGA definitions other than the BMP files.
+    pub(crate) bga: BgaResources,
 }
 
 impl ChartResources {
@@ -523,15 +557,104 @@ impl ChartResources {
         &self.bmp_files
     }
 
+    /// Get WAV playback parameters mapping. WAVs without an entry play with
+    /// [`WavParams::default`].
+    #[must_use]
+    pub const fn wav_params(&self) -> &HashMap<WavId, WavParams> {
+        &self.wav_params
+    }
+
+    /// Get the playback parameters of a WAV.
+    #[must_use]
+    pub fn wav_param(&self, id: WavId) -> WavParams {
+        self.wav_params.get(&id).copied().unwrap_or_default()
+    }
+
+    /// Get the play volume of the sounds, from `#VOLWAV`.
+    #[must_use]
+    pub const fn volume(&self) -> Volume {
+        self.volume
+    }
+
+    /// Get the BGA definitions other than the BMP files.
+    #[must_use]
+    pub const fn bga(&self) -> &BgaResources {
+        &self.bga
+    }
+
     /// Create a new `ChartResources` (internal API).
     #[must_use]
-    pub(crate) const fn new(
+    pub(crate) fn new(
         wav_files: HashMap<WavId, PathBuf>,
         bmp_files: HashMap<BmpId, PathBuf>,
+        wav_params: HashMap<WavId, WavParams>,
+        volume: Volume,
     ) -> Self {
         Self {
             wav_files,
             bmp_files,
+            wav_params,
+            volume,
+            bga: BgaResources::default(),
+        }
+    }
+
+    /// Set the BGA definitions (internal API).
+    #[must_use]
+    pub(crate) fn with_bga(mut self, bga: BgaResources) -> Self {
+        self.bga = bga;
+        self
+    }
+}
+
+/// Playback parameters of a WAV, from `#EXWAV` and `#WAVCMD`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WavParams {
+    /// Pan from -1.0 (leftmost) to 1.0 (rightmost).
+    pub pan: FinF64,
+    /// Volume in decibels, 0.0 for the original volume.
+    pub volume_db: FinF64,
+    /// Sample rate to play the sound at, in Hz.
+    pub frequency: Option<u64>,
+    /// Pitch in MIDI note numbers, where [`Self::ORIGINAL_PITCH`] plays the original pitch.
+    pub pitch: Option<u32>,
+    /// Duration to play the sound for, or `None` to play the whole sound.
+    pub length: Option<TimeSpan>,
+}
+
+impl WavParams {
+    /// The pitch which plays a sound at its original pitch.
+    pub const ORIGINAL_PITCH: u32 = 60;
+    /// The lowest volume in decibels, which is treated as silence.
+    pub const MIN_VOLUME_DB: f64 = -100.0;
+
+    /// Returns the volume as a linear amplitude factor, 0.0 for silence.
+    #[must_use]
+    pub fn volume_factor(&self) -> f64 {
+        if self.volume_db.as_f64() <= Self::MIN_VOLUME_DB {
+            0.0
+        } else {
+            10f64.powf(self.volume_db.as_f64() / 20.0)
+        }
+    }
+
+    /// Returns the ratio of the playback speed from [`Self::pitch`], 1.0 for the original pitch.
+    #[must_use]
+    pub fn pitch_ratio(&self) -> f64 {
+        self.pitch.map_or(1.0, |pitch| {
+            2f64.powf((f64::from(pitch) - f64::from(Self::ORIGINAL_PITCH)) / 12.0)
+        })
+    }
+}
+
+impl Default for WavParams {
+    fn default() -> Self {
+        Self {
+            pan: FinF64::ZERO,
+            volume_db: FinF64::ZERO,
+            frequency: None,
+            pitch: None,
+            length: None,
         }
     }
 }
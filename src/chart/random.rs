@@ -0,0 +1,143 @@
+//! Note-level random options, which shuffle the lanes of each timing row instead of whole lanes.
+//!
+//! - [`Chart::s_random`] places the notes of each row onto random lanes.
+//! - [`Chart::h_random`] does the same, but avoids lanes hit shortly before.
+//!
+//! Both are seeded with [`JavaRandom`] like the lane-level converters in
+//! [`crate::bms::command::channel::converter`]. Notes on the same lane in a row, such as a note and a
+//! landmine, move together. A long note holds its new lane until its end, so no note is placed onto
+//! that lane meanwhile, unless all the other lanes are taken in the row. Then the note is placed onto
+//! the held lane which is released first.
+
+use std::collections::HashMap;
+
+use gametime::TimeSpan;
+
+use super::Chart;
+use super::event::ChartEvent;
+use super::types::{Key, NoteKind, PlayerSide};
+use crate::bms::rng::JavaRandom;
+
+impl Chart {
+    /// Applies S-RANDOM to the notes on `keys` of `side`, seeded with `seed`.
+    ///
+    /// The notes of each row are placed onto random lanes among `keys` independently of the other rows.
+    pub fn s_random(&mut self, side: PlayerSide, keys: &[Key], seed: i64) {
+        self.shuffle_rows(side, keys, &mut JavaRandom::new(seed), None);
+    }
+
+    /// Applies H-RANDOM to the notes on `keys` of `side`, seeded with `seed`.
+    ///
+    /// Same as [`Chart::s_random`], but a note is not placed onto a lane hit less than `threshold`
+    /// before, unless all the free lanes are. Releasing a long note is not a hit.
+    pub fn h_random(&mut self, side: PlayerSide, keys: &[Key], seed: i64, threshold: TimeSpan) {
+        self.shuffle_rows(side, keys, &mut JavaRandom::new(seed), Some(threshold));
+    }
+
+    fn shuffle_rows(
+        &mut self,
+        side: PlayerSide,
+        keys: &[Key],
+        rng: &mut JavaRandom,
+        threshold: Option<TimeSpan>,
+    ) {
+        let rows: Vec<_> = self
+            .events
+            .as_by_y()
+            .iter()
+            .map(|(y, range)| (y.as_f64(), range.clone()))
+            .collect();
+        // End of the long note holding each lane.
+        let mut held: HashMap<Key, f64> = HashMap::new();
+        let mut last_hit: HashMap<Key, TimeSpan> = HashMap::new();
+        let events = self.events.as_events_mut();
+
+        for (y, range) in rows {
+            held.retain(|_, &mut end| end >= y);
+
+            // Indices of the notes in this row, grouped by their lanes in the order of `keys`.
+            let mut units: Vec<(Key, Vec<usize>)> = vec![];
+            for idx in range {
+                let Some(ChartEvent::Note {
+                    side: note_side,
+                    key,
+                    ..
+                }) = events.get(idx).map(|ev| &ev.event)
+                else {
+                    continue;
+                };
+                if *note_side != side || !keys.contains(key) {
+                    continue;
+                }
+                match units.iter_mut().find(|(unit_key, _)| unit_key == key) {
+                    Some((_, indices)) => indices.push(idx),
+                    None => units.push((*key, vec![idx])),
+                }
+            }
+            units.sort_by_key(|(key, _)| keys.iter().position(|k| k == key));
+
+            let mut free: Vec<Key> = keys
+                .iter()
+                .copied()
+                .filter(|key| !held.contains_key(key))
+                .collect();
+            let mut taken: Vec<Key> = vec![];
+            for (original, indices) in units {
+                let Some(time) = indices
+                    .first()
+                    .and_then(|&idx| events.get(idx))
+                    .map(|ev| ev.activate_time)
+                else {
+                    continue;
+                };
+                let rested: Vec<Key> = threshold.map_or_else(Vec::new, |threshold| {
+                    free.iter()
+                        .copied()
+                        .filter(|key| {
+                            last_hit
+                                .get(key)
+                                .is_none_or(|&last| time - last >= threshold)
+                        })
+                        .collect()
+                });
+                let candidates = if rested.is_empty() { &free } else { &rested };
+                let lane = if candidates.is_empty() {
+                    let position = |key: &Key| keys.iter().position(|k| k == key);
+                    held.iter()
+                        .filter(|(key, _)| !taken.contains(key))
+                        .min_by(|(a, a_end), (b, b_end)| {
+                            a_end
+                                .total_cmp(b_end)
+                                .then_with(|| position(a).cmp(&position(b)))
+                        })
+                        .map_or(original, |(&key, _)| key)
+                } else {
+                    let pick = rng.next_int_bound(candidates.len() as i32) as usize;
+                    candidates.get(pick).copied().unwrap_or(original)
+                };
+                free.retain(|&key| key != lane);
+                taken.push(lane);
+
+                for idx in indices {
+                    let Some(event) = events.get_mut(idx) else {
+                        continue;
+                    };
+                    let ChartEvent::Note {
+                        key, kind, length, ..
+                    } = &mut event.event
+                    else {
+                        continue;
+                    };
+                    *key = lane;
+                    if let (NoteKind::Long, Some(length)) = (*kind, length) {
+                        held.insert(lane, y + length.as_f64());
+                    }
+                    if kind.is_playable() {
+                        last_hit.insert(lane, time);
+                    }
+                }
+            }
+        }
+        self.sync_long_note_lanes();
+    }
+}
@@ -0,0 +1,493 @@
+//! Replays of plays, which can be re-simulated against a chart to verify the results.
+//!
+//! A [`Replay`] records the key inputs of a play with what is needed to reproduce the chart: the
+//! hash of the chart file, the seed of the RNG for `#RANDOM` and the lane option of each side with
+//! its seed. The crate does not hash files nor apply lane options by itself, so the hash is any
+//! string agreed by the recorder and the verifier (such as the hex digest of the file), and the chart
+//! passed to [`Replay::simulate`] must be built with the recorded options and seeds.
+//!
+//! [`Replay::to_bytes`] encodes a replay into a compact binary format. All integers are unsigned
+//! LEB128 unless noted:
+//!
+//! 1. The magic `BMSR` and the format version byte `1`.
+//! 2. The byte length and the UTF-8 bytes of the chart hash.
+//! 3. A flag byte of the seeds (bit 0: `#RANDOM` seed), followed by the present seed as a
+//!    little-endian `i64`.
+//! 4. The number of sides with lane options, then for each side the side byte, the lane option byte
+//!    and the seed as a little-endian `i64`.
+//! 5. The gauge type byte and the gauge rules byte.
+//! 6. The number of lanes, then for each lane the side byte, the key kind byte, the key number byte,
+//!    the number of inputs and the inputs in time order. Each input is the time and the action byte,
+//!    which is 1 for a release. The time is the zigzag-encoded nanoseconds for the first input of the
+//!    lane, or the nanoseconds from the previous input for the others.
+
+use std::collections::{BTreeMap, HashMap};
+
+use gametime::TimeSpan;
+use thiserror::Error;
+
+use super::Chart;
+use super::gauge::{Gauge, GaugeConfig, GaugeRules, GaugeType};
+use super::judge::{Judge, JudgeConfig, KeyAction, KeyInput};
+use super::score::{PlayResult, Score};
+use super::types::{Key, PlayerSide};
+
+const MAGIC: &[u8; 4] = b"BMSR";
+const VERSION: u8 = 1;
+const RANDOM_SEED_FLAG: u8 = 1;
+
+/// An error on decoding or verifying a [`Replay`].
+#[non_exhaustive]
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum ReplayError {
+    /// The data does not start with the magic of a replay.
+    #[error("not a replay")]
+    InvalidMagic,
+    /// The format version is not supported.
+    #[error("unsupported replay version {0}")]
+    UnsupportedVersion(u8),
+    /// The data ends in the middle of a replay.
+    #[error("unexpected end of replay data")]
+    UnexpectedEof,
+    /// A field has a value out of its range.
+    #[error("invalid {0} in replay data")]
+    InvalidField(&'static str),
+    /// There are bytes after the replay.
+    #[error("{0} trailing bytes after replay data")]
+    TrailingBytes(usize),
+    /// The replay is recorded on another chart.
+    #[error("chart hash mismatch: replay has {replay}, but chart has {chart}")]
+    ChartHashMismatch {
+        /// The hash in the replay.
+        replay: String,
+        /// The hash of the chart to verify against.
+        chart: String,
+    },
+    /// The re-simulated result differs from the claimed one.
+    #[error("claimed result does not match simulated result")]
+    ResultMismatch {
+        /// The claimed result.
+        claimed: Box<PlayResult>,
+        /// The result of the re-simulation.
+        simulated: Box<PlayResult>,
+    },
+}
+
+/// A lane option applied to the lanes of a side.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LaneOption {
+    /// The lanes are mirrored.
+    Mirror,
+    /// The lanes are shuffled.
+    Random,
+    /// The lanes are rotated.
+    RRandom,
+    /// The notes of each row are placed onto random lanes, see [`Chart::s_random`].
+    SRandom,
+    /// Same as [`LaneOption::SRandom`], avoiding lanes hit shortly before, see [`Chart::h_random`].
+    HRandom,
+}
+
+/// A recorded play.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Replay {
+    /// Hash of the played chart.
+    pub chart_hash: String,
+    /// Seed of the RNG for `#RANDOM`, if the chart uses it.
+    pub random_seed: Option<i64>,
+    /// Lane option of each side with its seed, which is ignored by the options without randomness.
+    pub lane_options: HashMap<PlayerSide, (LaneOption, i64)>,
+    /// The type of the gauge.
+    pub gauge_type: GaugeType,
+    /// The rules of the gauge.
+    pub gauge_rules: GaugeRules,
+    /// The key inputs in time order.
+    pub inputs: Vec<KeyInput>,
+}
+
+impl Replay {
+    /// Creates a replay without inputs.
+    #[must_use]
+    pub fn new(
+        chart_hash: impl Into<String>,
+        gauge_type: GaugeType,
+        gauge_rules: GaugeRules,
+    ) -> Self {
+        Self {
+            chart_hash: chart_hash.into(),
+            random_seed: None,
+            lane_options: HashMap::new(),
+            gauge_type,
+            gauge_rules,
+            inputs: vec![],
+        }
+    }
+
+    /// Records a key input.
+    pub fn record(&mut self, input: KeyInput) {
+        self.inputs.push(input);
+    }
+
+    /// Plays the inputs on `chart` to the end, and returns the result.
+    ///
+    /// The inputs are judged in time order, and a failed survival gauge does not stop the play.
+    #[must_use]
+    pub fn simulate(
+        &self,
+        chart: &Chart,
+        judge_config: JudgeConfig,
+        gauge_config: GaugeConfig,
+    ) -> PlayResult {
+        let mut inputs = self.inputs.clone();
+        inputs.sort_by_key(|input| input.time);
+        let mut judge = Judge::new(chart, judge_config);
+        let mut gauge = Gauge::new(self.gauge_type, self.gauge_rules, gauge_config);
+        let mut score = Score::new(gauge_config.notes);
+        let events = inputs
+            .into_iter()
+            .flat_map(|input| judge.input(input))
+            .collect::<Vec<_>>()
+            .into_iter()
+            .chain(judge.finish());
+        for event in events {
+            score.apply_event(&event);
+            gauge.apply_event(&event, chart);
+        }
+        PlayResult::new(&score, &gauge)
+    }
+
+    /// Re-simulates the replay on `chart` whose hash is `chart_hash`, and checks that the result is
+    /// `claimed`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ReplayError::ChartHashMismatch`] if the replay is recorded on another chart, or
+    /// [`ReplayError::ResultMismatch`] if the result differs.
+    pub fn verify(
+        &self,
+        chart_hash: &str,
+        chart: &Chart,
+        judge_config: JudgeConfig,
+        gauge_config: GaugeConfig,
+        claimed: &PlayResult,
+    ) -> Result<PlayResult, ReplayError> {
+        if self.chart_hash != chart_hash {
+            return Err(ReplayError::ChartHashMismatch {
+                replay: self.chart_hash.clone(),
+                chart: chart_hash.to_owned(),
+            });
+        }
+        let simulated = self.simulate(chart, judge_config, gauge_config);
+        if simulated != *claimed {
+            return Err(ReplayError::ResultMismatch {
+                claimed: Box::new(claimed.clone()),
+                simulated: Box::new(simulated),
+            });
+        }
+        Ok(simulated)
+    }
+
+    /// Encodes the replay into the binary format.
+    #[must_use]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(VERSION);
+        write_varint(&mut bytes, self.chart_hash.len() as u64);
+        bytes.extend_from_slice(self.chart_hash.as_bytes());
+
+        bytes.push(self.random_seed.map_or(0, |_| RANDOM_SEED_FLAG));
+        if let Some(seed) = self.random_seed {
+            bytes.extend_from_slice(&seed.to_le_bytes());
+        }
+        let lane_options: BTreeMap<_, _> = self
+            .lane_options
+            .iter()
+            .map(|(&side, &(option, seed))| (side_to_byte(side), (option, seed)))
+            .collect();
+        write_varint(&mut bytes, lane_options.len() as u64);
+        for (side, (option, seed)) in lane_options {
+            bytes.push(side);
+            bytes.push(lane_option_to_byte(option));
+            bytes.extend_from_slice(&seed.to_le_bytes());
+        }
+        bytes.push(gauge_type_to_byte(self.gauge_type));
+        bytes.push(gauge_rules_to_byte(self.gauge_rules));
+
+        let mut lanes: BTreeMap<[u8; 3], Vec<&KeyInput>> = BTreeMap::new();
+        for input in &self.inputs {
+            lanes
+                .entry(lane_to_bytes(input.side, input.key))
+                .or_default()
+                .push(input);
+        }
+        write_varint(&mut bytes, lanes.len() as u64);
+        for (lane, mut inputs) in lanes {
+            bytes.extend_from_slice(&lane);
+            write_varint(&mut bytes, inputs.len() as u64);
+            inputs.sort_by_key(|input| input.time);
+            let mut prev = None;
+            for input in inputs {
+                let nanos = input.time.as_nanos();
+                let time = prev.map_or_else(
+                    || zigzag(nanos),
+                    |prev: i64| nanos.wrapping_sub(prev) as u64,
+                );
+                write_varint(&mut bytes, time);
+                bytes.push(u8::from(input.action == KeyAction::Release));
+                prev = Some(nanos);
+            }
+        }
+        bytes
+    }
+
+    /// Decodes a replay from the binary format. The inputs are sorted by time, and the inputs at the
+    /// same time are in the order of their lanes.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ReplayError`] if `bytes` is not a valid replay.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ReplayError> {
+        let mut reader = Reader(bytes);
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(ReplayError::InvalidMagic);
+        }
+        let version = reader.byte()?;
+        if version != VERSION {
+            return Err(ReplayError::UnsupportedVersion(version));
+        }
+        let hash_len = reader.len()?;
+        let chart_hash = std::str::from_utf8(reader.take(hash_len)?)
+            .map_err(|_| ReplayError::InvalidField("chart hash"))?
+            .to_owned();
+
+        let flags = reader.byte()?;
+        if flags & !RANDOM_SEED_FLAG != 0 {
+            return Err(ReplayError::InvalidField("seed flags"));
+        }
+        let random_seed = if flags & RANDOM_SEED_FLAG == 0 {
+            None
+        } else {
+            Some(reader.i64()?)
+        };
+        let mut lane_options = HashMap::new();
+        for _ in 0..reader.len()? {
+            let side = side_from_byte(reader.byte()?)?;
+            let option = lane_option_from_byte(reader.byte()?)?;
+            if lane_options.insert(side, (option, reader.i64()?)).is_some() {
+                return Err(ReplayError::InvalidField("lane options"));
+            }
+        }
+        let gauge_type = gauge_type_from_byte(reader.byte()?)?;
+        let gauge_rules = gauge_rules_from_byte(reader.byte()?)?;
+
+        let mut inputs = vec![];
+        for _ in 0..reader.len()? {
+            let (side, key) = lane_from_bytes([reader.byte()?, reader.byte()?, reader.byte()?])?;
+            let mut prev = None;
+            for _ in 0..reader.len()? {
+                let time = reader.varint()?;
+                let nanos = prev.map_or_else(
+                    || unzigzag(time),
+                    |prev: i64| prev.wrapping_add(time as i64),
+                );
+                let action = match reader.byte()? {
+                    0 => KeyAction::Press,
+                    1 => KeyAction::Release,
+                    _ => return Err(ReplayError::InvalidField("key action")),
+                };
+                inputs.push(KeyInput {
+                    time: TimeSpan::new(nanos),
+                    side,
+                    key,
+                    action,
+                });
+                prev = Some(nanos);
+            }
+        }
+        if !reader.0.is_empty() {
+            return Err(ReplayError::TrailingBytes(reader.0.len()));
+        }
+        inputs.sort_by_key(|input| input.time);
+        Ok(Self {
+            chart_hash,
+            random_seed,
+            lane_options,
+            gauge_type,
+            gauge_rules,
+            inputs,
+        })
+    }
+}
+
+/// Reads the binary format from the front.
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    const fn take(&mut self, len: usize) -> Result<&'a [u8], ReplayError> {
+        if self.0.len() < len {
+            return Err(ReplayError::UnexpectedEof);
+        }
+        let (taken, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(taken)
+    }
+
+    fn byte(&mut self) -> Result<u8, ReplayError> {
+        let (&byte, rest) = self.0.split_first().ok_or(ReplayError::UnexpectedEof)?;
+        self.0 = rest;
+        Ok(byte)
+    }
+
+    fn i64(&mut self) -> Result<i64, ReplayError> {
+        let bytes = self
+            .take(8)?
+            .try_into()
+            .map_err(|_| ReplayError::UnexpectedEof)?;
+        Ok(i64::from_le_bytes(bytes))
+    }
+
+    fn varint(&mut self) -> Result<u64, ReplayError> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(ReplayError::InvalidField("varint"))
+    }
+
+    /// Reads a length, which must not exceed the rest of the data.
+    fn len(&mut self) -> Result<usize, ReplayError> {
+        let len = self.varint()?;
+        usize::try_from(len)
+            .ok()
+            .filter(|&len| len <= self.0.len())
+            .ok_or(ReplayError::UnexpectedEof)
+    }
+}
+
+fn write_varint(bytes: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        bytes.push((value & 0x7f) as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+const fn zigzag(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+const fn unzigzag(value: u64) -> i64 {
+    (value >> 1) as i64 ^ -((value & 1) as i64)
+}
+
+const fn gauge_type_to_byte(gauge_type: GaugeType) -> u8 {
+    match gauge_type {
+        GaugeType::Easy => 0,
+        GaugeType::Groove => 1,
+        GaugeType::Hard => 2,
+        GaugeType::ExHard => 3,
+        GaugeType::Hazard => 4,
+    }
+}
+
+const fn gauge_type_from_byte(byte: u8) -> Result<GaugeType, ReplayError> {
+    Ok(match byte {
+        0 => GaugeType::Easy,
+        1 => GaugeType::Groove,
+        2 => GaugeType::Hard,
+        3 => GaugeType::ExHard,
+        4 => GaugeType::Hazard,
+        _ => return Err(ReplayError::InvalidField("gauge type")),
+    })
+}
+
+const fn gauge_rules_to_byte(rules: GaugeRules) -> u8 {
+    match rules {
+        GaugeRules::Beatoraja => 0,
+        GaugeRules::Lr2 => 1,
+    }
+}
+
+const fn gauge_rules_from_byte(byte: u8) -> Result<GaugeRules, ReplayError> {
+    Ok(match byte {
+        0 => GaugeRules::Beatoraja,
+        1 => GaugeRules::Lr2,
+        _ => return Err(ReplayError::InvalidField("gauge rules")),
+    })
+}
+
+const fn lane_option_to_byte(option: LaneOption) -> u8 {
+    match option {
+        LaneOption::Mirror => 0,
+        LaneOption::Random => 1,
+        LaneOption::RRandom => 2,
+        LaneOption::SRandom => 3,
+        LaneOption::HRandom => 4,
+    }
+}
+
+const fn lane_option_from_byte(byte: u8) -> Result<LaneOption, ReplayError> {
+    Ok(match byte {
+        0 => LaneOption::Mirror,
+        1 => LaneOption::Random,
+        2 => LaneOption::RRandom,
+        3 => LaneOption::SRandom,
+        4 => LaneOption::HRandom,
+        _ => return Err(ReplayError::InvalidField("lane option")),
+    })
+}
+
+const fn side_to_byte(side: PlayerSide) -> u8 {
+    match side {
+        PlayerSide::Player1 => 0,
+        PlayerSide::Player2 => 1,
+    }
+}
+
+const fn side_from_byte(byte: u8) -> Result<PlayerSide, ReplayError> {
+    Ok(match byte {
+        0 => PlayerSide::Player1,
+        1 => PlayerSide::Player2,
+        _ => return Err(ReplayError::InvalidField("player side")),
+    })
+}
+
+const fn lane_to_bytes(side: PlayerSide, key: Key) -> [u8; 3] {
+    let side = side_to_byte(side);
+    let (kind, number) = match key {
+        Key::Key(number) => (0, number),
+        Key::Scratch(number) => (1, number),
+        Key::FootPedal => (2, 0),
+        Key::FreeZone => (3, 0),
+    };
+    [side, kind, number]
+}
+
+fn lane_from_bytes([side, kind, number]: [u8; 3]) -> Result<(PlayerSide, Key), ReplayError> {
+    let side = side_from_byte(side)?;
+    let key = match kind {
+        0 => Key::Key(number),
+        1 => Key::Scratch(number),
+        2 => Key::FootPedal,
+        3 => Key::FreeZone,
+        _ => return Err(ReplayError::InvalidField("key")),
+    };
+    Ok((side, key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{unzigzag, zigzag};
+
+    #[test]
+    fn zigzag_round_trip() {
+        for value in [0, 1, -1, 1_000_000_007, -1_000_000_007, i64::MIN, i64::MAX] {
+            assert_eq!(unzigzag(zigzag(value)), value);
+        }
+    }
+}
@@ -0,0 +1,333 @@
+//! Scores, ranks and clear lamps of a play.
+//!
+//! A [`Score`] counts the [`Judgment`]s of a play. The EX-score is 2 points for each PGREAT and 1
+//! point for each GREAT, and the miss count is the number of BADs, POORs and empty POORs. An empty
+//! POOR does not break the combo.
+
+use super::gauge::{Gauge, GaugeType};
+use super::judge::{JudgeEvent, Judgment};
+
+/// Numbers of each judgment.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct JudgmentCounts {
+    /// Number of PGREATs.
+    pub pgreat: usize,
+    /// Number of GREATs.
+    pub great: usize,
+    /// Number of GOODs.
+    pub good: usize,
+    /// Number of BADs.
+    pub bad: usize,
+    /// Number of POORs.
+    pub poor: usize,
+    /// Number of empty POORs.
+    pub empty_poor: usize,
+}
+
+impl JudgmentCounts {
+    /// Returns the number of the judgment.
+    #[must_use]
+    pub const fn get(&self, judgment: Judgment) -> usize {
+        match judgment {
+            Judgment::PGreat => self.pgreat,
+            Judgment::Great => self.great,
+            Judgment::Good => self.good,
+            Judgment::Bad => self.bad,
+            Judgment::Poor => self.poor,
+            Judgment::EmptyPoor => self.empty_poor,
+        }
+    }
+
+    /// Counts up the judgment.
+    pub const fn add(&mut self, judgment: Judgment) {
+        let count = match judgment {
+            Judgment::PGreat => &mut self.pgreat,
+            Judgment::Great => &mut self.great,
+            Judgment::Good => &mut self.good,
+            Judgment::Bad => &mut self.bad,
+            Judgment::Poor => &mut self.poor,
+            Judgment::EmptyPoor => &mut self.empty_poor,
+        };
+        *count += 1;
+    }
+
+    /// Returns the number of the judged notes, which excludes empty POORs.
+    #[must_use]
+    pub const fn notes(&self) -> usize {
+        self.pgreat + self.great + self.good + self.bad + self.poor
+    }
+
+    /// Returns the EX-score.
+    #[must_use]
+    pub const fn ex_score(&self) -> usize {
+        self.pgreat * 2 + self.great
+    }
+
+    /// Returns the number of BADs, POORs and empty POORs.
+    #[must_use]
+    pub const fn miss_count(&self) -> usize {
+        self.bad + self.poor + self.empty_poor
+    }
+}
+
+/// A rank by the rate of the EX-score to the max EX-score, in ninths.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Rank {
+    /// Less than 2/9.
+    F,
+    /// 2/9 or more.
+    E,
+    /// 3/9 or more.
+    D,
+    /// 4/9 or more.
+    C,
+    /// 5/9 or more.
+    B,
+    /// 6/9 or more.
+    A,
+    /// 7/9 or more.
+    Aa,
+    /// 8/9 or more.
+    Aaa,
+}
+
+impl Rank {
+    /// Returns the rank of the EX-score. It is [`Self::F`] if `max_ex_score` is 0.
+    #[must_use]
+    pub const fn from_ex_score(ex_score: usize, max_ex_score: usize) -> Self {
+        if max_ex_score == 0 {
+            return Self::F;
+        }
+        // The largest `n` such that `ex_score / max_ex_score >= n / 9`.
+        match ex_score * 9 / max_ex_score {
+            0 | 1 => Self::F,
+            2 => Self::E,
+            3 => Self::D,
+            4 => Self::C,
+            5 => Self::B,
+            6 => Self::A,
+            7 => Self::Aa,
+            _ => Self::Aaa,
+        }
+    }
+}
+
+/// A clear lamp of a play, ordered from the worst.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ClearLamp {
+    /// The chart is not played.
+    NoPlay,
+    /// The gauge is not cleared, or the play is quit before all the notes.
+    Failed,
+    /// Cleared with [`GaugeType::Easy`].
+    Easy,
+    /// Cleared with [`GaugeType::Groove`].
+    Normal,
+    /// Cleared with [`GaugeType::Hard`].
+    Hard,
+    /// Cleared with [`GaugeType::ExHard`] or [`GaugeType::Hazard`].
+    ExHard,
+    /// All the notes are judged without BADs and POORs.
+    FullCombo,
+    /// All the notes are judged PGREAT or GREAT.
+    Perfect,
+    /// All the notes are judged PGREAT.
+    Max,
+}
+
+impl ClearLamp {
+    /// Returns the lamp of the play which ends with the score and the gauge.
+    #[must_use]
+    pub fn new(score: &Score, gauge: &Gauge) -> Self {
+        let counts = score.counts();
+        if gauge.is_failed() || counts.notes() < score.total_notes() {
+            return Self::Failed;
+        }
+        if counts.bad + counts.poor == 0 {
+            return if counts.great + counts.good == 0 {
+                Self::Max
+            } else if counts.good == 0 {
+                Self::Perfect
+            } else {
+                Self::FullCombo
+            };
+        }
+        if !gauge.is_cleared() {
+            return Self::Failed;
+        }
+        match gauge.gauge_type() {
+            GaugeType::Easy => Self::Easy,
+            GaugeType::Groove => Self::Normal,
+            GaugeType::Hard => Self::Hard,
+            GaugeType::ExHard | GaugeType::Hazard => Self::ExHard,
+        }
+    }
+}
+
+/// A score of a play in progress.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Score {
+    counts: JudgmentCounts,
+    combo: usize,
+    max_combo: usize,
+    total_notes: usize,
+}
+
+impl Score {
+    /// Creates a score of a chart with `total_notes` judgments, such as
+    /// [`Chart::judgment_count`](super::Chart::judgment_count).
+    #[must_use]
+    pub const fn new(total_notes: usize) -> Self {
+        Self {
+            counts: JudgmentCounts {
+                pgreat: 0,
+                great: 0,
+                good: 0,
+                bad: 0,
+                poor: 0,
+                empty_poor: 0,
+            },
+            combo: 0,
+            max_combo: 0,
+            total_notes,
+        }
+    }
+
+    /// Counts a judgment.
+    pub const fn apply(&mut self, judgment: Judgment) {
+        self.counts.add(judgment);
+        if judgment.is_combo() {
+            self.combo += 1;
+            if self.max_combo < self.combo {
+                self.max_combo = self.combo;
+            }
+        } else if !matches!(judgment, Judgment::EmptyPoor) {
+            self.combo = 0;
+        }
+    }
+
+    /// Counts the judgment in an event of a [`Judge`](super::judge::Judge), if any.
+    pub const fn apply_event(&mut self, event: &JudgeEvent) {
+        if let Some(judgment) = event.judgment() {
+            self.apply(judgment);
+        }
+    }
+
+    /// Returns the numbers of each judgment.
+    #[must_use]
+    pub const fn counts(&self) -> &JudgmentCounts {
+        &self.counts
+    }
+
+    /// Returns the current combo.
+    #[must_use]
+    pub const fn combo(&self) -> usize {
+        self.combo
+    }
+
+    /// Returns the max combo.
+    #[must_use]
+    pub const fn max_combo(&self) -> usize {
+        self.max_combo
+    }
+
+    /// Returns the number of judgments of notes in a full play.
+    #[must_use]
+    pub const fn total_notes(&self) -> usize {
+        self.total_notes
+    }
+
+    /// Returns the EX-score.
+    #[must_use]
+    pub const fn ex_score(&self) -> usize {
+        self.counts.ex_score()
+    }
+
+    /// Returns the EX-score when all the notes are judged PGREAT.
+    #[must_use]
+    pub const fn max_ex_score(&self) -> usize {
+        self.total_notes * 2
+    }
+
+    /// Returns the number of BADs, POORs and empty POORs.
+    #[must_use]
+    pub const fn miss_count(&self) -> usize {
+        self.counts.miss_count()
+    }
+
+    /// Returns the rank of the EX-score.
+    #[must_use]
+    pub const fn rank(&self) -> Rank {
+        Rank::from_ex_score(self.ex_score(), self.max_ex_score())
+    }
+}
+
+/// A summary of a finished play.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PlayResult {
+    /// Numbers of each judgment.
+    pub counts: JudgmentCounts,
+    /// The EX-score.
+    pub ex_score: usize,
+    /// The max combo.
+    pub max_combo: usize,
+    /// Number of BADs, POORs and empty POORs.
+    pub miss_count: usize,
+    /// The rank of the EX-score.
+    pub rank: Rank,
+    /// The clear lamp.
+    pub lamp: ClearLamp,
+    /// The type of the gauge.
+    pub gauge_type: GaugeType,
+    /// The value of the gauge at the end.
+    pub gauge: f64,
+}
+
+impl PlayResult {
+    /// Summarizes the play which ends with the score and the gauge.
+    #[must_use]
+    pub fn new(score: &Score, gauge: &Gauge) -> Self {
+        Self {
+            counts: *score.counts(),
+            ex_score: score.ex_score(),
+            max_combo: score.max_combo(),
+            miss_count: score.miss_count(),
+            rank: score.rank(),
+            lamp: ClearLamp::new(score, gauge),
+            gauge_type: gauge.gauge_type(),
+            gauge: gauge.value(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Rank, Score};
+    use crate::chart::judge::Judgment;
+
+    #[test]
+    fn rank_boundaries() {
+        assert_eq!(Rank::from_ex_score(16, 18), Rank::Aaa);
+        assert_eq!(Rank::from_ex_score(15, 18), Rank::Aa);
+        assert_eq!(Rank::from_ex_score(3, 18), Rank::F);
+        assert_eq!(Rank::from_ex_score(0, 0), Rank::F);
+    }
+
+    #[test]
+    fn empty_poor_keeps_combo() {
+        let mut score = Score::new(3);
+        score.apply(Judgment::PGreat);
+        score.apply(Judgment::EmptyPoor);
+        score.apply(Judgment::Great);
+        assert_eq!(score.combo(), 2);
+        score.apply(Judgment::Bad);
+        assert_eq!(score.combo(), 0);
+        assert_eq!(score.max_combo(), 2);
+        assert_eq!(score.ex_score(), 3);
+        assert_eq!(score.miss_count(), 2);
+    }
+}
@@ -0,0 +1,260 @@
+//! Statistics of a chart, such as note counts, density, length and BPM range.
+//!
+//! [`ChartStats`] is computed from the precomputed events of a [`Chart`], so it is the same for a BMS
+//! and a BMSON score which produce the same chart.
+
+use gametime::TimeSpan;
+use strict_num_extended::PositiveF64;
+
+use super::Chart;
+use super::event::{ChartEvent, YCoordinate};
+use super::player::base_bpm::{BaseBpmGenerator, MainBpmByDurationGenerator};
+use super::timing::EventTimes;
+use super::types::{Key, NoteKind, PlayerSide};
+
+/// Default width of the sliding window for the note density.
+pub const DEFAULT_DENSITY_WINDOW: TimeSpan = TimeSpan::SECOND;
+
+/// Number of notes of each [`NoteKind`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NoteKindCounts {
+    /// Number of [`NoteKind::Visible`] notes.
+    pub visible: usize,
+    /// Number of [`NoteKind::Invisible`] notes.
+    pub invisible: usize,
+    /// Number of [`NoteKind::Long`] notes.
+    pub long: usize,
+    /// Number of [`NoteKind::Landmine`] notes.
+    pub landmine: usize,
+}
+
+impl NoteKindCounts {
+    /// Returns the number of notes of `kind`.
+    #[must_use]
+    pub const fn get(&self, kind: NoteKind) -> usize {
+        match kind {
+            NoteKind::Visible => self.visible,
+            NoteKind::Invisible => self.invisible,
+            NoteKind::Long => self.long,
+            NoteKind::Landmine => self.landmine,
+        }
+    }
+
+    /// Returns the number of playable notes, which are visible and long notes.
+    #[must_use]
+    pub const fn playable(&self) -> usize {
+        self.visible + self.long
+    }
+
+    const fn get_mut(&mut self, kind: NoteKind) -> &mut usize {
+        match kind {
+            NoteKind::Visible => &mut self.visible,
+            NoteKind::Invisible => &mut self.invisible,
+            NoteKind::Long => &mut self.long,
+            NoteKind::Landmine => &mut self.landmine,
+        }
+    }
+}
+
+/// Number of playable notes on a lane.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LaneNoteCount {
+    /// The side of the lane.
+    pub side: PlayerSide,
+    /// The key of the lane.
+    pub key: Key,
+    /// Number of playable notes on the lane.
+    pub count: usize,
+}
+
+/// Statistics of a chart.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ChartStats {
+    /// Number of notes of each kind.
+    pub notes: NoteKindCounts,
+    /// Number of playable notes on each lane which has any, in the order of the first note on it.
+    pub lanes: Vec<LaneNoteCount>,
+    /// Ratio of the playable notes on scratch lanes to all playable notes, or 0 without them.
+    pub scratch_ratio: f64,
+    /// Seconds from the first playable note to the last release of them, including the ends of long
+    /// notes.
+    pub playable_secs: f64,
+    /// The lowest BPM in the chart.
+    pub min_bpm: PositiveF64,
+    /// The highest BPM in the chart.
+    pub max_bpm: PositiveF64,
+    /// The BPM held for the longest time, or the initial BPM if the chart has no length.
+    pub main_bpm: PositiveF64,
+    /// Width of the sliding window for [`Self::peak_density`] in seconds.
+    pub density_window_secs: f64,
+    /// The most playable notes per second in a sliding window.
+    pub peak_density: f64,
+    /// Playable notes per second over [`Self::playable_secs`], or 0 if it is 0.
+    pub average_density: f64,
+    /// Number of playable notes in each measure between the bar lines.
+    pub density_per_measure: Vec<usize>,
+}
+
+impl Chart {
+    /// Computes the statistics of the chart with the [`DEFAULT_DENSITY_WINDOW`].
+    #[must_use]
+    pub fn stats(&self) -> ChartStats {
+        self.stats_with_window(DEFAULT_DENSITY_WINDOW)
+    }
+
+    /// Computes the statistics of the chart, finding the peak density in a sliding window of
+    /// `window`.
+    ///
+    /// A zero `window` is treated as the [`DEFAULT_DENSITY_WINDOW`].
+    #[must_use]
+    pub fn stats_with_window(&self, window: TimeSpan) -> ChartStats {
+        let window = if window > TimeSpan::ZERO {
+            window
+        } else {
+            DEFAULT_DENSITY_WINDOW
+        };
+        let times = EventTimes::new(self);
+
+        let mut notes = NoteKindCounts::default();
+        let mut lanes: Vec<LaneNoteCount> = vec![];
+        let mut hit_secs = vec![];
+        let mut first_secs = f64::INFINITY;
+        let mut last_secs = 0.0f64;
+        let mut min_bpm = self.init_bpm;
+        let mut max_bpm = self.init_bpm;
+        for event in self.events.as_events() {
+            let (&side, &key, &kind, length) = match &event.event {
+                ChartEvent::Note {
+                    side,
+                    key,
+                    kind,
+                    length,
+                    ..
+                } => (side, key, kind, length),
+                ChartEvent::BpmChange { bpm } => {
+                    min_bpm = min_bpm.min(*bpm);
+                    max_bpm = max_bpm.max(*bpm);
+                    continue;
+                }
+                _ => continue,
+            };
+            *notes.get_mut(kind) += 1;
+            if !kind.is_playable() {
+                continue;
+            }
+            if let Some(lane) = lanes
+                .iter_mut()
+                .find(|lane| lane.side == side && lane.key == key)
+            {
+                lane.count += 1;
+            } else {
+                lanes.push(LaneNoteCount {
+                    side,
+                    key,
+                    count: 1,
+                });
+            }
+            let secs = event.activate_time.as_secs_f64();
+            let end_secs = length.map_or(secs, |length| {
+                times.secs_at(event.position.as_f64() + length.as_f64())
+            });
+            hit_secs.push(secs);
+            first_secs = first_secs.min(secs);
+            last_secs = last_secs.max(end_secs);
+        }
+
+        let playable = notes.playable();
+        let scratches: usize = lanes
+            .iter()
+            .filter(|lane| matches!(lane.key, Key::Scratch(_)))
+            .map(|lane| lane.count)
+            .sum();
+        let playable_secs = (last_secs - first_secs).max(0.0);
+        let window_secs = window.as_secs_f64();
+        ChartStats {
+            notes,
+            lanes,
+            scratch_ratio: ratio(scratches as f64, playable as f64),
+            playable_secs,
+            min_bpm,
+            max_bpm,
+            main_bpm: MainBpmByDurationGenerator
+                .generate(self)
+                .map_or(self.init_bpm, |bpm| *bpm.value()),
+            density_window_secs: window_secs,
+            peak_density: peak_count(hit_secs, window_secs) as f64 / window_secs,
+            average_density: ratio(playable as f64, playable_secs),
+            density_per_measure: self.density_per_measure(),
+        }
+    }
+
+    /// Counts the playable notes between each pair of the bar lines, from Y 0 to the last note.
+    fn density_per_measure(&self) -> Vec<usize> {
+        let mut bar_lines: Vec<YCoordinate> = self
+            .events
+            .as_events()
+            .iter()
+            .filter(|event| matches!(event.event, ChartEvent::BarLine))
+            .map(|event| event.position)
+            .filter(|&y| y > YCoordinate::ZERO)
+            .collect();
+        bar_lines.sort_unstable();
+        bar_lines.dedup();
+
+        let mut counts = vec![];
+        for event in self.events.as_events() {
+            if !matches!(&event.event, ChartEvent::Note { kind, .. } if kind.is_playable()) {
+                continue;
+            }
+            let measure = bar_lines.partition_point(|&bar_line| bar_line <= event.position);
+            if counts.len() <= measure {
+                counts.resize(measure + 1, 0);
+            }
+            if let Some(count) = counts.get_mut(measure) {
+                *count += 1;
+            }
+        }
+        counts
+    }
+}
+
+/// Returns the most hits in a window of `window_secs` starting at any hit.
+fn peak_count(mut hit_secs: Vec<f64>, window_secs: f64) -> usize {
+    hit_secs.sort_by(f64::total_cmp);
+    let mut peak = 0;
+    let mut end = 0;
+    for (start, &secs) in hit_secs.iter().enumerate() {
+        end = end.max(start);
+        while hit_secs
+            .get(end)
+            .is_some_and(|&hit| hit < secs + window_secs)
+        {
+            end += 1;
+        }
+        peak = peak.max(end - start);
+    }
+    peak
+}
+
+fn ratio(numerator: f64, denominator: f64) -> f64 {
+    if denominator > 0.0 {
+        numerator / denominator
+    } else {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::peak_count;
+
+    #[test]
+    fn peak_count_uses_half_open_windows() {
+        assert_eq!(peak_count(vec![], 1.0), 0);
+        assert_eq!(peak_count(vec![0.0, 1.0, 2.0], 1.0), 1);
+        assert_eq!(peak_count(vec![2.5, 0.0, 0.5, 2.0, 2.9, 0.9], 1.0), 3);
+    }
+}
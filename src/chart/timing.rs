@@ -0,0 +1,330 @@
+//! Conversion between the positions and the times of a score.
+//!
+//! [`TimingMap`] converts in both directions between:
+//!
+//! - [`ObjTime`], the track and the fraction in it,
+//! - [`YCoordinate`], the number of measures from the start weighted by the section lengths,
+//! - beats, which are 4 per measure of the default length,
+//! - [`TimeSpan`], the time from the start of the playback.
+//!
+//! The time is taken from the BPM changes and stops of the flow events of a processed [`Chart`], so it
+//! agrees with [`PlayheadEvent::activate_time`]. A position at a stop is reached at the end of the stop,
+//! and a time in a stop is at the position of the stop.
+
+use std::num::NonZeroU64;
+
+use gametime::TimeSpan;
+use strict_num_extended::{NonNegativeF64, PositiveF64};
+
+use super::Chart;
+use super::event::{ChartEvent, FlowEvent, PlayheadEvent, YCoordinate};
+use super::player::secs_to_time_span;
+use super::process::Process;
+use super::{DEFAULT_BPM, MAX_NON_NEGATIVE_F64};
+use crate::bms::command::time::{ObjTime, Track};
+use crate::bms::model::Bms;
+use crate::bms::parse::check_playing::PlayingError;
+
+/// A position where the BPM changes or the scroll stops.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Anchor {
+    y: f64,
+    /// Seconds when the position is reached.
+    start: f64,
+    /// Seconds when the stops at the position end.
+    end: f64,
+    /// BPM from the position.
+    bpm: PositiveF64,
+}
+
+/// Map between the positions and the times of a score.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimingMap {
+    /// Start Y of the tracks up to the last track whose length is changed, with their lengths.
+    sections: Vec<(f64, f64)>,
+    /// Anchors sorted by Y, the first of which is at Y 0.
+    anchors: Vec<Anchor>,
+}
+
+impl TimingMap {
+    /// Creates a map from the flow events of a processed chart, where every track is a measure long.
+    ///
+    /// The BPM changes and stops are the ones which the activate times of the chart account for.
+    #[must_use]
+    pub fn from_chart(chart: &Chart) -> Self {
+        Self {
+            sections: vec![],
+            anchors: anchors(chart),
+        }
+    }
+
+    /// Creates a map from the section lengths and the flow events of a BMS score.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`PlayingError`] if the score cannot be processed into a chart.
+    pub fn from_bms(bms: &Bms) -> Result<Self, PlayingError> {
+        let chart = bms.process()?;
+        let last_track = bms
+            .section_len
+            .section_len_changes
+            .keys()
+            .next_back()
+            .map_or(0, |track| track.0 + 1);
+        let lengths = (0..last_track).map(|track| {
+            bms.section_len
+                .section_len_changes
+                .get(&Track(track))
+                .map_or(1.0, |change| change.length.as_f64().max(0.0))
+        });
+        Ok(Self {
+            sections: sections(lengths),
+            anchors: anchors(&chart),
+        })
+    }
+
+    /// Converts a time of an object into a Y coordinate.
+    #[must_use]
+    pub fn obj_time_to_y(&self, time: ObjTime) -> YCoordinate {
+        to_y(obj_time_to_y(&self.sections, time))
+    }
+
+    /// Converts a Y coordinate into the nearest time on the grid which divides each track by `denominator`.
+    #[must_use]
+    pub fn y_to_obj_time(&self, y: YCoordinate, denominator: NonZeroU64) -> ObjTime {
+        let y = y.as_f64();
+        let (track, start, length) = match self.sections.last() {
+            Some(&(last_start, last_length)) if y < last_start + last_length => {
+                let index = self
+                    .sections
+                    .partition_point(|&(start, _)| start <= y)
+                    .saturating_sub(1);
+                let (start, length) = self.sections.get(index).copied().unwrap_or((0.0, 1.0));
+                (index as u64, start, length)
+            }
+            last => {
+                let (tracks, end) = last.map_or((0, 0.0), |&(start, length)| {
+                    (self.sections.len() as u64, start + length)
+                });
+                let passed = (y - end).floor().max(0.0);
+                (tracks + passed as u64, end + passed, 1.0)
+            }
+        };
+        let fraction = if length > 0.0 {
+            (y - start) / length
+        } else {
+            0.0
+        };
+        let numerator = (fraction * denominator.get() as f64).round().max(0.0) as u64;
+        if numerator >= denominator.get() {
+            ObjTime::start_of(Track(track + 1))
+        } else {
+            ObjTime::new_checked(track, numerator, denominator)
+        }
+    }
+
+    /// Converts a Y coordinate into beats.
+    #[must_use]
+    pub const fn y_to_beats(&self, y: YCoordinate) -> f64 {
+        y.as_f64() * 4.0
+    }
+
+    /// Converts beats into a Y coordinate.
+    #[must_use]
+    pub fn beats_to_y(&self, beats: f64) -> YCoordinate {
+        to_y(beats / 4.0)
+    }
+
+    /// Converts a Y coordinate into the time when it is reached.
+    #[must_use]
+    pub fn y_to_time(&self, y: YCoordinate) -> TimeSpan {
+        let y = y.as_f64();
+        let secs = self.anchor_at_y(y).map_or(0.0, |anchor| {
+            anchor.end + (y - anchor.y).max(0.0) * 240.0 / anchor.bpm.as_f64()
+        });
+        secs_to_time_span(secs)
+    }
+
+    /// Converts a time into the Y coordinate reached then.
+    #[must_use]
+    pub fn time_to_y(&self, time: TimeSpan) -> YCoordinate {
+        let secs = time.as_secs_f64();
+        let y = self.anchor_at_time(secs).map_or(0.0, |anchor| {
+            if secs <= anchor.end {
+                anchor.y
+            } else {
+                anchor.y + (secs - anchor.end) * anchor.bpm.as_f64() / 240.0
+            }
+        });
+        to_y(y)
+    }
+
+    /// Converts a time of an object into the time when it is reached.
+    #[must_use]
+    pub fn obj_time_to_time(&self, time: ObjTime) -> TimeSpan {
+        self.y_to_time(self.obj_time_to_y(time))
+    }
+
+    /// Converts a time into the nearest time of an object on the grid which divides each track by `denominator`.
+    #[must_use]
+    pub fn time_to_obj_time(&self, time: TimeSpan, denominator: NonZeroU64) -> ObjTime {
+        self.y_to_obj_time(self.time_to_y(time), denominator)
+    }
+
+    /// Returns the BPM active at the Y coordinate.
+    #[must_use]
+    pub fn bpm_at_y(&self, y: YCoordinate) -> PositiveF64 {
+        self.anchor_at_y(y.as_f64())
+            .map_or(DEFAULT_BPM, |anchor| anchor.bpm)
+    }
+
+    /// Returns the BPM active at the time. In a stop, it is the BPM at the position of the stop.
+    #[must_use]
+    pub fn bpm_at_time(&self, time: TimeSpan) -> PositiveF64 {
+        self.anchor_at_time(time.as_secs_f64())
+            .map_or(DEFAULT_BPM, |anchor| anchor.bpm)
+    }
+
+    fn anchor_at_y(&self, y: f64) -> Option<&Anchor> {
+        let index = self.anchors.partition_point(|anchor| anchor.y <= y);
+        self.anchors.get(index.saturating_sub(1))
+    }
+
+    fn anchor_at_time(&self, secs: f64) -> Option<&Anchor> {
+        let index = self.anchors.partition_point(|anchor| anchor.start <= secs);
+        self.anchors.get(index.saturating_sub(1))
+    }
+}
+
+/// Activate times of the event positions of a [`Chart`] with the BPM from each of them.
+///
+/// This finds the time of a position between the events, such as the end of a long note, in the
+/// same way as the activate times of the chart.
+pub(crate) struct EventTimes(Vec<(f64, f64, PositiveF64)>);
+
+impl EventTimes {
+    pub(crate) fn new(chart: &Chart) -> Self {
+        let mut bpm = chart.init_bpm;
+        let mut positions = vec![(0.0, 0.0, bpm)];
+        for (y, range) in chart.events.as_by_y() {
+            let events = chart.events.as_events().get(range.clone()).unwrap_or(&[]);
+            for event in events {
+                if let ChartEvent::BpmChange { bpm: changed } = event.event {
+                    bpm = changed;
+                }
+            }
+            let secs = events
+                .first()
+                .map_or(0.0, |event| event.activate_time.as_secs_f64());
+            positions.push((y.as_f64(), secs, bpm));
+        }
+        Self(positions)
+    }
+
+    /// Returns the seconds when `y` is reached, scrolling from the last event position before it.
+    pub(crate) fn secs_at(&self, y: f64) -> f64 {
+        let index = self.0.partition_point(|&(at, _, _)| at <= y);
+        self.0
+            .get(index.saturating_sub(1))
+            .map_or(0.0, |&(at, secs, bpm)| {
+                secs + (y - at).max(0.0) * 240.0 / bpm.as_f64()
+            })
+    }
+
+    /// Returns the position reached at `secs`, which stays at the position of a stop during it.
+    pub(crate) fn y_at(&self, secs: f64) -> f64 {
+        let index = self.0.partition_point(|&(_, at_secs, _)| at_secs <= secs);
+        let next_y = self.0.get(index).map_or(f64::MAX, |&(at, _, _)| at);
+        self.0
+            .get(index.saturating_sub(1))
+            .map_or(0.0, |&(at, at_secs, bpm)| {
+                (at + (secs - at_secs).max(0.0) * bpm.as_f64() / 240.0).min(next_y)
+            })
+    }
+
+    /// Returns the time when a long note of `event` ends, or `None` if it is not a long note.
+    pub(crate) fn long_note_end(&self, event: &PlayheadEvent) -> Option<TimeSpan> {
+        let ChartEvent::Note {
+            length: Some(length),
+            ..
+        } = &event.event
+        else {
+            return None;
+        };
+        Some(secs_to_time_span(
+            self.secs_at(event.position.as_f64() + length.as_f64()),
+        ))
+    }
+}
+
+/// Returns the start Y and the length of each track.
+fn sections(lengths: impl IntoIterator<Item = f64>) -> Vec<(f64, f64)> {
+    let mut start = 0.0;
+    lengths
+        .into_iter()
+        .map(|length| {
+            let section = (start, length);
+            start += length;
+            section
+        })
+        .collect()
+}
+
+fn obj_time_to_y(sections: &[(f64, f64)], time: ObjTime) -> f64 {
+    let track = time.track().0;
+    let fraction = time.numerator() as f64 / time.denominator().get() as f64;
+    if let Some(&(start, length)) = sections.get(track as usize) {
+        return start + fraction * length;
+    }
+    let end = sections
+        .last()
+        .map_or(0.0, |&(start, length)| start + length);
+    end + (track - sections.len() as u64) as f64 + fraction
+}
+
+/// Returns the anchors at the BPM changes and stops among the flow events of `chart`.
+fn anchors(chart: &Chart) -> Vec<Anchor> {
+    let mut anchors = vec![Anchor {
+        y: 0.0,
+        start: 0.0,
+        end: 0.0,
+        bpm: chart.init_bpm,
+    }];
+    for (y, events) in chart.flow_events() {
+        let Some(&last) = anchors.last() else {
+            continue;
+        };
+        let mut bpm = None;
+        let mut stop_secs = 0.0;
+        for event in events {
+            match event {
+                FlowEvent::Bpm(changed) => bpm = Some(*changed),
+                FlowEvent::Stop(duration) => stop_secs += duration.as_secs_f64(),
+                FlowEvent::Speed(_) | FlowEvent::Scroll(_) => {}
+            }
+        }
+        if bpm.is_none() && stop_secs <= 0.0 {
+            continue;
+        }
+        let y = y.as_f64();
+        // Only the position 0 can be the same as the last anchor.
+        let (start, stops_from) = if y <= last.y {
+            anchors.pop();
+            (last.start, last.end)
+        } else {
+            let start = last.end + (y - last.y) * 240.0 / last.bpm.as_f64();
+            (start, start)
+        };
+        anchors.push(Anchor {
+            y,
+            start,
+            end: stops_from + stop_secs,
+            bpm: bpm.unwrap_or(last.bpm),
+        });
+    }
+    anchors
+}
+
+fn to_y(y: f64) -> YCoordinate {
+    YCoordinate::new(NonNegativeF64::new(y.max(0.0)).unwrap_or(MAX_NON_NEGATIVE_F64))
+}
@@ -0,0 +1,151 @@
+//! Voice allocation, which tells an audio engine how to play the sounds of the triggered events.
+//!
+//! Each triggered sound plays on a new voice, and stops the previous voice of the same WAV:
+//!
+//! - In BMS, triggering a WAV which is still playing cuts it off.
+//! - In BMSON, a sound channel is a sliced audio file. A note with `c = false` restarts the file,
+//!   and a note with `c = true` continues it from the time elapsed since the last restart on the
+//!   channel, which is [`ChartEvent::Note::continue_play`].
+//!
+//! A [`VoiceAllocator`] follows the events triggered by a
+//! [`ChartPlayer`](super::player::ChartPlayer), and [`Chart::voice_commands`] plans all the
+//! sounds of a chart ahead.
+
+use std::collections::HashMap;
+
+use gametime::TimeSpan;
+
+use super::Chart;
+use super::event::{ChartEvent, PlayheadEvent};
+use super::process::{ChartEventId, WavId};
+use super::types::NoteKind;
+
+/// Identifier of a voice, unique in a [`VoiceAllocator`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct VoiceId(pub u64);
+
+/// How to play the sound of a triggered event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct VoiceCommand {
+    /// The triggered event.
+    pub event_id: ChartEventId,
+    /// The sound to start.
+    pub wav_id: WavId,
+    /// The voice to start the sound on.
+    pub voice: VoiceId,
+    /// The voice to stop before starting the sound, which played the same sound. It may have
+    /// already ended.
+    pub stop: Option<VoiceId>,
+    /// Offset in the sound to start from.
+    pub offset: TimeSpan,
+}
+
+impl VoiceCommand {
+    /// Returns [`Self::offset`] in frames of a sound at `sample_rate`.
+    #[must_use]
+    pub fn sample_offset(&self, sample_rate: u32) -> u64 {
+        (self.offset.as_secs_f64().max(0.0) * f64::from(sample_rate)).round() as u64
+    }
+}
+
+/// Allocates the voices of the triggered sounds.
+#[derive(Debug, Clone, Default)]
+pub struct VoiceAllocator {
+    next_voice: u64,
+    /// The latest voice of each sound.
+    playing: HashMap<WavId, VoiceId>,
+}
+
+impl VoiceAllocator {
+    /// Creates an allocator with no voices.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allocates a voice for a triggered event. Returns `None` if the event has no sound.
+    ///
+    /// Landmines have no sound, as their id is the damage. Notes of the other kinds with a sound are
+    /// allocated, so the caller should only pass the notes it plays, such as the notes hit by the
+    /// player.
+    pub fn trigger(&mut self, event: &PlayheadEvent) -> Option<VoiceCommand> {
+        let (wav_id, offset) = match event.event() {
+            ChartEvent::Note {
+                kind,
+                wav_id: Some(wav_id),
+                continue_play,
+                ..
+            } if *kind != NoteKind::Landmine => (*wav_id, continue_play.unwrap_or(TimeSpan::ZERO)),
+            ChartEvent::Bgm {
+                wav_id: Some(wav_id),
+            } => (*wav_id, TimeSpan::ZERO),
+            _ => return None,
+        };
+        let voice = VoiceId(self.next_voice);
+        self.next_voice = self.next_voice.wrapping_add(1);
+        let stop = self.playing.insert(wav_id, voice);
+        Some(VoiceCommand {
+            event_id: event.id(),
+            wav_id,
+            voice,
+            stop,
+            offset,
+        })
+    }
+
+    /// Cancels a command from [`Self::trigger`] whose sound failed to start, so the voice which it
+    /// stops is the latest voice of the sound again.
+    pub fn cancel(&mut self, command: VoiceCommand) {
+        if self.voice_of(command.wav_id) != Some(command.voice) {
+            return;
+        }
+        match command.stop {
+            Some(previous) => {
+                self.playing.insert(command.wav_id, previous);
+            }
+            None => {
+                self.playing.remove(&command.wav_id);
+            }
+        }
+    }
+
+    /// Returns the latest voice of the sound.
+    #[must_use]
+    pub fn voice_of(&self, wav_id: WavId) -> Option<VoiceId> {
+        self.playing.get(&wav_id).copied()
+    }
+
+    /// Forgets the voices, such as after seeking, keeping the identifiers unique.
+    pub fn reset(&mut self) {
+        self.playing.clear();
+    }
+}
+
+impl Chart {
+    /// Allocates the voices for all the sounds of the chart, in the order of the trigger times.
+    ///
+    /// The sounds are of the BGM, and of the visible and long notes as in an autoplay.
+    #[must_use]
+    pub fn voice_commands(&self) -> Vec<VoiceCommand> {
+        let mut events: Vec<_> = self
+            .events
+            .as_events()
+            .iter()
+            .filter(|event| {
+                matches!(
+                    event.event(),
+                    ChartEvent::Note {
+                        kind: NoteKind::Visible | NoteKind::Long,
+                        ..
+                    } | ChartEvent::Bgm { .. }
+                )
+            })
+            .collect();
+        events.sort_by_key(|event| (event.activate_time, event.id));
+        let mut allocator = VoiceAllocator::new();
+        events
+            .into_iter()
+            .filter_map(|event| allocator.trigger(event))
+            .collect()
+    }
+}
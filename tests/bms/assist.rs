@@ -0,0 +1,151 @@
+use bms_rs::bms::prelude::*;
+use pretty_assertions::assert_eq;
+
+fn parse(source: &str) -> Bms {
+    let LexOutput {
+        tokens,
+        lex_warnings,
+    } = TokenStream::parse_lex(source);
+    assert_eq!(lex_warnings, vec![]);
+
+    let ParseOutput {
+        bms,
+        parse_warnings,
+    } = Bms::from_token_stream(&tokens, default_config().prompter(AlwaysUseNewer));
+    assert_eq!(parse_warnings, vec![]);
+    bms.expect("failed to parse BMS")
+}
+
+fn time(track: u64, numerator: u64, denominator: u64) -> ObjTime {
+    ObjTime::new(track, numerator, denominator).expect("denominator should be non-zero")
+}
+
+fn id(s: &str) -> ObjId {
+    ObjId::try_from(s, false).expect("id should be valid")
+}
+
+/// Kind and key of a note, `None` for BGM, with its time and id.
+type Note = (Option<(NoteKind, Key)>, ObjTime, ObjId);
+
+/// Notes and BGM sorted by time.
+fn notes(bms: &Bms) -> Vec<Note> {
+    bms.notes()
+        .all_notes()
+        .filter(|obj| !obj.wav_id.is_null())
+        .map(|obj| {
+            let lane = KeyLayoutBeat::from_channel_id(obj.channel_id)
+                .map(|layout| (layout.kind(), layout.key()));
+            (lane, obj.offset, obj.wav_id)
+        })
+        .collect()
+}
+
+const SOURCE: &str = "#00116:01
+#00112:0200
+#00151:0304
+#001D2:0005
+#00118:06
+#00119:0007
+#00115:0008
+";
+
+#[test]
+fn auto_scratch_keeps_keysounds_as_bgm() {
+    let mut bms = parse(SOURCE);
+
+    let report = bms.apply_assist::<KeyLayoutBeat>(Assist::AutoScratch);
+
+    assert_eq!(report.changed, 1);
+    assert!(report.is_assisted());
+    assert!(
+        notes(&bms).contains(&(None, time(1, 0, 1), id("01"))),
+        "scratch keysound should remain as BGM"
+    );
+    assert!(
+        notes(&bms)
+            .iter()
+            .all(|(lane, _, _)| !matches!(lane, Some((_, Key::Scratch(_))))),
+        "no note should remain on the scratch lane"
+    );
+}
+
+#[test]
+fn auto_scratch_counts_and_changes_only_scratch_notes() {
+    let mut bms = parse("#00116:01\n#00151:03000003\n#00111:0002\n");
+    let before: Vec<_> = notes(&bms)
+        .into_iter()
+        .filter(|(lane, _, _)| matches!(lane, Some((_, Key::Key(1)))))
+        .collect();
+
+    let report = bms.apply_assist::<KeyLayoutBeat>(Assist::AutoScratch);
+
+    assert_eq!(report.changed, 1);
+    let after: Vec<_> = notes(&bms)
+        .into_iter()
+        .filter(|(lane, _, _)| matches!(lane, Some((_, Key::Key(1)))))
+        .collect();
+    assert_eq!(after, before);
+}
+
+#[test]
+fn legacy_note_turns_long_notes_into_single_notes() {
+    let mut bms = parse(SOURCE);
+
+    let report = bms.apply_assist::<KeyLayoutBeat>(Assist::LegacyNote);
+
+    assert_eq!(report.changed, 1);
+    assert_eq!(bms.long_notes::<KeyLayoutBeat>().long_notes, vec![]);
+    let key1: Vec<_> = notes(&bms)
+        .into_iter()
+        .filter(|(lane, _, _)| matches!(lane, Some((_, Key::Key(1)))))
+        .collect();
+    assert_eq!(
+        key1,
+        vec![(
+            Some((NoteKind::Visible, Key::Key(1))),
+            time(1, 0, 1),
+            id("03")
+        )]
+    );
+}
+
+#[test]
+fn no_landmine_removes_landmines() {
+    let mut bms = parse(SOURCE);
+
+    let report = bms.apply_assist::<KeyLayoutBeat>(Assist::NoLandmine);
+
+    assert_eq!(report.changed, 1);
+    assert!(
+        notes(&bms)
+            .iter()
+            .all(|(lane, _, _)| !matches!(lane, Some((NoteKind::Landmine, _)))),
+    );
+    let again = bms.apply_assist::<KeyLayoutBeat>(Assist::NoLandmine);
+    assert!(!again.is_assisted());
+}
+
+#[test]
+fn five_keys_folds_and_reports_collisions() {
+    let mut bms = parse(SOURCE);
+
+    let report = bms.apply_assist::<KeyLayoutBeat>(Assist::FiveKeys(FoldStrategy::Pairwise));
+
+    // The key 6 folds onto the free key 4, and the key 7 collides with the note on the key 5.
+    assert_eq!(report.changed, 2);
+    let folded: Vec<_> = notes(&bms)
+        .into_iter()
+        .filter(|&(_, _, wav_id)| wav_id == id("06") || wav_id == id("07"))
+        .collect();
+    assert_eq!(
+        folded,
+        vec![
+            (
+                Some((NoteKind::Visible, Key::Key(4))),
+                time(1, 0, 1),
+                id("06")
+            ),
+            (None, time(1, 1, 2), id("07")),
+        ]
+    );
+}
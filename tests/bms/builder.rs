@@ -0,0 +1,193 @@
+use std::num::NonZeroU64;
+
+use bms_rs::bms::prelude::*;
+use pretty_assertions::assert_eq;
+use strict_num_extended::{FinF64, NonNegativeF64, PositiveF64};
+
+fn time(track: u64, numerator: u64, denominator: u64) -> ObjTime {
+    ObjTime::new(track, numerator, denominator).expect("denominator should be non-zero")
+}
+
+fn beat(beat: u64, numerator: u64, denominator: u64) -> MusicalTime {
+    MusicalTime::beat(beat, numerator, denominator).expect("denominator should be non-zero")
+}
+
+const fn non_zero(value: u64) -> NonZeroU64 {
+    NonZeroU64::new(value).expect("value should be non-zero")
+}
+
+#[test]
+fn builds_playable_and_valid_chart() {
+    let mut builder = BmsBuilder::<KeyLayoutBeat>::new();
+    builder
+        .title("Generated")
+        .artist("bms-rs")
+        .total(FinF64::new_const(200.0))
+        .bpm(PositiveF64::new_const(140.0))
+        .time_signature(Track(2), non_zero(3), non_zero(4))
+        .time_signature(Track(3), non_zero(4), non_zero(4));
+    let kick = builder.wav("kick.wav").expect("ids should be left");
+    let snare = builder.wav("snare.wav").expect("ids should be left");
+    assert_eq!(builder.wav("kick.wav"), Ok(kick));
+    let back = builder.bmp("back.png").expect("ids should be left");
+
+    builder
+        .note(
+            beat(0, 0, 1),
+            (PlayerSide::Player1, Key::Key(1), NoteKind::Visible),
+            kick,
+        )
+        .note(
+            beat(4, 1, 2),
+            (PlayerSide::Player1, Key::Scratch(1), NoteKind::Visible),
+            snare,
+        )
+        .note(
+            beat(7, 0, 1),
+            (PlayerSide::Player1, Key::Key(2), NoteKind::Visible),
+            snare,
+        )
+        .long_note(
+            time(3, 1, 4),
+            time(3, 3, 4),
+            (PlayerSide::Player1, Key::Key(3)),
+            kick,
+        )
+        .bgm(time(1, 0, 1), snare)
+        .bpm_change(beat(4, 0, 1), PositiveF64::new_const(180.0))
+        .stop(beat(4, 0, 1), NonNegativeF64::new_const(48.0))
+        .bga(time(1, 0, 1), BgaLayer::Base, back);
+
+    let bms = builder.build().expect("the chart should be valid");
+
+    let PlayingCheckOutput {
+        playing_warnings,
+        playing_errors,
+    } = bms.check_playing::<KeyLayoutBeat>();
+    assert_eq!(playing_warnings, vec![]);
+    assert_eq!(playing_errors, vec![]);
+    assert_eq!(bms.metadata.player, Some(PlayerMode::Single));
+
+    // Beat 4.5 falls on the 3/4 track 2, and beat 7 is at the start of track 3.
+    let notes: Vec<_> = bms
+        .notes()
+        .all_notes()
+        .map(|obj| (obj.offset, obj.wav_id))
+        .collect();
+    assert_eq!(
+        notes,
+        vec![
+            (time(1, 0, 1), kick),
+            (time(1, 0, 1), snare),
+            (time(2, 1, 6), snare),
+            (time(3, 0, 1), snare),
+            (time(3, 1, 4), kick),
+            (time(3, 3, 4), kick),
+        ]
+    );
+    assert_eq!(
+        bms.section_len
+            .section_len_changes
+            .values()
+            .map(|change| (change.track, change.length.as_f64()))
+            .collect::<Vec<_>>(),
+        vec![(Track(2), 0.75)]
+    );
+    assert_eq!(
+        bms.bpm.bpm_changes.keys().copied().collect::<Vec<_>>(),
+        vec![time(2, 0, 1)]
+    );
+    assert_eq!(bms.bpm.bpm_defs.len(), 1);
+    assert_eq!(bms.stop.stop_defs.len(), 1);
+    assert_eq!(bms.long_notes::<KeyLayoutBeat>().long_notes.len(), 1);
+}
+
+#[test]
+fn infers_double_play_from_sides() {
+    let mut builder = BmsBuilder::<KeyLayoutBeat>::new();
+    let id = builder.wav("a.wav").expect("ids should be left");
+    builder.note(
+        time(1, 0, 1),
+        (PlayerSide::Player2, Key::Key(1), NoteKind::Visible),
+        id,
+    );
+    let bms = builder.build().expect("the chart should be valid");
+
+    assert_eq!(bms.metadata.player, Some(PlayerMode::Double));
+    assert!(bms.bpm.bpm.is_some());
+}
+
+#[test]
+fn rejects_overlapping_notes() {
+    let mut builder = BmsBuilder::<KeyLayoutBeat>::new();
+    let a = builder.wav("a.wav").expect("ids should be left");
+    let b = builder.wav("b.wav").expect("ids should be left");
+    let lane = (PlayerSide::Player1, Key::Key(1), NoteKind::Visible);
+    builder
+        .note(time(1, 0, 1), lane, a)
+        .note(beat(0, 0, 1), lane, b);
+
+    let Err(BmsBuildError::Invalid(output)) = builder.build() else {
+        panic!("overlapping notes should be rejected");
+    };
+    assert_eq!(
+        output.invalid,
+        vec![ValidityInvalid::OverlapVisibleSingleWithSingle {
+            side: PlayerSide::Player1,
+            key: Key::Key(1),
+            time: time(1, 0, 1),
+        }]
+    );
+}
+
+#[test]
+fn rejects_chart_without_notes() {
+    let builder = BmsBuilder::<KeyLayoutBeat>::new();
+
+    assert_eq!(
+        builder.build(),
+        Err(BmsBuildError::Unplayable(vec![PlayingError::NoNotes]))
+    );
+}
+
+#[test]
+fn writes_long_notes_in_encoding_of_score() {
+    let sources = [
+        ("#WAV01 a.wav\n#00111:01\n", LongNoteEncoding::Rdm),
+        (
+            "#LNTYPE 2\n#WAV01 a.wav\n#00111:01\n",
+            LongNoteEncoding::Mgq,
+        ),
+        (
+            "#LNOBJ ZZ\n#WAV01 a.wav\n#WAVZZ z.wav\n#00111:01\n",
+            LongNoteEncoding::LnObj(ObjId::try_from("ZZ", false).expect("id should be valid")),
+        ),
+    ];
+    for (source, encoding) in sources {
+        let LexOutput { tokens, .. } = TokenStream::parse_lex(source);
+        let ParseOutput { bms, .. } =
+            Bms::from_token_stream(&tokens, default_config().prompter(AlwaysUseNewer));
+        let mut builder = BmsBuilder::<KeyLayoutBeat>::from_bms(bms.expect("failed to parse BMS"));
+        let id = builder.wav("a.wav").expect("ids should be left");
+        builder.long_note(
+            time(2, 1, 4),
+            time(2, 2, 4),
+            (PlayerSide::Player1, Key::Key(2)),
+            id,
+        );
+        let built = builder.build().expect("the chart should be valid");
+
+        let output = built.long_notes::<KeyLayoutBeat>();
+        assert_eq!(output.long_note_warnings, vec![], "source: {source}");
+        let spans: Vec<_> = output
+            .long_notes
+            .iter()
+            .map(|ln| (ln.start, ln.end, ln.encoding))
+            .collect();
+        assert_eq!(
+            spans,
+            vec![(time(2, 1, 4), time(2, 2, 4), encoding)],
+            "source: {source}"
+        );
+    }
+}
@@ -0,0 +1,310 @@
+use std::path::PathBuf;
+
+use bms_rs::bms::prelude::*;
+use pretty_assertions::assert_eq;
+
+fn parse(source: &str) -> Bms {
+    let LexOutput {
+        tokens,
+        lex_warnings,
+    } = TokenStream::parse_lex(source);
+    assert_eq!(lex_warnings, vec![]);
+
+    let ParseOutput {
+        bms,
+        parse_warnings,
+    } = Bms::from_token_stream(&tokens, default_config().prompter(AlwaysUseNewer));
+    assert_eq!(parse_warnings, vec![]);
+    bms.expect("failed to parse BMS")
+}
+
+fn id(s: &str) -> ObjId {
+    ObjId::try_from(s, false).expect("id should be valid")
+}
+
+fn time(track: u64, numerator: u64, denominator: u64) -> ObjTime {
+    ObjTime::new(track, numerator, denominator).expect("denominator should be non-zero")
+}
+
+/// A lane of a note, or `None` for BGM.
+type Lane = Option<(PlayerSide, NoteKind, Key)>;
+
+/// Lanes, times and ids of the notes which are not dangling.
+fn notes(bms: &Bms) -> Vec<(Lane, ObjTime, ObjId)> {
+    let mut notes: Vec<_> = bms
+        .notes()
+        .all_notes()
+        .filter(|obj| !obj.wav_id.is_null())
+        .map(|obj| {
+            (
+                KeyLayoutBeat::from_channel_id(obj.channel_id).map(|layout| layout.as_tuple()),
+                obj.offset,
+                obj.wav_id,
+            )
+        })
+        .collect();
+    notes.sort_by_key(|&(layout, time, wav_id)| (time, wav_id, format!("{layout:?}")));
+    notes
+}
+
+const SINGLE: &str = "#PLAYER 1
+#BPM 150
+#WAV01 kick.wav
+#WAV02 snare.wav
+#WAV03 bgm.wav
+#00101:03
+#00111:0102
+#00152:0201
+";
+
+#[test]
+fn combines_a_score_twice() {
+    let single = parse(SINGLE);
+    let DoublePlayOutput {
+        bms,
+        double_play_warnings,
+    } = Bms::combine_double_play(&single, &single).expect("ids should be left");
+
+    assert_eq!(double_play_warnings, vec![]);
+    assert_eq!(bms.metadata.player, Some(PlayerMode::Double));
+    assert_eq!(bms.wav.wav_files, single.wav.wav_files);
+    assert_eq!(bms.notes().bgms::<KeyLayoutBeat>().count(), 1);
+    for side in [PlayerSide::Player1, PlayerSide::Player2] {
+        let lanes: Vec<_> = notes(&bms)
+            .into_iter()
+            .filter_map(|(layout, time, wav_id)| {
+                let (note_side, kind, key) = layout?;
+                (note_side == side).then_some((kind, key, time, wav_id))
+            })
+            .collect();
+        assert_eq!(
+            lanes,
+            vec![
+                (NoteKind::Visible, Key::Key(1), time(1, 0, 1), id("01")),
+                (NoteKind::Long, Key::Key(2), time(1, 0, 1), id("02")),
+                (NoteKind::Long, Key::Key(2), time(1, 1, 2), id("01")),
+                (NoteKind::Visible, Key::Key(1), time(1, 1, 2), id("02")),
+            ]
+        );
+    }
+    assert_eq!(bms.long_notes::<KeyLayoutBeat>().long_notes.len(), 2);
+}
+
+#[test]
+fn combine_remaps_conflicting_ids() {
+    let player1 = parse(
+        "#BPM 150
+#WAV01 kick.wav
+#00111:01
+",
+    );
+    let player2 = parse(
+        "#BPM 180
+#WAV01 bass.wav
+#WAV02 kick.wav
+#00111:0102
+#00121:01
+",
+    );
+    let DoublePlayOutput {
+        bms,
+        double_play_warnings,
+    } = Bms::combine_double_play(&player1, &player2).expect("ids should be left");
+
+    assert_eq!(
+        double_play_warnings,
+        vec![
+            DoublePlayWarning::TimingMismatch,
+            DoublePlayWarning::OffSideNote {
+                key: Key::Key(1),
+                time: time(1, 0, 1),
+            },
+        ]
+    );
+    // `bass.wav` gets an unused id, and `kick.wav` shares the id with 1P.
+    assert_eq!(
+        bms.wav.wav_files.get(&id("03")),
+        Some(&PathBuf::from("bass.wav"))
+    );
+    assert_eq!(bms.wav.wav_files.len(), 2);
+    assert_eq!(
+        notes(&bms),
+        vec![
+            (
+                Some((PlayerSide::Player1, NoteKind::Visible, Key::Key(1))),
+                time(1, 0, 1),
+                id("01")
+            ),
+            (None, time(1, 0, 1), id("03")),
+            (
+                Some((PlayerSide::Player2, NoteKind::Visible, Key::Key(1))),
+                time(1, 0, 1),
+                id("03")
+            ),
+            (
+                Some((PlayerSide::Player2, NoteKind::Visible, Key::Key(1))),
+                time(1, 1, 2),
+                id("01")
+            ),
+        ]
+    );
+}
+
+#[test]
+fn splits_double_play_score() {
+    let double = parse(
+        "#PLAYER 3
+#WAV01 kick.wav
+#WAV02 snare.wav
+#WAV03 hold.wav
+#00111:01
+#00121:02
+#00162:0303
+#000E1:0001
+",
+    );
+    let (player1, player2) = double.split_double_play();
+
+    assert_eq!(player1.metadata.player, Some(PlayerMode::Single));
+    assert_eq!(
+        notes(&player1),
+        vec![
+            (
+                Some((PlayerSide::Player1, NoteKind::Visible, Key::Key(1))),
+                time(1, 0, 1),
+                id("01")
+            ),
+            (None, time(1, 0, 1), id("02")),
+            (None, time(1, 0, 1), id("03")),
+        ]
+    );
+
+    assert_eq!(player2.metadata.player, Some(PlayerMode::Single));
+    assert_eq!(
+        notes(&player2),
+        vec![
+            (
+                Some((PlayerSide::Player1, NoteKind::Landmine, Key::Key(1))),
+                time(0, 1, 2),
+                id("01")
+            ),
+            (None, time(1, 0, 1), id("01")),
+            (
+                Some((PlayerSide::Player1, NoteKind::Visible, Key::Key(1))),
+                time(1, 0, 1),
+                id("02")
+            ),
+            (
+                Some((PlayerSide::Player1, NoteKind::Long, Key::Key(2))),
+                time(1, 0, 1),
+                id("03")
+            ),
+            (
+                Some((PlayerSide::Player1, NoteKind::Long, Key::Key(2))),
+                time(1, 1, 2),
+                id("03")
+            ),
+        ]
+    );
+}
+
+#[test]
+fn split_keeps_overlapping_notes_of_a_side() {
+    let double = parse(
+        "#PLAYER 3
+#WAV01 kick.wav
+#WAV03 hold.wav
+#00161:03000003
+#00121:0001
+",
+    );
+    let (_, player2) = double.split_double_play();
+
+    assert_eq!(
+        notes(&player2),
+        vec![
+            (
+                Some((PlayerSide::Player1, NoteKind::Long, Key::Key(1))),
+                time(1, 0, 1),
+                id("03")
+            ),
+            (
+                Some((PlayerSide::Player1, NoteKind::Visible, Key::Key(1))),
+                time(1, 1, 2),
+                id("01")
+            ),
+            (
+                Some((PlayerSide::Player1, NoteKind::Long, Key::Key(1))),
+                time(1, 3, 4),
+                id("03")
+            ),
+        ]
+    );
+}
+
+/// Sides, keys, starts and ends of the long notes.
+fn long_notes(bms: &Bms) -> Vec<(PlayerSide, Key, ObjTime, ObjTime)> {
+    let mut long_notes: Vec<_> = bms
+        .long_notes::<KeyLayoutBeat>()
+        .long_notes
+        .into_iter()
+        .map(|ln| (ln.side, ln.key, ln.start, ln.end))
+        .collect();
+    long_notes.sort_by_key(|&(side, key, start, _)| (start, format!("{side:?}{key:?}")));
+    long_notes
+}
+
+#[test]
+fn combine_keeps_mgq_long_notes_of_2p() {
+    let single = parse(
+        "#PLAYER 1
+#LNTYPE 2
+#WAV01 hold.wav
+#00151:0101
+#00251:0101
+#00152:0001
+#00252:01000000
+",
+    );
+    let output = Bms::combine_double_play(&single, &single).expect("ids should be left");
+    assert_eq!(output.double_play_warnings, vec![]);
+
+    let mut expected = vec![];
+    for side in [PlayerSide::Player1, PlayerSide::Player2] {
+        expected.push((side, Key::Key(1), time(1, 0, 1), time(3, 0, 1)));
+        expected.push((side, Key::Key(2), time(1, 1, 2), time(2, 1, 4)));
+    }
+    expected.sort_by_key(|&(side, key, start, _)| (start, format!("{side:?}{key:?}")));
+    assert_eq!(long_notes(&single).len(), 2);
+    assert_eq!(long_notes(&output.bms), expected);
+}
+
+#[test]
+fn combine_does_not_end_long_notes_of_1p_by_lnobj_of_2p() {
+    let player1 = parse(
+        "#PLAYER 1
+#WAV01 kick.wav
+#WAV02 snare.wav
+#00111:0102
+",
+    );
+    let player2 = parse(
+        "#PLAYER 1
+#WAV01 kick.wav
+#WAV02 snare.wav
+#LNOBJ 02
+#00111:0102
+",
+    );
+    let output = Bms::combine_double_play(&player1, &player2).expect("ids should be left");
+
+    assert_eq!(
+        long_notes(&output.bms),
+        vec![(
+            PlayerSide::Player2,
+            Key::Key(1),
+            time(1, 0, 1),
+            time(1, 1, 2)
+        )]
+    );
+}
@@ -0,0 +1,131 @@
+use bms_rs::bms::prelude::*;
+use pretty_assertions::assert_eq;
+
+use NoteKind::{Landmine, Long, Visible};
+use PlayerSide::{Player1, Player2};
+
+fn parse(source: &str) -> Bms {
+    let LexOutput {
+        tokens,
+        lex_warnings,
+    } = TokenStream::parse_lex(source);
+    assert_eq!(lex_warnings, vec![]);
+
+    let ParseOutput {
+        bms,
+        parse_warnings,
+    } = Bms::from_token_stream(&tokens, default_config().prompter(AlwaysUseNewer));
+    assert_eq!(parse_warnings, vec![]);
+    bms.expect("failed to parse BMS")
+}
+
+fn time(track: u64, numerator: u64, denominator: u64) -> ObjTime {
+    ObjTime::new(track, numerator, denominator).expect("denominator should be non-zero")
+}
+
+fn keys() -> Vec<Key> {
+    (1..=7).map(Key::Key).collect()
+}
+
+/// Lanes and times of the notes, sorted by time.
+fn lanes(bms: &Bms) -> Vec<(PlayerSide, NoteKind, Key, ObjTime)> {
+    bms.notes()
+        .all_notes()
+        .filter_map(|obj| {
+            let layout = KeyLayoutBeat::from_channel_id(obj.channel_id)?;
+            Some((layout.side(), layout.kind(), layout.key(), obj.offset))
+        })
+        .collect()
+}
+
+const DOUBLE_PLAY: &str = "#PLAYER 3
+#00111:01
+#00152:0203
+#001D3:04
+#00121:05
+#00162:0607
+";
+
+#[test]
+fn mirror_converts_only_selected_side() {
+    let mut bms = parse(DOUBLE_PLAY);
+
+    let output = bms.convert_keys::<KeyLayoutBeat>(
+        PlayerSide::Player1,
+        &mut KeyMappingConvertMirror::new(keys()),
+    );
+
+    assert_eq!(output.moved, 3);
+    assert_eq!(output.unplaced, vec![]);
+    assert_eq!(
+        lanes(&bms),
+        vec![
+            (Player1, Visible, Key::Key(7), time(1, 0, 1)),
+            (Player1, Long, Key::Key(6), time(1, 0, 1)),
+            (Player1, Landmine, Key::Key(5), time(1, 0, 1)),
+            (Player2, Visible, Key::Key(1), time(1, 0, 1)),
+            (Player2, Long, Key::Key(2), time(1, 0, 1)),
+            (Player1, Long, Key::Key(6), time(1, 1, 2)),
+            (Player2, Long, Key::Key(2), time(1, 1, 2)),
+        ]
+    );
+}
+
+#[test]
+fn each_side_takes_its_own_option() {
+    let mut bms = parse(DOUBLE_PLAY);
+    let original = bms.long_notes::<KeyLayoutBeat>().long_notes;
+
+    let _ = bms.convert_keys::<KeyLayoutBeat>(
+        PlayerSide::Player1,
+        &mut KeyMappingConvertLaneRandomShuffle::new(&keys(), 1),
+    );
+    let _ = bms.convert_keys::<KeyLayoutBeat>(
+        PlayerSide::Player2,
+        &mut KeyMappingConvertLaneRotateShuffle::new(&keys(), 2),
+    );
+
+    let mut random = KeyMappingConvertLaneRandomShuffle::new(&keys(), 1);
+    let mut rotate = KeyMappingConvertLaneRotateShuffle::new(&keys(), 2);
+    let expected: Vec<_> = original
+        .iter()
+        .map(|ln| {
+            let key = match ln.side {
+                PlayerSide::Player1 => random.convert(ln.key),
+                PlayerSide::Player2 => rotate.convert(ln.key),
+            };
+            (ln.side, key, ln.start, ln.end, ln.objects.len())
+        })
+        .collect();
+    let converted = bms.long_notes::<KeyLayoutBeat>();
+    assert_eq!(converted.long_note_warnings, vec![]);
+    assert_eq!(
+        converted
+            .long_notes
+            .iter()
+            .map(|ln| (ln.side, ln.key, ln.start, ln.end, ln.objects.len()))
+            .collect::<Vec<_>>(),
+        expected
+    );
+}
+
+#[test]
+fn flip_swaps_sides_with_long_notes_and_landmines() {
+    let mut bms = parse(DOUBLE_PLAY);
+
+    let output = bms.convert_player_side_keys::<KeyLayoutBeat>(&mut KeyMappingConvertFlip);
+
+    assert_eq!(output.moved, 5);
+    assert_eq!(
+        lanes(&bms),
+        vec![
+            (Player2, Visible, Key::Key(1), time(1, 0, 1)),
+            (Player2, Long, Key::Key(2), time(1, 0, 1)),
+            (Player2, Landmine, Key::Key(3), time(1, 0, 1)),
+            (Player1, Visible, Key::Key(1), time(1, 0, 1)),
+            (Player1, Long, Key::Key(2), time(1, 0, 1)),
+            (Player2, Long, Key::Key(2), time(1, 1, 2)),
+            (Player1, Long, Key::Key(2), time(1, 1, 2)),
+        ]
+    );
+}
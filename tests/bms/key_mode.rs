@@ -0,0 +1,192 @@
+use bms_rs::bms::prelude::*;
+use pretty_assertions::assert_eq;
+
+fn parse(source: &str) -> Bms {
+    let LexOutput {
+        tokens,
+        lex_warnings,
+    } = TokenStream::parse_lex(source);
+    assert_eq!(lex_warnings, vec![]);
+
+    let ParseOutput {
+        bms,
+        parse_warnings,
+    } = Bms::from_token_stream(&tokens, default_config().prompter(AlwaysUseNewer));
+    assert_eq!(parse_warnings, vec![]);
+    bms.expect("failed to parse BMS")
+}
+
+fn time(track: u64, numerator: u64, denominator: u64) -> ObjTime {
+    ObjTime::new(track, numerator, denominator).expect("denominator should be non-zero")
+}
+
+fn channel<T: KeyLayoutMapper>(kind: NoteKind, key: Key) -> NoteChannelId {
+    T::new(PlayerSide::Player1, kind, key).to_channel_id()
+}
+
+/// Channels and times of the notes which are not dangling.
+fn notes(bms: &Bms) -> Vec<(NoteChannelId, ObjTime)> {
+    let mut notes: Vec<_> = bms
+        .notes()
+        .all_notes()
+        .filter(|obj| !obj.wav_id.is_null())
+        .map(|obj| (obj.channel_id, obj.offset))
+        .collect();
+    notes.sort();
+    notes
+}
+
+const SEVEN_KEYS: &str = "#00111:01000000
+#00114:00010000
+#00115:00000100
+#00118:01000000
+#00119:00000100
+";
+
+#[test]
+fn folds_7k_pairwise() {
+    let mut bms = parse(SEVEN_KEYS);
+    let output = bms.fold_7k_to_5k(FoldStrategy::Pairwise);
+
+    assert_eq!(output.moved, 1);
+    assert_eq!(
+        output.unplaced,
+        vec![UnplacedNote {
+            side: PlayerSide::Player1,
+            key: Key::Key(7),
+            kind: NoteKind::Visible,
+            time: time(1, 1, 2),
+        }]
+    );
+    let visible = |key| channel::<KeyLayoutBeat>(NoteKind::Visible, Key::Key(key));
+    let mut expected = vec![
+        (NoteChannelId::bgm(), time(1, 1, 2)),
+        (visible(1), time(1, 0, 1)),
+        (visible(4), time(1, 0, 1)),
+        (visible(4), time(1, 1, 4)),
+        (visible(5), time(1, 1, 2)),
+    ];
+    expected.sort();
+    assert_eq!(notes(&bms), expected);
+}
+
+#[test]
+fn folds_7k_into_bgm() {
+    let mut bms = parse(SEVEN_KEYS);
+    let output = bms.fold_7k_to_5k(FoldStrategy::Bgm);
+
+    assert_eq!(output.moved, 0);
+    assert_eq!(
+        output
+            .unplaced
+            .iter()
+            .map(|note| (note.key, note.time))
+            .collect::<Vec<_>>(),
+        vec![(Key::Key(6), time(1, 0, 1)), (Key::Key(7), time(1, 1, 2))]
+    );
+    assert_eq!(bms.notes().bgms::<KeyLayoutBeat>().count(), 2);
+}
+
+#[test]
+fn folds_long_note_as_a_whole() {
+    let mut bms = parse("#00158:0202\n");
+    let output = bms.fold_7k_to_5k(FoldStrategy::Pairwise);
+
+    assert_eq!(output.moved, 1);
+    assert_eq!(output.unplaced, vec![]);
+    let long = channel::<KeyLayoutBeat>(NoteKind::Long, Key::Key(4));
+    assert_eq!(
+        notes(&bms),
+        vec![(long, time(1, 0, 1)), (long, time(1, 1, 2))]
+    );
+    assert_eq!(bms.long_notes::<KeyLayoutBeat>().long_notes.len(), 1);
+}
+
+#[test]
+fn folds_long_note_ending_at_a_note() {
+    let mut bms = parse("#00114:0002\n#00158:0202\n");
+    let output = bms.fold_7k_to_5k(FoldStrategy::Pairwise);
+
+    assert_eq!(output.moved, 1);
+    assert_eq!(output.unplaced, vec![]);
+    let long = channel::<KeyLayoutBeat>(NoteKind::Long, Key::Key(4));
+    let visible = channel::<KeyLayoutBeat>(NoteKind::Visible, Key::Key(4));
+    assert_eq!(
+        notes(&bms),
+        vec![
+            (visible, time(1, 1, 2)),
+            (long, time(1, 0, 1)),
+            (long, time(1, 1, 2)),
+        ]
+    );
+}
+
+#[test]
+fn spreads_5k_to_center() {
+    let mut bms = parse(
+        "#00111:01
+#00115:01
+#00116:01
+",
+    );
+    let output = bms.spread_5k_to_7k(SpreadStrategy::Center);
+
+    assert_eq!(output.moved, 2);
+    let visible = |key| channel::<KeyLayoutBeat>(NoteKind::Visible, key);
+    let mut expected = vec![
+        (visible(Key::Key(2)), time(1, 0, 1)),
+        (visible(Key::Key(6)), time(1, 0, 1)),
+        (visible(Key::Scratch(1)), time(1, 0, 1)),
+    ];
+    expected.sort();
+    assert_eq!(notes(&bms), expected);
+}
+
+#[test]
+fn rechannels_pms_to_bme_type_and_back() {
+    let source = parse(
+        "#00111:01
+#00122:01
+#00125:01
+",
+    );
+    let mut bms = source.clone();
+    let output = bms.rechannel::<KeyLayoutPms, KeyLayoutPmsBmeType>();
+
+    assert_eq!(output.moved, 2);
+    assert_eq!(output.unplaced, vec![]);
+    let bme_type = |key| channel::<KeyLayoutPmsBmeType>(NoteKind::Visible, Key::Key(key));
+    let mut expected = vec![
+        (bme_type(1), time(1, 0, 1)),
+        (bme_type(6), time(1, 0, 1)),
+        (bme_type(9), time(1, 0, 1)),
+    ];
+    expected.sort();
+    assert_eq!(notes(&bms), expected);
+
+    let restored = bms.rechannel::<KeyLayoutPmsBmeType, KeyLayoutPms>();
+    assert_eq!(restored.moved, 2);
+    assert_eq!(notes(&bms), notes(&source));
+}
+
+#[test]
+fn rechannel_turns_unplaceable_notes_into_bgm() {
+    let mut bms = parse(
+        "#00111:01
+#00116:01
+",
+    );
+    let output = bms.rechannel::<KeyLayoutBeat, KeyLayoutPmsBmeType>();
+
+    assert_eq!(output.moved, 0);
+    assert_eq!(
+        output.unplaced,
+        vec![UnplacedNote {
+            side: PlayerSide::Player1,
+            key: Key::Scratch(1),
+            kind: NoteKind::Visible,
+            time: time(1, 0, 1),
+        }]
+    );
+    assert_eq!(bms.notes().bgms::<KeyLayoutBeat>().count(), 1);
+}
@@ -0,0 +1,289 @@
+use bms_rs::bms::prelude::*;
+use pretty_assertions::assert_eq;
+
+fn parse(source: &str) -> Bms {
+    let LexOutput {
+        tokens,
+        lex_warnings,
+    } = TokenStream::parse_lex(source);
+    assert_eq!(lex_warnings, vec![]);
+
+    let ParseOutput {
+        bms,
+        parse_warnings,
+    } = Bms::from_token_stream(&tokens, default_config().prompter(AlwaysUseNewer));
+    assert_eq!(parse_warnings, vec![]);
+    bms.expect("failed to parse BMS")
+}
+
+fn reparse(source: &Bms) -> Bms {
+    let tokens: Vec<TokenWithRange<'_>> = source
+        .unparse::<KeyLayoutBeat>()
+        .into_iter()
+        .map(|t| SourceRangeMixin::new(t, 0..0))
+        .collect();
+    let ParseOutput {
+        bms,
+        parse_warnings,
+    } = Bms::from_token_stream(&tokens, default_config().prompter(AlwaysUseNewer));
+    assert_eq!(parse_warnings, vec![]);
+    bms.expect("failed to parse unparsed BMS")
+}
+
+fn time(track: u64, numerator: u64, denominator: u64) -> ObjTime {
+    ObjTime::new(track, numerator, denominator).expect("denominator should be non-zero")
+}
+
+fn id(s: &str) -> ObjId {
+    ObjId::try_from(s, false).expect("id should be valid")
+}
+
+/// Lane, start, end, start id and end id of the long notes.
+fn spans(output: &LongNotesOutput) -> Vec<(Key, ObjTime, ObjTime, ObjId, ObjId)> {
+    output
+        .long_notes
+        .iter()
+        .map(|ln| (ln.key, ln.start, ln.end, ln.start_wav_id, ln.end_wav_id))
+        .collect()
+}
+
+#[test]
+fn rdm_pairs_objects_on_long_channel() {
+    let bms = parse(
+        "#LNMODE 2
+#00151:0A000B00
+#00152:0C
+#00251:0D
+",
+    );
+    let output = bms.long_notes::<KeyLayoutBeat>();
+
+    assert_eq!(
+        spans(&output),
+        vec![(
+            Key::Key(1),
+            time(1, 0, 1),
+            time(1, 1, 2),
+            id("0A"),
+            id("0B")
+        )]
+    );
+    assert!(output.long_notes.iter().all(|ln| ln.ln_mode == LnMode::Cn));
+    assert_eq!(
+        output.long_note_warnings,
+        vec![
+            LongNoteWarning::Unterminated {
+                side: PlayerSide::Player1,
+                key: Key::Key(1),
+                start: time(2, 0, 1),
+            },
+            LongNoteWarning::Unterminated {
+                side: PlayerSide::Player1,
+                key: Key::Key(2),
+                start: time(1, 0, 1),
+            },
+        ]
+    );
+}
+
+#[test]
+fn mgq_joins_consecutive_objects() {
+    let bms = parse(
+        "#LNTYPE 2
+#00151:01010001
+#00251:0100
+#00152:00000100
+",
+    );
+    let output = bms.long_notes::<KeyLayoutBeat>();
+
+    assert_eq!(
+        spans(&output),
+        vec![
+            (
+                Key::Key(1),
+                time(1, 0, 1),
+                time(1, 2, 4),
+                id("01"),
+                id("01")
+            ),
+            (
+                Key::Key(2),
+                time(1, 2, 4),
+                time(1, 3, 4),
+                id("01"),
+                id("01")
+            ),
+            (
+                Key::Key(1),
+                time(1, 3, 4),
+                time(2, 1, 2),
+                id("01"),
+                id("01")
+            ),
+        ]
+    );
+    assert_eq!(output.long_notes.get(2).map(|ln| ln.objects.len()), Some(2));
+    assert_eq!(output.long_note_warnings, vec![]);
+
+    // The grids of the messages survive unparsing.
+    assert_eq!(
+        spans(&reparse(&bms).long_notes::<KeyLayoutBeat>()),
+        spans(&output)
+    );
+}
+
+#[test]
+fn ln_obj_ends_preceding_visible_note() {
+    let bms = parse(
+        "#LNOBJ ZZ
+#00111:0100ZZ00
+#00112:ZZ
+",
+    );
+    let output = bms.long_notes::<KeyLayoutBeat>();
+
+    // The pair is moved onto the long note channel, and the end without a start is left.
+    let channels: Vec<_> = bms
+        .notes()
+        .all_notes()
+        .map(|obj| (obj.offset, obj.channel_id, obj.wav_id))
+        .collect();
+    let channel = |kind, key| KeyLayoutBeat::new(PlayerSide::Player1, kind, key).to_channel_id();
+    assert_eq!(
+        channels,
+        vec![
+            (
+                time(1, 0, 1),
+                channel(NoteKind::Long, Key::Key(1)),
+                id("01")
+            ),
+            (
+                time(1, 0, 1),
+                channel(NoteKind::Visible, Key::Key(2)),
+                id("ZZ")
+            ),
+            (
+                time(1, 1, 2),
+                channel(NoteKind::Long, Key::Key(1)),
+                id("ZZ")
+            ),
+        ]
+    );
+    assert_eq!(
+        spans(&output),
+        vec![(
+            Key::Key(1),
+            time(1, 0, 1),
+            time(1, 1, 2),
+            id("01"),
+            id("ZZ")
+        )]
+    );
+    assert_eq!(
+        output.long_notes.first().map(|ln| ln.encoding),
+        Some(LongNoteEncoding::LnObj(id("ZZ")))
+    );
+    assert_eq!(
+        output.long_note_warnings,
+        vec![LongNoteWarning::LnObjWithoutStart {
+            side: PlayerSide::Player1,
+            key: Key::Key(2),
+            end: time(1, 0, 1),
+            end_wav_id: id("ZZ"),
+        }]
+    );
+}
+
+#[test]
+fn reports_notes_overlapping_long_note() {
+    let bms = parse(
+        "#00151:01000200
+#00111:00030000
+",
+    );
+    let output = bms.long_notes::<KeyLayoutBeat>();
+
+    assert_eq!(
+        output.long_note_warnings,
+        vec![LongNoteWarning::Overlapping {
+            side: PlayerSide::Player1,
+            key: Key::Key(1),
+            ln_start: time(1, 0, 1),
+            ln_end: time(1, 1, 2),
+            time: time(1, 1, 4),
+        }]
+    );
+}
+
+#[test]
+fn note_at_long_note_end_does_not_overlap() {
+    let bms = parse(
+        "#00151:01000200
+#00111:00000300
+",
+    );
+
+    assert_eq!(bms.long_notes::<KeyLayoutBeat>().long_note_warnings, vec![]);
+}
+
+#[test]
+fn encodes_between_all_encodings() {
+    let source = "#00151:0102
+#00252:0304
+#00353:05000006
+";
+    let original = parse(source);
+    let expected = spans(&original.long_notes::<KeyLayoutBeat>());
+    assert_eq!(expected.len(), 3);
+
+    let encodings = [
+        LongNoteEncoding::Mgq,
+        LongNoteEncoding::LnObj(id("ZZ")),
+        LongNoteEncoding::Rdm,
+    ];
+    let mut bms = original;
+    for encoding in encodings {
+        let before = bms.encode_long_notes::<KeyLayoutBeat>(encoding);
+        assert_eq!(before.long_note_warnings, vec![]);
+
+        bms = reparse(&bms);
+        let output = bms.long_notes::<KeyLayoutBeat>();
+        assert_eq!(output.long_note_warnings, vec![]);
+        assert!(output.long_notes.iter().all(|ln| ln.encoding == encoding));
+        let resolved: Vec<_> = spans(&output)
+            .into_iter()
+            .map(|(key, start, end, start_id, _)| (key, start, end, start_id))
+            .collect();
+        let expected: Vec<_> = expected
+            .iter()
+            .map(|&(key, start, end, start_id, _)| (key, start, end, start_id))
+            .collect();
+        assert_eq!(resolved, expected);
+    }
+}
+
+#[test]
+fn encodes_mgq_on_fine_grid() {
+    let mut bms = parse("#WAV01 long.wav\n");
+    let channel_id =
+        KeyLayoutBeat::new(PlayerSide::Player1, NoteKind::Long, Key::Key(1)).to_channel_id();
+    let denominator = 1 << 41;
+    let start = time(1, denominator - 1, denominator);
+    for offset in [start, time(2, 0, 1)] {
+        bms.wav.notes.push_note(WavObj {
+            offset,
+            channel_id,
+            wav_id: id("01"),
+        });
+    }
+
+    let _ = bms.encode_long_notes::<KeyLayoutBeat>(LongNoteEncoding::Mgq);
+
+    let output = bms.long_notes::<KeyLayoutBeat>();
+    assert_eq!(output.long_note_warnings, vec![]);
+    assert_eq!(
+        spans(&output),
+        vec![(Key::Key(1), start, time(2, 0, 1), id("01"), id("01"))]
+    );
+}
@@ -1,18 +1,25 @@
 //! Tests for `bms_rs::bms`.
 
+mod assist;
 mod base_62;
+mod builder;
 mod comment;
 mod control_flow_model;
 mod cursor_with_edges;
 mod diagnostics_test;
+mod double_play;
 mod extra_channel;
 mod files;
+mod key_convert;
+mod key_mode;
+mod long_notes;
 mod nested_random;
 mod nested_switch;
 mod parse_extended_tokens;
 mod playing_conditions;
 mod prelude_test;
 mod prompt_handlers;
+mod union;
 mod unparse_merge;
 mod unparse_roundtrip;
 
@@ -0,0 +1,263 @@
+use std::path::Path;
+
+use bms_rs::bms::{command::channel::Channel, prelude::*};
+use pretty_assertions::assert_eq;
+
+fn parse(source: &str) -> Bms {
+    let LexOutput {
+        tokens,
+        lex_warnings,
+    } = TokenStream::parse_lex(source);
+    assert_eq!(lex_warnings, vec![]);
+
+    let ParseOutput {
+        bms,
+        parse_warnings,
+    } = Bms::from_token_stream(&tokens, default_config().prompter(AlwaysUseNewer));
+    assert_eq!(parse_warnings, vec![]);
+    bms.expect("failed to parse BMS")
+}
+
+fn id(s: &str) -> ObjId {
+    ObjId::try_from(s, false).expect("id should be valid")
+}
+
+fn time(track: u64, numerator: u64, denominator: u64) -> ObjTime {
+    ObjTime::new(track, numerator, denominator).expect("denominator should be non-zero")
+}
+
+const BASE: &str = "#WAV01 kick.wav
+#WAV02 snare.wav
+#BPM01 150
+#STOP01 96
+#00111:0102
+#00108:0001
+#00109:01
+#00101:01
+";
+
+const INCOMING: &str = "#WAV01 bass.wav
+#WAV02 snare.wav
+#BPM01 180
+#STOP01 48
+#00111:0202
+#00108:0001
+#00109:01
+#00101:02
+";
+
+fn expected_conflicts(workaround: &DuplicationWorkaround) -> Vec<UnionConflict> {
+    let key_1 = Channel::Note {
+        channel_id: KeyLayoutBeat::new(PlayerSide::Player1, NoteKind::Visible, Key::Key(1))
+            .to_channel_id(),
+    };
+    [
+        UnionConflictKind::Def {
+            command: "WAV",
+            id: id("01"),
+        },
+        UnionConflictKind::Def {
+            command: "BPM",
+            id: id("01"),
+        },
+        UnionConflictKind::Def {
+            command: "STOP",
+            id: id("01"),
+        },
+        // The events referring the definitions also differ.
+        UnionConflictKind::Channel {
+            time: time(1, 1, 2),
+            channel: Channel::BpmChange,
+        },
+        UnionConflictKind::Channel {
+            time: time(1, 0, 1),
+            channel: Channel::Stop,
+        },
+        UnionConflictKind::Channel {
+            time: time(1, 0, 1),
+            channel: key_1,
+        },
+    ]
+    .into_iter()
+    .map(|kind| UnionConflict {
+        kind,
+        workaround: workaround.clone(),
+    })
+    .collect()
+}
+
+#[test]
+fn union_with_keeps_older_on_conflicts() {
+    let mut bms = parse(BASE);
+    let conflicts = bms.union_with::<KeyLayoutBeat>(&parse(INCOMING), &AlwaysUseOlder);
+
+    assert_eq!(
+        conflicts,
+        expected_conflicts(&DuplicationWorkaround::UseOlder)
+    );
+    assert_eq!(
+        bms.wav.wav_files.get(&id("01")).map(AsRef::as_ref),
+        Some(Path::new("kick.wav"))
+    );
+    assert_eq!(
+        bms.notes()
+            .all_notes()
+            .filter(|obj| obj.channel_id != NoteChannelId::bgm())
+            .map(|obj| (obj.offset, obj.wav_id))
+            .collect::<Vec<_>>(),
+        vec![(time(1, 0, 1), id("01")), (time(1, 1, 2), id("02"))]
+    );
+    // BGM objects are layered.
+    assert_eq!(
+        bms.notes()
+            .all_notes()
+            .filter(|obj| obj.channel_id == NoteChannelId::bgm())
+            .count(),
+        2
+    );
+}
+
+#[test]
+fn union_with_takes_newer_on_conflicts() {
+    let mut bms = parse(BASE);
+    let conflicts = bms.union_with::<KeyLayoutBeat>(&parse(INCOMING), &AlwaysWarnAndUseNewer);
+
+    assert_eq!(
+        conflicts,
+        expected_conflicts(&DuplicationWorkaround::WarnAndUseNewer)
+    );
+    assert!(conflicts.iter().all(UnionConflict::uses_newer));
+    assert_eq!(
+        bms.wav.wav_files.get(&id("01")).map(AsRef::as_ref),
+        Some(Path::new("bass.wav"))
+    );
+    assert_eq!(
+        bms.stop
+            .stop_defs
+            .get(&id("01"))
+            .and_then(|def| def.value().as_ref().ok())
+            .map(|duration| duration.as_f64()),
+        Some(48.0)
+    );
+    assert_eq!(
+        bms.notes()
+            .all_notes()
+            .filter(|obj| obj.channel_id != NoteChannelId::bgm())
+            .map(|obj| (obj.offset, obj.wav_id))
+            .collect::<Vec<_>>(),
+        vec![(time(1, 0, 1), id("02")), (time(1, 1, 2), id("02"))]
+    );
+}
+
+#[test]
+fn union_with_reports_event_conflicts() {
+    let mut bms = parse(
+        "#BPM01 150
+#00108:01
+#00102:0.75
+",
+    );
+    let conflicts = bms.union_with::<KeyLayoutBeat>(
+        &parse(
+            "#BPM02 180
+#00108:02
+#00102:0.5
+#00202:0.5
+",
+        ),
+        &AlwaysUseNewer,
+    );
+
+    assert_eq!(
+        conflicts,
+        vec![
+            UnionConflict {
+                kind: UnionConflictKind::Track {
+                    track: Track(1),
+                    channel: Channel::SectionLen,
+                },
+                workaround: DuplicationWorkaround::UseNewer,
+            },
+            UnionConflict {
+                kind: UnionConflictKind::Channel {
+                    time: time(1, 0, 1),
+                    channel: Channel::BpmChange,
+                },
+                workaround: DuplicationWorkaround::UseNewer,
+            },
+        ]
+    );
+    assert_eq!(
+        bms.bpm
+            .bpm_changes
+            .get(&time(1, 0, 1))
+            .map(|change| change.bpm.as_f64()),
+        Some(180.0)
+    );
+    assert_eq!(bms.section_len.section_len_changes.len(), 2);
+}
+
+#[test]
+fn union_with_identical_scores_has_no_conflicts() {
+    let mut bms = parse(BASE);
+    let conflicts = bms.union_with::<KeyLayoutBeat>(&parse(BASE), &AlwaysWarnAndUseOlder);
+
+    assert_eq!(conflicts, vec![]);
+    assert_eq!(bms.wav.wav_files.len(), 2);
+}
+
+#[test]
+fn union_with_reports_notes_of_other_kinds_on_same_lane() {
+    let mut bms = parse("#00111:01\n");
+    let conflicts =
+        bms.union_with::<KeyLayoutBeat>(&parse("#00151:0202\n#00131:03\n"), &AlwaysUseNewer);
+
+    let long_key_1 =
+        KeyLayoutBeat::new(PlayerSide::Player1, NoteKind::Long, Key::Key(1)).to_channel_id();
+    assert_eq!(
+        conflicts,
+        vec![UnionConflict {
+            kind: UnionConflictKind::Channel {
+                time: time(1, 0, 1),
+                channel: Channel::Note {
+                    channel_id: long_key_1,
+                },
+            },
+            workaround: DuplicationWorkaround::UseNewer,
+        }]
+    );
+    // The invisible note does not occupy the lane, and the long note replaces the visible note.
+    let notes: Vec<_> = bms
+        .notes()
+        .all_notes()
+        .filter(|obj| !obj.wav_id.is_null())
+        .map(|obj| (obj.offset, obj.channel_id, obj.wav_id))
+        .collect();
+    assert_eq!(notes.len(), 3);
+    assert!(notes.contains(&(time(1, 0, 1), long_key_1, id("02"))));
+    assert_eq!(bms.long_notes::<KeyLayoutBeat>().long_notes.len(), 1);
+}
+
+#[test]
+fn union_with_drops_whole_long_note_losing_a_conflict() {
+    let mut bms = parse("#00111:01\n#00251:0102\n");
+    let conflicts = bms.union_with::<KeyLayoutBeat>(&parse("#00151:0102\n"), &AlwaysUseOlder);
+
+    assert_eq!(conflicts.len(), 1);
+    // The start of the newer long note loses to the older note, so its end is dropped too.
+    let long_notes: Vec<_> = bms
+        .long_notes::<KeyLayoutBeat>()
+        .long_notes
+        .into_iter()
+        .map(|ln| (ln.start, ln.end))
+        .collect();
+    assert_eq!(long_notes, vec![(time(2, 0, 1), time(2, 1, 2))]);
+    let mut times: Vec<_> = bms
+        .notes()
+        .all_notes()
+        .filter(|obj| !obj.wav_id.is_null())
+        .map(|obj| obj.offset)
+        .collect();
+    times.sort();
+    assert_eq!(times, vec![time(1, 0, 1), time(2, 0, 1), time(2, 1, 2)]);
+}
@@ -0,0 +1,102 @@
+use bms_rs::bms::prelude::*;
+use bms_rs::chart::prelude::*;
+
+use super::process_no_warnings;
+
+const SOURCE: &str = "#WAV01 scratch.wav
+#WAV02 long.wav
+#WAV06 six.wav
+#00116:01
+#00151:0202
+#001D2:0003
+#00118:06
+#00114:06
+";
+
+fn notes(chart: &Chart) -> Vec<(Key, NoteKind)> {
+    chart
+        .events()
+        .as_events()
+        .iter()
+        .filter_map(|ev| match ev.event() {
+            ChartEvent::Note { key, kind, .. } => Some((*key, *kind)),
+            _ => None,
+        })
+        .collect()
+}
+
+fn bgm_count(chart: &Chart) -> usize {
+    chart
+        .events()
+        .as_events()
+        .iter()
+        .filter(|ev| matches!(ev.event(), ChartEvent::Bgm { .. }))
+        .count()
+}
+
+#[test]
+fn test_chart_auto_scratch_keeps_keysounds_as_bgm() {
+    let mut chart = process_no_warnings(SOURCE);
+    let original_bgm = bgm_count(&chart);
+
+    let report = chart.apply_assist(Assist::AutoScratch);
+
+    assert_eq!(report.changed, 1);
+    assert_eq!(bgm_count(&chart), original_bgm + 1);
+    assert!(
+        notes(&chart)
+            .iter()
+            .all(|(key, _)| !matches!(key, Key::Scratch(_)))
+    );
+}
+
+#[test]
+fn test_chart_legacy_note_drops_lengths() {
+    let mut chart = process_no_warnings(SOURCE);
+
+    let report = chart.apply_assist(Assist::LegacyNote);
+
+    assert_eq!(report.changed, 1);
+    assert!(notes(&chart).contains(&(Key::Key(1), NoteKind::Visible)));
+    assert!(chart.events().as_events().iter().all(|ev| !matches!(
+        ev.event(),
+        ChartEvent::Note {
+            length: Some(_),
+            ..
+        }
+    )));
+}
+
+#[test]
+fn test_chart_no_landmine_removes_landmines() {
+    let mut chart = process_no_warnings(SOURCE);
+
+    let report = chart.apply_assist(Assist::NoLandmine);
+
+    assert_eq!(report.changed, 1);
+    assert!(!notes(&chart).contains(&(Key::Key(2), NoteKind::Landmine)));
+}
+
+#[test]
+fn test_chart_five_keys_turns_colliding_notes_into_bgm() {
+    let original = process_no_warnings(SOURCE);
+    let original_bgm = bgm_count(&original);
+
+    let mut pairwise = original.clone();
+    let report = pairwise.apply_assist(Assist::FiveKeys(FoldStrategy::Pairwise));
+    // The key 6 folds onto the key 4, which already has a note at the same position.
+    assert_eq!(report.changed, 1);
+    assert_eq!(bgm_count(&pairwise), original_bgm + 1);
+    assert_eq!(
+        notes(&pairwise)
+            .iter()
+            .filter(|&&(key, _)| key == Key::Key(4))
+            .count(),
+        1
+    );
+
+    let mut mirrored = original;
+    let mirrored_report = mirrored.apply_assist(Assist::FiveKeys(FoldStrategy::Mirrored));
+    assert_eq!(mirrored_report.changed, 1);
+    assert!(notes(&mirrored).contains(&(Key::Key(2), NoteKind::Visible)));
+}
@@ -0,0 +1,99 @@
+use bms_rs::bms::prelude::*;
+use bms_rs::chart::prelude::*;
+
+use super::parse_bms_no_warnings;
+
+const P1: PlayerSide = PlayerSide::Player1;
+
+/// - Notes on the key 1 at 2.0 and 2.5 seconds, and a landmine on it at 2.05 seconds.
+/// - A long note on the key 2 from 2.0 to 3.0 seconds.
+/// - A note on the scratch at 3.0 seconds, and an invisible note on the key 3.
+const SOURCE: &str = "#BPM 120
+#WAV01 a.wav
+#00111:01010000
+#001D1:00010000000000000000000000000000000000000000000000000000000000000000000000000000
+#00152:0101
+#00116:0001
+#00133:01
+";
+
+fn chart() -> (Bms, Chart) {
+    let bms = parse_bms_no_warnings(SOURCE, default_config().prompter(AlwaysUseNewer));
+    let chart = bms.process().expect("chart should be processed");
+    (bms, chart)
+}
+
+/// Judges the inputs, and returns the results.
+fn judge_all(bms: &Bms, chart: &Chart, inputs: &[KeyInput]) -> Vec<JudgeEvent> {
+    let mut judge = Judge::new(chart, JudgeConfig::from_bms(bms));
+    let mut events: Vec<_> = inputs
+        .iter()
+        .flat_map(|&input| judge.input(input))
+        .collect();
+    events.extend(judge.finish());
+    events
+}
+
+#[test]
+fn test_autoplay_presses_at_exact_times() {
+    let (bms, chart) = chart();
+    let inputs = chart.autoplay(&AutoplayConfig::default());
+
+    let key_1: Vec<_> = inputs
+        .iter()
+        .filter(|input| input.side == P1 && input.key == Key::Key(1))
+        .map(|input| (input.time, input.action))
+        .collect();
+    let ms = |millis: i64| TimeSpan::MILLISECOND * millis;
+    // Released just before the landmine, and after the default hold for the second note.
+    assert_eq!(
+        key_1,
+        vec![
+            (ms(2000), KeyAction::Press),
+            (ms(2050) - TimeSpan::new(1), KeyAction::Release),
+            (ms(2500), KeyAction::Press),
+            (ms(2580), KeyAction::Release),
+        ]
+    );
+    assert!(inputs.iter().any(|input| input.key == Key::Key(2)
+        && input.action == KeyAction::Release
+        && input.time == ms(3000)));
+    assert!(!inputs.iter().any(|input| input.key == Key::Key(3)));
+
+    let events = judge_all(&bms, &chart, &inputs);
+    assert_eq!(events.len(), 4);
+    assert!(
+        events
+            .iter()
+            .all(|event| event.judgment() == Some(Judgment::PGreat))
+    );
+}
+
+#[test]
+fn test_autoplay_jitter_is_bounded_and_deterministic() {
+    let (bms, chart) = chart();
+    let config = AutoplayConfig {
+        jitter: TimeSpan::MILLISECOND * 10,
+        seed: 7,
+        ..AutoplayConfig::default()
+    };
+    let inputs = chart.autoplay(&config);
+
+    assert_eq!(inputs, chart.autoplay(&config));
+    assert_ne!(inputs, chart.autoplay(&AutoplayConfig::default()));
+    assert!(inputs.windows(2).all(|pair| match pair {
+        [prev, next] => prev.time <= next.time,
+        _ => false,
+    }));
+    let events = judge_all(&bms, &chart, &inputs);
+    assert!(
+        events
+            .iter()
+            .all(|event| matches!(event.judgment(), Some(Judgment::PGreat | Judgment::Great)))
+    );
+
+    // The inputs can be stored as a replay.
+    let mut replay = Replay::new("hash", GaugeType::Groove, GaugeRules::Beatoraja);
+    replay.inputs = inputs;
+    assert_eq!(Replay::from_bytes(&replay.to_bytes()), Ok(replay));
+}
@@ -0,0 +1,66 @@
+use bms_rs::bms::prelude::*;
+use bms_rs::chart::prelude::*;
+use strict_num_extended::PositiveF64;
+
+use super::parse_bms_no_warnings;
+
+/// 2 measures at BPM 120 with six notes and a long stop, then 4 measures at BPM 180 with a note at the
+/// end.
+const SOURCE: &str = "#BPM 120
+#BPM01 180
+#WAV01 a.wav
+#STOP01 768
+#00011:01010101
+#00111:0101
+#00109:01
+#00208:01
+#00611:01
+";
+
+#[test]
+fn test_main_bpm_by_duration_ignores_stops() {
+    let bms = parse_bms_no_warnings(SOURCE, default_config().prompter(AlwaysUseNewer));
+
+    let main = MainBpmByDurationGenerator
+        .generate(&bms)
+        .expect("main BPM should be found");
+
+    assert_eq!(main, BaseBpm::new(PositiveF64::new_const(180.0)));
+}
+
+#[test]
+fn test_main_bpm_by_notes() {
+    let bms = parse_bms_no_warnings(SOURCE, default_config().prompter(AlwaysUseNewer));
+
+    let main = MainBpmByNotesGenerator
+        .generate(&bms)
+        .expect("main BPM should be found");
+
+    assert_eq!(main, BaseBpm::new(PositiveF64::new_const(120.0)));
+}
+
+#[test]
+fn test_average_bpm_weighted_by_time() {
+    let bms = parse_bms_no_warnings(SOURCE, default_config().prompter(AlwaysUseNewer));
+
+    let average = AverageBpmGenerator
+        .generate(&bms)
+        .expect("average BPM should be found");
+
+    // 4 seconds at BPM 120 and 16/3 seconds at BPM 180.
+    let expected = (120.0 * 4.0 + 180.0 * 16.0 / 3.0) / (4.0 + 16.0 / 3.0);
+    assert!(
+        (average.as_f64() - expected).abs() < 1e-9,
+        "expected {expected}, got {}",
+        average.as_f64()
+    );
+}
+
+#[test]
+fn test_main_bpm_without_notes() {
+    let bms = parse_bms_no_warnings("#BPM 150\n", default_config().prompter(AlwaysUseNewer));
+
+    assert!(MainBpmByNotesGenerator.generate(&bms).is_none());
+    assert!(MainBpmByDurationGenerator.generate(&bms).is_none());
+    assert!(AverageBpmGenerator.generate(&bms).is_none());
+}
@@ -0,0 +1,165 @@
+use gametime::TimeSpan;
+
+use bms_rs::bms::command::graphics::{PixelPoint, PixelSize};
+use bms_rs::bms::prelude::*;
+use bms_rs::chart::prelude::*;
+
+use super::process_no_warnings;
+
+/// At BPM 120, track 1 starts at 2 seconds and track 2 at 4 seconds.
+///
+/// - Track 1 shows the `#@BGA` crop `03` on the base layer at half opacity, and the `#BGA` crop
+///   `04` on the overlay layer from 3 seconds.
+/// - Track 2 shows the whole `01` on the base layer, tinted by `#ARGB01`.
+/// - `#BMP00` is shown over the BGA after a miss.
+/// - From track 1, pressing the key 1 of the player 1 plays `01` and `02` for 100 ms each, while the
+///   key is held.
+const SOURCE: &str = "#BPM 120
+#BMP00 poor.bmp
+#BMP01 base.bmp
+#EXBMP02 255,0,255,0 layer.bmp
+#@BGA03 01 10 20 100 50 5 6
+#BGA04 02 0 0 64 32 8 8
+#ARGB01 255,255,0,0
+#POORBGA 1
+#SWBGA01 100:0:11:0:255,0,0,0 0102
+#00104:03
+#00107:0004
+#0010B:80
+#001A5:01
+#00204:01
+#002A1:01
+";
+
+fn secs(value: i64) -> TimeSpan {
+    TimeSpan::SECOND * value
+}
+
+#[test]
+fn test_bga_layers_over_time() {
+    let chart = process_no_warnings(SOURCE);
+    let timeline = BgaTimeline::new(&chart);
+
+    let start = timeline.frame_at(secs(1), None);
+    assert!(start.base.is_none());
+    assert!(start.visible_layers().is_empty());
+
+    let first = timeline.frame_at(TimeSpan::MILLISECOND * 3500, None);
+    let base = first.base.expect("base layer should show");
+    assert_eq!(base.path, std::path::Path::new("base.bmp"));
+    assert_eq!(base.source, Some(BmpId::from(1)));
+    assert_eq!(
+        base.crop,
+        Some(BgaCrop {
+            top_left: PixelPoint::new(10, 20),
+            size: PixelSize::new(100, 50),
+        })
+    );
+    assert_eq!(base.draw_point, PixelPoint::new(5, 6));
+    assert_eq!(base.opacity, 0x80);
+    assert_eq!(base.argb, NO_TINT);
+
+    let overlay = first.overlay.expect("overlay layer should show");
+    assert_eq!(overlay.path, std::path::Path::new("layer.bmp"));
+    assert_eq!(
+        overlay.crop.map(|crop| crop.size),
+        Some(PixelSize::new(64, 32))
+    );
+    assert_eq!(
+        overlay.color_key,
+        Some(Argb {
+            alpha: 255,
+            red: 0,
+            green: 255,
+            blue: 0,
+        })
+    );
+
+    let second = timeline.frame_at(secs(5), None);
+    let plain = second.base.expect("base layer should show");
+    assert_eq!(plain.crop, None);
+    assert_eq!(plain.draw_point, PixelPoint::new(0, 0));
+    assert_eq!(
+        plain.argb,
+        Argb {
+            alpha: 255,
+            red: 255,
+            green: 0,
+            blue: 0,
+        }
+    );
+}
+
+#[test]
+fn test_poor_layer_after_miss() {
+    let chart = process_no_warnings(SOURCE);
+    let mut timeline = BgaTimeline::new(&chart);
+    timeline.set_poor_duration(TimeSpan::MILLISECOND * 500);
+
+    let missed = timeline.frame_at(secs(3), Some(TimeSpan::MILLISECOND * 2800));
+    assert!(missed.poor_showing);
+    let poor = missed.poor.expect("poor layer should exist");
+    assert_eq!(poor.path, std::path::Path::new("poor.bmp"));
+    assert_eq!(poor.bmp_id, None);
+    // `#POORBGA 1` overlays the POOR BGA onto the normal BGA.
+    let layers: Vec<_> = missed
+        .visible_layers()
+        .into_iter()
+        .map(|(layer, _)| layer)
+        .collect();
+    assert_eq!(
+        layers,
+        vec![BgaLayer::Base, BgaLayer::Overlay, BgaLayer::Poor]
+    );
+
+    let recovered = timeline.frame_at(secs(3), Some(TimeSpan::MILLISECOND * 2400));
+    assert!(!recovered.poor_showing);
+    assert_eq!(recovered.visible_layers().len(), 2);
+}
+
+#[test]
+fn test_keybound_animation_plays_while_key_is_held() {
+    let chart = process_no_warnings(SOURCE);
+    let mut timeline = BgaTimeline::new(&chart);
+    let ms = |millis: i64| TimeSpan::MILLISECOND * millis;
+    let path_at = |bga: &BgaTimeline, time| {
+        bga.frame_at(time, None)
+            .keybound
+            .map(|state| state.path.to_path_buf())
+    };
+
+    timeline.press_key(PlayerSide::Player1, Key::Key(1), secs(1));
+    assert_eq!(path_at(&timeline, ms(1500)), None);
+
+    timeline.press_key(PlayerSide::Player1, Key::Key(2), ms(2500));
+    assert_eq!(path_at(&timeline, ms(2550)), None);
+
+    timeline.press_key(PlayerSide::Player1, Key::Key(1), ms(2500));
+    let first = timeline
+        .frame_at(ms(2550), None)
+        .keybound
+        .expect("key-bound animation should play");
+    assert_eq!(first.path, std::path::Path::new("base.bmp"));
+    assert_eq!(
+        first.color_key,
+        Some(Argb {
+            alpha: 255,
+            red: 0,
+            green: 0,
+            blue: 0,
+        })
+    );
+    assert_eq!(
+        path_at(&timeline, ms(2650)),
+        Some("layer.bmp".into()),
+        "the second frame should show"
+    );
+    assert_eq!(
+        path_at(&timeline, ms(2850)),
+        Some("layer.bmp".into()),
+        "the last frame should stay without looping"
+    );
+
+    timeline.release_key(PlayerSide::Player1, Key::Key(1), ms(2900));
+    assert_eq!(path_at(&timeline, ms(2950)), None);
+}
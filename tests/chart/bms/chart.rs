@@ -2,7 +2,7 @@ use gametime::{TimeSpan, TimeStamp};
 
 use bms_rs::bms::command::channel::mapper::KeyLayoutBeat;
 use bms_rs::bms::prelude::*;
-use strict_num_extended::PositiveF64;
+use strict_num_extended::{NonNegativeF64, PositiveF64};
 
 /// Default BPM value (120.0) for tests
 const DEFAULT_BPM_120: PositiveF64 = PositiveF64::new_const(120.0);
@@ -94,3 +94,48 @@ fn test_parsed_chart_tracks_have_correct_y_coordinates_and_wav_ids() {
 
     assert_eq!(note_events, expected_events);
 }
+
+#[test]
+fn test_long_notes_of_every_encoding_yield_one_event() {
+    let sources = [
+        "#WAV01 a.wav\n#WAV02 b.wav\n#00151:0102\n",
+        "#LNTYPE 2\n#WAV01 a.wav\n#00151:01010000\n",
+        "#LNOBJ 02\n#WAV01 a.wav\n#WAV02 b.wav\n#00111:0102\n",
+    ];
+    for source in sources {
+        let config = default_config().prompter(AlwaysUseNewer);
+        let bms = parse_bms_no_warnings(source, config);
+        let chart = BmsProcessor::parse::<KeyLayoutBeat>(&bms).expect("failed to parse chart");
+
+        let note_events: Vec<_> = chart
+            .events()
+            .as_events()
+            .iter()
+            .filter_map(|ev| match ev.event() {
+                ChartEvent::Note {
+                    kind,
+                    wav_id,
+                    length,
+                    ..
+                } => Some((
+                    *ev.position(),
+                    *kind,
+                    *wav_id,
+                    length.map(NonNegativeF64::as_f64),
+                )),
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(
+            note_events,
+            vec![(
+                YCoordinate::ONE,
+                NoteKind::Long,
+                Some(WavId::new(1)),
+                Some(0.5)
+            )],
+            "source: {source}"
+        );
+    }
+}
@@ -0,0 +1,87 @@
+use gametime::{TimeSpan, TimeStamp};
+
+use bms_rs::chart::prelude::*;
+use strict_num_extended::{FinF64, PositiveF64};
+
+use super::{assert_time_close, process_no_warnings};
+
+/// BPM 150 in track 0, and BPM 300 from track 1.
+const SOURCE: &str = "#BPM 150
+#BPM01 300
+#WAV01 a.wav
+#00011:01
+#00108:01
+#00111:01
+";
+
+/// Seconds for which a note stays in the player's visible window at its current BPM.
+fn window_secs(player: &ChartPlayer) -> f64 {
+    let state = player.playback_state();
+    let window_y = player.visible_range_per_bpm().window_y(
+        state.current_bpm(),
+        state.current_speed(),
+        state.playback_ratio(),
+    );
+    window_y.as_f64() * 240.0 / state.current_bpm().as_f64()
+}
+
+#[test]
+fn test_lane_cover_sets_visibility() {
+    let chart = process_no_warnings(SOURCE);
+    let mut settings = DisplaySettings {
+        cover: LaneCover {
+            sudden: 250,
+            hidden: 0,
+            lift: 200,
+        },
+        hi_speed: PositiveF64::TWO,
+        ..DisplaySettings::default()
+    };
+    let visible_range = settings.visible_range_per_bpm();
+    let mut player = ChartPlayer::start(&chart, visible_range, TimeStamp::now());
+    settings.apply(&mut player);
+
+    // 800 ms for the whole lane, 800 of 1000 above the judgment line, 550 visible.
+    assert_time_close(0.64, window_secs(&player), "reaction time");
+    assert_time_close(
+        0.44,
+        settings
+            .green_number(PositiveF64::new_const(150.0))
+            .as_secs_f64(),
+        "green number",
+    );
+    let (std::ops::Bound::Included(bottom), std::ops::Bound::Included(top)) =
+        player.visibility_range()
+    else {
+        panic!("visibility range should be inclusive");
+    };
+    assert_eq!(bottom, FinF64::ZERO);
+    assert_time_close(550.0 / 800.0, top.as_f64(), "sudden+ edge");
+}
+
+#[test]
+fn test_floating_hi_speed_keeps_green_number() {
+    let chart = process_no_warnings(SOURCE);
+    let mut settings = DisplaySettings {
+        hi_speed: PositiveF64::TWO,
+        ..DisplaySettings::default()
+    };
+    let start = TimeStamp::now();
+    let mut player = ChartPlayer::start(&chart, settings.visible_range_per_bpm(), start);
+    settings.enable_floating(PositiveF64::new_const(150.0));
+    settings.apply(&mut player);
+    assert_time_close(0.8, window_secs(&player), "green number at BPM 150");
+
+    // Track 0 is 1.6 seconds long.
+    player.update(start + TimeSpan::MILLISECOND * 1700);
+    assert_time_close(300.0, player.playback_state().current_bpm().as_f64(), "bpm");
+    settings.apply(&mut player);
+    assert_time_close(1.0, settings.hi_speed.as_f64(), "floating hi-speed");
+    assert_time_close(0.8, window_secs(&player), "green number at BPM 300");
+
+    // A fixed hi-speed halves the green number instead.
+    settings.mode = HiSpeedMode::Fixed;
+    settings.hi_speed = PositiveF64::TWO;
+    settings.apply(&mut player);
+    assert_time_close(0.4, window_secs(&player), "fixed hi-speed");
+}
@@ -0,0 +1,87 @@
+use gametime::TimeSpan;
+
+use bms_rs::bms::prelude::*;
+use bms_rs::chart::prelude::*;
+
+use super::{assert_time_close, process_no_warnings};
+
+/// - `01` is a plain WAV with a `#WAVCMD` pitch.
+/// - `02` is an `#EXWAV` panned right at -6 dB and 22050 Hz, with a `#WAVCMD` volume of 50%.
+/// - `03` is a plain WAV with a `#WAVCMD` length.
+/// - Track 2 starts with a `#STP` of 500 milliseconds.
+const SOURCE: &str = "#BPM 120
+#WAV01 a.wav
+#WAV03 c.wav
+#EXWAV02 pvf 5000 -600 22050 b.wav
+#WAVCMD 00 01 72
+#WAVCMD 01 02 50
+#WAVCMD 02 03 1000
+#EXTCHR 3 01 0 0 32 32
+#EXTCHR 3 02 0 0 64 64
+#STP 002.000 500
+#00111:01
+#00211:02
+";
+
+#[test]
+fn test_wav_params() {
+    let chart = process_no_warnings(SOURCE);
+    let resources = chart.resources();
+    assert_eq!(
+        resources.wav_files().get(&WavId::from(2)),
+        Some(&"b.wav".into())
+    );
+
+    let plain = resources.wav_param(WavId::from(1));
+    assert_eq!(plain.pitch, Some(72));
+    assert_time_close(2.0, plain.pitch_ratio(), "pitch ratio");
+    assert_eq!(plain.length, None);
+    assert_time_close(0.0, plain.volume_db.as_f64(), "plain volume");
+    assert_eq!(
+        resources.wav_param(WavId::from(3)).length,
+        Some(TimeSpan::MILLISECOND * 500)
+    );
+
+    let ex = resources.wav_param(WavId::from(2));
+    assert_time_close(0.5, ex.pan.as_f64(), "pan");
+    assert_eq!(ex.frequency, Some(22050));
+    // -6 dB from `#EXWAV` and about -6 dB from the 50% of `#WAVCMD`.
+    assert_time_close(
+        -12.02,
+        (ex.volume_db.as_f64() * 100.0).round() / 100.0,
+        "volume",
+    );
+    assert_time_close(0.25, (ex.volume_factor() * 100.0).round() / 100.0, "factor");
+
+    assert_eq!(resources.wav_param(WavId::from(4)), WavParams::default());
+}
+
+#[test]
+fn test_character_sprite_and_millisecond_stop_events() {
+    let chart = process_no_warnings(SOURCE);
+    let sprites: Vec<_> = chart
+        .events()
+        .as_events()
+        .iter()
+        .filter_map(|event| match event.event() {
+            ChartEvent::Bms(BmsEvent::CharacterSpriteChange { event: sprite }) => {
+                Some((event.position().as_f64(), sprite.sprite_num, sprite.bmp_num))
+            }
+            _ => None,
+        })
+        .collect();
+    assert_eq!(sprites, vec![(0.0, 3, 1), (0.0, 3, 2)]);
+
+    let stops: Vec<_> = chart
+        .events()
+        .as_events()
+        .iter()
+        .filter_map(|event| match event.event() {
+            ChartEvent::Bms(BmsEvent::MillisecondStop { duration }) => {
+                Some((event.position().as_f64(), *duration))
+            }
+            _ => None,
+        })
+        .collect();
+    assert_eq!(stops, vec![(2.0, TimeSpan::MILLISECOND * 500)]);
+}
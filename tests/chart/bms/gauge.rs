@@ -0,0 +1,93 @@
+use bms_rs::bms::prelude::*;
+use bms_rs::chart::prelude::*;
+
+use super::{assert_time_close, parse_bms_no_warnings};
+
+const P1: PlayerSide = PlayerSide::Player1;
+
+fn parse(source: &str) -> Bms {
+    parse_bms_no_warnings(source, default_config().prompter(AlwaysUseNewer))
+}
+
+#[test]
+fn test_gauge_config_from_bms() {
+    let bms = parse("#TOTAL 300\n#WAV01 a.wav\n#00111:01010101\n");
+    let config = GaugeConfig::from_bms(&bms).expect("chart should be processed");
+    assert_time_close(300.0, config.total, "total");
+    assert_eq!(config.notes, 4);
+
+    // A long note counts its head and tail in CN, and `#TOTAL` defaults by the notes.
+    let cn = parse("#LNMODE 2\n#WAV01 a.wav\n#00111:01\n#00151:01\n#00251:01\n");
+    let cn_config = GaugeConfig::from_bms(&cn).expect("chart should be processed");
+    assert_eq!(cn_config.notes, 3);
+    assert_time_close(160.48, cn_config.total, "default total");
+}
+
+#[test]
+fn test_groove_gauge_recovers_by_total() {
+    let config = GaugeConfig::new(Some(200.0), 10);
+    let mut gauge = Gauge::new(GaugeType::Groove, GaugeRules::Beatoraja, config);
+    assert_time_close(20.0, gauge.value(), "initial");
+
+    gauge.apply(Judgment::PGreat);
+    gauge.apply(Judgment::Good);
+    assert_time_close(50.0, gauge.value(), "recovered");
+    assert!(!gauge.is_cleared());
+    for _ in 0..2 {
+        gauge.apply(Judgment::Great);
+    }
+    assert!(gauge.is_cleared());
+    for _ in 0..30 {
+        gauge.apply(Judgment::Poor);
+    }
+    // The groove gauge never fails and stays at the minimum.
+    assert_time_close(2.0, gauge.value(), "minimum");
+    assert!(!gauge.is_failed());
+}
+
+#[test]
+fn test_survival_gauges_fail_and_stay_failed() {
+    let config = GaugeConfig::new(None, 100);
+    let mut hard = Gauge::new(GaugeType::Hard, GaugeRules::Beatoraja, config);
+    for _ in 0..9 {
+        hard.apply(Judgment::Poor);
+    }
+    assert!(hard.is_cleared());
+    hard.apply(Judgment::Poor);
+    assert!(hard.is_failed());
+    assert!(!hard.is_cleared());
+    hard.apply(Judgment::PGreat);
+    assert_time_close(0.0, hard.value(), "failed");
+
+    let mut hazard = Gauge::new(GaugeType::Hazard, GaugeRules::Lr2, config);
+    hazard.apply(Judgment::EmptyPoor);
+    assert!(!hazard.is_failed());
+    hazard.apply(Judgment::Bad);
+    assert!(hazard.is_failed());
+
+    let mut ex_hard = Gauge::new(GaugeType::ExHard, GaugeRules::Lr2, config);
+    ex_hard.apply(Judgment::Bad);
+    assert_time_close(88.0, ex_hard.value(), "LR2 EX-HARD");
+}
+
+#[test]
+fn test_gauge_follows_judge_events() {
+    // A note at 2 seconds and a landmine at 3 seconds on the key 1.
+    let bms = parse("#BPM 120\n#WAV01 a.wav\n#00111:01\n#001D1:0001\n");
+    let chart = bms.process().expect("chart should be processed");
+    let mut judge = Judge::new(&chart, JudgeConfig::from_bms(&bms));
+    let config = GaugeConfig::from_bms(&bms).expect("chart should be processed");
+    let mut gauge = Gauge::new(GaugeType::Groove, GaugeRules::Beatoraja, config);
+    let ms = |millis: i64| TimeSpan::MILLISECOND * millis;
+
+    let mut events = judge.press(P1, Key::Key(1), ms(2000));
+    events.extend(judge.update(ms(3100)));
+    events.extend(judge.release(P1, Key::Key(1), ms(3200)));
+    events.extend(judge.finish());
+    for event in &events {
+        gauge.apply_event(event, &chart);
+    }
+
+    // 20 + 160 for the only note, then the landmine deals the damage of a POOR.
+    assert_time_close(94.0, gauge.value(), "gauge");
+}
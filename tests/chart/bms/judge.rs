@@ -0,0 +1,198 @@
+use bms_rs::bms::prelude::*;
+use bms_rs::chart::prelude::*;
+
+use super::parse_bms_no_warnings;
+
+const P1: PlayerSide = PlayerSide::Player1;
+
+/// At BPM 120, a measure takes 2 seconds.
+const HEADER: &str = "#BPM 120
+#WAV01 a.wav
+";
+
+fn ms(millis: i64) -> TimeSpan {
+    TimeSpan::MILLISECOND * millis
+}
+
+fn judge_of(body: &str) -> Judge {
+    let bms = parse_bms_no_warnings(
+        &format!("{HEADER}{body}"),
+        default_config().prompter(AlwaysUseNewer),
+    );
+    let chart = bms.process().expect("chart should be processed");
+    Judge::new(&chart, JudgeConfig::from_bms(&bms))
+}
+
+/// Returns the judged parts of notes and the judgments in the events.
+fn judgments(events: &[JudgeEvent]) -> Vec<(Option<NotePart>, Judgment)> {
+    events
+        .iter()
+        .filter_map(|event| match event.kind {
+            JudgeEventKind::Note { part, judgment, .. } => Some((Some(part), judgment)),
+            JudgeEventKind::EmptyPoor => Some((None, Judgment::EmptyPoor)),
+            _ => None,
+        })
+        .collect()
+}
+
+fn holds(events: &[JudgeEvent]) -> Vec<bool> {
+    events
+        .iter()
+        .filter_map(|event| match event.kind {
+            JudgeEventKind::HcnHold { holding, .. } => Some(holding),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn test_judge_notes_by_offset() {
+    // Key 1 at 2.0 and 2.5 seconds, and key 2 at 3.0 seconds.
+    let mut judge = judge_of("#00111:01010000\n#00112:00000100\n");
+
+    assert_eq!(
+        judgments(&judge.press(P1, Key::Key(3), ms(1000))),
+        vec![(None, Judgment::EmptyPoor)]
+    );
+    let events = judge.press(P1, Key::Key(1), ms(2010));
+    assert_eq!(
+        judgments(&events),
+        vec![(Some(NotePart::Whole), Judgment::PGreat)]
+    );
+    assert!(matches!(
+        events.first().map(|event| event.kind),
+        Some(JudgeEventKind::Note { offset: Some(offset), .. }) if offset == ms(10)
+    ));
+    judge.release(P1, Key::Key(1), ms(2100));
+    assert_eq!(
+        judgments(&judge.press(P1, Key::Key(1), ms(2450))),
+        vec![(Some(NotePart::Whole), Judgment::Good)]
+    );
+    assert!(!judge.is_finished());
+    assert_eq!(
+        judgments(&judge.finish()),
+        vec![(Some(NotePart::Whole), Judgment::Poor)]
+    );
+    assert!(judge.is_finished());
+}
+
+#[test]
+fn test_judge_misses_as_time_passes() {
+    let mut judge = judge_of("#00111:01\n");
+
+    assert!(judge.update(ms(2200)).is_empty());
+    let events = judge.update(ms(2201));
+    assert_eq!(
+        judgments(&events),
+        vec![(Some(NotePart::Whole), Judgment::Poor)]
+    );
+    assert_eq!(events.first().map(|event| event.time), Some(ms(2200)));
+    // The missed note is not judged by a late press.
+    assert_eq!(
+        judgments(&judge.press(P1, Key::Key(1), ms(2300))),
+        vec![(None, Judgment::EmptyPoor)]
+    );
+}
+
+#[test]
+fn test_judge_windows_from_rank_and_defexrank() {
+    let mut very_hard = judge_of("#RANK 0\n#00111:01\n");
+    assert_eq!(
+        judgments(&very_hard.press(P1, Key::Key(1), ms(2030))),
+        vec![(Some(NotePart::Whole), Judgment::Good)]
+    );
+
+    // `#DEFEXRANK` takes precedence over `#RANK`.
+    let mut wide = judge_of("#RANK 0\n#DEFEXRANK 200\n#00111:01\n");
+    assert_eq!(
+        judgments(&wide.press(P1, Key::Key(1), ms(2030))),
+        vec![(Some(NotePart::Whole), Judgment::PGreat)]
+    );
+}
+
+#[test]
+fn test_judge_windows_change_by_judge_events() {
+    // From 2 seconds, the windows are half of NORMAL.
+    let mut judge = judge_of("#EXRANK01 50\n#00111:0101\n#001A0:01\n");
+
+    assert_eq!(
+        judgments(&judge.press(P1, Key::Key(1), ms(2012))),
+        vec![(Some(NotePart::Whole), Judgment::Great)]
+    );
+}
+
+#[test]
+fn test_judge_long_note_modes() {
+    // A long note on the key 1 from 2 to 4 seconds.
+    let body = "#00151:01\n#00251:01\n";
+
+    let mut ln_held = judge_of(body);
+    assert!(ln_held.press(P1, Key::Key(1), ms(2000)).is_empty());
+    assert_eq!(
+        judgments(&ln_held.release(P1, Key::Key(1), ms(3900))),
+        vec![(Some(NotePart::Whole), Judgment::PGreat)]
+    );
+    let mut ln_released = judge_of(body);
+    ln_released.press(P1, Key::Key(1), ms(2000));
+    assert_eq!(
+        judgments(&ln_released.release(P1, Key::Key(1), ms(3000))),
+        vec![(Some(NotePart::Whole), Judgment::Poor)]
+    );
+
+    let mut cn_released = judge_of(&format!("#LNMODE 2\n{body}"));
+    assert_eq!(
+        judgments(&cn_released.press(P1, Key::Key(1), ms(2000))),
+        vec![(Some(NotePart::Head), Judgment::PGreat)]
+    );
+    assert_eq!(
+        judgments(&cn_released.release(P1, Key::Key(1), ms(4030))),
+        vec![(Some(NotePart::Tail), Judgment::Great)]
+    );
+    let mut cn_held = judge_of(&format!("#LNMODE 2\n{body}"));
+    cn_held.press(P1, Key::Key(1), ms(2000));
+    assert_eq!(
+        judgments(&cn_held.update(ms(4300))),
+        vec![(Some(NotePart::Tail), Judgment::Poor)]
+    );
+}
+
+#[test]
+fn test_judge_hcn_can_be_held_again() {
+    let mut hcn = judge_of("#LNMODE 3\n#00151:01\n#00251:01\n");
+
+    let head = hcn.press(P1, Key::Key(1), ms(2000));
+    assert_eq!(
+        judgments(&head),
+        vec![(Some(NotePart::Head), Judgment::PGreat)]
+    );
+    assert_eq!(holds(&head), vec![true]);
+    let early = hcn.release(P1, Key::Key(1), ms(3000));
+    assert!(judgments(&early).is_empty());
+    assert_eq!(holds(&early), vec![false]);
+    assert_eq!(holds(&hcn.press(P1, Key::Key(1), ms(3500))), vec![true]);
+    let tail = hcn.release(P1, Key::Key(1), ms(4000));
+    assert_eq!(
+        judgments(&tail),
+        vec![(Some(NotePart::Tail), Judgment::PGreat)]
+    );
+    assert_eq!(holds(&tail), vec![false]);
+    assert!(hcn.is_finished());
+}
+
+#[test]
+fn test_judge_landmine_explodes_while_held() {
+    // A landmine on the key 1 at 2 seconds and another at 3 seconds.
+    let mut judge = judge_of("#001D1:0101\n");
+
+    assert_eq!(
+        judgments(&judge.press(P1, Key::Key(1), ms(1900))),
+        vec![(None, Judgment::EmptyPoor)]
+    );
+    let events = judge.release(P1, Key::Key(1), ms(2500));
+    assert!(matches!(
+        events.as_slice(),
+        [JudgeEvent { time, kind: JudgeEventKind::Landmine { .. }, .. }] if *time == ms(2000)
+    ));
+    assert!(judge.finish().is_empty());
+    assert!(judge.is_finished());
+}
@@ -0,0 +1,81 @@
+use bms_rs::bms::prelude::*;
+use bms_rs::chart::prelude::*;
+
+use super::process_no_warnings;
+
+const DOUBLE_PLAY: &str = "#PLAYER 3
+#WAV01 a.wav
+#WAV02 b.wav
+#00111:01
+#00152:0202
+#001D3:01
+#00121:01
+#00162:0202
+";
+
+/// Lanes, kinds and lengths of the note events, sorted by id.
+fn notes(chart: &Chart) -> Vec<(PlayerSide, Key, NoteKind, Option<f64>)> {
+    chart
+        .events()
+        .as_events()
+        .iter()
+        .filter_map(|ev| match ev.event() {
+            ChartEvent::Note {
+                side,
+                key,
+                kind,
+                length,
+                ..
+            } => Some((*side, *key, *kind, length.map(NonNegativeF64::as_f64))),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn test_chart_convert_keys_on_each_side() {
+    let mut chart = process_no_warnings(DOUBLE_PLAY);
+    let keys: Vec<_> = (1..=7).map(Key::Key).collect();
+
+    chart.convert_keys(
+        PlayerSide::Player1,
+        &mut KeyMappingConvertMirror::new(keys.clone()),
+    );
+    chart.convert_keys(
+        PlayerSide::Player2,
+        &mut KeyMappingConvertMirror::new(keys.iter().take(3).copied().collect()),
+    );
+
+    let mut converted = notes(&chart);
+    converted.sort_by_key(|&(side, key, ..)| (side == PlayerSide::Player2, key.key_number()));
+    assert_eq!(
+        converted,
+        vec![
+            (PlayerSide::Player1, Key::Key(5), NoteKind::Landmine, None),
+            (PlayerSide::Player1, Key::Key(6), NoteKind::Long, Some(0.5)),
+            (PlayerSide::Player1, Key::Key(7), NoteKind::Visible, None),
+            (PlayerSide::Player2, Key::Key(2), NoteKind::Long, Some(0.5)),
+            (PlayerSide::Player2, Key::Key(3), NoteKind::Visible, None),
+        ]
+    );
+}
+
+#[test]
+fn test_chart_flip_keeps_long_notes() {
+    let mut chart = process_no_warnings(DOUBLE_PLAY);
+    let original = notes(&chart);
+
+    chart.convert_player_side_keys(&mut KeyMappingConvertFlip);
+
+    let flipped: Vec<_> = original
+        .into_iter()
+        .map(|(side, key, kind, length)| {
+            let side = match side {
+                PlayerSide::Player1 => PlayerSide::Player2,
+                PlayerSide::Player2 => PlayerSide::Player1,
+            };
+            (side, key, kind, length)
+        })
+        .collect();
+    assert_eq!(notes(&chart), flipped);
+}
@@ -0,0 +1,71 @@
+#![cfg(feature = "mixdown")]
+
+use std::path::Path;
+
+use bms_rs::chart::prelude::*;
+
+use super::{assert_time_close, process_no_warnings};
+
+/// Output sample rate, low enough to read frames by milliseconds.
+const SAMPLE_RATE: u32 = 1000;
+
+/// - `01` is a key sound of constant 0.5 for 1.5 seconds, played at 2 and 3 seconds.
+/// - `02` is a BGM of constant 0.25 for 0.2 seconds panned to the left, played at 2 seconds.
+/// - `#VOLWAV` halves all the sounds.
+const SOURCE: &str = "#BPM 120
+#VOLWAV 50
+#WAV01 key.wav
+#EXWAV02 p -10000 bgm.wav
+#00101:02
+#00111:0101
+";
+
+/// Provides constant sounds by the file names.
+struct ConstantSounds;
+
+impl SampleProvider for ConstantSounds {
+    fn load(&mut self, _id: WavId, path: &Path) -> Option<Samples> {
+        let (value, frames) = match path.to_str()? {
+            "key.wav" => (0.5, 1500),
+            "bgm.wav" => (0.25, 200),
+            _ => return None,
+        };
+        Some(Samples {
+            sample_rate: SAMPLE_RATE,
+            channels: 1,
+            data: vec![value; frames],
+        })
+    }
+}
+
+fn frame(mixdown: &Mixdown, millis: usize) -> (f32, f32) {
+    let samples = mixdown.samples();
+    (
+        samples.get(millis * 2).copied().unwrap_or_default(),
+        samples.get(millis * 2 + 1).copied().unwrap_or_default(),
+    )
+}
+
+#[test]
+fn test_mixdown_volumes_pan_and_cut() {
+    let chart = process_no_warnings(SOURCE);
+    let config = MixdownConfig {
+        sample_rate: SAMPLE_RATE,
+        ..MixdownConfig::default()
+    };
+    let mixdown = chart.mixdown(&mut ConstantSounds, &config);
+
+    assert_eq!(frame(&mixdown, 1000), (0.0, 0.0));
+    // The key sound on both channels, and the BGM on the left.
+    assert_eq!(frame(&mixdown, 2100), (0.375, 0.25));
+    assert_eq!(frame(&mixdown, 2500), (0.25, 0.25));
+    // The second key sound cuts the first, so they do not overlap.
+    assert_eq!(frame(&mixdown, 3200), (0.25, 0.25));
+    assert_time_close(4.5, mixdown.duration().as_secs_f64(), "duration");
+
+    let mut wav = Vec::new();
+    mixdown
+        .write_wav(&mut wav)
+        .expect("writing to a Vec should succeed");
+    assert_eq!(wav.len(), 44 + mixdown.samples().len() * 2);
+}
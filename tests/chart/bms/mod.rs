@@ -1,11 +1,32 @@
 //! Integration tests for `bms_rs::chart::BmsProcessor`.
 
+mod assist;
+mod autoplay;
+mod base_bpm;
+mod bga;
 mod chart;
+mod display;
+mod extended_commands;
+mod gauge;
+mod judge;
+mod key_convert;
+mod mixdown;
+mod note_end;
 mod playback_state;
+mod practice;
+mod random;
+mod replay;
+mod score;
 mod section;
+mod seek;
+mod stats;
+mod stop;
+mod timing;
 mod visible_events;
+mod voice;
 
 use bms_rs::bms::prelude::*;
+use bms_rs::chart::prelude::{Chart, Process};
 
 use super::{MICROSECOND_EPSILON, assert_time_close};
 
@@ -34,3 +55,15 @@ where
     assert_eq!(parse_warnings, vec![]);
     bms_res.expect("Failed to parse BMS in test setup")
 }
+
+/// Parse BMS source with the default config and process it into a chart, asserting no warnings.
+///
+/// # Panics
+///
+/// Panics if there are any lex or parse warnings, or the chart cannot be processed.
+#[must_use]
+pub fn process_no_warnings(source: &str) -> Chart {
+    parse_bms_no_warnings(source, default_config().prompter(AlwaysUseNewer))
+        .process()
+        .expect("chart should be processed")
+}
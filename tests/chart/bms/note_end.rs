@@ -0,0 +1,120 @@
+use bms_rs::bms::prelude::*;
+use bms_rs::chart::prelude::*;
+
+use super::{assert_time_close, process_no_warnings};
+
+/// A hell charge note on key 1 from track 1 to track 2, 2 seconds per track, and a note on key 2.
+const SOURCE: &str = "#BPM 120
+#LNTYPE 1
+#LNMODE 3
+#WAV01 a.wav
+#00151:01
+#00251:01
+#00112:01
+";
+
+fn long_note(chart: &Chart) -> &PlayheadEvent {
+    chart
+        .events()
+        .as_events()
+        .iter()
+        .find(|event| {
+            matches!(
+                event.event(),
+                ChartEvent::Note {
+                    kind: NoteKind::Long,
+                    ..
+                }
+            )
+        })
+        .expect("long note should exist")
+}
+
+/// Lanes, positions and times of the events of the long note other than its start.
+fn tail_events(chart: &Chart, start_id: ChartEventId) -> Vec<(Key, f64, f64)> {
+    chart
+        .events()
+        .as_events()
+        .iter()
+        .filter_map(|event| match event.event() {
+            ChartEvent::NoteEnd { key, .. } | ChartEvent::HcnTick { key, .. }
+                if event.event().long_note_start() == Some(start_id) =>
+            {
+                Some((
+                    *key,
+                    event.position().as_f64(),
+                    event.activate_time().as_secs_f64(),
+                ))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn test_long_note_end_event() {
+    let chart = process_no_warnings(SOURCE);
+    let start_id = long_note(&chart).id();
+
+    let ends: Vec<_> = chart
+        .events()
+        .as_events()
+        .iter()
+        .filter(|event| matches!(event.event(), ChartEvent::NoteEnd { .. }))
+        .collect();
+    let [end] = ends.as_slice() else {
+        panic!("expected one note end, got {ends:?}");
+    };
+    let ChartEvent::NoteEnd {
+        side,
+        key,
+        start_id: end_start_id,
+        ln_mode,
+    } = end.event()
+    else {
+        panic!("expected a note end, got {end:?}");
+    };
+    assert_eq!((*side, *key), (PlayerSide::Player1, Key::Key(1)));
+    assert_eq!(*end_start_id, start_id);
+    assert_eq!(*ln_mode, LnMode::Hcn);
+    assert_time_close(2.0, end.position().as_f64(), "end y");
+    assert_time_close(4.0, end.activate_time().as_secs_f64(), "end time");
+}
+
+#[test]
+fn test_hcn_ticks() {
+    let mut chart = process_no_warnings(SOURCE);
+    chart.add_hcn_ticks(TimeSpan::MILLISECOND * 500);
+
+    let events = tail_events(&chart, long_note(&chart).id());
+    assert_eq!(events.len(), 4);
+    let mut ys: Vec<_> = events.iter().map(|&(_, y, _)| y).collect();
+    ys.sort_by(f64::total_cmp);
+    for (expected, actual) in [1.25, 1.5, 1.75, 2.0].into_iter().zip(ys) {
+        assert_time_close(expected, actual, "tick y");
+    }
+    for &(_, y, secs) in &events {
+        assert_time_close(y * 2.0, secs, "tick time");
+    }
+
+    // The ticks are not added for other modes.
+    let mut cn = process_no_warnings(&SOURCE.replace("#LNMODE 3", "#LNMODE 2"));
+    cn.add_hcn_ticks(TimeSpan::MILLISECOND * 500);
+    assert_eq!(tail_events(&cn, long_note(&cn).id()).len(), 1);
+}
+
+#[test]
+fn test_note_end_follows_lane_conversion() {
+    let mut chart = process_no_warnings(SOURCE);
+    chart.add_hcn_ticks(TimeSpan::SECOND);
+    let keys: Vec<_> = (1..=7).map(Key::Key).collect();
+    chart.convert_keys(PlayerSide::Player1, &mut KeyMappingConvertMirror::new(keys));
+
+    let start_id = long_note(&chart).id();
+    let events = tail_events(&chart, start_id);
+    assert_eq!(events.len(), 2);
+    assert!(events.iter().all(|&(key, _, _)| key == Key::Key(7)));
+
+    let _ = chart.apply_assist(Assist::LegacyNote);
+    assert!(tail_events(&chart, start_id).is_empty());
+}
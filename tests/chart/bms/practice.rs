@@ -0,0 +1,138 @@
+use gametime::{TimeSpan, TimeStamp};
+
+use bms_rs::bms::command::time::Track;
+use bms_rs::bms::prelude::*;
+use bms_rs::chart::prelude::*;
+use strict_num_extended::{FinF64, PositiveF64};
+
+use super::{assert_time_close, process_no_warnings};
+
+/// Notes at the start of tracks 1 to 4, 2 seconds per track, and a long note from the middle of
+/// track 2 to the middle of track 3.
+const SOURCE: &str = "#BPM 120
+#LNTYPE 1
+#WAV01 a.wav
+#00112:01
+#00212:01
+#00312:01
+#00412:01
+#00251:0001
+#00351:0001
+";
+
+fn ms(millis: i64) -> TimeSpan {
+    TimeSpan::MILLISECOND * millis
+}
+
+fn start(chart: &Chart, config: PracticeConfig, start: TimeStamp) -> PracticePlayer<'_> {
+    let visible_range =
+        VisibleRangePerBpm::new(&PositiveF64::new_const(120.0), TimeSpan::MILLISECOND * 600);
+    let range = LoopRange::from_tracks(chart, Track(1), Track(3)).expect("tracks should exist");
+    PracticePlayer::start(chart, visible_range, range, config, start)
+}
+
+/// Positions of the visible notes.
+fn note_ys(events: &[PlayheadEvent]) -> Vec<f64> {
+    events
+        .iter()
+        .filter(|event| {
+            matches!(
+                event.event(),
+                ChartEvent::Note {
+                    kind: NoteKind::Visible,
+                    ..
+                }
+            )
+        })
+        .map(|event| event.position().as_f64())
+        .collect()
+}
+
+fn long_note_count(events: &[PlayheadEvent]) -> usize {
+    events
+        .iter()
+        .filter(|event| {
+            matches!(
+                event.event(),
+                ChartEvent::Note {
+                    kind: NoteKind::Long,
+                    ..
+                }
+            )
+        })
+        .count()
+}
+
+#[test]
+fn test_loop_triggers_each_note_once() {
+    let chart = process_no_warnings(SOURCE);
+    let now = TimeStamp::now();
+    let mut practice = start(&chart, PracticeConfig::default(), now);
+
+    let first = practice.update(now + ms(1));
+    assert!(!first.restarted);
+    assert_eq!(note_ys(&first.events), vec![1.0]);
+
+    let second = practice.update(now + ms(2100));
+    assert_eq!(note_ys(&second.events), vec![2.0]);
+    assert_eq!(long_note_count(&second.events), 0);
+    assert_eq!(long_note_count(&practice.update(now + ms(3100)).events), 1);
+
+    // The note at the end of the range is not triggered, and the next iteration starts.
+    let looped = practice.update(now + ms(4100));
+    assert!(looped.restarted);
+    assert_eq!(note_ys(&looped.events), vec![1.0]);
+    assert_eq!(practice.iteration(), 1);
+    assert_time_close(
+        1.05,
+        practice.player().playback_state().progressed_y().as_f64(),
+        "y after loop",
+    );
+    assert!(note_ys(&practice.update(now + ms(4200)).events).is_empty());
+}
+
+#[test]
+fn test_lead_in_and_ratio_ramp() {
+    let chart = process_no_warnings(SOURCE);
+    let now = TimeStamp::now();
+    let config = PracticeConfig {
+        lead_in: ms(1000),
+        ratio_step: FinF64::new(0.5).expect("finite"),
+        max_ratio: FinF64::new(1.5).expect("finite"),
+        ..PracticeConfig::default()
+    };
+    let mut practice = start(&chart, config, now);
+
+    // The iteration starts half a track before the range.
+    assert!(note_ys(&practice.update(now + ms(900)).events).is_empty());
+    assert_eq!(note_ys(&practice.update(now + ms(1100)).events), vec![1.0]);
+
+    assert!(practice.update(now + ms(5000)).restarted);
+    assert_time_close(
+        1.5,
+        practice.player().playback_state().playback_ratio().as_f64(),
+        "ratio",
+    );
+    // The ratio stays at the max.
+    assert!(practice.update(now + ms(9000)).restarted);
+    assert_eq!(practice.iteration(), 2);
+    assert_time_close(
+        1.5,
+        practice.player().playback_state().playback_ratio().as_f64(),
+        "max ratio",
+    );
+}
+
+#[test]
+fn test_skip_straddling_long_notes() {
+    let chart = process_no_warnings(SOURCE);
+    let now = TimeStamp::now();
+    let config = PracticeConfig {
+        straddling_notes: StraddlingNotes::Skip,
+        ..PracticeConfig::default()
+    };
+    let mut practice = start(&chart, config, now);
+
+    practice.update(now + ms(1));
+    assert_eq!(long_note_count(&practice.update(now + ms(3100)).events), 0);
+}
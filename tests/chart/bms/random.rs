@@ -0,0 +1,180 @@
+use gametime::TimeSpan;
+
+use bms_rs::bms::prelude::*;
+use bms_rs::chart::prelude::*;
+
+use super::process_no_warnings;
+
+/// Jacks on the key 1 and chords on the keys 2 and 4, with a long note on the key 3 under them.
+const ROWS: &str = "#BPM 120
+#WAV01 a.wav
+#00111:0101010101010101
+#00112:0000000001000000
+#00114:0100000001000000
+#00153:0101
+#001D1:01
+";
+
+fn keys() -> Vec<Key> {
+    (1..=7).map(Key::Key).collect()
+}
+
+/// Keys of the notes in each row, with the kinds of the notes, sorted by id in each row.
+fn rows(chart: &Chart) -> Vec<Vec<(u8, NoteKind)>> {
+    chart
+        .events()
+        .as_by_y()
+        .values()
+        .map(|range| {
+            chart
+                .events()
+                .as_events()
+                .iter()
+                .skip(range.start)
+                .take(range.len())
+                .filter_map(|ev| match ev.event() {
+                    ChartEvent::Note {
+                        side: PlayerSide::Player1,
+                        key,
+                        kind,
+                        ..
+                    } => Some((key.key_number()?, *kind)),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        })
+        .filter(|row| !row.is_empty())
+        .collect()
+}
+
+fn lanes(chart: &Chart) -> Vec<Vec<u8>> {
+    rows(chart)
+        .into_iter()
+        .map(|row| {
+            let mut lanes: Vec<_> = row.into_iter().map(|(key, _)| key).collect();
+            lanes.sort_unstable();
+            lanes
+        })
+        .collect()
+}
+
+/// Returns the key of the long note and the keys of the notes placed while it is held.
+fn long_note_and_notes_under_it(chart: &Chart) -> (u8, Vec<u8>) {
+    let rows = rows(chart);
+    let long_note = rows
+        .iter()
+        .flatten()
+        .find(|(_, kind)| *kind == NoteKind::Long)
+        .map(|&(key, _)| key)
+        .expect("long note should remain");
+    let under = rows
+        .iter()
+        .take(5)
+        .flatten()
+        .filter(|(_, kind)| *kind != NoteKind::Long)
+        .map(|&(key, _)| key)
+        .collect();
+    (long_note, under)
+}
+
+#[test]
+fn test_s_random_pins_sequence_for_seed() {
+    let mut chart = process_no_warnings(ROWS);
+    chart.s_random(PlayerSide::Player1, &keys(), 12345);
+    assert_eq!(
+        lanes(&chart),
+        vec![
+            vec![2, 5, 6, 6],
+            vec![1],
+            vec![2],
+            vec![6],
+            vec![1, 2, 4],
+            vec![2],
+            vec![2],
+            vec![2],
+        ]
+    );
+
+    let mut other = process_no_warnings(ROWS);
+    other.s_random(PlayerSide::Player1, &keys(), 12345);
+    assert_eq!(lanes(&other), lanes(&chart));
+}
+
+#[test]
+fn test_h_random_pins_sequence_for_seed() {
+    let mut chart = process_no_warnings(ROWS);
+    chart.h_random(
+        PlayerSide::Player1,
+        &keys(),
+        4752,
+        TimeSpan::MILLISECOND * 200,
+    );
+    assert_eq!(
+        lanes(&chart),
+        vec![
+            vec![1, 1, 2, 3],
+            vec![5],
+            vec![5],
+            vec![4],
+            vec![3, 4, 7],
+            vec![2],
+            vec![7],
+            vec![5],
+        ]
+    );
+}
+
+#[test]
+fn test_long_note_holds_its_lane() {
+    for seed in 0..32 {
+        let mut chart = process_no_warnings(ROWS);
+        chart.s_random(PlayerSide::Player1, &keys(), seed);
+        let (long_note, under) = long_note_and_notes_under_it(&chart);
+        assert!(
+            !under.contains(&long_note),
+            "seed {seed}: note placed on the held lane {long_note}: {under:?}"
+        );
+    }
+}
+
+#[test]
+fn test_note_falls_onto_lane_released_first_without_free_lanes() {
+    // The long note on the key 1 ends on the row of the notes on the keys 1 and 2.
+    const SOURCE: &str = "#BPM 120
+#WAV01 a.wav
+#00111:0001
+#00112:0001
+#00151:0101
+";
+    let keys = [Key::Key(1), Key::Key(2)];
+    for seed in [0, 1, 42, 1234, -5, 4752, 12345, 31337] {
+        let mut chart = process_no_warnings(SOURCE);
+        chart.s_random(PlayerSide::Player1, &keys, seed);
+        let lanes = lanes(&chart);
+        assert_eq!(lanes.last(), Some(&vec![1, 2]), "seed {seed}: {lanes:?}");
+    }
+}
+
+#[test]
+fn test_h_random_avoids_jacks() {
+    // Rows are 250ms apart at BPM 120, so a threshold of 300ms forbids every jack.
+    for seed in 0..32 {
+        let mut chart = process_no_warnings(ROWS);
+        chart.h_random(
+            PlayerSide::Player1,
+            &keys(),
+            seed,
+            TimeSpan::MILLISECOND * 300,
+        );
+        let lanes = lanes(&chart);
+        for pair in lanes.windows(2) {
+            let [previous, next] = pair else {
+                continue;
+            };
+            assert!(
+                next.iter().all(|key| !previous.contains(key)),
+                "seed {seed}: jack in {lanes:?}"
+            );
+        }
+    }
+}
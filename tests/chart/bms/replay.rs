@@ -0,0 +1,130 @@
+use bms_rs::bms::prelude::*;
+use bms_rs::chart::prelude::*;
+
+use super::parse_bms_no_warnings;
+
+const HASH: &str = "0123456789abcdef";
+
+/// A note on the key 1 at 2 seconds, a long note on the scratch from 2.5 to 3 seconds and a note on
+/// the key 2 of the player 2 at 3.5 seconds.
+const SOURCE: &str = "#BPM 120
+#WAV01 a.wav
+#00111:01
+#00156:00010100
+#00122:00000001
+";
+
+fn ms(millis: i64) -> TimeSpan {
+    TimeSpan::MILLISECOND * millis
+}
+
+fn input(millis: i64, side: PlayerSide, key: Key, action: KeyAction) -> KeyInput {
+    KeyInput {
+        time: ms(millis),
+        side,
+        key,
+        action,
+    }
+}
+
+fn replay() -> Replay {
+    let mut replay = Replay::new(HASH, GaugeType::Hard, GaugeRules::Lr2);
+    replay.random_seed = Some(-42);
+    let p1 = PlayerSide::Player1;
+    let p2 = PlayerSide::Player2;
+    replay
+        .lane_options
+        .insert(p1, (LaneOption::SRandom, 1 << 40));
+    replay.lane_options.insert(p2, (LaneOption::Mirror, 0));
+    for recorded in [
+        input(-100, p1, Key::Key(3), KeyAction::Press),
+        input(-50, p1, Key::Key(3), KeyAction::Release),
+        input(2010, p1, Key::Key(1), KeyAction::Press),
+        input(2080, p1, Key::Key(1), KeyAction::Release),
+        input(2500, p1, Key::Scratch(1), KeyAction::Press),
+        input(3000, p1, Key::Scratch(1), KeyAction::Release),
+        input(3560, p2, Key::Key(2), KeyAction::Press),
+        input(3600, p2, Key::Key(2), KeyAction::Release),
+    ] {
+        replay.record(recorded);
+    }
+    replay
+}
+
+#[test]
+fn test_replay_round_trips_through_bytes() {
+    let replay = replay();
+    let bytes = replay.to_bytes();
+
+    assert_eq!(Replay::from_bytes(&bytes), Ok(replay));
+    assert_eq!(
+        Replay::from_bytes(bytes.split_last().map_or(&[], |(_, rest)| rest)),
+        Err(ReplayError::UnexpectedEof)
+    );
+    let mut trailing = bytes;
+    trailing.push(0);
+    assert_eq!(
+        Replay::from_bytes(&trailing),
+        Err(ReplayError::TrailingBytes(1))
+    );
+    assert_eq!(
+        Replay::from_bytes(b"BMSX\x01"),
+        Err(ReplayError::InvalidMagic)
+    );
+}
+
+#[test]
+fn test_replay_round_trips_extreme_times() {
+    let mut replay = Replay::new(HASH, GaugeType::Easy, GaugeRules::Beatoraja);
+    for (nanos, side) in [
+        (i64::MIN, PlayerSide::Player1),
+        (i64::MAX, PlayerSide::Player1),
+        (i64::MAX, PlayerSide::Player2),
+    ] {
+        replay.record(KeyInput {
+            time: TimeSpan::new(nanos),
+            side,
+            key: Key::Key(1),
+            action: KeyAction::Release,
+        });
+    }
+
+    assert_eq!(Replay::from_bytes(&replay.to_bytes()), Ok(replay));
+}
+
+#[test]
+fn test_replay_verifies_claimed_result() {
+    let bms = parse_bms_no_warnings(SOURCE, default_config().prompter(AlwaysUseNewer));
+    let chart = bms.process().expect("chart should be processed");
+    let judge_config = JudgeConfig::from_bms(&bms);
+    let gauge_config = GaugeConfig::from_bms(&bms).expect("chart should be processed");
+    let replay = Replay::from_bytes(&replay().to_bytes()).expect("replay should be decoded");
+
+    let result = replay.simulate(&chart, judge_config, gauge_config);
+    assert_eq!(result.counts.pgreat, 2);
+    assert_eq!(result.counts.good, 1);
+    assert_eq!(result.counts.empty_poor, 1);
+    assert_eq!(result.max_combo, 3);
+    assert_eq!(result.lamp, ClearLamp::FullCombo);
+    assert_eq!(
+        replay.verify(HASH, &chart, judge_config, gauge_config, &result),
+        Ok(result.clone())
+    );
+
+    let mut claimed = result.clone();
+    claimed.ex_score += 1;
+    assert!(matches!(
+        replay.verify(HASH, &chart, judge_config, gauge_config, &claimed),
+        Err(ReplayError::ResultMismatch { .. })
+    ));
+    assert!(matches!(
+        replay.verify(
+            "fedcba9876543210",
+            &chart,
+            judge_config,
+            gauge_config,
+            &result
+        ),
+        Err(ReplayError::ChartHashMismatch { .. })
+    ));
+}
@@ -0,0 +1,67 @@
+use bms_rs::bms::prelude::*;
+use bms_rs::chart::prelude::*;
+
+use super::parse_bms_no_warnings;
+
+const P1: PlayerSide = PlayerSide::Player1;
+
+/// Notes on the key 1 at 2.0, 2.5, 3.0 and 3.5 seconds.
+const SOURCE: &str = "#BPM 120\n#TOTAL 400\n#WAV01 a.wav\n#00111:01010101\n";
+
+fn ms(millis: i64) -> TimeSpan {
+    TimeSpan::MILLISECOND * millis
+}
+
+/// Plays the notes with the offsets in milliseconds, and returns the result.
+fn play(gauge_type: GaugeType, offsets: &[i64]) -> PlayResult {
+    let bms = parse_bms_no_warnings(SOURCE, default_config().prompter(AlwaysUseNewer));
+    let chart = bms.process().expect("chart should be processed");
+    let config = GaugeConfig::from_bms(&bms).expect("chart should be processed");
+    let mut judge = Judge::new(&chart, JudgeConfig::from_bms(&bms));
+    let mut score = Score::new(config.notes);
+    let mut gauge = Gauge::new(gauge_type, GaugeRules::Beatoraja, config);
+    let mut events = vec![];
+    for (&offset, start) in offsets.iter().zip([2000, 2500, 3000, 3500]) {
+        events.extend(judge.press(P1, Key::Key(1), ms(start + offset)));
+        events.extend(judge.release(P1, Key::Key(1), ms(start + offset + 50)));
+    }
+    events.extend(judge.finish());
+    for event in &events {
+        score.apply_event(event);
+        gauge.apply_event(event, &chart);
+    }
+    PlayResult::new(&score, &gauge)
+}
+
+#[test]
+fn test_score_of_perfect_play() {
+    let max = play(GaugeType::Groove, &[0, 0, 0, 0]);
+    assert_eq!(max.ex_score, 8);
+    assert_eq!(max.max_combo, 4);
+    assert_eq!(max.rank, Rank::Aaa);
+    assert_eq!(max.lamp, ClearLamp::Max);
+
+    let perfect = play(GaugeType::Groove, &[0, 30, 0, 0]);
+    assert_eq!(perfect.counts.great, 1);
+    assert_eq!(perfect.ex_score, 7);
+    assert_eq!(perfect.rank, Rank::Aa);
+    assert_eq!(perfect.lamp, ClearLamp::Perfect);
+}
+
+#[test]
+fn test_score_lamps_by_gauge() {
+    // The third note is missed by pressing too early, which is an empty POOR and then a POOR.
+    let offsets = [0, 0, -450, 0];
+    let normal = play(GaugeType::Groove, &offsets);
+    assert_eq!(normal.counts.poor, 1);
+    assert_eq!(normal.counts.empty_poor, 1);
+    assert_eq!(normal.miss_count, 2);
+    assert_eq!(normal.max_combo, 2);
+    assert_eq!(normal.lamp, ClearLamp::Normal);
+
+    let hard = play(GaugeType::Hard, &offsets);
+    assert_eq!(hard.lamp, ClearLamp::Hard);
+    let hazard = play(GaugeType::Hazard, &offsets);
+    assert_eq!(hazard.lamp, ClearLamp::Failed);
+    assert!(ClearLamp::Failed < ClearLamp::Easy && ClearLamp::ExHard < ClearLamp::FullCombo);
+}
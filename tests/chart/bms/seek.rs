@@ -0,0 +1,206 @@
+use gametime::{TimeSpan, TimeStamp};
+
+use bms_rs::bms::prelude::*;
+use bms_rs::chart::prelude::*;
+use strict_num_extended::{NonNegativeF64, PositiveF64};
+
+use super::{assert_time_close, process_no_warnings};
+
+/// - Track 1 starts a BGA and a long note until track 2, at BPM 120 (2 seconds per track).
+/// - Track 2 changes the BPM to 240 (1 second per track).
+/// - Track 3 sets the BGM volume.
+/// - Track 4 stops for a measure, for 1 second at BPM 240.
+const SOURCE: &str = "#BPM 120
+#BPM01 240
+#LNTYPE 1
+#WAV01 a.wav
+#BMP01 a.bmp
+#STOP01 192
+#00104:01
+#00151:01
+#00251:01
+#00208:01
+#00397:40
+#00409:01
+#00511:01
+";
+
+fn ms(millis: i64) -> TimeSpan {
+    TimeSpan::MILLISECOND * millis
+}
+
+fn y(value: f64) -> YCoordinate {
+    YCoordinate::new(NonNegativeF64::new(value).expect("y should be non-negative"))
+}
+
+fn player(chart: &Chart, start: TimeStamp) -> ChartPlayer<'_> {
+    let visible_range =
+        VisibleRangePerBpm::new(&PositiveF64::new_const(120.0), TimeSpan::MILLISECOND * 600);
+    ChartPlayer::start(chart, visible_range, start)
+}
+
+fn has_long_note(events: &[PlayheadEvent]) -> bool {
+    events.iter().any(|event| {
+        matches!(
+            event.event(),
+            ChartEvent::Note {
+                kind: NoteKind::Long,
+                ..
+            }
+        )
+    })
+}
+
+fn has_bga(events: &[PlayheadEvent]) -> bool {
+    events
+        .iter()
+        .any(|event| matches!(event.event(), ChartEvent::BgaChange { .. }))
+}
+
+fn has_bgm_volume(events: &[PlayheadEvent]) -> bool {
+    events.iter().any(|event| {
+        matches!(
+            event.event(),
+            ChartEvent::Bms(BmsEvent::BgmVolumeChange { .. })
+        )
+    })
+}
+
+#[test]
+fn test_seek_to_y_rebuilds_state() {
+    let chart = process_no_warnings(SOURCE);
+    let start = TimeStamp::now();
+    let mut player = player(&chart, start);
+
+    let snapshot = player.seek_to_y(y(1.5), start);
+    assert!(has_bga(&snapshot));
+    assert!(has_long_note(&snapshot));
+    assert!(!has_bgm_volume(&snapshot));
+    assert_time_close(3.0, player.playhead_time().as_secs_f64(), "playhead");
+    assert_time_close(120.0, player.playback_state().current_bpm().as_f64(), "bpm");
+
+    // Playback continues from the seeked position.
+    player.update(start + ms(1000));
+    assert_time_close(
+        2.0,
+        player.playback_state().progressed_y().as_f64(),
+        "y after update",
+    );
+    assert_time_close(
+        240.0,
+        player.playback_state().current_bpm().as_f64(),
+        "bpm after update",
+    );
+}
+
+#[test]
+fn test_seek_to_time() {
+    let chart = process_no_warnings(SOURCE);
+    let start = TimeStamp::now();
+    let mut player = player(&chart, start);
+
+    let snapshot = player.seek_to_time(ms(5500), start);
+    assert!(has_bga(&snapshot));
+    assert!(!has_long_note(&snapshot));
+    assert!(has_bgm_volume(&snapshot));
+    assert_time_close(3.5, player.playback_state().progressed_y().as_f64(), "y");
+    assert_time_close(240.0, player.playback_state().current_bpm().as_f64(), "bpm");
+
+    // A time in a stop keeps the rest of the stop.
+    player.seek_to_time(ms(6500), start);
+    assert_time_close(
+        4.0,
+        player.playback_state().progressed_y().as_f64(),
+        "y in stop",
+    );
+    assert_time_close(
+        0.5,
+        player.playback_state().stop_remaining().as_secs_f64(),
+        "stop remaining",
+    );
+    player.update(start + ms(400));
+    assert_time_close(
+        4.0,
+        player.playback_state().progressed_y().as_f64(),
+        "y in stop after update",
+    );
+    let events = player.update(start + ms(1600));
+    assert!(
+        events
+            .iter()
+            .any(|event| matches!(event.event(), ChartEvent::Note { .. }))
+    );
+}
+
+#[test]
+fn test_seek_ignores_speed_factors() {
+    let chart = process_no_warnings(
+        "#BPM 120
+#WAV01 a.wav
+#SPEED01 2
+#001SP:01
+#00211:01
+",
+    );
+    let start = TimeStamp::now();
+    let mut player = player(&chart, start);
+
+    player.seek_to_y(y(2.0), start);
+    assert_time_close(4.0, player.playhead_time().as_secs_f64(), "playhead");
+    player.seek_to_time(ms(3000), start);
+    assert_time_close(1.5, player.playback_state().progressed_y().as_f64(), "y");
+}
+
+#[test]
+fn test_seek_to_track() {
+    let chart = process_no_warnings(SOURCE);
+    let start = TimeStamp::now();
+    let mut player = player(&chart, start);
+
+    player
+        .seek_to_track(2, start)
+        .expect("track 2 should have a bar line");
+    assert_time_close(2.0, player.playback_state().progressed_y().as_f64(), "y");
+    // Events at the track start are treated as passed.
+    assert_time_close(240.0, player.playback_state().current_bpm().as_f64(), "bpm");
+    assert_time_close(4.0, player.playhead_time().as_secs_f64(), "playhead");
+
+    // A stop at the track start is not yet consumed.
+    player
+        .seek_to_track(4, start)
+        .expect("track 4 should have a bar line");
+    assert_time_close(
+        1.0,
+        player.playback_state().stop_remaining().as_secs_f64(),
+        "stop remaining",
+    );
+
+    assert!(player.seek_to_track(99, start).is_none());
+}
+
+#[test]
+fn test_pause_and_resume() {
+    let chart = process_no_warnings(SOURCE);
+    let start = TimeStamp::now();
+    let mut player = player(&chart, start);
+
+    player.update(start + ms(1000));
+    player.pause(start + ms(1000));
+    assert!(player.is_paused());
+    assert!(player.update(start + ms(3000)).is_empty());
+    assert_time_close(
+        0.5,
+        player.playback_state().progressed_y().as_f64(),
+        "y while paused",
+    );
+
+    player.resume(start + ms(5000));
+    assert!(!player.is_paused());
+    player.update(start + ms(6000));
+    assert_time_close(
+        1.0,
+        player.playback_state().progressed_y().as_f64(),
+        "y after resume",
+    );
+    assert_time_close(2.0, player.playhead_time().as_secs_f64(), "playhead");
+}
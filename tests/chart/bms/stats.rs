@@ -0,0 +1,88 @@
+use bms_rs::bms::prelude::*;
+use bms_rs::chart::prelude::*;
+use strict_num_extended::PositiveF64;
+
+use super::{assert_time_close, parse_bms_no_warnings};
+
+/// - Track 0 at BPM 120 has a scratch and four notes on the key 1.
+/// - Track 1 at BPM 240 has two notes on the key 2, a long note on the key 1 until the end of it and
+///   an invisible note.
+const SOURCE: &str = "#BPM 120
+#BPM01 240
+#WAV01 a.wav
+#00016:01
+#00011:01010101
+#00108:01
+#00112:0101
+#00131:01
+#00151:0001
+#00251:01
+";
+
+#[test]
+fn test_chart_stats_counts_notes() {
+    let bms = parse_bms_no_warnings(SOURCE, default_config().prompter(AlwaysUseNewer));
+
+    let stats = bms.chart_stats().expect("chart should be processed");
+
+    assert_eq!(
+        stats.notes,
+        NoteKindCounts {
+            visible: 7,
+            invisible: 1,
+            long: 1,
+            landmine: 0,
+        }
+    );
+    assert_eq!(stats.notes.playable(), 8);
+    let lane = |key| {
+        stats
+            .lanes
+            .iter()
+            .find(|lane| lane.side == PlayerSide::Player1 && lane.key == key)
+            .map(|lane| lane.count)
+    };
+    assert_eq!(lane(Key::Scratch(1)), Some(1));
+    assert_eq!(lane(Key::Key(1)), Some(5));
+    assert_eq!(lane(Key::Key(2)), Some(2));
+    assert_eq!(stats.lanes.len(), 3);
+    assert_time_close(0.125, stats.scratch_ratio, "scratch ratio");
+    assert_eq!(stats.density_per_measure, vec![5, 3]);
+}
+
+#[test]
+fn test_chart_stats_length_bpm_and_density() {
+    let bms = parse_bms_no_warnings(SOURCE, default_config().prompter(AlwaysUseNewer));
+
+    let stats = bms.chart_stats().expect("chart should be processed");
+
+    // The long note ends at the start of track 2, 3 seconds from the start.
+    assert_time_close(3.0, stats.playable_secs, "playable seconds");
+    assert_eq!(stats.min_bpm, PositiveF64::new_const(120.0));
+    assert_eq!(stats.max_bpm, PositiveF64::new_const(240.0));
+    assert_eq!(stats.main_bpm, PositiveF64::new_const(120.0));
+    // Three notes in [0, 1) seconds and in [2, 3) seconds.
+    assert_time_close(3.0, stats.peak_density, "peak density");
+    assert_time_close(8.0 / 3.0, stats.average_density, "average density");
+
+    let chart = bms.process().expect("chart should be processed");
+    let wide = chart.stats_with_window(TimeSpan::SECOND * 2);
+    assert_time_close(2.0, wide.density_window_secs, "window");
+    assert_time_close(2.5, wide.peak_density, "peak density");
+}
+
+#[test]
+fn test_chart_stats_without_notes() {
+    let bms = parse_bms_no_warnings("#BPM 150\n", default_config().prompter(AlwaysUseNewer));
+
+    let stats = bms.chart_stats().expect("chart should be processed");
+
+    assert_eq!(stats.notes, NoteKindCounts::default());
+    assert!(stats.lanes.is_empty());
+    assert_time_close(0.0, stats.scratch_ratio, "scratch ratio");
+    assert_time_close(0.0, stats.playable_secs, "playable seconds");
+    assert_time_close(0.0, stats.peak_density, "peak density");
+    assert_time_close(0.0, stats.average_density, "average density");
+    assert_eq!(stats.main_bpm, PositiveF64::new_const(150.0));
+    assert!(stats.density_per_measure.is_empty());
+}
@@ -0,0 +1,130 @@
+use gametime::{TimeSpan, TimeStamp};
+
+use bms_rs::chart::prelude::*;
+use strict_num_extended::PositiveF64;
+
+use super::{assert_time_close, process_no_warnings};
+
+/// - Track 1 starts with a note and a stop of a measure, for 2 seconds at BPM 120.
+/// - Track 2 starts with a note, and a `#STP` of 500 milliseconds.
+/// - Track 3 starts with a note.
+const SOURCE: &str = "#BPM 120
+#WAV01 a.wav
+#STOP01 192
+#STP 002.000 500
+#00109:01
+#00111:01
+#00211:01
+#00311:01
+";
+
+fn ms(millis: i64) -> TimeSpan {
+    TimeSpan::MILLISECOND * millis
+}
+
+fn note_times(events: &[PlayheadEvent]) -> Vec<f64> {
+    events
+        .iter()
+        .filter(|event| matches!(event.event(), ChartEvent::Note { .. }))
+        .map(|event| event.activate_time().as_secs_f64())
+        .collect()
+}
+
+#[test]
+fn test_stops_are_flow_events() {
+    let chart = process_no_warnings(SOURCE);
+
+    let stops: Vec<_> = chart
+        .flow_events()
+        .iter()
+        .flat_map(|(y, events)| {
+            events.iter().filter_map(move |event| match event {
+                FlowEvent::Stop(duration) => Some((y.as_f64(), *duration)),
+                _ => None,
+            })
+        })
+        .collect();
+    let [(first_y, first), (second_y, second)] = stops.as_slice() else {
+        panic!("expected two stops, got {stops:?}");
+    };
+    assert_time_close(1.0, *first_y, "#STOP y");
+    assert_eq!(*first, ms(2000));
+    assert_time_close(2.0, *second_y, "#STP y");
+    assert_eq!(*second, ms(500));
+
+    // A note at a stop is activated at the end of the stop.
+    let mut times = note_times(chart.events().as_events());
+    times.sort_by(f64::total_cmp);
+    assert_eq!(times.len(), 3);
+    for (expected, actual) in [4.0, 6.5, 8.5].into_iter().zip(times) {
+        assert_time_close(expected, actual, "activate time");
+    }
+}
+
+#[test]
+fn test_player_freezes_during_stops() {
+    let chart = process_no_warnings(SOURCE);
+    let start = TimeStamp::now();
+    let visible_range =
+        VisibleRangePerBpm::new(&PositiveF64::new_const(120.0), TimeSpan::MILLISECOND * 600);
+    let mut player = ChartPlayer::start(&chart, visible_range, start);
+
+    // The playhead reaches the first note at the start of the stop.
+    assert_eq!(note_times(&player.update(start + ms(2001))).len(), 1);
+    assert!(player.playback_state().is_stopped());
+    assert_time_close(
+        1.0,
+        player.playback_state().progressed_y().as_f64(),
+        "y in stop",
+    );
+
+    assert!(note_times(&player.update(start + ms(3500))).is_empty());
+    assert_time_close(
+        1.0,
+        player.playback_state().progressed_y().as_f64(),
+        "y in stop",
+    );
+    assert_time_close(
+        0.5,
+        player.playback_state().stop_remaining().as_secs_f64(),
+        "stop remaining",
+    );
+
+    assert!(note_times(&player.update(start + ms(5999))).is_empty());
+    assert!(!player.playback_state().is_stopped());
+    assert_eq!(note_times(&player.update(start + ms(6001))).len(), 1);
+
+    // The `#STP` freezes the playhead at the second note.
+    player.update(start + ms(6400));
+    assert_time_close(
+        2.0,
+        player.playback_state().progressed_y().as_f64(),
+        "y in #STP",
+    );
+    assert!(note_times(&player.update(start + ms(8499))).is_empty());
+    assert_time_close(
+        8.5,
+        note_times(&player.update(start + ms(8501))).iter().sum(),
+        "third note",
+    );
+}
+
+#[test]
+fn test_player_stop_follows_playback_ratio() {
+    let chart = process_no_warnings(SOURCE);
+    let start = TimeStamp::now();
+    let visible_range =
+        VisibleRangePerBpm::new(&PositiveF64::new_const(120.0), TimeSpan::MILLISECOND * 600);
+    let mut player = ChartPlayer::start(&chart, visible_range, start);
+    player.set_playback_ratio(strict_num_extended::FinF64::TWO);
+
+    // At the double speed, the first note is at 1 second and the stop ends at 2 seconds.
+    player.update(start + ms(1900));
+    assert!(player.playback_state().is_stopped());
+    player.update(start + ms(2250));
+    assert_time_close(
+        1.25,
+        player.playback_state().progressed_y().as_f64(),
+        "y after stop",
+    );
+}
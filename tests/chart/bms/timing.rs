@@ -0,0 +1,141 @@
+use std::num::NonZeroU64;
+use std::time::Duration;
+
+use bms_rs::bms::prelude::*;
+use bms_rs::chart::prelude::*;
+use strict_num_extended::{NonNegativeF64, PositiveF64};
+
+use super::{assert_time_close, parse_bms_no_warnings};
+
+/// - Tracks 0 and 1 at BPM 120 take 2 seconds each.
+/// - From track 2, BPM is 240 and a measure takes 1 second.
+/// - Track 3 starts with a stop of a measure, for 1 second.
+/// - Track 4 starts with a `#STP` of 500 milliseconds.
+/// - Track 5 has half the length and takes 0.5 seconds.
+///
+/// The start of a track with a stop is reached at the end of the stop.
+const SOURCE: &str = "#BPM 120
+#BPM01 240
+#STOP01 192
+#STP 004.000 500
+#00208:01
+#00309:01
+#00502:0.5
+";
+
+fn time(track: u64, numerator: u64, denominator: u64) -> ObjTime {
+    ObjTime::new(track, numerator, denominator).expect("denominator should be non-zero")
+}
+
+fn y(value: f64) -> YCoordinate {
+    YCoordinate::new(NonNegativeF64::new(value).expect("y should be non-negative"))
+}
+
+fn secs(value: f64) -> TimeSpan {
+    TimeSpan::from_duration(Duration::from_secs_f64(value))
+}
+
+fn timing_map() -> TimingMap {
+    let bms = parse_bms_no_warnings(SOURCE, default_config().prompter(AlwaysUseNewer));
+    TimingMap::from_bms(&bms).expect("chart should be processed")
+}
+
+#[test]
+fn test_timing_map_obj_time_to_y_and_beats() {
+    let map = timing_map();
+
+    assert_time_close(5.25, map.obj_time_to_y(time(5, 1, 2)).as_f64(), "y");
+    assert_time_close(5.5, map.obj_time_to_y(time(6, 0, 1)).as_f64(), "y");
+    assert_time_close(6.0, map.y_to_beats(y(1.5)), "beats");
+    assert_time_close(1.5, map.beats_to_y(6.0).as_f64(), "y");
+}
+
+#[test]
+fn test_timing_map_obj_time_to_seconds() {
+    let map = timing_map();
+
+    let cases = [
+        (time(1, 0, 1), 2.0),
+        (time(1, 1, 2), 3.0),
+        (time(2, 0, 1), 4.0),
+        (time(3, 0, 1), 6.0),
+        (time(3, 1, 2), 6.5),
+        (time(4, 0, 1), 7.5),
+        (time(4, 1, 2), 8.0),
+        (time(5, 1, 2), 8.75),
+        (time(6, 0, 1), 9.0),
+    ];
+    for (obj_time, expected) in cases {
+        assert_time_close(
+            expected,
+            map.obj_time_to_time(obj_time).as_secs_f64(),
+            &format!("time of {obj_time:?}"),
+        );
+    }
+}
+
+#[test]
+fn test_timing_map_agrees_with_activate_times() {
+    let bms = parse_bms_no_warnings(
+        "#BPM 120
+#BPM01 240
+#WAV01 a.wav
+#STOP01 192
+#00208:01
+#00309:01
+#00311:01
+#00411:0001
+",
+        default_config().prompter(AlwaysUseNewer),
+    );
+    let chart = bms.process().expect("chart should be processed");
+    let map = TimingMap::from_bms(&bms).expect("chart should be processed");
+
+    for event in chart.events().as_events() {
+        assert_time_close(
+            event.activate_time().as_secs_f64(),
+            map.y_to_time(*event.position()).as_secs_f64(),
+            &format!("time of {:?}", event.event()),
+        );
+    }
+}
+
+#[test]
+fn test_timing_map_seconds_to_grid() {
+    let map = timing_map();
+    let quarter = NonZeroU64::new(4).expect("4 should be non-zero");
+
+    assert_eq!(map.time_to_obj_time(secs(3.0), quarter), time(1, 1, 2));
+    assert_eq!(map.time_to_obj_time(secs(6.5), quarter), time(3, 1, 2));
+    // 0.95 measures rounds up to the start of the next track.
+    assert_eq!(map.time_to_obj_time(secs(1.9), quarter), time(1, 0, 1));
+    // A time in a stop is at the position of the stop.
+    assert_time_close(3.0, map.time_to_y(secs(5.5)).as_f64(), "y in stop");
+    assert_time_close(4.0, map.time_to_y(secs(7.3)).as_f64(), "y in #STP");
+}
+
+#[test]
+fn test_timing_map_round_trips_grid_positions() {
+    let map = timing_map();
+    let sixteenth = NonZeroU64::new(16).expect("16 should be non-zero");
+
+    for track in 0..7 {
+        for numerator in 0..16 {
+            let obj_time = time(track, numerator, 16);
+            assert_eq!(
+                map.time_to_obj_time(map.obj_time_to_time(obj_time), sixteenth),
+                obj_time
+            );
+        }
+    }
+}
+
+#[test]
+fn test_timing_map_bpm_at_time() {
+    let map = timing_map();
+
+    assert_eq!(map.bpm_at_time(secs(3.9)), PositiveF64::new_const(120.0));
+    assert_eq!(map.bpm_at_time(secs(4.0)), PositiveF64::new_const(240.0));
+    assert_eq!(map.bpm_at_time(secs(5.5)), PositiveF64::new_const(240.0));
+    assert_eq!(map.bpm_at_y(y(1.0)), PositiveF64::new_const(120.0));
+}
@@ -453,3 +453,85 @@ fn test_visibility_range_bound_types() {
     // Closed range should include events on the boundary
     assert!(count_closed >= count_open);
 }
+
+#[test]
+fn test_display_ratio_integrates_scroll_changes() {
+    // A scroll change to 0.5 at track 1 and to -1 at track 2, notes at the middle of tracks 0 and 1.
+    let source = "#BPM 120
+#WAV01 a.wav
+#SCROLL01 0.5
+#SCROLL02 -1
+#00011:0001
+#00111:0001
+#001SC:01
+#002SC:02
+";
+    let bms = parse_bms_no_warnings(source, default_config().prompter(AlwaysUseNewer));
+    let chart = bms.process().expect("chart should be processed");
+
+    let y = |value: f64| YCoordinate::new(NonNegativeF64::new(value).expect("non-negative"));
+    for (y_value, expected) in [(0.5, 0.5), (1.5, 1.25), (2.0, 1.5), (2.5, 1.0)] {
+        assert_time_close(expected, chart.display_y(&y(y_value)).as_f64(), "display y");
+    }
+
+    // The visible window is 2 measures at BPM 120.
+    let visible_range = VisibleRangePerBpm::new(&TEST_BPM_120, TimeSpan::SECOND * 4);
+    let mut player = ChartPlayer::start(&chart, visible_range, TimeStamp::now());
+    let ratios = |chart_player: &mut ChartPlayer| -> Vec<f64> {
+        chart_player
+            .visible_events()
+            .iter()
+            .filter(|(event, _)| matches!(event.event(), ChartEvent::Note { .. }))
+            .map(|(_, range)| range.start().value().as_f64())
+            .collect()
+    };
+    let integrated = ratios(&mut player);
+    assert_eq!(integrated.len(), 2);
+    for (expected, actual) in [0.25, 0.625].into_iter().zip(integrated) {
+        assert_time_close(expected, actual, "integrated display ratio");
+    }
+
+    player.set_display_scroll(DisplayScroll::Current);
+    let current = ratios(&mut player);
+    assert_eq!(current.len(), 2);
+    for (expected, actual) in [0.25, 0.75].into_iter().zip(current) {
+        assert_time_close(expected, actual, "current scroll display ratio");
+    }
+}
+
+#[test]
+fn test_visible_events_are_selected_by_display_y() {
+    // A scroll of 0.5 from the start, notes at the middle of tracks 1 and 2.
+    let source = "#BPM 120
+#WAV01 a.wav
+#SCROLL01 0.5
+#000SC:01
+#00111:0001
+#00211:0001
+";
+    let bms = parse_bms_no_warnings(source, default_config().prompter(AlwaysUseNewer));
+    let chart = bms.process().expect("chart should be processed");
+
+    // The visible window is a measure at BPM 120, which shows 2 measures at the scroll of 0.5.
+    let visible_range = VisibleRangePerBpm::new(&TEST_BPM_120, TimeSpan::SECOND * 2);
+    let start = TimeStamp::now();
+    let mut player = ChartPlayer::start(&chart, visible_range, start);
+    let ratios = |chart_player: &mut ChartPlayer| -> Vec<f64> {
+        chart_player
+            .visible_events()
+            .iter()
+            .filter(|(event, _)| matches!(event.event(), ChartEvent::Note { .. }))
+            .map(|(_, range)| range.start().value().as_f64())
+            .collect()
+    };
+    let integrated = ratios(&mut player);
+    assert_eq!(integrated.len(), 1);
+    assert_time_close(0.75, integrated.iter().sum(), "integrated display ratio");
+
+    // The scroll at the start is applied on the first update.
+    player.set_display_scroll(DisplayScroll::Current);
+    player.update(start + TimeSpan::MICROSECOND);
+    let current = ratios(&mut player);
+    assert_eq!(current.len(), 1);
+    assert!((current.iter().sum::<f64>() - 0.75).abs() < 1e-3);
+}
@@ -0,0 +1,113 @@
+use bms_rs::bms::prelude::*;
+use bms_rs::chart::prelude::*;
+
+use super::process_no_warnings;
+
+/// - `01` is triggered by two notes and a BGM.
+/// - `02` is triggered once.
+const SOURCE: &str = "#BPM 120
+#WAV01 a.wav
+#WAV02 b.wav
+#00101:0001
+#00111:0102
+#00112:0100
+";
+
+#[test]
+fn test_same_wav_cuts_previous_voice() {
+    let chart = process_no_warnings(SOURCE);
+    let commands = chart.voice_commands();
+
+    let plan: Vec<_> = commands
+        .iter()
+        .map(|command| (command.wav_id, command.voice, command.stop))
+        .collect();
+    assert_eq!(
+        plan,
+        vec![
+            (WavId::from(1), VoiceId(0), None),
+            (WavId::from(1), VoiceId(1), Some(VoiceId(0))),
+            (WavId::from(1), VoiceId(2), Some(VoiceId(1))),
+            (WavId::from(2), VoiceId(3), None),
+        ]
+    );
+    assert!(
+        commands
+            .iter()
+            .all(|command| command.offset == TimeSpan::ZERO)
+    );
+}
+
+#[test]
+fn test_allocator_reset() {
+    let chart = process_no_warnings(SOURCE);
+    let note = chart
+        .events()
+        .as_events()
+        .iter()
+        .find(|event| matches!(event.event(), ChartEvent::Note { .. }))
+        .expect("chart should have a note");
+
+    let mut allocator = VoiceAllocator::new();
+    let first = allocator.trigger(note).expect("note should have a sound");
+    assert_eq!(allocator.voice_of(first.wav_id), Some(first.voice));
+    allocator.reset();
+    let second = allocator.trigger(note).expect("note should have a sound");
+    assert_eq!(second.stop, None);
+    assert_ne!(second.voice, first.voice);
+}
+
+#[test]
+fn test_allocator_cancel_restores_previous_voice() {
+    let chart = process_no_warnings(SOURCE);
+    let note = chart
+        .events()
+        .as_events()
+        .iter()
+        .find(|event| matches!(event.event(), ChartEvent::Note { .. }))
+        .expect("chart should have a note");
+
+    let mut allocator = VoiceAllocator::new();
+    let first = allocator.trigger(note).expect("note should have a sound");
+    let failed = allocator.trigger(note).expect("note should have a sound");
+    allocator.cancel(failed);
+    assert_eq!(allocator.voice_of(first.wav_id), Some(first.voice));
+    let retried = allocator.trigger(note).expect("note should have a sound");
+    assert_eq!(retried.stop, Some(first.voice));
+
+    allocator.reset();
+    let alone = allocator.trigger(note).expect("note should have a sound");
+    allocator.cancel(alone);
+    assert_eq!(allocator.voice_of(alone.wav_id), None);
+}
+
+#[test]
+fn test_landmines_and_invisible_notes_have_no_voices() {
+    let chart = process_no_warnings(
+        "#BPM 120
+#WAV01 a.wav
+#00111:01
+#00131:0001
+#001D1:0001
+",
+    );
+    let commands = chart.voice_commands();
+    assert_eq!(commands.len(), 1);
+    assert_eq!(commands.first().and_then(|command| command.stop), None);
+
+    let mine = chart
+        .events()
+        .as_events()
+        .iter()
+        .find(|event| {
+            matches!(
+                event.event(),
+                ChartEvent::Note {
+                    kind: NoteKind::Landmine,
+                    ..
+                }
+            )
+        })
+        .expect("chart should have a landmine");
+    assert_eq!(VoiceAllocator::new().trigger(mine), None);
+}
@@ -0,0 +1,58 @@
+#![cfg(feature = "bmson")]
+
+use bms_rs::bmson::parse_bmson;
+use bms_rs::chart::prelude::*;
+use strict_num_extended::PositiveF64;
+
+/// 2 measures at BPM 150 with three notes, a stop of 8 measures, then 3 measures at BPM 200 with a
+/// note at the end.
+const JSON: &str = r#"{
+    "version": "1.0.0",
+    "info": {
+        "title": "Main BPM",
+        "artist": "",
+        "genre": "",
+        "level": 1,
+        "init_bpm": 150.0,
+        "resolution": 240
+    },
+    "bpm_events": [ { "y": 1920, "bpm": 200.0 } ],
+    "stop_events": [ { "y": 960, "duration": 7680 } ],
+    "sound_channels": [
+        {
+            "name": "a.wav",
+            "notes": [
+                { "x": 1, "y": 0, "l": 0, "c": false },
+                { "x": 2, "y": 480, "l": 0, "c": false },
+                { "x": 3, "y": 960, "l": 0, "c": false },
+                { "x": 1, "y": 4800, "l": 0, "c": false }
+            ]
+        }
+    ]
+}"#;
+
+#[test]
+fn test_bmson_main_bpm_generators() {
+    let output = parse_bmson(JSON);
+    let bmson = output.bmson.expect("Failed to parse BMSON in test setup");
+
+    let by_duration = MainBpmByDurationGenerator
+        .generate(&bmson)
+        .expect("main BPM should be found");
+    let by_notes = MainBpmByNotesGenerator
+        .generate(&bmson)
+        .expect("main BPM should be found");
+    let average = AverageBpmGenerator
+        .generate(&bmson)
+        .expect("average BPM should be found");
+
+    // 3.2 seconds at BPM 150 and 3.6 seconds at BPM 200.
+    assert_eq!(by_duration, BaseBpm::new(PositiveF64::new_const(200.0)));
+    assert_eq!(by_notes, BaseBpm::new(PositiveF64::new_const(150.0)));
+    let expected = (150.0 * 3.2 + 200.0 * 3.6) / (3.2 + 3.6);
+    assert!(
+        (average.as_f64() - expected).abs() < 1e-9,
+        "expected {expected}, got {}",
+        average.as_f64()
+    );
+}
@@ -0,0 +1,68 @@
+#![cfg(feature = "bmson")]
+
+use bms_rs::bmson::parse_bmson;
+use bms_rs::bmson::prelude::BmsonProcessor;
+use bms_rs::chart::prelude::*;
+
+use super::assert_time_close;
+
+/// Two notes at 2 and 2.5 seconds at BPM 120, and a landmine with 15 damage at 3 seconds on the key 1.
+const JSON: &str = r#"{
+    "version": "1.0.0",
+    "info": {
+        "title": "Gauge",
+        "artist": "",
+        "genre": "",
+        "level": 1,
+        "init_bpm": 120.0,
+        "total": 50,
+        "resolution": 240
+    },
+    "sound_channels": [
+        {
+            "name": "a.wav",
+            "notes": [
+                { "x": 1, "y": 960, "l": 0, "c": false },
+                { "x": 2, "y": 1200, "l": 0, "c": false }
+            ]
+        }
+    ],
+    "mine_channels": [
+        {
+            "name": "mine.wav",
+            "notes": [
+                { "x": 1, "y": 1440, "damage": 15.0 }
+            ]
+        }
+    ],
+    "key_channels": []
+}"#;
+
+#[test]
+fn test_bmson_gauge_honors_total_and_mine_damage() {
+    let output = parse_bmson(JSON);
+    let bmson = output.bmson.expect("Failed to parse BMSON in test setup");
+    let chart = BmsonProcessor::parse(&bmson);
+    let config = GaugeConfig::from_bmson(&bmson);
+    assert_eq!(config.notes, 2);
+    // 50% of the default TOTAL for 2 notes.
+    assert_time_close(80.16, config.total, "total");
+
+    let mut gauge = Gauge::new(GaugeType::Hard, GaugeRules::Beatoraja, config);
+    let mut judge = Judge::new(&chart, JudgeConfig::from_bmson(&bmson));
+    let ms = |millis: i64| TimeSpan::MILLISECOND * millis;
+    let mut events = judge.press(PlayerSide::Player1, Key::Key(1), ms(2000));
+    events.extend(judge.update(ms(3100)));
+    events.extend(judge.release(PlayerSide::Player1, Key::Key(1), ms(3200)));
+    for event in &events {
+        gauge.apply_event(event, &chart);
+    }
+
+    // The missed note deals 10 damage, and the landmine deals its own damage.
+    assert!(
+        events
+            .iter()
+            .any(|event| matches!(event.kind, JudgeEventKind::Landmine { .. }))
+    );
+    assert_time_close(75.0, gauge.value(), "gauge");
+}
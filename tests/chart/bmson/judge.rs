@@ -0,0 +1,101 @@
+#![cfg(feature = "bmson")]
+
+use bms_rs::bms::prelude::LnMode;
+use bms_rs::bmson::parse_bmson;
+use bms_rs::bmson::prelude::BmsonProcessor;
+use bms_rs::chart::prelude::*;
+
+/// A note at 2 seconds and a long note from 3 to 4 seconds at BPM 120, with the doubled judge windows
+/// and CN set after parsing.
+const JSON: &str = r#"{
+    "version": "1.0.0",
+    "info": {
+        "title": "Judge",
+        "artist": "",
+        "genre": "",
+        "level": 1,
+        "init_bpm": 120.0,
+        "judge_rank": 200,
+        "resolution": 240
+    },
+    "sound_channels": [
+        {
+            "name": "a.wav",
+            "notes": [
+                { "x": 1, "y": 960, "l": 0, "c": false },
+                { "x": 2, "y": 1440, "l": 480, "c": false }
+            ]
+        }
+    ]
+}"#;
+
+#[test]
+fn test_bmson_judge_uses_judge_rank_and_ln_type() {
+    let output = parse_bmson(JSON);
+    let mut bmson = output.bmson.expect("Failed to parse BMSON in test setup");
+    bmson.info.ln_type = LnMode::Cn;
+    let chart = BmsonProcessor::parse(&bmson);
+    let mut judge = Judge::new(&chart, JudgeConfig::from_bmson(&bmson));
+    let ms = |millis: i64| TimeSpan::MILLISECOND * millis;
+
+    let note = judge.press(PlayerSide::Player1, Key::Key(1), ms(2030));
+    assert_eq!(
+        note.first().and_then(JudgeEvent::judgment),
+        Some(Judgment::PGreat)
+    );
+    let head = judge.press(PlayerSide::Player1, Key::Key(2), ms(3000));
+    assert!(matches!(
+        head.first().map(|event| event.kind),
+        Some(JudgeEventKind::Note {
+            part: NotePart::Head,
+            judgment: Judgment::PGreat,
+            ..
+        })
+    ));
+    let tail = judge.release(PlayerSide::Player1, Key::Key(2), ms(4000));
+    assert!(matches!(
+        tail.first().map(|event| event.kind),
+        Some(JudgeEventKind::Note {
+            part: NotePart::Tail,
+            judgment: Judgment::PGreat,
+            ..
+        })
+    ));
+}
+
+#[test]
+fn test_bmson_judge_uses_ln_mode_of_each_note() {
+    let output = parse_bmson(JSON);
+    let mut bmson = output.bmson.expect("Failed to parse BMSON in test setup");
+    for note in bmson
+        .sound_channels
+        .iter_mut()
+        .flat_map(|channel| channel.notes.iter_mut())
+        .filter(|note| note.l > 0)
+    {
+        note.t = Some(LnMode::Cn);
+    }
+    let chart = BmsonProcessor::parse(&bmson);
+    assert_eq!(chart.judgment_count(LnMode::Ln), 3);
+    let mut judge = Judge::new(&chart, JudgeConfig::from_bmson(&bmson));
+    assert_eq!(judge.ln_mode(), LnMode::Ln);
+    let ms = |millis: i64| TimeSpan::MILLISECOND * millis;
+
+    let head = judge.press(PlayerSide::Player1, Key::Key(2), ms(3000));
+    assert!(matches!(
+        head.last().map(|event| event.kind),
+        Some(JudgeEventKind::Note {
+            part: NotePart::Head,
+            ..
+        })
+    ));
+    let tail = judge.release(PlayerSide::Player1, Key::Key(2), ms(4000));
+    assert!(matches!(
+        tail.first().map(|event| event.kind),
+        Some(JudgeEventKind::Note {
+            part: NotePart::Tail,
+            judgment: Judgment::PGreat,
+            ..
+        })
+    ));
+}
@@ -0,0 +1,70 @@
+#![cfg(feature = "mixdown")]
+
+use std::path::Path;
+
+use bms_rs::bmson::parse_bmson;
+use bms_rs::bmson::prelude::BmsonProcessor;
+use bms_rs::chart::prelude::*;
+
+/// Output sample rate, low enough to read frames by milliseconds.
+const SAMPLE_RATE: u32 = 1000;
+
+/// Provides a ramp rising by 0.25 per second.
+struct Ramp;
+
+impl SampleProvider for Ramp {
+    fn load(&mut self, _id: WavId, _path: &Path) -> Option<Samples> {
+        Some(Samples {
+            sample_rate: SAMPLE_RATE,
+            channels: 1,
+            data: (0..3000).map(|frame| frame as f32 / 4000.0).collect(),
+        })
+    }
+}
+
+#[test]
+fn test_mixdown_continues_sound() {
+    // A note at 0 seconds, and notes continuing and restarting the sound at 1 second.
+    let json = r#"{
+        "version": "1.0.0",
+        "info": {
+            "title": "Test",
+            "artist": "",
+            "genre": "",
+            "level": 1,
+            "init_bpm": 120.0,
+            "resolution": 240
+        },
+        "sound_channels": [
+            {
+                "name": "continued.wav",
+                "notes": [
+                    { "x": 1, "y": 0, "l": 0, "c": false },
+                    { "x": 1, "y": 480, "l": 0, "c": true }
+                ]
+            },
+            {
+                "name": "restarted.wav",
+                "notes": [
+                    { "x": 2, "y": 0, "l": 0, "c": false },
+                    { "x": 2, "y": 480, "l": 0, "c": false }
+                ]
+            }
+        ]
+    }"#;
+    let bmson = parse_bmson(json)
+        .bmson
+        .expect("Failed to parse BMSON in test setup");
+    let chart = BmsonProcessor::parse(&bmson);
+    let config = MixdownConfig {
+        sample_rate: SAMPLE_RATE,
+        ..MixdownConfig::default()
+    };
+    let mixdown = chart.mixdown(&mut Ramp, &config);
+
+    // 0.375 from the continued sound, and 0.125 from the restarted sound.
+    let left = mixdown.samples().get(1500 * 2).copied().unwrap_or_default();
+    assert!((left - 0.5).abs() < 1e-6, "left: {left}");
+    // The continued sound ends at 3 seconds, and the restarted one at 4 seconds.
+    assert_eq!(mixdown.samples().len(), 4000 * 2);
+}
@@ -3,9 +3,17 @@
 //! Integration tests for `bms_rs::chart::BmsonProcessor`.
 
 mod activate_time;
+mod base_bpm;
 mod chart;
 mod continue_time;
+mod gauge;
+mod judge;
+mod mixdown;
 mod playback_state;
+mod stats;
+mod stop;
+mod timing;
 mod visible_events;
+mod voice;
 
 use super::{MICROSECOND_EPSILON, assert_time_close};
@@ -0,0 +1,49 @@
+#![cfg(feature = "bmson")]
+
+use bms_rs::bmson::parse_bmson;
+use bms_rs::chart::prelude::*;
+
+use super::assert_time_close;
+
+/// A measure at BPM 120 with a scratch, two notes and a long note of half a measure.
+const JSON: &str = r#"{
+    "version": "1.0.0",
+    "info": {
+        "title": "Stats",
+        "artist": "",
+        "genre": "",
+        "level": 1,
+        "init_bpm": 120.0,
+        "resolution": 240
+    },
+    "sound_channels": [
+        {
+            "name": "a.wav",
+            "notes": [
+                { "x": 8, "y": 0, "l": 0, "c": false },
+                { "x": 1, "y": 0, "l": 0, "c": false },
+                { "x": 2, "y": 240, "l": 0, "c": false },
+                { "x": 3, "y": 480, "l": 480, "c": false }
+            ]
+        }
+    ]
+}"#;
+
+#[test]
+fn test_bmson_chart_stats_serializes() {
+    let output = parse_bmson(JSON);
+    let bmson = output.bmson.expect("Failed to parse BMSON in test setup");
+
+    let stats = bmson.chart_stats();
+
+    assert_eq!(stats.notes.visible, 3);
+    assert_eq!(stats.notes.long, 1);
+    assert_time_close(0.25, stats.scratch_ratio, "scratch ratio");
+    assert_time_close(2.0, stats.playable_secs, "playable seconds");
+    assert_time_close(2.0, stats.average_density, "average density");
+
+    let json = serde_json::to_string(&stats).expect("stats should be serialized");
+    let deserialized: ChartStats =
+        serde_json::from_str(&json).expect("stats should be deserialized");
+    assert_eq!(deserialized, stats);
+}
@@ -0,0 +1,56 @@
+#![cfg(feature = "bmson")]
+
+use bms_rs::bmson::parse_bmson;
+use bms_rs::bmson::prelude::BmsonProcessor;
+use bms_rs::chart::prelude::*;
+use gametime::TimeStamp;
+use strict_num_extended::PositiveF64;
+
+use super::assert_time_close;
+
+/// A stop of half a measure at y=0.5, at BPM 240 from there. A measure takes 2 seconds before it and
+/// 1 second after it.
+const JSON: &str = r#"{
+    "version": "1.0.0",
+    "info": {
+        "title": "Stop",
+        "artist": "",
+        "genre": "",
+        "level": 1,
+        "init_bpm": 120.0,
+        "resolution": 240
+    },
+    "bpm_events": [ { "y": 480, "bpm": 240.0 } ],
+    "stop_events": [ { "y": 480, "duration": 480 } ],
+    "sound_channels": [
+        { "name": "a.wav", "notes": [ { "x": 1, "y": 960, "l": 0, "c": false } ] }
+    ]
+}"#;
+
+#[test]
+fn test_bmson_player_freezes_during_stop() {
+    let output = parse_bmson(JSON);
+    let bmson = output.bmson.expect("Failed to parse BMSON in test setup");
+    let chart = BmsonProcessor::parse(&bmson);
+    let start = TimeStamp::now();
+    let visible_range =
+        VisibleRangePerBpm::new(&PositiveF64::new_const(120.0), TimeSpan::MILLISECOND * 600);
+    let mut player = ChartPlayer::start(&chart, visible_range, start);
+    let ms = |millis: i64| TimeSpan::MILLISECOND * millis;
+
+    // The stop lasts half a measure at BPM 240, from 1 to 1.5 seconds.
+    player.update(start + ms(1400));
+    assert!(player.playback_state().is_stopped());
+    assert_time_close(
+        0.5,
+        player.playback_state().progressed_y().as_f64(),
+        "y in stop",
+    );
+
+    let triggered = player.update(start + ms(2001));
+    let note = triggered
+        .iter()
+        .find(|event| matches!(event.event(), ChartEvent::Note { .. }))
+        .expect("note should be triggered");
+    assert_time_close(2.0, note.activate_time().as_secs_f64(), "activate time");
+}
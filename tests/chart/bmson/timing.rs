@@ -0,0 +1,45 @@
+#![cfg(feature = "bmson")]
+
+use std::num::NonZeroU64;
+use std::time::Duration;
+
+use bms_rs::bms::prelude::ObjTime;
+use bms_rs::bmson::parse_bmson;
+use bms_rs::bmson::prelude::BmsonProcessor;
+use bms_rs::chart::prelude::*;
+use strict_num_extended::PositiveF64;
+
+use super::assert_time_close;
+
+#[test]
+fn test_bmson_timing_map_accounts_for_bpm_and_stops() {
+    let json = r#"{
+        "version": "1.0.0",
+        "info": {
+            "title": "Timing",
+            "artist": "",
+            "genre": "",
+            "level": 1,
+            "init_bpm": 120.0,
+            "resolution": 240
+        },
+        "bpm_events": [ { "y": 960, "bpm": 240.0 } ],
+        "stop_events": [ { "y": 1920, "duration": 960 } ],
+        "sound_channels": []
+    }"#;
+    let output = parse_bmson(json);
+    let bmson = output.bmson.expect("Failed to parse BMSON in test setup");
+    let map = TimingMap::from_chart(&BmsonProcessor::parse(&bmson));
+
+    let measure = |track: u64| ObjTime::new(track, 0, 1).expect("denominator should be non-zero");
+    // 2 seconds at BPM 120, 1 second at BPM 240, a stop of 1 second, then 1 second at BPM 240.
+    assert_time_close(2.0, map.obj_time_to_time(measure(1)).as_secs_f64(), "time");
+    assert_time_close(4.0, map.obj_time_to_time(measure(2)).as_secs_f64(), "time");
+    assert_time_close(5.0, map.obj_time_to_time(measure(3)).as_secs_f64(), "time");
+
+    // A time in the stop is at the position of the stop.
+    let time = TimeSpan::from_duration(Duration::from_secs_f64(3.5));
+    let eighth = NonZeroU64::new(8).expect("8 should be non-zero");
+    assert_eq!(map.time_to_obj_time(time, eighth), measure(2));
+    assert_eq!(map.bpm_at_time(time), PositiveF64::new_const(240.0));
+}
@@ -0,0 +1,56 @@
+#![cfg(feature = "bmson")]
+
+use bms_rs::bmson::parse_bmson;
+use bms_rs::bmson::prelude::BmsonProcessor;
+
+use super::assert_time_close;
+
+#[test]
+fn test_continue_notes_seek_into_sound() {
+    // At BPM 120 and resolution 240, a quarter is 0.5 seconds. The notes are at 0, 1, 1.5 and 2
+    // seconds, where the third restarts the sound.
+    let json = r#"{
+        "version": "1.0.0",
+        "info": {
+            "title": "Test",
+            "artist": "",
+            "genre": "",
+            "level": 1,
+            "init_bpm": 120.0,
+            "resolution": 240
+        },
+        "sound_channels": [
+            {
+                "name": "sliced.wav",
+                "notes": [
+                    { "x": 1, "y": 0, "l": 0, "c": false },
+                    { "x": 2, "y": 480, "l": 0, "c": true },
+                    { "x": 3, "y": 720, "l": 0, "c": false },
+                    { "x": 4, "y": 960, "l": 0, "c": true }
+                ]
+            }
+        ]
+    }"#;
+    let bmson = parse_bmson(json)
+        .bmson
+        .expect("Failed to parse BMSON in test setup");
+    let chart = BmsonProcessor::parse(&bmson);
+    let commands = chart.voice_commands();
+
+    let offsets: Vec<_> = commands
+        .iter()
+        .map(|command| command.offset.as_secs_f64())
+        .collect();
+    assert_eq!(offsets.len(), 4);
+    for (expected, actual) in [0.0, 1.0, 0.0, 0.5].into_iter().zip(offsets) {
+        assert_time_close(expected, actual, "offset");
+    }
+    assert_eq!(commands.get(1).map(|c| c.sample_offset(44100)), Some(44100));
+
+    // Each note stops the voice of the previous one.
+    for pair in commands.windows(2) {
+        if let [previous, next] = pair {
+            assert_eq!(next.stop, Some(previous.voice));
+        }
+    }
+}